@@ -4,8 +4,6 @@ use std::fmt::{Debug, Display, Formatter};
 use log::{debug, error, info, trace, warn};
 use serde::Serialize;
 
-use naive_logger;
-
 const CONFIG: &str = r#"
 [appenders.console]
 kind = "console"
@@ -104,6 +102,7 @@ fn main() {
         info!(key1, key2, key3=value, example1:%; "this is an info log: {i}");
         scope::log(i, example1.clone(), example2.clone());
     }
+    log::logger().flush();
 }
 
 mod scope {
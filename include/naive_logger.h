@@ -0,0 +1,29 @@
+#ifndef NAIVE_LOGGER_H
+#define NAIVE_LOGGER_H
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+#define NAIVE_LOGGER_LEVEL_ERROR 1
+#define NAIVE_LOGGER_LEVEL_WARN 2
+#define NAIVE_LOGGER_LEVEL_INFO 3
+#define NAIVE_LOGGER_LEVEL_DEBUG 4
+#define NAIVE_LOGGER_LEVEL_TRACE 5
+
+/* Initializes the logger with the config file; returns 0 on success, or -1 on failure. */
+int naive_logger_init_from_file(const char *path);
+
+/* Logs the message with the level and target; returns 0 on success,
+   or -1 if the arguments are invalid or the logging panicked. */
+int naive_logger_log(int level, const char *target, const char *message);
+
+/* Flushes all the appenders, waiting until the pending messages are written;
+   returns 0 on success, or -1 if the flushing panicked. */
+int naive_logger_flush(void);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
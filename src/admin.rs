@@ -0,0 +1,218 @@
+//! A small HTTP admin server for the live level control, enabled by the `admin` feature:
+//!
+//! * `GET /loggers`: the levels of the loggers as a JSON object, e.g. `{"myapp::":"debug","root":"info"}`
+//! * `PUT /loggers/<name>`: changes the level of the logger to the body, e.g. `debug`;
+//!   the root logger is named `root`
+//! * `POST /flush`: flushes all the appenders
+//!
+//! It can be started by [`serve`], or plugged into other HTTP servers by [`handle`].
+//! It has no authentication; bind it to a private address.
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+use log::LevelFilter;
+
+use crate::Error;
+
+/// Limits the request body, as only a level is expected.
+const MAX_BODY_SIZE: usize = 1024;
+/// Limits the request line and the headers.
+const MAX_HEADER_SIZE: usize = 8 * 1024;
+/// Keeps a stalled client from blocking the server: the whole request must be read in time,
+/// and each write of the response must finish in time.
+const READ_TIMEOUT: Duration = Duration::from_secs(5);
+const WRITE_TIMEOUT: Duration = Duration::from_secs(5);
+
+pub struct Response {
+    pub status: u16,
+    /// The JSON body; empty for `204 No Content`.
+    pub body: String,
+}
+
+impl Response {
+    fn new(status: u16, body: String) -> Self {
+        Self { status, body }
+    }
+
+    fn error(status: u16, message: &str) -> Self {
+        Self::new(status, serde_json::json!({ "error": message }).to_string())
+    }
+}
+
+/// Handles an admin request with the method, the path (without the query) and the body.
+pub fn handle(method: &str, path: &str, body: &str) -> Response {
+    let path = percent_decode(path);
+    match (method, path.as_str()) {
+        ("GET", "/loggers") => match crate::logger_levels() {
+            Ok(levels) => {
+                let map: serde_json::Map<_, _> = levels
+                    .into_iter()
+                    .map(|(name, level)| (name, level.to_string().to_lowercase().into()))
+                    .collect();
+                Response::new(200, serde_json::Value::Object(map).to_string())
+            }
+            Err(e) => Response::error(503, &e.to_string()),
+        },
+        ("PUT", path) if path.starts_with("/loggers/") => {
+            let name = &path["/loggers/".len()..];
+            let Ok(level) = LevelFilter::from_str(body.trim().trim_matches('"')) else {
+                return Response::error(400, &format!("invalid level '{}'", body.trim()));
+            };
+            match crate::set_logger_level(name, level) {
+                Ok(_) => Response::new(204, String::new()),
+                Err(e) => Response::error(404, &e.to_string()),
+            }
+        }
+        ("POST", "/flush") => {
+            log::logger().flush();
+            Response::new(204, String::new())
+        }
+        _ => Response::error(404, "not found"),
+    }
+}
+
+/// Starts the admin server in a background thread; returns the bound address.
+pub fn serve<A: ToSocketAddrs>(addr: A) -> Result<SocketAddr, Error> {
+    let listener = TcpListener::bind(addr)
+        .map_err(|e| Error::from(format!("failed to bind admin server: {}", e)))?;
+    let addr = listener
+        .local_addr()
+        .map_err(|e| Error::from(format!("failed to bind admin server: {}", e)))?;
+    std::thread::Builder::new()
+        .name("naive-logger-admin".to_string())
+        .spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let _ = serve_connection(stream);
+            }
+        })
+        .map_err(|e| Error::from(format!("failed to spawn admin server: {}", e)))?;
+    Ok(addr)
+}
+
+/// Reads the stream until the deadline, by setting the read timeout to the remaining time before each read,
+/// so that a client sending the request slowly can't hold the server longer than a stalled one.
+struct DeadlineReader<'a> {
+    stream: &'a TcpStream,
+    deadline: Instant,
+}
+
+impl Read for DeadlineReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let remaining = self.deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            return Err(std::io::Error::new(std::io::ErrorKind::TimedOut, "request timed out"));
+        }
+        let mut stream = self.stream;
+        stream.set_read_timeout(Some(remaining))?;
+        stream.read(buf)
+    }
+}
+
+/// Serves a single request on the connection.
+fn serve_connection(stream: TcpStream) -> std::io::Result<()> {
+    stream.set_write_timeout(Some(WRITE_TIMEOUT))?;
+    let reader = DeadlineReader {
+        stream: &stream,
+        deadline: Instant::now() + READ_TIMEOUT,
+    };
+    let mut reader = BufReader::new(reader.take((MAX_HEADER_SIZE + MAX_BODY_SIZE) as u64));
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next().unwrap_or_default().to_string();
+    let target = parts.next().unwrap_or_default();
+    let path = target.split('?').next().unwrap_or_default().to_string();
+
+    let mut content_length = 0;
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 || line.trim().is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            if name.trim().eq_ignore_ascii_case("content-length") {
+                content_length = value.trim().parse().unwrap_or(0);
+            }
+        }
+    }
+    let response = if content_length > MAX_BODY_SIZE {
+        Response::error(413, "body too large")
+    } else {
+        let mut body = vec![0; content_length];
+        reader.read_exact(&mut body)?;
+        handle(&method, &path, &String::from_utf8_lossy(&body))
+    };
+
+    let reason = match response.status {
+        200 => "OK",
+        204 => "No Content",
+        400 => "Bad Request",
+        404 => "Not Found",
+        413 => "Payload Too Large",
+        _ => "Service Unavailable",
+    };
+    let mut writer = &stream;
+    write!(
+        writer,
+        "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        response.status,
+        reason,
+        response.body.len(),
+        response.body
+    )?;
+    writer.flush()
+}
+
+fn percent_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut result = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' && i + 2 < bytes.len() {
+            let hex = std::str::from_utf8(&bytes[i + 1..i + 3]).unwrap_or_default();
+            if let Ok(x) = u8::from_str_radix(hex, 16) {
+                result.push(x);
+                i += 3;
+                continue;
+            }
+        }
+        result.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&result).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Read, Write};
+    use std::net::TcpStream;
+
+    #[test]
+    fn test_percent_decode() {
+        assert_eq!(super::percent_decode("/loggers/myapp%3A%3A"), "/loggers/myapp::");
+        assert_eq!(super::percent_decode("/loggers/100%"), "/loggers/100%");
+    }
+
+    #[test]
+    fn test_handle() {
+        assert_eq!(super::handle("GET", "/unknown", "").status, 404);
+        assert_eq!(super::handle("PUT", "/loggers/root", "verbose").status, 400);
+        assert_eq!(super::handle("POST", "/flush", "").status, 204);
+    }
+
+    #[test]
+    fn test_serve() {
+        let addr = super::serve("127.0.0.1:0").unwrap();
+        let mut stream = TcpStream::connect(addr).unwrap();
+        stream
+            .write_all(b"PUT /loggers/root HTTP/1.1\r\nContent-Length: 7\r\n\r\nverbose")
+            .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 400 Bad Request\r\n"), "{}", response);
+        assert!(response.ends_with(r#"{"error":"invalid level 'verbose'"}"#));
+    }
+}
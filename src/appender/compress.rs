@@ -0,0 +1,148 @@
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::{Path, PathBuf};
+
+use crate::config::BackupCompression;
+use crate::Error;
+
+/// Compresses the backup files of the file appender, by the codecs enabled by the cargo features.
+#[derive(Clone, Copy)]
+pub struct Compressor {
+    kind: BackupCompression,
+    level: i32,
+}
+
+impl Compressor {
+    pub fn new(kind: BackupCompression, level: Option<i32>) -> Result<Self, Error> {
+        let (default_level, levels, feature_enabled) = match kind {
+            BackupCompression::Gzip => (6, 0..=9, cfg!(feature = "gzip")),
+            BackupCompression::Zstd => (3, 1..=22, cfg!(feature = "zstd")),
+        };
+        if !feature_enabled {
+            return Err(Error::from(format!(
+                "compress_backups '{}' requires the cargo feature of the same name",
+                kind.name()
+            )));
+        }
+        let level = level.unwrap_or(default_level);
+        if !levels.contains(&level) {
+            return Err(Error::from(format!(
+                "compression_level of '{}' should be in {}..={}",
+                kind.name(),
+                levels.start(),
+                levels.end()
+            )));
+        }
+        Ok(Self { kind, level })
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self.kind {
+            BackupCompression::Gzip => ".gz",
+            BackupCompression::Zstd => ".zst",
+        }
+    }
+
+    /// Compresses the file to the path with the extension appended, then removes it.
+    /// The output is written to a temporary file first, so a partial output is never taken as a backup;
+    /// it takes the permissions of the file, so that the `file_mode` of the appender is kept.
+    pub fn compress(&self, src: &Path) -> std::io::Result<()> {
+        let dst = append_to_path(src, self.extension());
+        let tmp = append_to_path(&dst, ".tmp");
+        let input = BufReader::new(File::open(src)?);
+        let output = File::create(&tmp)?;
+        output.set_permissions(input.get_ref().metadata()?.permissions())?;
+        self.encode(input, output)?.sync_all()?;
+        std::fs::rename(&tmp, &dst)?;
+        std::fs::remove_file(src)
+    }
+
+    fn encode(&self, mut input: impl Read, output: File) -> std::io::Result<File> {
+        match self.kind {
+            #[cfg(feature = "gzip")]
+            BackupCompression::Gzip => {
+                let level = flate2::Compression::new(self.level as u32);
+                let mut encoder = flate2::write::GzEncoder::new(output, level);
+                std::io::copy(&mut input, &mut encoder)?;
+                encoder.finish()
+            }
+            #[cfg(feature = "zstd")]
+            BackupCompression::Zstd => {
+                let mut encoder = zstd::Encoder::new(output, self.level)?;
+                std::io::copy(&mut input, &mut encoder)?;
+                encoder.finish()
+            }
+            #[allow(unreachable_patterns)]
+            _ => {
+                let _ = (&mut input, output, self.level);
+                unreachable!("checked by Compressor::new()")
+            }
+        }
+    }
+}
+
+pub fn append_to_path(path: &Path, suffix: &str) -> PathBuf {
+    let mut path = path.as_os_str().to_os_string();
+    path.push(suffix);
+    path.into()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::BackupCompression;
+
+    #[test]
+    fn test_new() {
+        assert_eq!(
+            super::Compressor::new(BackupCompression::Gzip, None).is_ok(),
+            cfg!(feature = "gzip")
+        );
+        assert_eq!(
+            super::Compressor::new(BackupCompression::Zstd, Some(19)).is_ok(),
+            cfg!(feature = "zstd")
+        );
+        assert!(super::Compressor::new(BackupCompression::Zstd, Some(0)).is_err());
+        assert!(super::Compressor::new(BackupCompression::Gzip, Some(10)).is_err());
+    }
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn test_compress_zstd() {
+        use std::io::{Read, Write};
+
+        let content = "line\n".repeat(100);
+        std::fs::File::create("__test_compress.log")
+            .unwrap()
+            .write_all(content.as_bytes())
+            .unwrap();
+        let compressor = super::Compressor::new(BackupCompression::Zstd, None).unwrap();
+        compressor.compress("__test_compress.log".as_ref()).unwrap();
+        assert!(!std::path::Path::new("__test_compress.log").exists());
+
+        let file = std::fs::File::open("__test_compress.log.zst").unwrap();
+        let mut decompressed = String::new();
+        zstd::Decoder::new(file).unwrap().read_to_string(&mut decompressed).unwrap();
+        assert_eq!(decompressed, content);
+        std::fs::remove_file("__test_compress.log.zst").unwrap();
+    }
+
+    #[cfg(feature = "gzip")]
+    #[test]
+    fn test_compress_gzip() {
+        use std::io::{Read, Write};
+
+        let content = "line\n".repeat(100);
+        std::fs::File::create("__test_compress_gzip.log")
+            .unwrap()
+            .write_all(content.as_bytes())
+            .unwrap();
+        let compressor = super::Compressor::new(BackupCompression::Gzip, Some(9)).unwrap();
+        compressor.compress("__test_compress_gzip.log".as_ref()).unwrap();
+
+        let file = std::fs::File::open("__test_compress_gzip.log.gz").unwrap();
+        let mut decompressed = String::new();
+        flate2::read::GzDecoder::new(file).read_to_string(&mut decompressed).unwrap();
+        assert_eq!(decompressed, content);
+        std::fs::remove_file("__test_compress_gzip.log.gz").unwrap();
+    }
+}
@@ -1,49 +1,219 @@
-use std::io::{stderr, Stderr, stdout, Stdout, Write};
+use std::io::{IsTerminal, stderr, Stderr, StderrLock, stdout, Stdout, StdoutLock, Write};
+use std::sync::Arc;
 
-use log::{LevelFilter, Record};
+use log::LevelFilter;
 
 use crate::{Datetime, Error};
-use crate::appender::Appender;
-use crate::config::ConsoleAppenderConfig;
+use crate::appender::{Appender, EncodedRecord};
+use crate::config::{ColorMode, ConsoleAppenderConfig};
 use crate::encoder::{self, Encoder};
+use crate::record::OwnedRecord;
+
+/// Turns off the colors of the `auto` mode if set to a non-empty value, see <https://no-color.org>.
+const NO_COLOR_ENV: &str = "NO_COLOR";
+/// Turns on the colors of the `auto` mode even if not a terminal, if set to a non-empty value other than `0`.
+const CLICOLOR_FORCE_ENV: &str = "CLICOLOR_FORCE";
 
 pub struct ConsoleAppender {
-    encoder: Box<dyn Encoder + Send>,
+    encoder: Arc<ConsoleEncoder>,
     stdout: Stdout,
     stderr: Stderr,
     stderr_level: LevelFilter,
 }
 
+/// Encodes the records by the encoder of the stream they are written to.
+struct ConsoleEncoder {
+    /// The encoders of stdout and stderr, which write the color codes only if used for the stream;
+    /// e.g. not when piped to a file.
+    stdout_encoder: Box<dyn Encoder + Send + Sync>,
+    stderr_encoder: Box<dyn Encoder + Send + Sync>,
+    stderr_level: LevelFilter,
+}
+
+impl Encoder for ConsoleEncoder {
+    fn encode(&self, datetime: &Datetime, record: &OwnedRecord, buf: &mut Vec<u8>) {
+        if record.level <= self.stderr_level {
+            self.stderr_encoder.encode(datetime, record, buf);
+        } else {
+            self.stdout_encoder.encode(datetime, record, buf);
+        }
+    }
+
+    fn is_binary(&self) -> bool {
+        self.stdout_encoder.is_binary()
+    }
+}
+
 impl TryFrom<&ConsoleAppenderConfig> for ConsoleAppender {
     type Error = Error;
 
     fn try_from(config: &ConsoleAppenderConfig) -> Result<Self, Self::Error> {
-        let encoder = encoder::from_config(&config.common.encoder)
-            .map_err(|e| e.concat("failed to create encoder"))?;
+        let stdout = stdout();
+        let stderr = stderr();
+        let no_color = env_flag(NO_COLOR_ENV, |value| !value.is_empty());
+        let force_color = env_flag(CLICOLOR_FORCE_ENV, |value| !value.is_empty() && value != "0");
+        // fall back to no colors if the console can't be switched to process them
+        let stdout_color = use_color(config.color, stdout.is_terminal(), no_color, force_color)
+            && (!stdout.is_terminal() || enable_virtual_terminal(&stdout));
+        let stderr_color = use_color(config.color, stderr.is_terminal(), no_color, force_color)
+            && (!stderr.is_terminal() || enable_virtual_terminal(&stderr));
+        let encoder = |color: bool| {
+            encoder::from_config_with_color(&config.common.encoder, color)
+                .map(|x| encoder::with_redaction(x, config.common.redact.as_ref()))
+                .map_err(|e| e.concat("failed to create encoder"))
+        };
+        let encoder = ConsoleEncoder {
+            stdout_encoder: encoder(stdout_color)?,
+            stderr_encoder: encoder(stderr_color)?,
+            stderr_level: config.stderr_level,
+        };
         Ok(Self {
-            encoder,
-            stdout: stdout(),
-            stderr: stderr(),
+            encoder: Arc::new(encoder),
+            stdout,
+            stderr,
             stderr_level: config.stderr_level,
         })
     }
 }
 
 impl Appender for ConsoleAppender {
-    fn append(&mut self, datetime: &Datetime, record: &Record) {
-        let s = self.encoder.encode(datetime, record);
-        let destination: &mut dyn Write = if record.level() <= self.stderr_level {
-            &mut self.stderr
-        } else {
-            &mut self.stdout
-        };
-        writeln!(destination, "{}", s).unwrap();
+    fn append(&mut self, datetime: &Datetime, record: &OwnedRecord) -> Result<(), Error> {
+        let to_stderr = record.level <= self.stderr_level;
+        let mut buffer = vec![];
+        self.encoder.encode_entry(datetime, record, &mut buffer);
+        self.write(to_stderr, &buffer)
+    }
+
+    fn append_batch(&mut self, records: &[(Datetime, Arc<OwnedRecord>)]) -> Result<(), Error> {
+        let entries: Vec<_> = records
+            .iter()
+            .map(|(datetime, record)| EncodedRecord::new(&*self.encoder, datetime, record))
+            .collect();
+        self.append_encoded(&entries)
     }
 
-    fn flush(&mut self) {
-        self.stdout.flush().unwrap();
+    fn flush(&mut self) -> Result<(), Error> {
+        self.stdout
+            .flush()
+            .map_err(|e| Error::from(format!("failed to flush stdout: {}", e)))?;
         if self.stderr_level > LevelFilter::Off {
-            self.stderr.flush().unwrap();
+            self.stderr
+                .flush()
+                .map_err(|e| Error::from(format!("failed to flush stderr: {}", e)))?;
+        }
+        Ok(())
+    }
+
+    fn entry_encoder(&self) -> Option<Arc<dyn Encoder + Send + Sync>> {
+        Some(self.encoder.clone())
+    }
+
+    /// Writes the batch with the streams locked throughout, so that a burst of records
+    /// won't interleave with the other outputs of the program, and the locks are acquired only once.
+    fn append_encoded(&mut self, entries: &[EncodedRecord]) -> Result<(), Error> {
+        // the consecutive entries of the same stream are written together,
+        // with only that stream locked, as the program may lock both in the other order
+        let mut buffer = vec![];
+        let mut to_stderr = false;
+        for entry in entries {
+            let is_stderr = entry.level <= self.stderr_level;
+            if is_stderr != to_stderr && !buffer.is_empty() {
+                self.write(to_stderr, &buffer)?;
+                buffer.clear();
+            }
+            to_stderr = is_stderr;
+            buffer.extend_from_slice(&entry.content);
+        }
+        if !buffer.is_empty() {
+            self.write(to_stderr, &buffer)?;
+        }
+        Ok(())
+    }
+}
+
+impl ConsoleAppender {
+    /// Writes the whole content with the stream locked only once,
+    /// so that it won't interleave with the other outputs of the program.
+    fn write(&mut self, to_stderr: bool, content: &[u8]) -> Result<(), Error> {
+        if to_stderr {
+            write_stderr(&mut self.stderr.lock(), content)
+        } else {
+            write_stdout(&mut self.stdout.lock(), content)
         }
     }
 }
+
+fn write_stdout(stdout: &mut StdoutLock, content: &[u8]) -> Result<(), Error> {
+    stdout.write_all(content).map_err(|e| Error::from(format!("failed to write to stdout: {}", e)))
+}
+
+fn write_stderr(stderr: &mut StderrLock, content: &[u8]) -> Result<(), Error> {
+    stderr.write_all(content).map_err(|e| Error::from(format!("failed to write to stderr: {}", e)))
+}
+
+fn env_flag(name: &str, f: impl FnOnce(&str) -> bool) -> bool {
+    std::env::var_os(name).is_some_and(|value| f(&value.to_string_lossy()))
+}
+
+/// Decides whether to keep the colors of a stream; the environment variables only affect the `auto` mode,
+/// and `NO_COLOR` wins over `CLICOLOR_FORCE`.
+fn use_color(mode: ColorMode, is_terminal: bool, no_color: bool, force_color: bool) -> bool {
+    match mode {
+        ColorMode::Auto => !no_color && (force_color || is_terminal),
+        ColorMode::Always => true,
+        ColorMode::Never => false,
+    }
+}
+
+/// Enables the processing of the ANSI escape sequences by the Windows console (off by default
+/// in cmd.exe and the older PowerShell), so that the colors aren't printed as garbage;
+/// returns `false` if it's not supported, e.g. by the consoles before Windows 10.
+#[cfg(windows)]
+fn enable_virtual_terminal(stream: &impl std::os::windows::io::AsRawHandle) -> bool {
+    use std::ffi::c_void;
+
+    const ENABLE_VIRTUAL_TERMINAL_PROCESSING: u32 = 0x0004;
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn GetConsoleMode(console: *mut c_void, mode: *mut u32) -> i32;
+        fn SetConsoleMode(console: *mut c_void, mode: u32) -> i32;
+    }
+
+    let handle = stream.as_raw_handle();
+    let mut mode = 0;
+    // the handle of the standard stream stays valid for the whole program
+    unsafe {
+        if GetConsoleMode(handle, &mut mode) == 0 {
+            // not a console, e.g. the pipe of a terminal emulator like mintty, which processes the sequences itself
+            return true;
+        }
+        mode & ENABLE_VIRTUAL_TERMINAL_PROCESSING != 0
+            || SetConsoleMode(handle, mode | ENABLE_VIRTUAL_TERMINAL_PROCESSING) != 0
+    }
+}
+
+/// The terminals of the other platforms process the ANSI escape sequences.
+#[cfg(not(windows))]
+fn enable_virtual_terminal<S>(_stream: &S) -> bool {
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::ColorMode;
+
+    #[test]
+    fn test_use_color() {
+        use super::use_color;
+
+        assert!(use_color(ColorMode::Auto, true, false, false));
+        assert!(!use_color(ColorMode::Auto, false, false, false));
+        assert!(!use_color(ColorMode::Auto, true, true, false));
+        assert!(use_color(ColorMode::Auto, false, false, true));
+        assert!(!use_color(ColorMode::Auto, false, true, true));
+        assert!(use_color(ColorMode::Always, false, true, false));
+        assert!(!use_color(ColorMode::Never, true, false, true));
+    }
+
+}
@@ -0,0 +1,92 @@
+use std::collections::HashMap;
+use std::sync::{Arc, OnceLock};
+
+use crate::appender::Appender;
+use crate::config::CustomAppenderConfig;
+use crate::sync::Mutex;
+use crate::Error;
+
+type Factory = dyn Fn(&serde_json::Value) -> Result<Box<dyn Appender + Send>, Error> + Send + Sync;
+
+type Registry = Mutex<HashMap<String, Arc<Factory>>>;
+
+fn registry() -> &'static Registry {
+    static REGISTRY: OnceLock<Registry> = OnceLock::new();
+    REGISTRY.get_or_init(Default::default)
+}
+
+pub(crate) fn register<A, F>(kind: &str, factory: F)
+where
+    A: Appender + Send + 'static,
+    F: Fn(&serde_json::Value) -> Result<A, Error> + Send + Sync + 'static,
+{
+    let factory = move |properties: &serde_json::Value| -> Result<Box<dyn Appender + Send>, Error> {
+        Ok(Box::new(factory(properties)?))
+    };
+    registry().lock().insert(kind.to_string(), Arc::new(factory));
+}
+
+/// Creates the appender by the factory registered as the kind, with the other properties of the config.
+pub(crate) fn create(config: &CustomAppenderConfig) -> Result<Box<dyn Appender + Send>, Error> {
+    let factory = registry()
+        .lock()
+        .get(&config.name)
+        .cloned()
+        .ok_or_else(|| Error::from(format!("no appender kind registered as '{}'", config.name)))?;
+    // called with the registry unlocked, in case the factory registers another kind
+    factory(&serde_json::Value::Object(config.properties.clone()))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use serde::Deserialize;
+
+    use crate::appender::Appender;
+    use crate::config::AppenderConfig;
+    use crate::record::OwnedRecord;
+    use crate::sync::Mutex;
+    use crate::{Datetime, Error};
+
+    struct NopAppender;
+
+    impl Appender for NopAppender {
+        fn append(&mut self, _datetime: &Datetime, _record: &OwnedRecord) -> Result<(), Error> {
+            Ok(())
+        }
+
+        fn flush(&mut self) -> Result<(), Error> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_create() {
+        #[derive(Deserialize)]
+        struct Properties {
+            prefix: String,
+        }
+        let prefix = Arc::new(Mutex::new(None));
+        let prefix_clone = prefix.clone();
+        super::register("test_prefix", move |properties: &serde_json::Value| {
+            let properties = Properties::deserialize(properties).map_err(|e| Error::from(e.to_string()))?;
+            *prefix_clone.lock() = Some(properties.prefix);
+            Ok(NopAppender)
+        });
+        let custom_config = |s: &str| match serde_json::from_str(s).unwrap() {
+            AppenderConfig::Custom(config) => config,
+            _ => panic!("not a custom appender config"),
+        };
+
+        let config = custom_config(r#"{"kind": "custom", "name": "test_prefix", "prefix": ">> ", "queue_size": 10}"#);
+        assert_eq!(config.queue.queue_size, 10);
+        assert!(super::create(&config).is_ok());
+        assert_eq!(prefix.lock().as_deref(), Some(">> "));
+
+        let config = custom_config(r#"{"kind": "custom", "name": "test_prefix"}"#);
+        assert!(super::create(&config).is_err());
+        let config = custom_config(r#"{"kind": "custom", "name": "test_missing"}"#);
+        assert!(super::create(&config).is_err());
+    }
+}
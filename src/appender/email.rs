@@ -0,0 +1,385 @@
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpStream;
+use std::sync::mpsc::{channel, sync_channel, Receiver, RecvTimeoutError, Sender, SyncSender};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use log::Level;
+
+use crate::{Datetime, dropped, encoder, Error};
+use crate::appender::Appender;
+use crate::config::EmailAppenderConfig;
+use crate::encoder::Encoder;
+use crate::record::OwnedRecord;
+
+/// The maximum length of the first message in the subject, in characters.
+const MAX_SUBJECT_MESSAGE_LEN: usize = 80;
+
+enum Message {
+    Record(Arc<str>, String, Vec<u8>),
+    Flush(SyncSender<()>),
+}
+
+/// Mails the `error` records by SMTP; the records within the throttle interval after a mail
+/// are collected and sent together when the interval ends, by a mailer thread.
+/// Flushing sends the collected records at once, regardless of the throttle.
+pub struct EmailAppender {
+    encoder: Box<dyn Encoder + Send + Sync>,
+    sender: Sender<Message>,
+}
+
+impl TryFrom<&EmailAppenderConfig> for EmailAppender {
+    type Error = Error;
+
+    fn try_from(config: &EmailAppenderConfig) -> Result<Self, Self::Error> {
+        let encoder = encoder::from_config(&config.common.encoder)
+            .map_err(|e| e.concat("failed to create encoder"))?;
+        let encoder = encoder::with_redaction(encoder, config.common.redact.as_ref());
+        if config.to.is_empty() {
+            return Err(Error::from("to must not be empty"));
+        }
+        if config.max_records == 0 {
+            return Err(Error::from("max_records must be positive"));
+        }
+        if config.connect_timeout == 0 || config.write_timeout == 0 {
+            return Err(Error::from("connect_timeout and write_timeout must be positive"));
+        }
+        let mailer = Mailer {
+            address: format!("{}:{}", config.host, config.port),
+            hostname: crate::processor::hostname().unwrap_or_else(|| "localhost".to_string()),
+            from: config.from.clone(),
+            to: config.to.clone(),
+            subject: config.subject.clone(),
+            connect_timeout: Duration::from_secs(config.connect_timeout),
+            write_timeout: Duration::from_secs(config.write_timeout),
+        };
+        let throttle = Duration::from_secs(config.throttle);
+        let max_records = config.max_records;
+
+        let (sender, receiver) = channel();
+        std::thread::Builder::new()
+            .name("naive-logger-email".to_string())
+            .spawn(move || run(mailer, receiver, throttle, max_records))
+            .map_err(|e| Error::from(format!("failed to spawn mailer thread: {}", e)))?;
+        Ok(Self { encoder, sender })
+    }
+}
+
+impl Appender for EmailAppender {
+    fn append(&mut self, datetime: &Datetime, record: &OwnedRecord) -> Result<(), Error> {
+        if record.level != Level::Error {
+            return Ok(());
+        }
+        let mut line = vec![];
+        self.encoder.encode_entry(datetime, record, &mut line);
+        let _ = self.sender.send(Message::Record(record.target.clone(), record.message.clone(), line));
+        Ok(())
+    }
+
+    /// Sends the collected records and waits for it.
+    fn flush(&mut self) -> Result<(), Error> {
+        let (ack_sender, ack_receiver) = sync_channel(1);
+        if self.sender.send(Message::Flush(ack_sender)).is_ok() {
+            let _ = ack_receiver.recv();
+        }
+        Ok(())
+    }
+}
+
+/// The records collected for the next mail.
+#[derive(Default)]
+struct Batch {
+    /// The targets of all the records, to report if the mail fails.
+    targets: Vec<Arc<str>>,
+    first_message: String,
+    /// The encoded lines of at most `max_records` records.
+    lines: Vec<Vec<u8>>,
+}
+
+fn run(mailer: Mailer, receiver: Receiver<Message>, throttle: Duration, max_records: usize) {
+    let mut batch = Batch::default();
+    let mut last_sent: Option<Instant> = None;
+    // the time to send the batch, the end of the throttle interval
+    let mut deadline: Option<Instant> = None;
+    loop {
+        let message = match deadline {
+            None => receiver.recv().map_err(|_| RecvTimeoutError::Disconnected),
+            Some(deadline) => receiver.recv_timeout(deadline.saturating_duration_since(Instant::now())),
+        };
+        match message {
+            Ok(Message::Record(target, message, line)) => {
+                if batch.targets.is_empty() {
+                    batch.first_message = message;
+                    let now = Instant::now();
+                    deadline = Some(last_sent.map_or(now, |x| (x + throttle).max(now)));
+                }
+                batch.targets.push(target);
+                if batch.lines.len() < max_records {
+                    batch.lines.push(line);
+                }
+                continue;
+            }
+            Ok(Message::Flush(ack)) => {
+                send(&mailer, &mut batch, &mut last_sent);
+                let _ = ack.send(());
+            }
+            Err(RecvTimeoutError::Timeout) => send(&mailer, &mut batch, &mut last_sent),
+            Err(RecvTimeoutError::Disconnected) => {
+                send(&mailer, &mut batch, &mut last_sent);
+                return;
+            }
+        }
+        deadline = None;
+    }
+}
+
+/// Mails the batch; the records are dropped and reported if failed.
+fn send(mailer: &Mailer, batch: &mut Batch, last_sent: &mut Option<Instant>) {
+    if batch.targets.is_empty() {
+        return;
+    }
+    *last_sent = Some(Instant::now());
+    if mailer.send(batch).is_err() {
+        for target in &batch.targets {
+            dropped::count(target, 1);
+        }
+    }
+    *batch = Batch::default();
+}
+
+/// A minimal SMTP client, sending each mail on a new connection.
+struct Mailer {
+    /// `host:port`
+    address: String,
+    hostname: String,
+    from: String,
+    to: Vec<String>,
+    subject: String,
+    connect_timeout: Duration,
+    write_timeout: Duration,
+}
+
+impl Mailer {
+    fn send(&self, batch: &Batch) -> std::io::Result<()> {
+        let stream = super::net::connect(&self.address, self.connect_timeout, self.write_timeout)?;
+        stream.set_read_timeout(Some(self.write_timeout))?;
+        let mut session = Session {
+            reader: BufReader::new(stream.try_clone()?),
+            writer: stream,
+        };
+        session.expect("220")?;
+        session.command(&format!("EHLO {}", self.hostname), "250")?;
+        session.command(&format!("MAIL FROM:<{}>", self.from), "250")?;
+        for to in &self.to {
+            session.command(&format!("RCPT TO:<{}>", to), "25")?;
+        }
+        session.command("DATA", "354")?;
+        session.writer.write_all(&self.compose(batch))?;
+        session.command(".", "250")?;
+        let _ = session.command("QUIT", "221");
+        Ok(())
+    }
+
+    /// Composes the headers and the body, with the lines dot-stuffed and ended by CRLF.
+    fn compose(&self, batch: &Batch) -> Vec<u8> {
+        let mut mail = format!(
+            "From: <{}>\r\nTo: {}\r\nSubject: {}\r\nDate: {}\r\nMIME-Version: 1.0\r\n\
+             Content-Type: text/plain; charset=utf-8\r\nContent-Transfer-Encoding: 8bit\r\n\r\n",
+            self.from,
+            self.to.iter().map(|x| format!("<{}>", x)).collect::<Vec<_>>().join(", "),
+            encode_header(&self.subject(batch)),
+            chrono::Local::now().to_rfc2822()
+        )
+        .into_bytes();
+
+        let mut body: Vec<u8> = batch.lines.concat();
+        let omitted = batch.targets.len() - batch.lines.len();
+        if omitted > 0 {
+            body.extend_from_slice(format!("(and {} more records)\n", omitted).as_bytes());
+        }
+        for line in body.split(|&x| x == b'\n').filter(|x| !x.is_empty()) {
+            let line = line.strip_suffix(b"\r").unwrap_or(line);
+            if line.starts_with(b".") {
+                mail.push(b'.');
+            }
+            mail.extend_from_slice(line);
+            mail.extend_from_slice(b"\r\n");
+        }
+        mail
+    }
+
+    fn subject(&self, batch: &Batch) -> String {
+        let message: String = batch
+            .first_message
+            .lines()
+            .next()
+            .unwrap_or_default()
+            .chars()
+            .take(MAX_SUBJECT_MESSAGE_LEN)
+            .collect();
+        self.subject
+            .replace("{program}", &crate::program_name())
+            .replace("{hostname}", &self.hostname)
+            .replace("{count}", &batch.targets.len().to_string())
+            .replace("{message}", &message)
+    }
+}
+
+struct Session {
+    reader: BufReader<TcpStream>,
+    writer: TcpStream,
+}
+
+impl Session {
+    fn command(&mut self, command: &str, expected: &str) -> std::io::Result<()> {
+        self.writer.write_all(command.as_bytes())?;
+        self.writer.write_all(b"\r\n")?;
+        self.expect(expected)
+    }
+
+    /// Reads a reply, which may have multiple lines like `250-...`; fails unless its code starts with the prefix.
+    fn expect(&mut self, prefix: &str) -> std::io::Result<()> {
+        loop {
+            let mut line = String::new();
+            if self.reader.read_line(&mut line)? == 0 {
+                return Err(std::io::ErrorKind::UnexpectedEof.into());
+            }
+            if !line.starts_with(prefix) {
+                return Err(std::io::Error::other(format!("unexpected reply '{}'", line.trim())));
+            }
+            if line.as_bytes().get(3) != Some(&b'-') {
+                return Ok(());
+            }
+        }
+    }
+}
+
+/// Encodes the header value by RFC 2047 if it's not ASCII.
+fn encode_header(value: &str) -> String {
+    if value.is_ascii() {
+        return value.to_string();
+    }
+    const TABLE: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut encoded = String::from("=?UTF-8?B?");
+    for chunk in value.as_bytes().chunks(3) {
+        let n = chunk.iter().enumerate().fold(0u32, |n, (i, &x)| n | ((x as u32) << (16 - 8 * i)));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(TABLE[((n >> (18 - 6 * i)) & 0x3f) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded.push_str("?=");
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+
+    use log::{Level, RecordBuilder};
+
+    use crate::appender::Appender;
+    use crate::config::{AppenderCommonProperties, EmailAppenderConfig, EncoderConfig, PatternEncoderConfig};
+    use crate::encoder::tests::*;
+    use crate::record::OwnedRecord;
+
+    #[test]
+    fn test_encode_header() {
+        assert_eq!(super::encode_header("plain"), "plain");
+        assert_eq!(super::encode_header("日志"), "=?UTF-8?B?5pel5b+X?=");
+        assert_eq!(super::encode_header("é"), "=?UTF-8?B?w6k=?=");
+    }
+
+    /// Accepts the mails, and returns the recipients and the data of each.
+    fn serve(listener: TcpListener, n: usize) -> Vec<(Vec<String>, String)> {
+        let mut mails = vec![];
+        for _ in 0..n {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            stream.write_all(b"220 localhost ESMTP\r\n").unwrap();
+            let (mut recipients, mut data) = (vec![], String::new());
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                if let Some(to) = line.strip_prefix("RCPT TO:") {
+                    recipients.push(to.trim().to_string());
+                }
+                match line.trim_end() {
+                    "DATA" => {
+                        stream.write_all(b"354 go ahead\r\n").unwrap();
+                        loop {
+                            let mut line = String::new();
+                            reader.read_line(&mut line).unwrap();
+                            if line == ".\r\n" {
+                                break;
+                            }
+                            data.push_str(&line);
+                        }
+                        stream.write_all(b"250 queued\r\n").unwrap();
+                    }
+                    "QUIT" => {
+                        stream.write_all(b"221 bye\r\n").unwrap();
+                        break;
+                    }
+                    x if x.starts_with("EHLO") => stream.write_all(b"250-localhost\r\n250 8BITMIME\r\n").unwrap(),
+                    _ => stream.write_all(b"250 ok\r\n").unwrap(),
+                }
+            }
+            mails.push((recipients, data));
+        }
+        mails
+    }
+
+    #[test]
+    fn test_append_with_throttle() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = std::thread::spawn(move || serve(listener, 2));
+
+        let config = EmailAppenderConfig {
+            enabled: true,
+            queue: Default::default(),
+            filters: vec![],
+            common: AppenderCommonProperties {
+                encoder: EncoderConfig::Pattern(PatternEncoderConfig {
+                    pattern: "{message}".to_string(),
+                    ..Default::default()
+                }),
+                redact: None,
+            },
+            host: "127.0.0.1".to_string(),
+            port,
+            from: "app@example.com".to_string(),
+            to: vec!["ops@example.com".to_string(), "dev@example.com".to_string()],
+            subject: "{count} error(s): {message}".to_string(),
+            throttle: 60,
+            max_records: 1,
+            connect_timeout: 1,
+            write_timeout: 5,
+        };
+        let mut appender = super::EmailAppender::try_from(&config).unwrap();
+        let record = |level: Level, message: &str| {
+            let mut builder = RecordBuilder::new();
+            prepare_test_log_record(&mut builder);
+            OwnedRecord::from(&builder.level(level).args(format_args!("{}", message)).build())
+        };
+        appender.append(&test_datetime(), &record(Level::Error, "first")).unwrap();
+        appender.append(&test_datetime(), &record(Level::Info, "ignored")).unwrap();
+        // sent at once, then the following ones are held until the throttle interval ends or flushed
+        std::thread::sleep(std::time::Duration::from_millis(200));
+        appender.append(&test_datetime(), &record(Level::Error, ".second")).unwrap();
+        appender.append(&test_datetime(), &record(Level::Error, "third")).unwrap();
+        appender.flush().unwrap();
+
+        let mails = server.join().unwrap();
+        assert_eq!(mails[0].0, ["<ops@example.com>", "<dev@example.com>"]);
+        assert!(mails[0].1.contains("Subject: 1 error(s): first\r\n"));
+        assert!(mails[0].1.ends_with("\r\n\r\nfirst\r\n"));
+        assert!(mails[1].1.contains("Subject: 2 error(s): .second\r\n"));
+        assert!(mails[1].1.ends_with("\r\n\r\n..second\r\n(and 1 more records)\r\n"));
+    }
+}
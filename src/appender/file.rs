@@ -1,124 +1,963 @@
+use std::ffi::{OsStr, OsString};
 use std::fs::File;
-use std::io::{Seek, Write};
-use std::path::PathBuf;
+use std::io::{BufWriter, Seek, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Weak};
+use std::thread::JoinHandle;
+use std::time::{Duration, SystemTime};
 
-use log::Record;
+use chrono::format::{Item, StrftimeItems};
+use log::Level;
 
-use crate::{Datetime, encoder, Error};
-use crate::appender::Appender;
-use crate::config::FileAppenderConfig;
+use crate::{clock, Datetime, encoder, Error};
+use crate::appender::{Appender, EncodedRecord};
+use crate::appender::Compressor;
+use crate::appender::hook::RotateHook;
+use crate::config::{BackupNaming, CleanupConfig, FileAppenderConfig, SyncPolicy, Timezone};
 use crate::encoder::Encoder;
+use crate::record::OwnedRecord;
+use crate::sync::Mutex;
+
+/// Incremented by [`request_reopen`]; each appender reopens its file at the next write once it's changed.
+static REOPEN_GENERATION: AtomicU64 = AtomicU64::new(0);
+
+/// Requests all the file appenders to reopen their files at the next write, e.g. after rotated by logrotate.
+/// Only an atomic is touched, so it can be called from a signal handler.
+pub fn request_reopen() {
+    REOPEN_GENERATION.fetch_add(1, Ordering::Relaxed);
+}
+
+/// The suffix of the lock file of a shared log file.
+const LOCK_FILE_SUFFIX: &str = ".lock";
+
+/// The format of the timestamps of the backup names, without the characters invalid in the Windows file names.
+const BACKUP_TIMESTAMP_FORMAT: &str = "%Y-%m-%dT%H-%M-%S";
+/// The length of the timestamps formatted by [`BACKUP_TIMESTAMP_FORMAT`].
+const BACKUP_TIMESTAMP_LEN: usize = 19;
+
+/// The path of the log file with the `strftime` specifiers, formatted by the time of the records.
+pub(crate) struct PathPattern(String);
+
+impl PathPattern {
+    /// Gets the pattern of the config if set; either `path` or `path_pattern` should be set.
+    pub fn from_config(config: &FileAppenderConfig) -> Result<Option<Self>, Error> {
+        let Some(pattern) = &config.path_pattern else {
+            if config.path.as_os_str().is_empty() {
+                return Err(Error::from("either path or path_pattern should be set"));
+            }
+            return Ok(None);
+        };
+        if !config.path.as_os_str().is_empty() {
+            return Err(Error::from("path and path_pattern can't be set together"));
+        }
+        let pattern = pattern
+            .to_str()
+            .ok_or_else(|| Error::from("path_pattern should be valid UTF-8"))?;
+        if StrftimeItems::new(pattern).any(|x| matches!(x, Item::Error)) {
+            return Err(Error::from(format!("invalid path_pattern '{}'", pattern)));
+        }
+        Ok(Some(Self(pattern.to_string())))
+    }
+
+    pub fn format(&self, datetime: &Datetime) -> PathBuf {
+        PathBuf::from(datetime.format(&self.0).to_string())
+    }
+
+    /// The files in the directory of the current path matching the file name of the pattern,
+    /// with the specifiers as `*`, except the current path; their backups of the indexes are included.
+    fn matching_files(&self, current: &Path) -> Vec<PathBuf> {
+        let Some(name_pattern) = Path::new(&self.0).file_name().and_then(|x| x.to_str()) else {
+            return vec![];
+        };
+        let mut wildcard = vec![];
+        let mut chars = name_pattern.chars();
+        while let Some(c) = chars.next() {
+            if c != '%' {
+                wildcard.push(c);
+                continue;
+            }
+            // the padding modifiers and the widths, like `%-d` and `%3f`, are followed by the specifier
+            if chars.by_ref().find(|x| !matches!(x, '-' | '_' | '0'..='9' | ':' | '.')) == Some('%') {
+                wildcard.push('%');
+            } else if wildcard.last() != Some(&'*') {
+                wildcard.push('*');
+            }
+        }
+        wildcard.push('*');
+
+        let dir = match current.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => Path::new("."),
+        };
+        let Ok(entries) = std::fs::read_dir(dir) else {
+            return vec![];
+        };
+        let current_name = current.file_name();
+        entries
+            .flatten()
+            .filter(|entry| Some(entry.file_name().as_os_str()) != current_name)
+            .filter(|entry| !entry.file_name().to_string_lossy().ends_with(LOCK_FILE_SUFFIX))
+            .filter(|entry| {
+                let name = entry.file_name();
+                name.to_str()
+                    .is_some_and(|x| wildcard_match(&wildcard, &x.chars().collect::<Vec<_>>()))
+            })
+            .filter(|entry| entry.file_type().is_ok_and(|x| x.is_file()))
+            .map(|entry| current.with_file_name(entry.file_name()))
+            .collect()
+    }
+}
 
 pub struct FileAppender {
-    encoder: Box<dyn Encoder + Send>,
+    encoder: Arc<dyn Encoder + Send + Sync>,
+    /// The current path, formatted from the pattern if set.
     path: PathBuf,
-    filename: String,
-    file: File,
+    filename: OsString,
+    path_pattern: Option<PathPattern>,
+    /// The UTC timestamp in seconds when the pattern was last formatted,
+    /// so that it's formatted at most once per second.
+    pattern_formatted_secs: i64,
+    file: BufWriter<File>,
+    /// Whether the writes are kept in the buffer until flushed; otherwise flushed after each append.
+    buffered: bool,
+    /// Flushes after this number of records if buffered; `0` means no limit.
+    flush_every_n_records: usize,
+    unflushed_records: usize,
+    /// Whether the file is shared by multiple processes, see [`FileAppenderConfig::shared`].
+    shared: bool,
+    /// Whether to reopen the path if the file is moved away, see [`FileAppenderConfig::reopen`].
+    reopen: bool,
+    /// The UTC timestamp in seconds when the file was last checked for `reopen`.
+    reopen_checked_secs: i64,
+    /// The [`REOPEN_GENERATION`] seen; the file is reopened when it's changed.
+    reopen_generation: u64,
+    /// The symlink pointing at the current file, updated when switched.
+    symlink: Option<PathBuf>,
+    modes: Modes,
+    sync: SyncPolicy,
+    /// Whether written since the last sync, for the `interval` policy.
+    unsynced: bool,
+    on_rotate: Option<RotateHook>,
+    /// The length of the file including the buffered writes; of this process only if shared,
+    /// refreshed before the size is checked.
     file_len: u64,
     max_file_size: u64,
     max_backup_index: usize,
+    backup_naming: BackupNaming,
+    /// `0` means no limit.
+    max_total_size: u64,
+    /// In seconds; `0` means no limit.
+    max_backup_age: u64,
+    /// In seconds; `0` means no time-based rotation.
+    rotation_interval: i64,
+    /// The UTC timestamp in seconds of the last write, or the modification of the file if not written yet.
+    last_write_secs: i64,
+    compressor: Option<Compressor>,
+    /// The compression and the hook of the last backup, waited for before the next rotation,
+    /// so that the backup isn't renamed meanwhile.
+    compressing: Option<JoinHandle<()>>,
 }
 
-impl TryFrom<&FileAppenderConfig> for FileAppender {
+/// The timezone is the configured one, in which the initial file of the path pattern is named.
+impl TryFrom<(&FileAppenderConfig, Timezone)> for FileAppender {
     type Error = Error;
 
-    fn try_from(config: &FileAppenderConfig) -> Result<Self, Self::Error> {
+    fn try_from((config, timezone): (&FileAppenderConfig, Timezone)) -> Result<Self, Self::Error> {
         let encoder = encoder::from_config(&config.common.encoder)
             .map_err(|e| e.concat("failed to create encoder"))?;
+        let encoder = Arc::from(encoder::with_redaction(encoder, config.common.redact.as_ref()));
+        let compressor = config
+            .compress_backups
+            .map(|kind| Compressor::new(kind, config.compression_level))
+            .transpose()?;
 
-        match config.path.parent() {
-            None => {}
-            Some(dir) => {
-                std::fs::create_dir_all(dir)
-                    .map_err(|e| Error::from(format!("failed to prepare log directory: {}", e)))?;
-            }
-        }
-        let filename = config
-            .path
+        let on_rotate = config.on_rotate.as_ref().map(RotateHook::try_from).transpose()?;
+
+        let path_pattern = PathPattern::from_config(config)?;
+        let path = initial_path(config, path_pattern.as_ref(), timezone);
+        let filename = path
             .file_name()
             .ok_or_else(|| Error::from("failed to get file name from log path"))?
-            .to_str()
-            .ok_or_else(|| Error::from("filename contains invalid UTF-8"))?
-            .to_string();
-
-        let mut file = File::options()
-            .create(true)
-            .write(true)
-            .open(&config.path)
-            .map_err(|e| Error::from(format!("failed to open log file: {}", e)))?;
-        let file_len = file
-            .seek(std::io::SeekFrom::End(0))
-            .map_err(|e| Error::from(format!("failed to seek to the end of log file: {}", e)))?;
-
-        Ok(Self {
+            .to_os_string();
+
+        let modes = Modes {
+            file: config.file_mode,
+            dir: config.dir_mode,
+        };
+        let (file, file_len) = open(&path, config.shared, modes)?;
+        let last_write_secs = file
+            .metadata()
+            .and_then(|x| x.modified())
+            .unwrap_or_else(|_| SystemTime::now())
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map_or(0, |x| x.as_secs() as i64);
+
+        if let Some(symlink) = &config.create_symlink {
+            if symlink == &path || symlink.file_name().is_none() {
+                return Err(Error::from(format!("invalid create_symlink '{}'", symlink.display())));
+            }
+            update_symlink(symlink, &path);
+        }
+
+        let appender = Self {
             encoder,
-            path: config.path.clone(),
+            path,
             filename,
-            file,
+            path_pattern,
+            pattern_formatted_secs: 0,
+            file: BufWriter::new(file),
+            buffered: config.flush_interval > 0 || config.flush_every_n_records > 0,
+            flush_every_n_records: config.flush_every_n_records,
+            unflushed_records: 0,
+            shared: config.shared,
+            reopen: config.reopen,
+            reopen_checked_secs: 0,
+            reopen_generation: REOPEN_GENERATION.load(Ordering::Relaxed),
+            symlink: config.create_symlink.clone(),
+            modes,
+            sync: config.sync,
+            unsynced: false,
+            on_rotate,
             file_len,
             max_file_size: config.max_file_size,
             max_backup_index: config.max_backup_index,
-        })
+            backup_naming: config.backup_naming,
+            max_total_size: config.max_total_size,
+            max_backup_age: config.max_backup_age,
+            rotation_interval: config.rotation_interval as i64,
+            last_write_secs,
+            compressor,
+            compressing: None,
+        };
+        appender.enforce_retention();
+        Ok(appender)
     }
 }
 
 impl Appender for FileAppender {
-    fn append(&mut self, datetime: &Datetime, record: &Record) {
-        let content = self.encoder.encode(datetime, record);
-        self.rotate_if_needed(content.len() + 1);
-        writeln!(self.file, "{}", content).unwrap();
-        self.file_len += content.len() as u64 + 1;
+    fn append(&mut self, datetime: &Datetime, record: &OwnedRecord) -> Result<(), Error> {
+        self.append_encoded(&[EncodedRecord::new(&*self.encoder, datetime, record)])
+    }
+
+    fn append_batch(&mut self, records: &[(Datetime, Arc<OwnedRecord>)]) -> Result<(), Error> {
+        let entries: Vec<_> = records
+            .iter()
+            .map(|(datetime, record)| EncodedRecord::new(&*self.encoder, datetime, record))
+            .collect();
+        self.append_encoded(&entries)
     }
 
-    fn flush(&mut self) {
-        self.file.flush().unwrap();
+    fn flush(&mut self) -> Result<(), Error> {
+        self.unflushed_records = 0;
+        self.file
+            .flush()
+            .map_err(|e| Error::from(format!("failed to write log file '{}': {}", self.path.display(), e)))
+    }
+
+    fn entry_encoder(&self) -> Option<Arc<dyn Encoder + Send + Sync>> {
+        Some(self.encoder.clone())
+    }
+
+    fn append_encoded(&mut self, entries: &[EncodedRecord]) -> Result<(), Error> {
+        if let Some(entry) = entries.first() {
+            self.reopen_if_needed(&entry.datetime);
+        }
+        for entry in entries {
+            self.rotate_if_needed(entry.content.len(), &entry.datetime)?;
+            self.write(&entry.content)?;
+            self.file_len += entry.content.len() as u64;
+            self.last_write_secs = entry.datetime.timestamp();
+        }
+        self.flush_if_needed(entries.len())?;
+        self.sync_if_needed(entries.iter().any(|entry| entry.level == Level::Error))
+    }
+}
+
+impl Drop for FileAppender {
+    fn drop(&mut self) {
+        self.wait_for_compression();
     }
 }
 
 impl FileAppender {
+    fn write(&mut self, buffer: &[u8]) -> Result<(), Error> {
+        self.file
+            .write_all(buffer)
+            .map_err(|e| Error::from(format!("failed to write log file '{}': {}", self.path.display(), e)))
+    }
+
+    /// Flushes after the records are written, unless buffered and the limit is not reached.
+    fn flush_if_needed(&mut self, records: usize) -> Result<(), Error> {
+        self.unflushed_records += records;
+        if !self.buffered || (self.flush_every_n_records > 0 && self.unflushed_records >= self.flush_every_n_records) {
+            return self.flush();
+        }
+        Ok(())
+    }
+
+    /// Syncs the file to the disk after the records are written, by the policy.
+    fn sync_if_needed(&mut self, has_error: bool) -> Result<(), Error> {
+        match self.sync {
+            SyncPolicy::None => Ok(()),
+            SyncPolicy::EveryRecord => self.sync(),
+            SyncPolicy::OnError if has_error => self.sync(),
+            SyncPolicy::OnError => Ok(()),
+            SyncPolicy::Interval(_) => {
+                self.unsynced = true;
+                Ok(())
+            }
+        }
+    }
+
+    /// Flushes the buffered writes and syncs the data of the file to the disk.
+    fn sync(&mut self) -> Result<(), Error> {
+        self.flush()?;
+        self.unsynced = false;
+        self.file
+            .get_ref()
+            .sync_data()
+            .map_err(|e| Error::from(format!("failed to sync log file '{}': {}", self.path.display(), e)))
+    }
+
+    /// The backup path by the time of the rotation, like `app.log.2024-07-31T12-00-00`;
+    /// suffixed by a counter if the one of the same second exists, e.g. by the rotations of `max_file_size`.
+    fn timestamped_backup_path(&self, datetime: &Datetime) -> PathBuf {
+        let mut filename = self.filename.clone();
+        filename.push(format!(".{}", datetime.format(BACKUP_TIMESTAMP_FORMAT)));
+        let path = self.path.with_file_name(&filename);
+        let exists = |path: &Path| {
+            path.exists()
+                || self
+                    .compressor
+                    .is_some_and(|x| super::compress::append_to_path(path, x.extension()).exists())
+        };
+        if !exists(&path) {
+            return path;
+        }
+        (1..)
+            .map(|i| super::compress::append_to_path(&path, &format!(".{}", i)))
+            .find(|x| !exists(x))
+            .unwrap()
+    }
+
+    /// The existing timestamped backups, from the oldest to the newest.
+    fn timestamped_backups(&self) -> Vec<PathBuf> {
+        let dir = match self.path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => Path::new("."),
+        };
+        let Ok(entries) = std::fs::read_dir(dir) else {
+            return vec![];
+        };
+        let mut paths: Vec<_> = entries
+            .flatten()
+            .filter(|entry| is_timestamped_backup(&self.filename, &entry.file_name()))
+            .map(|entry| self.path.with_file_name(entry.file_name()))
+            .collect();
+        // the timestamps are ordered as the names
+        paths.sort();
+        paths
+    }
+
+    /// Deletes the oldest timestamped backups beyond `max_backup_index + 1`, keeping the new one.
+    fn remove_excess_backups(&self, new_backup: &Path) {
+        let backups: Vec<_> = self
+            .timestamped_backups()
+            .into_iter()
+            .filter(|x| x != new_backup)
+            .collect();
+        let excess = (backups.len() + 1).saturating_sub(self.max_backup_index + 1);
+        for path in &backups[..excess] {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+
     fn backup_file_path(&self, index: usize) -> PathBuf {
-        self.path
-            .with_file_name(format!("{}.{}", self.filename, index))
+        let mut filename = self.filename.clone();
+        filename.push(format!(".{}", index));
+        self.path.with_file_name(filename)
     }
-    fn rotate_if_needed(&mut self, reserve_len: usize) {
-        if self.max_file_size == 0 || self.file_len + reserve_len as u64 <= self.max_file_size {
+
+    /// The backup file paths of the index, compressed or not;
+    /// a backup is left uncompressed if the compression failed.
+    fn backup_file_paths(&self, index: usize) -> Vec<PathBuf> {
+        let path = self.backup_file_path(index);
+        match &self.compressor {
+            None => vec![path],
+            Some(compressor) => {
+                let compressed = super::compress::append_to_path(&path, compressor.extension());
+                vec![path, compressed]
+            }
+        }
+    }
+
+    fn wait_for_compression(&mut self) {
+        if let Some(handle) = self.compressing.take() {
+            let _ = handle.join();
+        }
+    }
+
+    fn rotation(&mut self, reserve_len: usize, datetime: &Datetime) -> Rotation {
+        if let Some(pattern) = &self.path_pattern {
+            let secs = datetime.timestamp();
+            if secs != self.pattern_formatted_secs {
+                self.pattern_formatted_secs = secs;
+                let path = pattern.format(datetime);
+                if path != self.path {
+                    return Rotation::Switch(path);
+                }
+            }
+        }
+        if self.shared && self.max_file_size > 0 {
+            // the other processes write to the file as well
+            if let Ok(metadata) = self.file.get_ref().metadata() {
+                self.file_len = metadata.len() + self.file.buffer().len() as u64;
+            }
+        }
+        if self.max_file_size > 0 && self.file_len + reserve_len as u64 > self.max_file_size {
+            return Rotation::Backup;
+        }
+        if self.rotation_interval > 0 && self.file_len > 0 {
+            // the intervals are aligned in the timezone of the record, e.g. `1d` rotates at the local midnight
+            let offset = datetime.offset().local_minus_utc() as i64;
+            let interval = |secs: i64| (secs + offset).div_euclid(self.rotation_interval);
+            if interval(datetime.timestamp()) != interval(self.last_write_secs) {
+                return Rotation::Backup;
+            }
+        }
+        Rotation::None
+    }
+
+    fn rotate_if_needed(&mut self, reserve_len: usize, datetime: &Datetime) -> Result<(), Error> {
+        let rotation = self.rotation(reserve_len, datetime);
+        self.rotate(rotation, datetime)
+    }
+
+    fn rotate(&mut self, rotation: Rotation, datetime: &Datetime) -> Result<(), Error> {
+        match rotation {
+            Rotation::None => {}
+            Rotation::Backup => self.backup(datetime)?,
+            Rotation::Switch(path) => self.switch(path),
+        }
+        Ok(())
+    }
+
+    /// Switches to the file of the path formatted from the pattern; kept if it can't be opened.
+    fn switch(&mut self, path: PathBuf) {
+        let Some(filename) = path.file_name() else {
             return;
+        };
+        if let Ok((file, file_len)) = open(&path, self.shared, self.modes) {
+            let _ = self.file.flush();
+            self.filename = filename.to_os_string();
+            self.path = path;
+            self.file = BufWriter::new(file);
+            self.file_len = file_len;
+            if let Some(symlink) = &self.symlink {
+                update_symlink(symlink, &self.path);
+            }
+            self.enforce_retention();
         }
+    }
 
-        let last_backup_file_path = self.backup_file_path(self.max_backup_index);
-        if last_backup_file_path.exists() {
-            std::fs::remove_file(&last_backup_file_path).unwrap();
+    /// Renames the file to the backup, shifting the older backups by the indexes, and starts a new file.
+    /// If failed, the records keep being written to the current file, and the rotation is tried again by the next one.
+    fn backup(&mut self, datetime: &Datetime) -> Result<(), Error> {
+        // written before renamed, so that the compression gets the complete file
+        let _ = self.file.flush();
+        let _lock = if self.shared {
+            let lock = lock_rotation(&self.path);
+            if !self.is_current_file() {
+                // rotated by another process; follows the new file instead of rotating again
+                self.reopen_file();
+                return Ok(());
+            }
+            lock
+        } else {
+            None
+        };
+        self.wait_for_compression();
+        let rotate_error = |e: std::io::Error| {
+            Error::from(format!("failed to rotate log file '{}': {}", self.path.display(), e))
+        };
+
+        let dst = match self.backup_naming {
+            BackupNaming::Index => {
+                for last_backup_file_path in self.backup_file_paths(self.max_backup_index) {
+                    if last_backup_file_path.exists() {
+                        std::fs::remove_file(&last_backup_file_path).map_err(rotate_error)?;
+                    }
+                }
+                for i in (0..self.max_backup_index).rev() {
+                    for (src, dst) in self.backup_file_paths(i).into_iter().zip(self.backup_file_paths(i + 1)) {
+                        if src.exists() {
+                            std::fs::rename(src, dst).map_err(rotate_error)?;
+                        }
+                    }
+                }
+                self.backup_file_path(0)
+            }
+            BackupNaming::Timestamp => self.timestamped_backup_path(datetime),
+        };
+        std::fs::rename(&self.path, &dst).map_err(rotate_error)?;
+        if self.backup_naming == BackupNaming::Timestamp {
+            self.remove_excess_backups(&dst);
+        }
+        if self.compressor.is_some() || self.on_rotate.is_some() {
+            // compressed in the background so that the appends won't be stalled;
+            // the backup is kept uncompressed if it fails
+            let compressor = self.compressor;
+            let on_rotate = self.on_rotate.clone();
+            let path = self.path.clone();
+            self.compressing = std::thread::Builder::new()
+                .name("naive-logger-rotate".to_string())
+                .spawn(move || {
+                    let mut backup = dst;
+                    if let Some(compressor) = compressor {
+                        if compressor.compress(&backup).is_ok() {
+                            backup = super::compress::append_to_path(&backup, compressor.extension());
+                        }
+                    }
+                    if let Some(on_rotate) = on_rotate {
+                        on_rotate.run(&backup, &path);
+                    }
+                })
+                .ok();
         }
 
-        for i in (0..self.max_backup_index).rev() {
-            let src = self.backup_file_path(i);
-            let dst = self.backup_file_path(i + 1);
-            if src.exists() {
-                std::fs::rename(src, dst).unwrap();
+        // the records keep being written to the renamed file if the new one can't be created
+        if self.shared {
+            // the other processes may have created it by then
+            let (file, file_len) = open(&self.path, true, self.modes)?;
+            self.file = BufWriter::new(file);
+            self.file_len = file_len;
+        } else {
+            let file = File::options()
+                .create_new(true)
+                .write(true)
+                .open(&self.path)
+                .map_err(rotate_error)?;
+            set_file_mode(&file, self.modes.file).map_err(rotate_error)?;
+            self.file = BufWriter::new(file);
+            self.file_len = 0;
+        }
+        self.enforce_retention();
+        Ok(())
+    }
+
+    /// Reopens the file if requested by [`request_reopen`], or if it's moved away with `reopen` enabled.
+    fn reopen_if_needed(&mut self, datetime: &Datetime) {
+        let generation = REOPEN_GENERATION.load(Ordering::Relaxed);
+        let mut requested = generation != self.reopen_generation;
+        self.reopen_generation = generation;
+        if !requested && self.reopen {
+            let secs = datetime.timestamp();
+            if secs != self.reopen_checked_secs {
+                self.reopen_checked_secs = secs;
+                requested = !self.is_current_file();
             }
         }
+        if requested {
+            self.reopen_file();
+        }
+    }
+
+    /// Opens the path again, creating it if moved away; the current file is kept if it can't be opened.
+    fn reopen_file(&mut self) {
+        let _ = self.file.flush();
+        if let Ok((file, file_len)) = open(&self.path, self.shared, self.modes) {
+            self.file = BufWriter::new(file);
+            self.file_len = file_len;
+        }
+    }
 
-        let dst = self.backup_file_path(0);
-        std::fs::rename(&self.path, dst).unwrap();
+    /// Whether the path still refers to the opened file, rather than a new one after a rotation.
+    #[cfg(unix)]
+    fn is_current_file(&self) -> bool {
+        use std::os::unix::fs::MetadataExt;
 
-        self.file = File::options()
-            .create_new(true)
-            .write(true)
-            .open(&self.path)
-            .unwrap();
-        self.file_len = 0;
+        match (std::fs::metadata(&self.path), self.file.get_ref().metadata()) {
+            (Ok(a), Ok(b)) => a.dev() == b.dev() && a.ino() == b.ino(),
+            _ => false,
+        }
+    }
+
+    /// The files can't be identified without the unstable APIs; only the file moved away is detected.
+    #[cfg(not(unix))]
+    fn is_current_file(&self) -> bool {
+        self.path.exists()
+    }
+
+    /// The existing backups: the ones of the indexes, and the other files matching the path pattern if set.
+    fn backup_files(&self) -> Vec<PathBuf> {
+        let mut paths: Vec<PathBuf> = match self.backup_naming {
+            BackupNaming::Index => (0..=self.max_backup_index)
+                .flat_map(|i| self.backup_file_paths(i))
+                .filter(|x| x.exists())
+                .collect(),
+            BackupNaming::Timestamp => self.timestamped_backups(),
+        };
+        if let Some(pattern) = &self.path_pattern {
+            paths.extend(pattern.matching_files(&self.path));
+            paths.sort();
+            paths.dedup();
+        }
+        paths
+    }
+
+    /// Deletes the backups modified more than `max_backup_age` ago, and the oldest ones
+    /// exceeding `max_total_size` together with the log file; the backups failed to be inspected are kept.
+    fn enforce_retention(&self) {
+        if self.max_total_size == 0 && self.max_backup_age == 0 {
+            return;
+        }
+        let mut backups: Vec<_> = self
+            .backup_files()
+            .into_iter()
+            .filter_map(|path| {
+                let metadata = std::fs::metadata(&path).ok()?;
+                let modified = metadata.modified().ok()?;
+                Some((modified, metadata.len(), path))
+            })
+            .collect();
+        // the newest first, so that the oldest ones are deleted once the budget is exceeded
+        backups.sort_by_key(|x| std::cmp::Reverse(x.0));
+        let now = SystemTime::now();
+        let max_age = Duration::from_secs(self.max_backup_age);
+        let mut total_size = self.file_len;
+        for (modified, len, path) in backups {
+            total_size += len;
+            let expired = self.max_backup_age > 0 && now.duration_since(modified).unwrap_or_default() > max_age;
+            let exceeded = self.max_total_size > 0 && total_size > self.max_total_size;
+            if expired || exceeded {
+                let _ = std::fs::remove_file(path);
+            }
+        }
+    }
+}
+
+/// Spawns a thread flushing the buffered writes of the appender periodically, until the appender is dropped.
+pub fn spawn_flusher(appender: Weak<Mutex<FileAppender>>, interval: Duration) -> Result<(), Error> {
+    std::thread::Builder::new()
+        .name("naive-logger-flush".to_string())
+        .spawn(move || loop {
+            std::thread::sleep(interval);
+            let Some(appender) = appender.upgrade() else {
+                return;
+            };
+            let mut appender = appender.lock();
+            if appender.unflushed_records > 0 {
+                // the errors are left to the next write, as the appends do
+                let _ = appender.file.flush();
+                appender.unflushed_records = 0;
+            }
+        })
+        .map(|_| ())
+        .map_err(|e| Error::from(format!("failed to spawn flusher thread: {}", e)))
+}
+
+/// Spawns a thread syncing the writes of the appender to the disk periodically, until the appender is dropped.
+pub fn spawn_syncer(appender: Weak<Mutex<FileAppender>>, interval: Duration) -> Result<(), Error> {
+    std::thread::Builder::new()
+        .name("naive-logger-sync".to_string())
+        .spawn(move || loop {
+            std::thread::sleep(interval);
+            let Some(appender) = appender.upgrade() else {
+                return;
+            };
+            let mut appender = appender.lock();
+            if appender.unsynced {
+                // the errors are left to the next write, as the appends do
+                let _ = appender.sync();
+            }
+        })
+        .map(|_| ())
+        .map_err(|e| Error::from(format!("failed to spawn syncer thread: {}", e)))
+}
+
+enum Rotation {
+    None,
+    /// Renames the file to the backup `.0`.
+    Backup,
+    /// Switches to another file formatted from the path pattern.
+    Switch(PathBuf),
+}
+
+/// The permission bits of the log files and the directories created for them, see [`FileAppenderConfig::file_mode`].
+#[derive(Clone, Copy, Default)]
+struct Modes {
+    file: Option<u32>,
+    dir: Option<u32>,
+}
+
+/// Opens the file for appending, creating it and its directory if not existing; returns it with its length.
+/// If shared, it's opened in the append mode, so that the writes of the processes are not interleaved.
+fn open(path: &Path, shared: bool, modes: Modes) -> Result<(File, u64), Error> {
+    if let Some(dir) = path.parent() {
+        create_dirs(dir, modes.dir).map_err(|e| Error::from(format!("failed to prepare log directory: {}", e)))?;
+    }
+    let mut file = File::options()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .append(shared)
+        .open(path)
+        .map_err(|e| Error::from(format!("failed to open log file: {}", e)))?;
+    set_file_mode(&file, modes.file).map_err(|e| Error::from(format!("failed to set log file mode: {}", e)))?;
+    let file_len = file
+        .seek(std::io::SeekFrom::End(0))
+        .map_err(|e| Error::from(format!("failed to seek to the end of log file: {}", e)))?;
+    Ok((file, file_len))
+}
+
+/// Creates the directory and its missing ancestors, setting the mode of the created ones if specified.
+fn create_dirs(dir: &Path, mode: Option<u32>) -> std::io::Result<()> {
+    let missing: Vec<&Path> = dir
+        .ancestors()
+        .take_while(|x| !x.as_os_str().is_empty() && !x.exists())
+        .collect();
+    std::fs::create_dir_all(dir)?;
+    #[cfg(unix)]
+    if let Some(mode) = mode {
+        use std::os::unix::fs::PermissionsExt;
+
+        // the outermost first, so that the inner ones are still accessible if the mode is restrictive
+        for dir in missing.into_iter().rev() {
+            std::fs::set_permissions(dir, std::fs::Permissions::from_mode(mode))?;
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = (missing, mode);
+    Ok(())
+}
+
+/// Sets the mode of the file if specified; it's applied exactly, regardless of the umask.
+fn set_file_mode(file: &File, mode: Option<u32>) -> std::io::Result<()> {
+    #[cfg(unix)]
+    if let Some(mode) = mode {
+        use std::os::unix::fs::PermissionsExt;
+
+        file.set_permissions(std::fs::Permissions::from_mode(mode))?;
+    }
+    #[cfg(not(unix))]
+    let _ = (file, mode);
+    Ok(())
+}
+
+/// Points the symlink at the file, replacing the existing one atomically; the errors are ignored,
+/// as the symlink is only a convenience (e.g. it can't be created without the privilege on Windows).
+/// The target is relative if they are in the same directory, so that the directory can be moved.
+fn update_symlink(symlink: &Path, target: &Path) {
+    let target = if target.parent() == symlink.parent() {
+        target.file_name().map_or(target, Path::new)
+    } else {
+        target
+    };
+    let mut tmp_name = symlink.file_name().unwrap_or_default().to_os_string();
+    tmp_name.push(".tmp");
+    let tmp = symlink.with_file_name(tmp_name);
+    let _ = std::fs::remove_file(&tmp);
+    #[cfg(unix)]
+    let result = std::os::unix::fs::symlink(target, &tmp);
+    #[cfg(windows)]
+    let result = std::os::windows::fs::symlink_file(target, &tmp);
+    #[cfg(not(any(unix, windows)))]
+    let result: std::io::Result<()> = Err(std::io::ErrorKind::Unsupported.into());
+    if result.is_ok() && std::fs::rename(&tmp, symlink).is_err() {
+        let _ = std::fs::remove_file(&tmp);
+    }
+}
+
+/// Takes the advisory lock serializing the rotations of the processes sharing the file, released when dropped.
+/// The rotation goes on unlocked if the lock file can't be opened or locked.
+fn lock_rotation(path: &Path) -> Option<File> {
+    let mut filename = path.file_name()?.to_os_string();
+    filename.push(LOCK_FILE_SUFFIX);
+    let file = File::options()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(path.with_file_name(filename))
+        .ok()?;
+    file.lock().ok()?;
+    Some(file)
+}
+
+/// The path of the file written first: the path, or the path pattern formatted by the current time in the timezone.
+fn initial_path(config: &FileAppenderConfig, pattern: Option<&PathPattern>, timezone: Timezone) -> PathBuf {
+    match pattern {
+        None => config.path.clone(),
+        Some(pattern) => pattern.format(&clock::now(timezone)),
+    }
+}
+
+/// The paths of the files written first by the file appenders, which are never deleted by the cleanups;
+/// the ones with the invalid path patterns are skipped, as they fail to be created anyway.
+pub(crate) fn initial_paths<'a, I: Iterator<Item = &'a FileAppenderConfig>>(configs: I, timezone: Timezone) -> Vec<PathBuf> {
+    configs
+        .filter_map(|config| {
+            let pattern = PathPattern::from_config(config).ok()?;
+            Some(initial_path(config, pattern.as_ref(), timezone))
+        })
+        .collect()
+}
+
+/// Checks that the cleanup pattern is a file name pattern, so that only the files in the log directory are deleted.
+pub(crate) fn validate_cleanup_pattern(pattern: &str) -> Result<(), Error> {
+    if pattern.is_empty() || pattern.contains(['/', '\\']) || pattern == "." || pattern == ".." {
+        return Err(Error::from(format!(
+            "invalid cleanup pattern '{}': expecting a file name pattern without the path separators",
+            pattern
+        )));
+    }
+    Ok(())
+}
+
+/// Deletes the stale files matching the cleanup pattern of the file appender before it's created,
+/// except the log files of all the file appenders (`log_files`, see [`initial_paths`]) and their backups,
+/// as they may share the directory. The files which can't be inspected or deleted are ignored,
+/// as the cleanup is best-effort.
+pub(crate) fn cleanup_stale_files(config: &FileAppenderConfig, timezone: Timezone, log_files: &[PathBuf]) -> Result<(), Error> {
+    let Some(cleanup) = &config.cleanup else {
+        return Ok(());
+    };
+    validate_cleanup_pattern(&cleanup.pattern)?;
+    let path = initial_path(config, PathPattern::from_config(config)?.as_ref(), timezone);
+    cleanup_dir(log_dir(&path), cleanup, log_files);
+    Ok(())
+}
+
+fn log_dir(path: &Path) -> &Path {
+    match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    }
+}
+
+fn cleanup_dir(dir: &Path, config: &CleanupConfig, log_files: &[PathBuf]) {
+    let name_pattern: Vec<char> = config.pattern.chars().collect();
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+    // the names of the log files in the directory
+    let canonical_dir = std::fs::canonicalize(dir).ok();
+    let filenames: Vec<&OsStr> = log_files
+        .iter()
+        .filter(|path| std::fs::canonicalize(log_dir(path)).ok() == canonical_dir)
+        .filter_map(|path| path.file_name())
+        .collect();
+    let is_log_file = |name: &OsStr| {
+        filenames.iter().any(|filename| {
+            if name == *filename {
+                return true;
+            }
+            let (Some(name), Some(filename)) = (name.to_str(), filename.to_str()) else {
+                return false;
+            };
+            // the backups may have the extensions of the compression, e.g. `app.log.1.zst`
+            let indexed = name
+                .strip_prefix(filename)
+                .and_then(|x| x.strip_prefix('.'))
+                .and_then(|x| x.split('.').next())
+                .is_some_and(|x| x.parse::<usize>().is_ok());
+            indexed || is_timestamped_backup(filename.as_ref(), name.as_ref())
+        })
+    };
+
+    let mut files = vec![];
+    for entry in entries.flatten() {
+        let name = entry.file_name();
+        let Some(name_str) = name.to_str() else {
+            continue;
+        };
+        if !wildcard_match(&name_pattern, &name_str.chars().collect::<Vec<_>>()) {
+            continue;
+        }
+        if is_log_file(&name) {
+            continue;
+        }
+        let Ok(metadata) = entry.metadata() else {
+            continue;
+        };
+        if !metadata.is_file() {
+            continue;
+        }
+        let modified = metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+        files.push((modified, entry.path()));
+    }
+
+    // the newest first, so that the ones to keep are at the front
+    files.sort_by_key(|x| std::cmp::Reverse(x.0));
+    let now = SystemTime::now();
+    for (modified, path) in files.into_iter().skip(config.max_count.unwrap_or(0)) {
+        if let Some(days) = config.max_age_days {
+            let age = now.duration_since(modified).unwrap_or_default();
+            if age <= Duration::from_secs(days * 24 * 60 * 60) {
+                continue;
+            }
+        }
+        let _ = std::fs::remove_file(path);
     }
 }
 
+/// Whether the file name is of a timestamped backup of the log file name, e.g. `app.log.2024-07-31T12-00-00`,
+/// optionally with a counter and the extension of the compression, like `app.log.2024-07-31T12-00-00.1.zst`.
+fn is_timestamped_backup(filename: &OsStr, name: &OsStr) -> bool {
+    let (Some(filename), Some(name)) = (filename.to_str(), name.to_str()) else {
+        return false;
+    };
+    let Some(rest) = name.strip_prefix(filename).and_then(|x| x.strip_prefix('.')) else {
+        return false;
+    };
+    let (Some(timestamp), Some(suffix)) = (rest.get(..BACKUP_TIMESTAMP_LEN), rest.get(BACKUP_TIMESTAMP_LEN..)) else {
+        return false;
+    };
+    (suffix.is_empty() || suffix.starts_with('.'))
+        && !suffix.ends_with(".tmp")
+        && chrono::NaiveDateTime::parse_from_str(timestamp, BACKUP_TIMESTAMP_FORMAT).is_ok()
+}
+
+/// Matches the name against the pattern with the wildcards `*` (any characters) and `?` (one character).
+fn wildcard_match(pattern: &[char], name: &[char]) -> bool {
+    let (mut p, mut n) = (0, 0);
+    // the position of the last `*` in the pattern and the name position it's matched up to
+    let mut backtrack = None;
+    while n < name.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == name[n]) {
+            p += 1;
+            n += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            backtrack = Some((p, n));
+            p += 1;
+        } else if let Some((star, matched)) = backtrack {
+            backtrack = Some((star, matched + 1));
+            p = star + 1;
+            n = matched + 1;
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|&x| x == '*')
+}
+
 #[cfg(test)]
 mod tests {
     use std::fs::File;
     use std::io::{Read, Write};
+    use std::path::Path;
+    use std::sync::Arc;
 
-    use crate::config::{EncoderConfig, JsonEncoderConfig};
+    use log::RecordBuilder;
+
+    use crate::appender::Appender;
+    use crate::config::{BackupNaming, EncoderConfig, JsonEncoderConfig, PatternEncoderConfig, SyncPolicy, Timezone};
+    use crate::encoder::tests::*;
+    use crate::record::OwnedRecord;
 
     #[test]
     fn test_rotate() {
+        let rotated = Arc::new(crate::sync::Mutex::new(vec![]));
         {
             for i in 0..=3 {
                 let mut f = File::options()
@@ -138,17 +977,44 @@ mod tests {
             writeln!(file, "file be rotated").unwrap();
 
             let mut appender = super::FileAppender {
-                encoder: super::encoder::from_config(&EncoderConfig::Json(JsonEncoderConfig))
-                    .unwrap(),
+                encoder: super::encoder::from_config(&EncoderConfig::Json(JsonEncoderConfig::default()))
+                    .unwrap()
+                    .into(),
                 path: "__test.log".into(),
-                filename: "__test.log".to_string(),
-                file,
+                filename: "__test.log".into(),
+                path_pattern: None,
+                pattern_formatted_secs: 0,
+                file: std::io::BufWriter::new(file),
+                buffered: false,
+                flush_every_n_records: 0,
+                unflushed_records: 0,
+                shared: false,
+                reopen: false,
+                reopen_checked_secs: 0,
+                reopen_generation: 0,
+                symlink: None,
+                modes: Default::default(),
+                sync: SyncPolicy::None,
+                unsynced: false,
+                on_rotate: Some(super::RotateHook::Custom({
+                    let rotated = rotated.clone();
+                    Arc::new(move |backup: &std::path::Path| rotated.lock().push(backup.to_path_buf()))
+                })),
                 file_len: 1024,
                 max_file_size: 1024,
                 max_backup_index: 3,
+                backup_naming: BackupNaming::Index,
+                max_total_size: 0,
+                max_backup_age: 0,
+                rotation_interval: 0,
+                last_write_secs: 0,
+                compressor: None,
+                compressing: None,
             };
-            appender.rotate_if_needed(1);
+            appender.rotate_if_needed(1, &test_datetime()).unwrap();
         }
+        // the hook is waited for when the appender is dropped
+        assert_eq!(*rotated.lock(), [std::path::PathBuf::from("__test.log.0")]);
 
         let mut content = String::new();
         File::open("__test.log")
@@ -176,4 +1042,775 @@ mod tests {
             std::fs::remove_file(format!("__test.log.{}", i)).unwrap();
         }
     }
+
+    #[test]
+    fn test_append_batch() {
+        {
+            let file = File::options()
+                .create(true)
+                .write(true)
+                .truncate(true)
+                .open("__test_batch.log")
+                .unwrap();
+            let mut appender = super::FileAppender {
+                encoder: super::encoder::from_config(&EncoderConfig::Pattern(PatternEncoderConfig {
+                    pattern: "{message}".to_string(),
+                    ..Default::default()
+                }))
+                .unwrap()
+                .into(),
+                path: "__test_batch.log".into(),
+                filename: "__test_batch.log".into(),
+                path_pattern: None,
+                pattern_formatted_secs: 0,
+                file: std::io::BufWriter::new(file),
+                buffered: false,
+                flush_every_n_records: 0,
+                unflushed_records: 0,
+                shared: false,
+                reopen: false,
+                reopen_checked_secs: 0,
+                reopen_generation: 0,
+                symlink: None,
+                modes: Default::default(),
+                sync: SyncPolicy::None,
+                unsynced: false,
+                on_rotate: None,
+                file_len: 0,
+                max_file_size: 8,
+                max_backup_index: 0,
+                backup_naming: BackupNaming::Index,
+                max_total_size: 0,
+                max_backup_age: 0,
+                rotation_interval: 0,
+                last_write_secs: 0,
+                compressor: None,
+                compressing: None,
+            };
+            let datetime = test_datetime();
+            let records: Vec<_> = ["aaa", "bbb", "ccc"]
+                .into_iter()
+                .map(|message| {
+                    let mut builder = RecordBuilder::new();
+                    prepare_test_log_record(&mut builder);
+                    let record = OwnedRecord::from(&builder.args(format_args!("{}", message)).build());
+                    (datetime, Arc::new(record))
+                })
+                .collect();
+            appender.append_batch(&records).unwrap();
+        }
+
+        let mut content = String::new();
+        File::open("__test_batch.log")
+            .unwrap()
+            .read_to_string(&mut content)
+            .unwrap();
+        assert_eq!(content, "ccc\n");
+        content.clear();
+        File::open("__test_batch.log.0")
+            .unwrap()
+            .read_to_string(&mut content)
+            .unwrap();
+        assert_eq!(content, "aaa\nbbb\n");
+
+        std::fs::remove_file("__test_batch.log").unwrap();
+        std::fs::remove_file("__test_batch.log.0").unwrap();
+    }
+
+    #[test]
+    fn test_rotate_by_interval() {
+        {
+            let file = File::options()
+                .create(true)
+                .write(true)
+                .truncate(true)
+                .open("__test_interval.log")
+                .unwrap();
+            let mut appender = super::FileAppender {
+                encoder: super::encoder::from_config(&EncoderConfig::Pattern(PatternEncoderConfig {
+                    pattern: "{message}".to_string(),
+                    ..Default::default()
+                }))
+                .unwrap()
+                .into(),
+                path: "__test_interval.log".into(),
+                filename: "__test_interval.log".into(),
+                path_pattern: None,
+                pattern_formatted_secs: 0,
+                file: std::io::BufWriter::new(file),
+                buffered: false,
+                flush_every_n_records: 0,
+                unflushed_records: 0,
+                shared: false,
+                reopen: false,
+                reopen_checked_secs: 0,
+                reopen_generation: 0,
+                symlink: None,
+                modes: Default::default(),
+                sync: SyncPolicy::None,
+                unsynced: false,
+                on_rotate: None,
+                file_len: 0,
+                max_file_size: 1024,
+                max_backup_index: 0,
+                backup_naming: BackupNaming::Index,
+                max_total_size: 0,
+                max_backup_age: 0,
+                rotation_interval: 60 * 60,
+                last_write_secs: 0,
+                compressor: None,
+                compressing: None,
+            };
+            let datetime = test_datetime();
+            let records: Vec<_> = [("aaa", 1), ("bbb", 2), ("ccc", 60 * 60 + 1)]
+                .into_iter()
+                .map(|(message, secs)| {
+                    let mut builder = RecordBuilder::new();
+                    prepare_test_log_record(&mut builder);
+                    let record = OwnedRecord::from(&builder.args(format_args!("{}", message)).build());
+                    (datetime + chrono::Duration::seconds(secs), Arc::new(record))
+                })
+                .collect();
+            appender.append_batch(&records).unwrap();
+        }
+
+        let mut content = String::new();
+        File::open("__test_interval.log")
+            .unwrap()
+            .read_to_string(&mut content)
+            .unwrap();
+        assert_eq!(content, "ccc\n");
+        content.clear();
+        File::open("__test_interval.log.0")
+            .unwrap()
+            .read_to_string(&mut content)
+            .unwrap();
+        assert_eq!(content, "aaa\nbbb\n");
+
+        std::fs::remove_file("__test_interval.log").unwrap();
+        std::fs::remove_file("__test_interval.log.0").unwrap();
+    }
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn test_rotate_compressed() {
+        use crate::config::BackupCompression;
+
+        let _ = std::fs::remove_file("__test_compressed.log.1.zst");
+        {
+            let file = File::options()
+                .create(true)
+                .write(true)
+                .truncate(true)
+                .open("__test_compressed.log")
+                .unwrap();
+            let mut appender = super::FileAppender {
+                encoder: super::encoder::from_config(&EncoderConfig::Pattern(PatternEncoderConfig {
+                    pattern: "{message}".to_string(),
+                    ..Default::default()
+                }))
+                .unwrap()
+                .into(),
+                path: "__test_compressed.log".into(),
+                filename: "__test_compressed.log".into(),
+                path_pattern: None,
+                pattern_formatted_secs: 0,
+                file: std::io::BufWriter::new(file),
+                buffered: false,
+                flush_every_n_records: 0,
+                unflushed_records: 0,
+                shared: false,
+                reopen: false,
+                reopen_checked_secs: 0,
+                reopen_generation: 0,
+                symlink: None,
+                modes: Default::default(),
+                sync: SyncPolicy::None,
+                unsynced: false,
+                on_rotate: None,
+                file_len: 0,
+                max_file_size: 4,
+                max_backup_index: 1,
+                backup_naming: BackupNaming::Index,
+                max_total_size: 0,
+                max_backup_age: 0,
+                rotation_interval: 0,
+                last_write_secs: 0,
+                compressor: Some(super::Compressor::new(BackupCompression::Zstd, None).unwrap()),
+                compressing: None,
+            };
+            for message in ["aaa", "bbb", "ccc"] {
+                let mut builder = RecordBuilder::new();
+                prepare_test_log_record(&mut builder);
+                let record = OwnedRecord::from(&builder.args(format_args!("{}", message)).build());
+                appender.append(&test_datetime(), &record).unwrap();
+            }
+        }
+
+        for (path, expected) in [("__test_compressed.log.0.zst", "bbb\n"), ("__test_compressed.log.1.zst", "aaa\n")] {
+            let mut content = String::new();
+            zstd::Decoder::new(File::open(path).unwrap())
+                .unwrap()
+                .read_to_string(&mut content)
+                .unwrap();
+            assert_eq!(content, expected);
+            std::fs::remove_file(path).unwrap();
+        }
+        assert!(!std::path::Path::new("__test_compressed.log.0").exists());
+        std::fs::remove_file("__test_compressed.log").unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_non_utf8_path() {
+        use std::ffi::OsStr;
+        use std::os::unix::ffi::OsStrExt;
+        use std::path::PathBuf;
+
+        use crate::config::{AppenderCommonProperties, FileAppenderConfig};
+
+        let path = PathBuf::from(OsStr::from_bytes(b"__test_\xff.log"));
+        let config = FileAppenderConfig {
+            common: AppenderCommonProperties {
+                encoder: EncoderConfig::Json(JsonEncoderConfig::default()),
+                redact: None,
+            },
+            path: path.clone(),
+            ..Default::default()
+        };
+        let appender = super::FileAppender::try_from((&config, Timezone::Local)).unwrap();
+        assert_eq!(
+            appender.backup_file_path(1),
+            PathBuf::from(OsStr::from_bytes(b"__test_\xff.log.1"))
+        );
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_path_pattern() {
+        use crate::config::{AppenderCommonProperties, FileAppenderConfig};
+
+        let dir = std::path::Path::new("__test_pattern");
+        let _ = std::fs::remove_dir_all(dir);
+        let mut config = FileAppenderConfig {
+            common: AppenderCommonProperties {
+                encoder: EncoderConfig::Pattern(PatternEncoderConfig {
+                    pattern: "{message}".to_string(),
+                    ..Default::default()
+                }),
+                redact: None,
+            },
+            path: "app.log".into(),
+            path_pattern: Some(dir.join("app-%Y-%m-%d.log")),
+            ..Default::default()
+        };
+        assert!(super::FileAppender::try_from((&config, Timezone::Local)).is_err());
+        config.path = Default::default();
+        config.path_pattern = Some("app-%Q.log".into());
+        assert!(super::FileAppender::try_from((&config, Timezone::Local)).is_err());
+
+        config.path_pattern = Some(dir.join("app-%Y-%m-%d.log"));
+        {
+            let mut appender = super::FileAppender::try_from((&config, Timezone::Local)).unwrap();
+            let datetime = test_datetime();
+            for (message, days) in [("aaa", 0), ("bbb", 0), ("ccc", 1)] {
+                let mut builder = RecordBuilder::new();
+                prepare_test_log_record(&mut builder);
+                let record = OwnedRecord::from(&builder.args(format_args!("{}", message)).build());
+                appender.append(&(datetime + chrono::Duration::days(days)), &record).unwrap();
+            }
+        }
+
+        let datetime = test_datetime();
+        for (days, expected) in [(0, "aaa\nbbb\n"), (1, "ccc\n")] {
+            let path = dir.join(format!("app-{}.log", (datetime + chrono::Duration::days(days)).format("%Y-%m-%d")));
+            assert_eq!(std::fs::read_to_string(path).unwrap(), expected);
+        }
+        std::fs::remove_dir_all(dir).unwrap();
+
+        // the initial file is named in the configured timezone; the two are always on different dates
+        #[cfg(feature = "tz")]
+        for tz in [chrono_tz::Pacific::Kiritimati, chrono_tz::Etc::GMTPlus12] {
+            let timezone = Timezone::Named(tz);
+            drop(super::FileAppender::try_from((&config, timezone)).unwrap());
+            let expected = format!("app-{}.log", crate::clock::now(timezone).format("%Y-%m-%d"));
+            let names: Vec<_> = std::fs::read_dir(dir).unwrap().map(|x| x.unwrap().file_name()).collect();
+            assert_eq!(names, [expected.as_str()]);
+            std::fs::remove_dir_all(dir).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_enforce_retention() {
+        use std::path::Path;
+        use std::time::{Duration, SystemTime};
+
+        use crate::config::{AppenderCommonProperties, FileAppenderConfig};
+
+        let dir = Path::new("__test_retention");
+        let _ = std::fs::remove_dir_all(dir);
+        std::fs::create_dir(dir).unwrap();
+        let now = SystemTime::now();
+        // the backups of the indexes and the ones of the pattern, from the newest to the oldest, 10 bytes each
+        let names = ["app.log.0", "app.log.1", "app-1.log", "app.log.2", "app-0.log.gz", "other.log"];
+        for (i, name) in names.iter().enumerate() {
+            std::fs::write(dir.join(name), "0123456789").unwrap();
+            let file = File::options().write(true).open(dir.join(name)).unwrap();
+            file.set_modified(now - Duration::from_secs((i as u64 + 1) * 24 * 60 * 60 - 60)).unwrap();
+        }
+        let list = || {
+            let mut names: Vec<_> = std::fs::read_dir(dir)
+                .unwrap()
+                .map(|x| x.unwrap().file_name().into_string().unwrap())
+                .collect();
+            names.sort();
+            names
+        };
+
+        let mut config = FileAppenderConfig {
+            common: AppenderCommonProperties {
+                encoder: EncoderConfig::Json(JsonEncoderConfig::default()),
+                redact: None,
+            },
+            path: dir.join("app.log"),
+            max_backup_index: 2,
+            max_backup_age: 4 * 24 * 60 * 60,
+            ..Default::default()
+        };
+        // the ones not of the indexes are not backups without the pattern
+        drop(super::FileAppender::try_from((&config, Timezone::Local)).unwrap());
+        assert_eq!(list(), ["app-0.log.gz", "app-1.log", "app.log", "app.log.0", "app.log.1", "app.log.2", "other.log"]);
+
+        config.path = Default::default();
+        config.path_pattern = Some(dir.join("app-%Y.log"));
+        let appender = super::FileAppender::try_from((&config, Timezone::Local)).unwrap();
+        std::fs::remove_file(&appender.path).unwrap();
+        drop(appender);
+        assert_eq!(list(), ["app-1.log", "app.log", "app.log.0", "app.log.1", "app.log.2", "other.log"]);
+
+        config.path = dir.join("app.log");
+        config.path_pattern = None;
+        config.max_backup_age = 0;
+        config.max_total_size = 25;
+        drop(super::FileAppender::try_from((&config, Timezone::Local)).unwrap());
+        assert_eq!(list(), ["app-1.log", "app.log", "app.log.0", "app.log.1", "other.log"]);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_flush_every_n_records() {
+        use crate::config::{AppenderCommonProperties, FileAppenderConfig};
+
+        let path = std::path::PathBuf::from("__test_buffered.log");
+        let config = FileAppenderConfig {
+            common: AppenderCommonProperties {
+                encoder: EncoderConfig::Pattern(PatternEncoderConfig {
+                    pattern: "{message}".to_string(),
+                    ..Default::default()
+                }),
+                redact: None,
+            },
+            path: path.clone(),
+            flush_every_n_records: 3,
+            ..Default::default()
+        };
+        let mut appender = super::FileAppender::try_from((&config, Timezone::Local)).unwrap();
+        let datetime = test_datetime();
+        let mut append = |message: &str| {
+            let mut builder = RecordBuilder::new();
+            prepare_test_log_record(&mut builder);
+            let record = OwnedRecord::from(&builder.args(format_args!("{}", message)).build());
+            appender.append(&datetime, &record).unwrap();
+        };
+        append("aaa");
+        append("bbb");
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "");
+        append("ccc");
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "aaa\nbbb\nccc\n");
+        append("ddd");
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "aaa\nbbb\nccc\n");
+        appender.flush().unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "aaa\nbbb\nccc\nddd\n");
+
+        drop(appender);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_sync_on_error() {
+        use crate::config::{AppenderCommonProperties, FileAppenderConfig};
+
+        let path = std::path::PathBuf::from("__test_sync.log");
+        let config = FileAppenderConfig {
+            common: AppenderCommonProperties {
+                encoder: EncoderConfig::Pattern(PatternEncoderConfig {
+                    pattern: "{message}".to_string(),
+                    ..Default::default()
+                }),
+                redact: None,
+            },
+            path: path.clone(),
+            flush_every_n_records: 100,
+            sync: SyncPolicy::OnError,
+            ..Default::default()
+        };
+        let mut appender = super::FileAppender::try_from((&config, Timezone::Local)).unwrap();
+        let datetime = test_datetime();
+        let mut append = |level: log::Level, message: &str| {
+            let mut builder = RecordBuilder::new();
+            prepare_test_log_record(&mut builder);
+            let record = OwnedRecord::from(&builder.level(level).args(format_args!("{}", message)).build());
+            appender.append(&datetime, &record).unwrap();
+        };
+        append(log::Level::Info, "aaa");
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "");
+        // the buffered records are written and synced together
+        append(log::Level::Error, "bbb");
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "aaa\nbbb\n");
+
+        drop(appender);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_shared() {
+        use crate::config::{AppenderCommonProperties, FileAppenderConfig};
+
+        let dir = std::path::Path::new("__test_shared");
+        let _ = std::fs::remove_dir_all(dir);
+        let config = FileAppenderConfig {
+            common: AppenderCommonProperties {
+                encoder: EncoderConfig::Pattern(PatternEncoderConfig {
+                    pattern: "{message}".to_string(),
+                    ..Default::default()
+                }),
+                redact: None,
+            },
+            path: dir.join("app.log"),
+            max_file_size: 20,
+            max_backup_index: 9,
+            shared: true,
+            ..Default::default()
+        };
+        // as if by two processes
+        let mut a = super::FileAppender::try_from((&config, Timezone::Local)).unwrap();
+        let mut b = super::FileAppender::try_from((&config, Timezone::Local)).unwrap();
+        let datetime = test_datetime();
+        let mut expected = vec![];
+        for i in 0..10 {
+            for (name, appender) in [("a", &mut a), ("b", &mut b)] {
+                let message = format!("{}{}", name, i);
+                let mut builder = RecordBuilder::new();
+                prepare_test_log_record(&mut builder);
+                let record = OwnedRecord::from(&builder.args(format_args!("{}", message)).build());
+                appender.append(&datetime, &record).unwrap();
+                expected.push(message);
+            }
+        }
+        drop((a, b));
+
+        let mut lines = vec![];
+        for entry in std::fs::read_dir(dir).unwrap() {
+            let path = entry.unwrap().path();
+            if path.extension().is_some_and(|x| x == "lock") {
+                continue;
+            }
+            let content = std::fs::read_to_string(&path).unwrap();
+            // rotated by the size including the writes of the other
+            assert!(content.len() <= 20, "{}: {:?}", path.display(), content);
+            lines.extend(content.lines().map(str::to_string));
+        }
+        lines.sort();
+        expected.sort();
+        assert_eq!(lines, expected);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_reopen() {
+        use crate::config::{AppenderCommonProperties, FileAppenderConfig};
+
+        let dir = std::path::Path::new("__test_reopen");
+        let _ = std::fs::remove_dir_all(dir);
+        let config = FileAppenderConfig {
+            common: AppenderCommonProperties {
+                encoder: EncoderConfig::Pattern(PatternEncoderConfig {
+                    pattern: "{message}".to_string(),
+                    ..Default::default()
+                }),
+                redact: None,
+            },
+            path: dir.join("app.log"),
+            reopen: true,
+            ..Default::default()
+        };
+        let mut appender = super::FileAppender::try_from((&config, Timezone::Local)).unwrap();
+        let datetime = test_datetime();
+        let mut append = |message: &str, secs: i64| {
+            let mut builder = RecordBuilder::new();
+            prepare_test_log_record(&mut builder);
+            let record = OwnedRecord::from(&builder.args(format_args!("{}", message)).build());
+            appender.append(&(datetime + chrono::Duration::seconds(secs)), &record).unwrap();
+        };
+        append("aaa", 0);
+        // moved by logrotate; detected by the next check a second later
+        std::fs::rename(dir.join("app.log"), dir.join("app.log.1")).unwrap();
+        append("bbb", 0);
+        append("ccc", 1);
+        assert_eq!(std::fs::read_to_string(dir.join("app.log.1")).unwrap(), "aaa\nbbb\n");
+        assert_eq!(std::fs::read_to_string(dir.join("app.log")).unwrap(), "ccc\n");
+
+        // reopened on request at once, without waiting for the next check
+        std::fs::rename(dir.join("app.log"), dir.join("app.log.2")).unwrap();
+        std::fs::write(dir.join("app.log"), "").unwrap();
+        super::request_reopen();
+        append("ddd", 1);
+        assert_eq!(std::fs::read_to_string(dir.join("app.log")).unwrap(), "ddd\n");
+
+        drop(appender);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_create_symlink() {
+        use crate::config::{AppenderCommonProperties, FileAppenderConfig};
+
+        let dir = std::path::Path::new("__test_symlink");
+        let _ = std::fs::remove_dir_all(dir);
+        let config = FileAppenderConfig {
+            common: AppenderCommonProperties {
+                encoder: EncoderConfig::Pattern(PatternEncoderConfig {
+                    pattern: "{message}".to_string(),
+                    ..Default::default()
+                }),
+                redact: None,
+            },
+            path: Default::default(),
+            path_pattern: Some(dir.join("app-%Y-%m-%d.log")),
+            create_symlink: Some(dir.join("current.log")),
+            ..Default::default()
+        };
+        let mut appender = super::FileAppender::try_from((&config, Timezone::Local)).unwrap();
+        let datetime = test_datetime();
+        let mut builder = RecordBuilder::new();
+        prepare_test_log_record(&mut builder);
+        let record = OwnedRecord::from(&builder.args(format_args!("aaa")).build());
+        appender.append(&datetime, &record).unwrap();
+
+        let name = format!("app-{}.log", datetime.format("%Y-%m-%d"));
+        assert_eq!(std::fs::read_link(dir.join("current.log")).unwrap(), std::path::Path::new(&name));
+        assert_eq!(std::fs::read_to_string(dir.join("current.log")).unwrap(), "aaa\n");
+        assert!(!dir.join("current.log.tmp").exists());
+
+        drop(appender);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_modes() {
+        use std::os::unix::fs::PermissionsExt;
+
+        use crate::config::{AppenderCommonProperties, FileAppenderConfig};
+
+        let dir = std::path::Path::new("__test_modes");
+        let _ = std::fs::remove_dir_all(dir);
+        let config = FileAppenderConfig {
+            common: AppenderCommonProperties {
+                encoder: EncoderConfig::Pattern(PatternEncoderConfig {
+                    pattern: "{message}".to_string(),
+                    ..Default::default()
+                }),
+                redact: None,
+            },
+            path: dir.join("logs/app.log"),
+            max_file_size: 4,
+            max_backup_index: 1,
+            file_mode: Some(0o640),
+            dir_mode: Some(0o750),
+            ..Default::default()
+        };
+        let mut appender = super::FileAppender::try_from((&config, Timezone::Local)).unwrap();
+        let datetime = test_datetime();
+        for message in ["aaa", "bbb"] {
+            let mut builder = RecordBuilder::new();
+            prepare_test_log_record(&mut builder);
+            let record = OwnedRecord::from(&builder.args(format_args!("{}", message)).build());
+            appender.append(&datetime, &record).unwrap();
+        }
+
+        let mode = |path: &std::path::Path| std::fs::metadata(path).unwrap().permissions().mode() & 0o7777;
+        assert_eq!(mode(dir), 0o750);
+        assert_eq!(mode(&dir.join("logs")), 0o750);
+        assert_eq!(mode(&dir.join("logs/app.log")), 0o640);
+        assert_eq!(mode(&dir.join("logs/app.log.0")), 0o640);
+
+        drop(appender);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_timestamped_backups() {
+        use crate::config::{AppenderCommonProperties, FileAppenderConfig};
+
+        let dir = std::path::Path::new("__test_timestamped");
+        let _ = std::fs::remove_dir_all(dir);
+        let config = FileAppenderConfig {
+            common: AppenderCommonProperties {
+                encoder: EncoderConfig::Pattern(PatternEncoderConfig {
+                    pattern: "{message}".to_string(),
+                    ..Default::default()
+                }),
+                redact: None,
+            },
+            path: dir.join("app.log"),
+            max_file_size: 4,
+            max_backup_index: 1,
+            backup_naming: BackupNaming::Timestamp,
+            ..Default::default()
+        };
+        let mut appender = super::FileAppender::try_from((&config, Timezone::Local)).unwrap();
+        let datetime = test_datetime();
+        let mut append = |secs: i64, message: &str| {
+            let mut builder = RecordBuilder::new();
+            prepare_test_log_record(&mut builder);
+            let record = OwnedRecord::from(&builder.args(format_args!("{}", message)).build());
+            appender.append(&(datetime + chrono::Duration::seconds(secs)), &record).unwrap();
+        };
+        append(0, "aaa");
+        append(0, "bbb");
+        append(0, "ccc");
+        append(1, "ddd");
+
+        let name = |secs: i64| format!("app.log.{}", (datetime + chrono::Duration::seconds(secs)).format("%Y-%m-%dT%H-%M-%S"));
+        let mut names: Vec<_> = std::fs::read_dir(dir)
+            .unwrap()
+            .map(|x| x.unwrap().file_name().into_string().unwrap())
+            .collect();
+        names.sort();
+        // `aaa` is deleted, as only 2 backups are kept
+        let mut expected = vec!["app.log".to_string(), format!("{}.1", name(0)), name(1)];
+        expected.sort();
+        assert_eq!(names, expected);
+        assert_eq!(std::fs::read_to_string(dir.join(format!("{}.1", name(0)))).unwrap(), "bbb\n");
+        assert_eq!(std::fs::read_to_string(dir.join(name(1))).unwrap(), "ccc\n");
+        assert_eq!(std::fs::read_to_string(dir.join("app.log")).unwrap(), "ddd\n");
+
+        assert!(super::is_timestamped_backup("app.log".as_ref(), "app.log.2024-07-31T12-00-00.1.zst".as_ref()));
+        assert!(!super::is_timestamped_backup("app.log".as_ref(), "app.log.2024-07-31T12-00-00.zst.tmp".as_ref()));
+        assert!(!super::is_timestamped_backup("app.log".as_ref(), "app.log.1".as_ref()));
+
+        drop(appender);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_wildcard_match() {
+        let cases = [
+            ("*.log", "app.log", true),
+            ("*.log", "app.log.1", false),
+            ("old-*.log*", "old-app.log.1", true),
+            ("app-?.log", "app-1.log", true),
+            ("app-?.log", "app-12.log", false),
+            ("*a*b", "xaxxb", true),
+            ("*", "", true),
+            ("a", "", false),
+        ];
+        for (pattern, name, expected) in cases {
+            let pattern: Vec<char> = pattern.chars().collect();
+            let name: Vec<char> = name.chars().collect();
+            assert_eq!(super::wildcard_match(&pattern, &name), expected);
+        }
+    }
+
+    #[test]
+    fn test_cleanup_stale_files() {
+        use std::time::{Duration, SystemTime};
+
+        use crate::config::{CleanupConfig, FileAppenderConfig};
+
+        let dir = Path::new("__test_cleanup");
+        let _ = std::fs::remove_dir_all(dir);
+        std::fs::create_dir(dir).unwrap();
+        let now = SystemTime::now();
+        let names = ["app.log", "app.log.0", "a.log", "b.log", "c.log", "other.txt"];
+        for (i, name) in names.iter().enumerate() {
+            let file = File::create(dir.join(name)).unwrap();
+            // a bit less than `i` days old
+            let age = Duration::from_secs(i as u64 * 24 * 60 * 60);
+            file.set_modified(now - age + Duration::from_secs(60)).unwrap();
+        }
+        let list = || {
+            let mut names: Vec<_> = std::fs::read_dir(dir)
+                .unwrap()
+                .map(|x| x.unwrap().file_name().into_string().unwrap())
+                .collect();
+            names.sort();
+            names
+        };
+
+        let config = |pattern: &str, max_age_days, max_count| FileAppenderConfig {
+            path: dir.join("app.log"),
+            cleanup: Some(CleanupConfig {
+                pattern: pattern.to_string(),
+                max_age_days,
+                max_count,
+            }),
+            ..Default::default()
+        };
+        let cleanup = |config: &FileAppenderConfig| {
+            super::cleanup_stale_files(config, Timezone::Utc, std::slice::from_ref(&config.path)).unwrap()
+        };
+        cleanup(&config("*.log*", Some(3), None));
+        assert_eq!(list(), ["a.log", "app.log", "app.log.0", "b.log", "other.txt"]);
+
+        cleanup(&config("*.log", None, Some(1)));
+        assert_eq!(list(), ["a.log", "app.log", "app.log.0", "other.txt"]);
+
+        cleanup(&config("*", None, None));
+        assert_eq!(list(), ["app.log", "app.log.0"]);
+
+        // the log files of the other appenders in the directory and their backups are kept
+        for name in ["other.log", "other.log.1", "stale.log"] {
+            File::create(dir.join(name)).unwrap();
+        }
+        let config = config("*.log*", None, None);
+        let log_files = [config.path.clone(), dir.join("other.log")];
+        super::cleanup_stale_files(&config, Timezone::Utc, &log_files).unwrap();
+        assert_eq!(list(), ["app.log", "app.log.0", "other.log", "other.log.1"]);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_cleanup_pattern_outside_log_dir() {
+        use crate::config::{CleanupConfig, FileAppenderConfig};
+
+        let dir = Path::new("__test_cleanup_outside");
+        let _ = std::fs::remove_dir_all(dir);
+        std::fs::create_dir_all(dir.join("logs/sub")).unwrap();
+        File::create(dir.join("outside.log")).unwrap();
+        File::create(dir.join("logs/sub/inside.log")).unwrap();
+
+        for pattern in ["../*.log", "sub/*.log", "sub\\*.log", "..", ""] {
+            let config = FileAppenderConfig {
+                path: dir.join("logs/app.log"),
+                cleanup: Some(CleanupConfig {
+                    pattern: pattern.to_string(),
+                    max_age_days: None,
+                    max_count: None,
+                }),
+                ..Default::default()
+            };
+            let result = super::cleanup_stale_files(&config, Timezone::Utc, std::slice::from_ref(&config.path));
+            assert!(result.is_err(), "{}", pattern);
+        }
+        assert!(dir.join("outside.log").exists());
+        assert!(dir.join("logs/sub/inside.log").exists());
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
 }
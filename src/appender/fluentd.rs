@@ -0,0 +1,158 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use indexmap::IndexMap;
+use serde::Serialize;
+
+use crate::{Datetime, Error};
+use crate::appender::Appender;
+use crate::appender::net::BufferedConnection;
+use crate::config::FluentdAppenderConfig;
+use crate::record::OwnedRecord;
+
+/// The extension type of the `EventTime` of the forward protocol.
+const EVENT_TIME_EXT_TYPE: i8 = 0;
+
+/// Ships the records to fluentd or fluent-bit by the forward protocol,
+/// one message (`[tag, time, record]` in MessagePack) per record, over a buffered TCP connection.
+/// The records are sent as the maps of their fields rather than encoded, so that no parser is needed.
+pub struct FluentdAppender {
+    tag: String,
+    connection: BufferedConnection,
+}
+
+impl TryFrom<&FluentdAppenderConfig> for FluentdAppender {
+    type Error = Error;
+
+    fn try_from(config: &FluentdAppenderConfig) -> Result<Self, Self::Error> {
+        if config.tag.is_empty() {
+            return Err(Error::from("tag must not be empty"));
+        }
+        if config.connect_timeout == 0 || config.write_timeout == 0 {
+            return Err(Error::from("connect_timeout and write_timeout must be positive"));
+        }
+        let connection = BufferedConnection::new(
+            format!("{}:{}", config.host, config.port),
+            Duration::from_secs(config.connect_timeout),
+            Duration::from_secs(config.write_timeout),
+            config.max_buffered_bytes,
+        );
+        Ok(Self {
+            tag: config.tag.clone(),
+            connection,
+        })
+    }
+}
+
+impl Appender for FluentdAppender {
+    fn append(&mut self, datetime: &Datetime, record: &OwnedRecord) -> Result<(), Error> {
+        self.connection.push(record.target.clone(), encode_message(&self.tag, datetime, record));
+        self.connection.send_buffered();
+        Ok(())
+    }
+
+    fn append_batch(&mut self, records: &[(Datetime, Arc<OwnedRecord>)]) -> Result<(), Error> {
+        for (datetime, record) in records {
+            self.connection.push(record.target.clone(), encode_message(&self.tag, datetime, record));
+        }
+        self.connection.send_buffered();
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), Error> {
+        self.connection.flush();
+        Ok(())
+    }
+}
+
+/// Encodes the record as a message in the `Message Mode`, with the time as an `EventTime`.
+fn encode_message(tag: &str, datetime: &Datetime, record: &OwnedRecord) -> Vec<u8> {
+    #[derive(Serialize)]
+    struct X<'a> {
+        level: &'a str,
+        target: &'a str,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        module: Option<&'a str>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        file: Option<&'a str>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        line: Option<u32>,
+        message: &'a str,
+        #[serde(skip_serializing_if = "IndexMap::is_empty")]
+        args: IndexMap<&'a str, &'a serde_json::Value>,
+    }
+    let x = X {
+        level: record.level.as_str(),
+        target: &record.target,
+        module: record.module_path.as_deref(),
+        file: record.file.as_deref(),
+        line: record.line,
+        message: &record.message,
+        args: record.kvs.iter().map(|(key, value)| (key.as_str(), value)).collect(),
+    };
+
+    // writing to a vector never fails
+    let mut buf = vec![];
+    rmp::encode::write_array_len(&mut buf, 3).unwrap();
+    rmp::encode::write_str(&mut buf, tag).unwrap();
+    rmp::encode::write_ext_meta(&mut buf, 8, EVENT_TIME_EXT_TYPE).unwrap();
+    buf.extend_from_slice(&(datetime.timestamp() as u32).to_be_bytes());
+    buf.extend_from_slice(&datetime.timestamp_subsec_nanos().to_be_bytes());
+    rmp_serde::encode::write_named(&mut buf, &x).unwrap();
+    buf
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+    use std::net::TcpListener;
+
+    use log::RecordBuilder;
+
+    use crate::appender::Appender;
+    use crate::config::FluentdAppenderConfig;
+    use crate::encoder::tests::*;
+    use crate::record::OwnedRecord;
+
+    #[test]
+    fn test_append() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let config = FluentdAppenderConfig {
+            enabled: true,
+            queue: Default::default(),
+            filters: vec![],
+            host: "127.0.0.1".to_string(),
+            port: listener.local_addr().unwrap().port(),
+            tag: "app.test".to_string(),
+            max_buffered_bytes: 1024,
+            connect_timeout: 1,
+            write_timeout: 1,
+        };
+        let mut appender = super::FluentdAppender::try_from(&config).unwrap();
+        let kvs = [("user_id", log::kv::Value::from(42))];
+        let mut builder = RecordBuilder::new();
+        prepare_test_log_record(&mut builder);
+        let record = OwnedRecord::from(&builder.args(format_args!("{}", TEST_MESSAGE)).key_values(&kvs).build());
+        let datetime = test_datetime();
+        appender.append(&datetime, &record).unwrap();
+
+        let (stream, _) = listener.accept().unwrap();
+        drop(appender);
+        let mut buf = vec![];
+        stream.take(1024).read_to_end(&mut buf).unwrap();
+
+        let mut reader = buf.as_slice();
+        assert_eq!(rmp::decode::read_array_len(&mut reader).unwrap(), 3);
+        let mut tag = [0; 16];
+        assert_eq!(rmp::decode::read_str(&mut reader, &mut tag).unwrap(), "app.test");
+        let meta = rmp::decode::read_ext_meta(&mut reader).unwrap();
+        assert_eq!((meta.typeid, meta.size), (0, 8));
+        assert_eq!(u32::from_be_bytes(reader[..4].try_into().unwrap()) as i64, datetime.timestamp());
+        reader = &reader[8..];
+        let value: serde_json::Value = rmp_serde::from_read(reader).unwrap();
+        assert_eq!(value["level"], TEST_LEVEL.as_str());
+        assert_eq!(value["target"], TEST_TARGET);
+        assert_eq!(value["message"], TEST_MESSAGE);
+        assert_eq!(value["args"]["user_id"], 42);
+    }
+}
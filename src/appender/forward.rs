@@ -0,0 +1,130 @@
+use std::collections::HashMap;
+use std::sync::{Arc, OnceLock};
+
+use log::{Log, Record};
+
+use crate::{Datetime, Error};
+use crate::appender::Appender;
+use crate::config::ForwardAppenderConfig;
+use crate::record::OwnedRecord;
+use crate::sync::Mutex;
+
+type Registry = Mutex<HashMap<String, Arc<dyn Log>>>;
+
+fn registry() -> &'static Registry {
+    static REGISTRY: OnceLock<Registry> = OnceLock::new();
+    REGISTRY.get_or_init(Default::default)
+}
+
+pub(crate) fn register(name: &str, logger: Box<dyn Log>) {
+    registry().lock().insert(name.to_string(), Arc::from(logger));
+}
+
+/// Forwards the records to another `log::Log` implementation registered by name.
+pub struct ForwardAppender {
+    logger: Arc<dyn Log>,
+}
+
+impl TryFrom<&ForwardAppenderConfig> for ForwardAppender {
+    type Error = Error;
+
+    fn try_from(config: &ForwardAppenderConfig) -> Result<Self, Self::Error> {
+        let logger = registry()
+            .lock()
+            .get(&config.name)
+            .cloned()
+            .ok_or_else(|| Error::from(format!("no forward target registered as '{}'", config.name)))?;
+        Ok(Self { logger })
+    }
+}
+
+impl Appender for ForwardAppender {
+    fn append(&mut self, _datetime: &Datetime, record: &OwnedRecord) -> Result<(), Error> {
+        let kvs: Vec<(&str, log::kv::Value)> = record
+            .kvs
+            .iter()
+            .map(|(key, value)| (key.as_str(), log::kv::Value::from_serde(value)))
+            .collect();
+        self.logger.log(
+            &Record::builder()
+                .level(record.level)
+                .target(&record.target)
+                .module_path(record.module_path.as_deref())
+                .file(record.file.as_deref())
+                .line(record.line)
+                .args(format_args!("{}", record.message))
+                .key_values(&kvs)
+                .build(),
+        );
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), Error> {
+        self.logger.flush();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use log::{Level, Log, Metadata, Record, RecordBuilder};
+
+    use crate::appender::Appender;
+    use crate::config::ForwardAppenderConfig;
+    use crate::encoder::tests::test_datetime;
+    use crate::record::OwnedRecord;
+    use crate::sync::Mutex;
+
+    struct TestLog(&'static Mutex<Vec<OwnedRecord>>);
+
+    impl Log for TestLog {
+        fn enabled(&self, _metadata: &Metadata) -> bool {
+            true
+        }
+
+        fn log(&self, record: &Record) {
+            self.0.lock().push(OwnedRecord::from(record));
+        }
+
+        fn flush(&self) {}
+    }
+
+    #[test]
+    fn test_forward() {
+        let records: &'static Mutex<Vec<OwnedRecord>> = Box::leak(Box::new(Mutex::new(vec![])));
+        super::register("__test_forward", Box::new(TestLog(records)));
+        let config = ForwardAppenderConfig {
+            enabled: true,
+            queue: Default::default(),
+            filters: vec![],
+            name: "__test_forward".to_string(),
+        };
+        let mut appender = super::ForwardAppender::try_from(&config).unwrap();
+
+        let kvs: &[(&str, i32)] = &[("number", 42)];
+        let record = OwnedRecord::from(
+            &RecordBuilder::new()
+                .level(Level::Warn)
+                .target("test_forward")
+                .args(format_args!("hello"))
+                .key_values(&kvs)
+                .build(),
+        );
+        appender.append(&test_datetime(), &record).unwrap();
+
+        let records = records.lock();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].level, Level::Warn);
+        assert_eq!(&*records[0].target, "test_forward");
+        assert_eq!(records[0].message, "hello");
+        assert_eq!(records[0].kvs, vec![("number".to_string(), 42.into())]);
+
+        let config = ForwardAppenderConfig {
+            enabled: true,
+            queue: Default::default(),
+            filters: vec![],
+            name: "__test_unknown".to_string(),
+        };
+        assert!(super::ForwardAppender::try_from(&config).is_err());
+    }
+}
@@ -0,0 +1,119 @@
+use std::collections::HashMap;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::sync::{Arc, OnceLock};
+
+use crate::config::RotateHookConfig;
+use crate::sync::Mutex;
+use crate::Error;
+
+/// The environment variable of the hook command with the path of the backup.
+const BACKUP_FILE_ENV: &str = "NAIVE_LOGGER_BACKUP_FILE";
+/// The environment variable of the hook command with the path of the log file.
+const LOG_FILE_ENV: &str = "NAIVE_LOGGER_LOG_FILE";
+
+type Callback = dyn Fn(&Path) + Send + Sync;
+
+type Registry = Mutex<HashMap<String, Arc<Callback>>>;
+
+fn registry() -> &'static Registry {
+    static REGISTRY: OnceLock<Registry> = OnceLock::new();
+    REGISTRY.get_or_init(Default::default)
+}
+
+pub(crate) fn register<F: Fn(&Path) + Send + Sync + 'static>(name: &str, hook: F) {
+    registry().lock().insert(name.to_string(), Arc::new(hook));
+}
+
+/// Runs after the file appender creates a backup, with the path of the backup.
+#[derive(Clone)]
+pub enum RotateHook {
+    /// Run by the shell, with the paths in the environment variables.
+    Command(String),
+    Custom(Arc<Callback>),
+}
+
+impl TryFrom<&RotateHookConfig> for RotateHook {
+    type Error = Error;
+
+    fn try_from(config: &RotateHookConfig) -> Result<Self, Self::Error> {
+        match config {
+            RotateHookConfig::Command(config) => {
+                if config.command.trim().is_empty() {
+                    return Err(Error::from("on_rotate command must not be empty"));
+                }
+                Ok(RotateHook::Command(config.command.clone()))
+            }
+            RotateHookConfig::Custom(config) => registry()
+                .lock()
+                .get(&config.name)
+                .cloned()
+                .map(RotateHook::Custom)
+                .ok_or_else(|| Error::from(format!("no rotate hook registered as '{}'", config.name))),
+        }
+    }
+}
+
+impl RotateHook {
+    /// Runs the hook and waits for it; the failures are ignored, as the backup is already created.
+    pub fn run(&self, backup: &Path, log_file: &Path) {
+        match self {
+            RotateHook::Command(command) => {
+                #[cfg(windows)]
+                let mut shell = Command::new("cmd");
+                #[cfg(windows)]
+                shell.arg("/C");
+                #[cfg(not(windows))]
+                let mut shell = Command::new("sh");
+                #[cfg(not(windows))]
+                shell.arg("-c");
+                let _ = shell
+                    .arg(command)
+                    .env(BACKUP_FILE_ENV, backup)
+                    .env(LOG_FILE_ENV, log_file)
+                    .stdin(Stdio::null())
+                    .status();
+            }
+            RotateHook::Custom(hook) => hook(backup),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+    use std::sync::Arc;
+
+    use crate::config::RotateHookConfig;
+    use crate::sync::Mutex;
+
+    #[test]
+    fn test_custom() {
+        let paths = Arc::new(Mutex::new(Vec::<PathBuf>::new()));
+        let captured = paths.clone();
+        super::register("__test_hook", move |backup: &Path| captured.lock().push(backup.to_path_buf()));
+
+        let config: RotateHookConfig = serde_json::from_str(r#"{"kind": "custom", "name": "__test_hook"}"#).unwrap();
+        let hook = super::RotateHook::try_from(&config).unwrap();
+        hook.run(Path::new("app.log.0"), Path::new("app.log"));
+        assert_eq!(*paths.lock(), [PathBuf::from("app.log.0")]);
+
+        let config: RotateHookConfig = serde_json::from_str(r#"{"kind": "custom", "name": "__unknown"}"#).unwrap();
+        assert!(super::RotateHook::try_from(&config).is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_command() {
+        let output = std::env::temp_dir().join(format!("__test_hook_{}", std::process::id()));
+        let config: RotateHookConfig = serde_json::from_value(serde_json::json!({
+            "kind": "command",
+            "command": format!("echo \"$NAIVE_LOGGER_BACKUP_FILE $NAIVE_LOGGER_LOG_FILE\" > {}", output.display()),
+        }))
+        .unwrap();
+        let hook = super::RotateHook::try_from(&config).unwrap();
+        hook.run(Path::new("app.log.0"), Path::new("app.log"));
+        assert_eq!(std::fs::read_to_string(&output).unwrap(), "app.log.0 app.log\n");
+        std::fs::remove_file(output).unwrap();
+    }
+}
@@ -0,0 +1,339 @@
+use std::io::{BufRead, BufReader, Write};
+use std::sync::mpsc::{sync_channel, Receiver, RecvTimeoutError, SyncSender, TrySendError};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::{Datetime, dropped, encoder, Error};
+use crate::appender::Appender;
+use crate::config::{HttpAppenderConfig, OverflowPolicy};
+use crate::encoder::Encoder;
+use crate::record::OwnedRecord;
+
+/// The number of the retries of a failed request, and the backoff before the first one,
+/// doubled before each of the others.
+const MAX_RETRIES: u32 = 2;
+const RETRY_BACKOFF: Duration = Duration::from_millis(500);
+
+enum Message {
+    Line(Arc<str>, Vec<u8>),
+    Flush(SyncSender<()>),
+}
+
+/// POSTs the encoded records in batches to an HTTP endpoint.
+/// The batches are sent by a flusher thread when full or delayed too long,
+/// so that a slow endpoint only delays the batches rather than the appender worker.
+/// The records waiting for the flusher are bounded by the `queue_size` and `overflow_policy` of the appender.
+pub struct HttpAppender {
+    encoder: Box<dyn Encoder + Send + Sync>,
+    sender: SyncSender<Message>,
+    overflow_policy: OverflowPolicy,
+}
+
+impl TryFrom<&HttpAppenderConfig> for HttpAppender {
+    type Error = Error;
+
+    fn try_from(config: &HttpAppenderConfig) -> Result<Self, Self::Error> {
+        let encoder = encoder::from_config(&config.common.encoder)
+            .map_err(|e| e.concat("failed to create encoder"))?;
+        let encoder = encoder::with_redaction(encoder, config.common.redact.as_ref());
+        if config.batch_size == 0 || config.max_delay == 0 {
+            return Err(Error::from("batch_size and max_delay must be positive"));
+        }
+        if config.connect_timeout == 0 || config.write_timeout == 0 {
+            return Err(Error::from("connect_timeout and write_timeout must be positive"));
+        }
+        let client = Client::new(config)?;
+        let batch_size = config.batch_size;
+        let max_delay = Duration::from_secs(config.max_delay);
+
+        if config.queue.queue_size == 0 {
+            return Err(Error::from("queue_size must be positive"));
+        }
+        let (sender, receiver) = sync_channel(config.queue.queue_size);
+        std::thread::Builder::new()
+            .name("naive-logger-http".to_string())
+            .spawn(move || run(client, receiver, batch_size, max_delay))
+            .map_err(|e| Error::from(format!("failed to spawn flusher thread: {}", e)))?;
+        Ok(Self {
+            encoder,
+            sender,
+            overflow_policy: config.queue.overflow_policy,
+        })
+    }
+}
+
+impl Appender for HttpAppender {
+    fn append(&mut self, datetime: &Datetime, record: &OwnedRecord) -> Result<(), Error> {
+        let mut line = vec![];
+        self.encoder.encode_entry(datetime, record, &mut line);
+        let message = Message::Line(record.target.clone(), line);
+        match self.overflow_policy {
+            OverflowPolicy::Block => {
+                let _ = self.sender.send(message);
+            }
+            OverflowPolicy::Drop => {
+                if let Err(TrySendError::Full(_)) = self.sender.try_send(message) {
+                    dropped::count(&record.target, 1);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Sends the pending batch and waits for it.
+    fn flush(&mut self) -> Result<(), Error> {
+        let (ack_sender, ack_receiver) = sync_channel(1);
+        if self.sender.send(Message::Flush(ack_sender)).is_ok() {
+            let _ = ack_receiver.recv();
+        }
+        Ok(())
+    }
+}
+
+fn run(client: Client, receiver: Receiver<Message>, batch_size: usize, max_delay: Duration) {
+    let mut batch = vec![];
+    // the time to send the batch, counted from its first record
+    let mut deadline: Option<Instant> = None;
+    loop {
+        let message = match deadline {
+            None => receiver.recv().map_err(|_| RecvTimeoutError::Disconnected),
+            Some(deadline) => receiver.recv_timeout(deadline.saturating_duration_since(Instant::now())),
+        };
+        match message {
+            Ok(Message::Line(target, line)) => {
+                if batch.is_empty() {
+                    deadline = Some(Instant::now() + max_delay);
+                }
+                batch.push((target, line));
+                if batch.len() >= batch_size {
+                    send(&client, &mut batch);
+                    deadline = None;
+                }
+            }
+            Ok(Message::Flush(ack)) => {
+                send(&client, &mut batch);
+                deadline = None;
+                let _ = ack.send(());
+            }
+            Err(RecvTimeoutError::Timeout) => {
+                send(&client, &mut batch);
+                deadline = None;
+            }
+            Err(RecvTimeoutError::Disconnected) => {
+                send(&client, &mut batch);
+                return;
+            }
+        }
+    }
+}
+
+/// Sends the batch, retrying with a backoff; the records are dropped and reported if still failed.
+fn send(client: &Client, batch: &mut Vec<(Arc<str>, Vec<u8>)>) {
+    if batch.is_empty() {
+        return;
+    }
+    let body: Vec<u8> = batch.iter().flat_map(|(_, line)| line.iter().copied()).collect();
+    let mut result = client.post(&body);
+    let mut backoff = RETRY_BACKOFF;
+    for _ in 0..MAX_RETRIES {
+        if result.is_ok() {
+            break;
+        }
+        std::thread::sleep(backoff);
+        backoff *= 2;
+        result = client.post(&body);
+    }
+    if result.is_err() {
+        for (target, _) in batch.iter() {
+            dropped::count(target, 1);
+        }
+    }
+    batch.clear();
+}
+
+/// A minimal HTTP/1.1 client, sending each request on a new connection.
+struct Client {
+    /// `host:port`
+    address: String,
+    host: String,
+    path: String,
+    headers: Vec<(String, String)>,
+    content_type: String,
+    connect_timeout: Duration,
+    write_timeout: Duration,
+}
+
+impl Client {
+    fn new(config: &HttpAppenderConfig) -> Result<Self, Error> {
+        let rest = config.url.strip_prefix("http://").ok_or_else(|| {
+            Error::from(format!("url '{}' should start with 'http://' (HTTPS is not supported)", config.url))
+        })?;
+        let (host, path) = match rest.find('/') {
+            Some(i) => (&rest[..i], &rest[i..]),
+            None => (rest, "/"),
+        };
+        if host.is_empty() {
+            return Err(Error::from(format!("url '{}' has no host", config.url)));
+        }
+        let address = if host.rsplit_once(':').is_some_and(|(_, port)| port.parse::<u16>().is_ok()) {
+            host.to_string()
+        } else {
+            format!("{}:80", host)
+        };
+        Ok(Self {
+            address,
+            host: host.to_string(),
+            path: path.to_string(),
+            headers: config.headers.iter().map(|(k, v)| (k.clone(), v.clone())).collect(),
+            content_type: config.content_type.clone(),
+            connect_timeout: Duration::from_secs(config.connect_timeout),
+            write_timeout: Duration::from_secs(config.write_timeout),
+        })
+    }
+
+    /// POSTs the body; succeeds only with a `2xx` status.
+    fn post(&self, body: &[u8]) -> std::io::Result<()> {
+        let mut stream = super::net::connect(&self.address, self.connect_timeout, self.write_timeout)?;
+        stream.set_read_timeout(Some(self.write_timeout))?;
+
+        let mut request = format!(
+            "POST {} HTTP/1.1\r\nHost: {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n",
+            self.path,
+            self.host,
+            self.content_type,
+            body.len()
+        );
+        for (name, value) in &self.headers {
+            request.push_str(&format!("{}: {}\r\n", name, value));
+        }
+        request.push_str("\r\n");
+        stream.write_all(request.as_bytes())?;
+        stream.write_all(body)?;
+
+        let mut status_line = String::new();
+        BufReader::new(&stream).read_line(&mut status_line)?;
+        match status_line.split_whitespace().nth(1) {
+            Some(status) if status.starts_with('2') => Ok(()),
+            _ => Err(std::io::Error::other(format!("unexpected response '{}'", status_line.trim()))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+
+    use log::RecordBuilder;
+
+    use crate::appender::Appender;
+    use crate::config::{AppenderCommonProperties, EncoderConfig, HttpAppenderConfig, PatternEncoderConfig};
+    use crate::encoder::tests::*;
+    use crate::record::OwnedRecord;
+
+    fn http_config(url: &str) -> HttpAppenderConfig {
+        HttpAppenderConfig {
+            enabled: true,
+            queue: Default::default(),
+            filters: vec![],
+            common: AppenderCommonProperties {
+                encoder: EncoderConfig::Pattern(PatternEncoderConfig {
+                    pattern: "{message}".to_string(),
+                    ..Default::default()
+                }),
+                redact: None,
+            },
+            url: url.to_string(),
+            headers: [("X-Token".to_string(), "secret".to_string())].into_iter().collect(),
+            content_type: "text/plain".to_string(),
+            batch_size: 2,
+            max_delay: 60,
+            connect_timeout: 1,
+            write_timeout: 5,
+        }
+    }
+
+    #[test]
+    fn test_client_new() {
+        let client = super::Client::new(&http_config("http://localhost:3100/loki/api/v1/push")).unwrap();
+        assert_eq!(client.address, "localhost:3100");
+        assert_eq!(client.path, "/loki/api/v1/push");
+        let client = super::Client::new(&http_config("http://example.com")).unwrap();
+        assert_eq!(client.address, "example.com:80");
+        assert_eq!(client.path, "/");
+        assert!(super::Client::new(&http_config("https://example.com")).is_err());
+    }
+
+    /// The head lines and the body of a request.
+    type Request = (Vec<String>, String);
+
+    /// Serves the requests with the statuses in order, returning the requests.
+    fn serve(statuses: &'static [&'static str]) -> (u16, std::thread::JoinHandle<Vec<Request>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = std::thread::spawn(move || {
+            let mut requests = vec![];
+            for status in statuses {
+                let (mut stream, _) = listener.accept().unwrap();
+                let mut reader = BufReader::new(&stream);
+                let mut head = vec![];
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    if line == "\r\n" {
+                        break;
+                    }
+                    head.push(line.trim_end().to_string());
+                }
+                let length: usize = head
+                    .iter()
+                    .find_map(|x| x.strip_prefix("Content-Length: "))
+                    .unwrap()
+                    .parse()
+                    .unwrap();
+                let mut body = vec![0; length];
+                reader.read_exact(&mut body).unwrap();
+                stream.write_all(format!("HTTP/1.1 {}\r\n\r\n", status).as_bytes()).unwrap();
+                requests.push((head, String::from_utf8(body).unwrap()));
+            }
+            requests
+        });
+        (port, server)
+    }
+
+    fn record(message: &str) -> OwnedRecord {
+        let mut builder = RecordBuilder::new();
+        prepare_test_log_record(&mut builder);
+        OwnedRecord::from(&builder.args(format_args!("{}", message)).build())
+    }
+
+    #[test]
+    fn test_append_batch() {
+        let (port, server) = serve(&["204 No Content", "204 No Content"]);
+
+        let mut appender = super::HttpAppender::try_from(&http_config(&format!("http://127.0.0.1:{}/ingest", port))).unwrap();
+        for message in ["aaa", "bbb", "ccc"] {
+            appender.append(&test_datetime(), &record(message)).unwrap();
+        }
+        appender.flush().unwrap();
+
+        let requests = server.join().unwrap();
+        assert_eq!(requests[0].0[0], "POST /ingest HTTP/1.1");
+        assert!(requests[0].0.contains(&"X-Token: secret".to_string()));
+        assert!(requests[0].0.contains(&"Content-Type: text/plain".to_string()));
+        assert_eq!(requests[0].1, "aaa\nbbb\n");
+        assert_eq!(requests[1].1, "ccc\n");
+    }
+
+    #[test]
+    fn test_retry() {
+        let (port, server) = serve(&["503 Service Unavailable", "204 No Content"]);
+        let mut appender = super::HttpAppender::try_from(&http_config(&format!("http://127.0.0.1:{}/ingest", port))).unwrap();
+        appender.append(&test_datetime(), &record("aaa")).unwrap();
+        appender.flush().unwrap();
+
+        let requests = server.join().unwrap();
+        assert_eq!(requests[0].1, "aaa\n");
+        assert_eq!(requests[1].1, "aaa\n");
+    }
+}
@@ -0,0 +1,213 @@
+#[cfg(unix)]
+use std::os::unix::net::UnixDatagram;
+use std::path::PathBuf;
+
+use log::Level;
+
+use crate::{Datetime, dropped, Error};
+use crate::appender::Appender;
+use crate::config::JournaldAppenderConfig;
+use crate::record::OwnedRecord;
+
+/// Submits the records to systemd-journald by its native protocol, one datagram per record.
+/// The key-value pairs are submitted as the journal fields with the uppercase names,
+/// so that `journalctl -o json` shows the structured data.
+pub struct JournaldAppender {
+    #[cfg(unix)]
+    socket: UnixDatagram,
+    path: PathBuf,
+    syslog_identifier: String,
+}
+
+impl TryFrom<&JournaldAppenderConfig> for JournaldAppender {
+    type Error = Error;
+
+    #[cfg(unix)]
+    fn try_from(config: &JournaldAppenderConfig) -> Result<Self, Self::Error> {
+        if !config.socket.exists() {
+            return Err(Error::from(format!(
+                "journald socket '{}' doesn't exist",
+                config.socket.display()
+            )));
+        }
+        let socket = UnixDatagram::unbound()
+            .map_err(|e| Error::from(format!("failed to create journald socket: {}", e)))?;
+        Ok(Self {
+            socket,
+            path: config.socket.clone(),
+            syslog_identifier: config.syslog_identifier.clone().unwrap_or_else(crate::program_name),
+        })
+    }
+
+    #[cfg(not(unix))]
+    fn try_from(_config: &JournaldAppenderConfig) -> Result<Self, Self::Error> {
+        Err(Error::from("journald appender is only supported on Unix"))
+    }
+}
+
+impl Appender for JournaldAppender {
+    fn append(&mut self, _datetime: &Datetime, record: &OwnedRecord) -> Result<(), Error> {
+        let mut buffer = vec![];
+        write_field(&mut buffer, "MESSAGE", &record.message);
+        write_field(&mut buffer, "PRIORITY", priority(record.level));
+        write_field(&mut buffer, "SYSLOG_IDENTIFIER", &self.syslog_identifier);
+        write_field(&mut buffer, "TARGET", &record.target);
+        if let Some(module_path) = &record.module_path {
+            write_field(&mut buffer, "CODE_MODULE", module_path);
+        }
+        if let Some(file) = &record.file {
+            write_field(&mut buffer, "CODE_FILE", file);
+        }
+        if let Some(line) = record.line {
+            write_field(&mut buffer, "CODE_LINE", &line.to_string());
+        }
+        for (key, value) in &record.kvs {
+            let Some(name) = field_name(key) else {
+                continue;
+            };
+            match value {
+                serde_json::Value::String(s) => write_field(&mut buffer, &name, s),
+                value => write_field(&mut buffer, &name, &value.to_string()),
+            }
+        }
+
+        // the records larger than the datagram limit are not supported, as they need the file descriptor passing
+        if self.send(&buffer).is_err() {
+            dropped::count(&record.target, 1);
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+impl JournaldAppender {
+    #[cfg(unix)]
+    fn send(&self, buf: &[u8]) -> std::io::Result<()> {
+        self.socket.send_to(buf, &self.path).map(|_| ())
+    }
+
+    #[cfg(not(unix))]
+    fn send(&self, _buf: &[u8]) -> std::io::Result<()> {
+        Err(std::io::ErrorKind::Unsupported.into())
+    }
+}
+
+/// Maps the level to the syslog priority; both `debug` and `trace` are mapped to `debug`.
+fn priority(level: Level) -> &'static str {
+    match level {
+        Level::Error => "3",
+        Level::Warn => "4",
+        Level::Info => "6",
+        Level::Debug | Level::Trace => "7",
+    }
+}
+
+/// Converts the key to a journal field name: uppercase ASCII letters, digits and underscores,
+/// not starting with an underscore (reserved for the trusted fields) or a digit, at most 64 characters.
+fn field_name(key: &str) -> Option<String> {
+    let name: String = key
+        .chars()
+        .map(|c| match c {
+            'a'..='z' => c.to_ascii_uppercase(),
+            'A'..='Z' | '0'..='9' => c,
+            _ => '_',
+        })
+        .skip_while(|&c| c == '_' || c.is_ascii_digit())
+        .take(64)
+        .collect();
+    if name.is_empty() {
+        None
+    } else {
+        Some(name)
+    }
+}
+
+/// Writes the field as `NAME=value\n`, or in the binary form if the value has newlines.
+fn write_field(buf: &mut Vec<u8>, name: &str, value: &str) {
+    buf.extend_from_slice(name.as_bytes());
+    if value.contains('\n') {
+        buf.push(b'\n');
+        buf.extend_from_slice(&(value.len() as u64).to_le_bytes());
+    } else {
+        buf.push(b'=');
+    }
+    buf.extend_from_slice(value.as_bytes());
+    buf.push(b'\n');
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn test_field_name() {
+        let cases = [
+            ("user_id", Some("USER_ID")),
+            ("http.status", Some("HTTP_STATUS")),
+            ("_secret", Some("SECRET")),
+            ("1st", Some("ST")),
+            ("__", None),
+        ];
+        for (key, expected) in cases {
+            assert_eq!(super::field_name(key).as_deref(), expected);
+        }
+        assert_eq!(super::field_name(&"x".repeat(100)).unwrap().len(), 64);
+    }
+
+    #[test]
+    fn test_write_field() {
+        let mut buf = vec![];
+        super::write_field(&mut buf, "A", "foo");
+        super::write_field(&mut buf, "B", "x\ny");
+        let mut expected = b"A=foo\nB\n".to_vec();
+        expected.extend_from_slice(&3u64.to_le_bytes());
+        expected.extend_from_slice(b"x\ny\n");
+        assert_eq!(buf, expected);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_append() {
+        use std::os::unix::net::UnixDatagram;
+
+        use log::RecordBuilder;
+
+        use crate::appender::Appender;
+        use crate::config::JournaldAppenderConfig;
+        use crate::encoder::tests::*;
+        use crate::record::OwnedRecord;
+
+        let path = std::env::temp_dir().join(format!("__test_journald_{}.sock", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let server = UnixDatagram::bind(&path).unwrap();
+
+        let config = JournaldAppenderConfig {
+            enabled: true,
+            queue: Default::default(),
+            filters: vec![],
+            socket: path.clone(),
+            syslog_identifier: Some("myapp".to_string()),
+        };
+        let mut appender = super::JournaldAppender::try_from(&config).unwrap();
+        let kvs = [("user_id", log::kv::Value::from(42)), ("name", log::kv::Value::from("foo"))];
+        let record = OwnedRecord::from(
+            &RecordBuilder::new()
+                .level(log::Level::Error)
+                .target(TEST_TARGET)
+                .args(format_args!("{}", TEST_MESSAGE))
+                .key_values(&kvs)
+                .build(),
+        );
+        appender.append(&test_datetime(), &record).unwrap();
+
+        let mut buf = [0; 1024];
+        let n = server.recv(&mut buf).unwrap();
+        let expected = format!(
+            "MESSAGE={}\nPRIORITY=3\nSYSLOG_IDENTIFIER=myapp\nTARGET={}\nUSER_ID=42\nNAME=foo\n",
+            TEST_MESSAGE, TEST_TARGET
+        );
+        assert_eq!(std::str::from_utf8(&buf[..n]).unwrap(), expected);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
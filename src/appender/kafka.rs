@@ -0,0 +1,197 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use rdkafka::ClientConfig;
+use rdkafka::ClientContext;
+use rdkafka::error::{KafkaError, RDKafkaErrorCode};
+use rdkafka::producer::{BaseProducer, BaseRecord, DeliveryResult, Producer, ProducerContext};
+
+use crate::{Datetime, dropped, encoder, Error};
+use crate::appender::Appender;
+use crate::config::{KafkaAcks, KafkaAppenderConfig};
+use crate::encoder::Encoder;
+use crate::record::OwnedRecord;
+
+/// The maximum time to wait for the pending messages to be delivered when flushed.
+const FLUSH_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Reports the messages failed to be delivered as dropped, by the targets passed along with them.
+struct DeliveryContext;
+
+impl ClientContext for DeliveryContext {}
+
+impl ProducerContext for DeliveryContext {
+    type DeliveryOpaque = Box<Arc<str>>;
+
+    fn delivery(&self, delivery_result: &DeliveryResult<'_>, target: Self::DeliveryOpaque) {
+        if delivery_result.is_err() {
+            dropped::count(&target, 1);
+        }
+    }
+}
+
+/// Publishes each encoded record as a message to a Kafka topic.
+/// The messages are queued and sent by librdkafka in the background;
+/// the delivery reports are served when appending and flushing.
+pub struct KafkaAppender {
+    encoder: Box<dyn Encoder + Send + Sync>,
+    producer: BaseProducer<DeliveryContext>,
+    topic: String,
+    key: Option<String>,
+}
+
+impl TryFrom<&KafkaAppenderConfig> for KafkaAppender {
+    type Error = Error;
+
+    fn try_from(config: &KafkaAppenderConfig) -> Result<Self, Self::Error> {
+        let encoder = encoder::from_config(&config.common.encoder)
+            .map_err(|e| e.concat("failed to create encoder"))?;
+        let encoder = encoder::with_redaction(encoder, config.common.redact.as_ref());
+        if config.brokers.is_empty() {
+            return Err(Error::from("brokers must not be empty"));
+        }
+        if config.topic.is_empty() {
+            return Err(Error::from("topic must not be empty"));
+        }
+        let mut client_config = ClientConfig::new();
+        for (key, value) in &config.properties {
+            client_config.set(key, value);
+        }
+        client_config
+            .set("bootstrap.servers", config.brokers.join(","))
+            .set("acks", acks(config.acks));
+        // not connected here; the brokers are connected by librdkafka in the background
+        let producer = client_config
+            .create_with_context(DeliveryContext)
+            .map_err(|e| Error::from(format!("failed to create kafka producer: {}", e)))?;
+        Ok(Self {
+            encoder,
+            producer,
+            topic: config.topic.clone(),
+            key: config.key.clone(),
+        })
+    }
+}
+
+impl Appender for KafkaAppender {
+    fn append(&mut self, datetime: &Datetime, record: &OwnedRecord) -> Result<(), Error> {
+        self.send(datetime, record);
+        self.producer.poll(Duration::ZERO);
+        Ok(())
+    }
+
+    fn append_batch(&mut self, records: &[(Datetime, Arc<OwnedRecord>)]) -> Result<(), Error> {
+        for (datetime, record) in records {
+            self.send(datetime, record);
+        }
+        self.producer.poll(Duration::ZERO);
+        Ok(())
+    }
+
+    /// Waits for the queued messages to be delivered, at most for [`FLUSH_TIMEOUT`].
+    fn flush(&mut self) -> Result<(), Error> {
+        let _ = self.producer.flush(FLUSH_TIMEOUT);
+        Ok(())
+    }
+}
+
+impl Drop for KafkaAppender {
+    fn drop(&mut self) {
+        let _ = self.flush();
+    }
+}
+
+impl KafkaAppender {
+    fn send(&mut self, datetime: &Datetime, record: &OwnedRecord) {
+        let mut payload = vec![];
+        self.encoder.encode(datetime, record, &mut payload);
+        let key = self.key.as_deref().and_then(|name| message_key(record, name));
+
+        let target = Box::new(record.target.clone());
+        let mut message = BaseRecord::with_opaque_to(&self.topic, target).payload(&payload);
+        if let Some(key) = &key {
+            message = message.key(key);
+        }
+        if let Err((e, message)) = self.producer.send(message) {
+            // the local queue is full; serve the delivery reports to make room, then retry once
+            let result = match e {
+                KafkaError::MessageProduction(RDKafkaErrorCode::QueueFull) => {
+                    self.producer.poll(Duration::from_millis(100));
+                    self.producer.send(message).map_err(|(_, message)| message)
+                }
+                _ => Err(message),
+            };
+            if result.is_err() {
+                dropped::count(&record.target, 1);
+            }
+        }
+    }
+}
+
+/// Maps the acknowledgement setting to the value of the `acks` property.
+fn acks(acks: KafkaAcks) -> &'static str {
+    match acks {
+        KafkaAcks::None => "0",
+        KafkaAcks::Leader => "1",
+        KafkaAcks::All => "all",
+    }
+}
+
+/// Gets the value of the key-value pair as the message key; the strings are used without the quotes.
+fn message_key(record: &OwnedRecord, name: &str) -> Option<String> {
+    record.kvs.iter().find(|(key, _)| key == name).map(|(_, value)| match value {
+        serde_json::Value::String(s) => s.clone(),
+        value => value.to_string(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use log::RecordBuilder;
+
+    use crate::config::{AppenderCommonProperties, EncoderConfig, KafkaAcks, KafkaAppenderConfig, PatternEncoderConfig};
+    use crate::encoder::tests::*;
+    use crate::record::OwnedRecord;
+
+    fn kafka_config() -> KafkaAppenderConfig {
+        KafkaAppenderConfig {
+            enabled: true,
+            queue: Default::default(),
+            filters: vec![],
+            common: AppenderCommonProperties {
+                encoder: EncoderConfig::Pattern(PatternEncoderConfig {
+                    pattern: "{message}".to_string(),
+                    ..Default::default()
+                }),
+                redact: None,
+            },
+            brokers: vec!["127.0.0.1:1".to_string()],
+            topic: "logs".to_string(),
+            key: Some("user_id".to_string()),
+            acks: KafkaAcks::All,
+            properties: Default::default(),
+        }
+    }
+
+    #[test]
+    fn test_message_key() {
+        let kvs = [("user_id", log::kv::Value::from(42)), ("name", log::kv::Value::from("foo"))];
+        let mut builder = RecordBuilder::new();
+        prepare_test_log_record(&mut builder);
+        let record = OwnedRecord::from(&builder.key_values(&kvs).build());
+        assert_eq!(super::message_key(&record, "user_id").as_deref(), Some("42"));
+        assert_eq!(super::message_key(&record, "name").as_deref(), Some("foo"));
+        assert_eq!(super::message_key(&record, "missing"), None);
+    }
+
+    #[test]
+    fn test_try_from() {
+        let mut config = kafka_config();
+        config.brokers.clear();
+        assert!(super::KafkaAppender::try_from(&config).is_err());
+
+        let mut config = kafka_config();
+        config.properties.insert("no.such.property".to_string(), "x".to_string());
+        assert!(super::KafkaAppender::try_from(&config).is_err());
+    }
+}
@@ -0,0 +1,134 @@
+use std::sync::Arc;
+
+use crate::{Datetime, Error, filter};
+use crate::appender::Appender;
+use crate::config::{LevelRange, LevelRouterAppenderConfig, Timezone};
+use crate::filter::Filter;
+use crate::record::OwnedRecord;
+use crate::sync::Mutex;
+
+/// Passes the records to the inner appenders whose level ranges contain their levels,
+/// on the thread of this appender; the records not in any range are discarded.
+pub struct LevelRouterAppender {
+    routes: Vec<Route>,
+}
+
+struct Route {
+    levels: LevelRange,
+    filters: Vec<Arc<dyn Filter>>,
+    appender: Arc<Mutex<dyn Appender + Send>>,
+}
+
+impl Route {
+    fn accepts(&self, record: &OwnedRecord) -> bool {
+        self.levels.contains(record.level) && self.filters.iter().all(|x| x.filter(record))
+    }
+}
+
+impl TryFrom<(&LevelRouterAppenderConfig, Timezone)> for LevelRouterAppender {
+    type Error = Error;
+
+    fn try_from((config, timezone): (&LevelRouterAppenderConfig, Timezone)) -> Result<Self, Self::Error> {
+        if config.routes.is_empty() {
+            return Err(Error::from("routes must not be empty"));
+        }
+        let mut routes = vec![];
+        for (levels, config) in &config.routes {
+            // the disabled ones are ignored, like the references to the disabled appenders
+            if !config.enabled() {
+                continue;
+            }
+            let appender = super::from_config(config, timezone)
+                .map_err(|e| e.concat(format!("failed to create route '{}'", levels)))?;
+            let filters = filter::from_configs(config.filters())
+                .map_err(|e| e.concat(format!("failed to create route '{}'", levels)))?;
+            routes.push(Route {
+                levels: *levels,
+                filters,
+                appender,
+            });
+        }
+        Ok(Self { routes })
+    }
+}
+
+impl Appender for LevelRouterAppender {
+    fn append(&mut self, datetime: &Datetime, record: &OwnedRecord) -> Result<(), Error> {
+        let mut result = Ok(());
+        for route in self.routes.iter().filter(|x| x.accepts(record)) {
+            result = result.and(route.appender.lock().append(datetime, record));
+        }
+        result
+    }
+
+    /// Passes the records of each route as a batch, so that the inner appenders can still combine the writes.
+    fn append_batch(&mut self, records: &[(Datetime, Arc<OwnedRecord>)]) -> Result<(), Error> {
+        let mut result = Ok(());
+        for route in &self.routes {
+            let batch: Vec<_> = records.iter().filter(|(_, record)| route.accepts(record)).cloned().collect();
+            if !batch.is_empty() {
+                result = result.and(route.appender.lock().append_batch(&batch));
+            }
+        }
+        result
+    }
+
+    fn flush(&mut self) -> Result<(), Error> {
+        let mut result = Ok(());
+        for route in &self.routes {
+            result = result.and(route.appender.lock().flush());
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use log::{Level, RecordBuilder};
+
+    use crate::appender::Appender;
+    use crate::config::AppenderConfig;
+    use crate::encoder::tests::*;
+    use crate::record::OwnedRecord;
+
+    #[test]
+    fn test_route() {
+        let dir = std::path::Path::new("__test_level_router");
+        let _ = std::fs::remove_dir_all(dir);
+        let config: AppenderConfig = serde_json::from_str(r#"{"kind": "level_router", "routes": {
+            "error": {"kind": "file", "encoder": {"kind": "pattern", "pattern": "{message}"}, "path": "__test_level_router/error.log"},
+            "trace..warn": {"kind": "file", "encoder": {"kind": "pattern", "pattern": "{message}"}, "path": "__test_level_router/app.log"},
+            "warn..error": {
+                "kind": "file", "encoder": {"kind": "pattern", "pattern": "{message}"}, "path": "__test_level_router/alert.log",
+                "filters": [{"kind": "regex", "pattern": "disk"}]
+            }
+        }}"#)
+        .unwrap();
+        let AppenderConfig::LevelRouter(config) = &config else {
+            unreachable!();
+        };
+        let mut appender = super::LevelRouterAppender::try_from((config, Default::default())).unwrap();
+        let datetime = test_datetime();
+        let records: Vec<_> = [(Level::Error, "disk failed"), (Level::Warn, "disk almost full"), (Level::Info, "started")]
+            .into_iter()
+            .map(|(level, message)| {
+                let mut builder = RecordBuilder::new();
+                prepare_test_log_record(&mut builder);
+                (datetime, Arc::new(OwnedRecord::from(&builder.level(level).args(format_args!("{}", message)).build())))
+            })
+            .collect();
+        appender.append(&records[0].0, &records[0].1).unwrap();
+        appender.append_batch(&records[1..]).unwrap();
+        appender.flush().unwrap();
+
+        let read = |name: &str| std::fs::read_to_string(dir.join(name)).unwrap();
+        assert_eq!(read("error.log"), "disk failed\n");
+        assert_eq!(read("app.log"), "disk almost full\nstarted\n");
+        assert_eq!(read("alert.log"), "disk failed\ndisk almost full\n");
+
+        drop(appender);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
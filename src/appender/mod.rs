@@ -1,27 +1,197 @@
-use std::sync::{Arc, Mutex};
+use std::sync::Arc;
+use std::time::Duration;
 
-use log::Record;
+use log::Level;
 
 use crate::{Datetime, Error};
 use crate::appender::console::ConsoleAppender;
-use crate::config::AppenderConfig;
+use crate::config::{AppenderConfig, SyncPolicy, Timezone};
+use crate::encoder::Encoder;
+use crate::record::OwnedRecord;
+use crate::sync::Mutex;
 
+pub(crate) use compress::Compressor;
+pub(crate) use custom::register as register_appender_kind;
+#[cfg(feature = "cli")]
+pub(crate) use file::{validate_cleanup_pattern, PathPattern};
+pub(crate) use file::{cleanup_stale_files, initial_paths, request_reopen};
+pub(crate) use forward::register as register_forward_target;
+pub(crate) use hook::register as register_rotate_hook;
+pub use worker::AppenderWorker;
+
+mod compress;
 mod console;
+mod custom;
+mod email;
 mod file;
+#[cfg(feature = "fluentd")]
+mod fluentd;
+mod forward;
+mod hook;
+mod http;
+mod journald;
+#[cfg(feature = "kafka")]
+mod kafka;
+mod level_router;
+mod net;
+mod rate_limit;
+#[cfg(feature = "sqlite")]
+mod sqlite;
+mod syslog;
+mod tcp;
+mod websocket;
+mod worker;
 
+/// Writes the records to a destination; driven by a dedicated thread of the appender (unless `async` is off).
+/// The custom ones can be registered by [`crate::register_appender_kind`].
+///
+/// The datetime of a record is in the configured timezone.
+/// The errors returned are handled by the `error_policy` of the config.
 pub trait Appender {
-    fn append(&mut self, datetime: &Datetime, record: &Record);
-    fn flush(&mut self);
+    fn append(&mut self, datetime: &Datetime, record: &OwnedRecord) -> Result<(), Error>;
+
+    /// Appends the records drained from the worker queue at once.
+    /// The appenders may override it to combine the writes.
+    /// The records after a failed one are still appended; the first error is returned.
+    fn append_batch(&mut self, records: &[(Datetime, Arc<OwnedRecord>)]) -> Result<(), Error> {
+        let mut result = Ok(());
+        for (datetime, record) in records {
+            result = result.and(self.append(datetime, record));
+        }
+        result
+    }
+
+    fn flush(&mut self) -> Result<(), Error>;
+
+    /// The encoder of the entries written by [`Appender::append_encoded`], for the appenders writing the encoded records
+    /// as they are; then the records are encoded by the logging threads if `async` is on, and queued as the entries.
+    /// The other appenders get the records queued, as by default.
+    fn entry_encoder(&self) -> Option<Arc<dyn Encoder + Send + Sync>> {
+        None
+    }
+
+    /// Appends the entries encoded by the [`Appender::entry_encoder`], drained from the worker queue at once.
+    fn append_encoded(&mut self, entries: &[EncodedRecord]) -> Result<(), Error> {
+        let _ = entries;
+        Err(Error::from("the appender doesn't write the encoded records"))
+    }
+}
+
+/// A record encoded as an entry of the appender, e.g. a line of the log file.
+pub struct EncodedRecord {
+    pub datetime: Datetime,
+    pub level: Level,
+    /// The target of the record, for counting it if dropped.
+    pub target: Arc<str>,
+    pub content: Vec<u8>,
 }
 
-pub fn from_config(config: &AppenderConfig) -> Result<Arc<Mutex<dyn Appender + Send>>, Error> {
+impl EncodedRecord {
+    pub fn new(encoder: &dyn Encoder, datetime: &Datetime, record: &OwnedRecord) -> Self {
+        let mut content = vec![];
+        encoder.encode_entry(datetime, record, &mut content);
+        Self {
+            datetime: *datetime,
+            level: record.level,
+            target: record.target.clone(),
+            content,
+        }
+    }
+}
+
+impl<A: Appender + ?Sized> Appender for Box<A> {
+    fn append(&mut self, datetime: &Datetime, record: &OwnedRecord) -> Result<(), Error> {
+        (**self).append(datetime, record)
+    }
+
+    fn append_batch(&mut self, records: &[(Datetime, Arc<OwnedRecord>)]) -> Result<(), Error> {
+        (**self).append_batch(records)
+    }
+
+    fn flush(&mut self) -> Result<(), Error> {
+        (**self).flush()
+    }
+
+    fn entry_encoder(&self) -> Option<Arc<dyn Encoder + Send + Sync>> {
+        (**self).entry_encoder()
+    }
+
+    fn append_encoded(&mut self, entries: &[EncodedRecord]) -> Result<(), Error> {
+        (**self).append_encoded(entries)
+    }
+}
+
+pub fn from_config(config: &AppenderConfig, timezone: Timezone) -> Result<Arc<Mutex<dyn Appender + Send>>, Error> {
     match config {
         AppenderConfig::Console(config) => {
             let appender = ConsoleAppender::try_from(config)?;
             Ok(Arc::new(Mutex::new(appender)))
         }
         AppenderConfig::File(config) => {
-            let appender = file::FileAppender::try_from(config)?;
+            let appender = Arc::new(Mutex::new(file::FileAppender::try_from((config, timezone))?));
+            if config.flush_interval > 0 {
+                file::spawn_flusher(Arc::downgrade(&appender), Duration::from_secs(config.flush_interval))?;
+            }
+            if let SyncPolicy::Interval(secs) = config.sync {
+                file::spawn_syncer(Arc::downgrade(&appender), Duration::from_secs(secs))?;
+            }
+            Ok(appender)
+        }
+        AppenderConfig::Forward(config) => {
+            let appender = forward::ForwardAppender::try_from(config)?;
+            Ok(Arc::new(Mutex::new(appender)))
+        }
+        AppenderConfig::Syslog(config) => {
+            let appender = syslog::SyslogAppender::try_from(config)?;
+            Ok(Arc::new(Mutex::new(appender)))
+        }
+        AppenderConfig::Journald(config) => {
+            let appender = journald::JournaldAppender::try_from(config)?;
+            Ok(Arc::new(Mutex::new(appender)))
+        }
+        AppenderConfig::Tcp(config) => {
+            let appender = tcp::TcpAppender::try_from(config)?;
+            Ok(Arc::new(Mutex::new(appender)))
+        }
+        AppenderConfig::Http(config) => {
+            let appender = http::HttpAppender::try_from(config)?;
+            Ok(Arc::new(Mutex::new(appender)))
+        }
+        AppenderConfig::Email(config) => {
+            let appender = email::EmailAppender::try_from(config)?;
+            Ok(Arc::new(Mutex::new(appender)))
+        }
+        #[cfg(feature = "fluentd")]
+        AppenderConfig::Fluentd(config) => {
+            let appender = fluentd::FluentdAppender::try_from(config)?;
+            Ok(Arc::new(Mutex::new(appender)))
+        }
+        #[cfg(not(feature = "fluentd"))]
+        AppenderConfig::Fluentd(_) => Err(Error::from("fluentd appender requires the `fluentd` feature")),
+        #[cfg(feature = "kafka")]
+        AppenderConfig::Kafka(config) => {
+            let appender = kafka::KafkaAppender::try_from(config)?;
+            Ok(Arc::new(Mutex::new(appender)))
+        }
+        #[cfg(not(feature = "kafka"))]
+        AppenderConfig::Kafka(_) => Err(Error::from("kafka appender requires the `kafka` feature")),
+        #[cfg(feature = "sqlite")]
+        AppenderConfig::Sqlite(config) => {
+            let appender = sqlite::SqliteAppender::try_from(config)?;
+            Ok(Arc::new(Mutex::new(appender)))
+        }
+        #[cfg(not(feature = "sqlite"))]
+        AppenderConfig::Sqlite(_) => Err(Error::from("sqlite appender requires the `sqlite` feature")),
+        AppenderConfig::WebSocket(config) => {
+            let appender = websocket::WebSocketAppender::try_from(config)?;
+            Ok(Arc::new(Mutex::new(appender)))
+        }
+        AppenderConfig::LevelRouter(config) => {
+            let appender = level_router::LevelRouterAppender::try_from((config, timezone))?;
+            Ok(Arc::new(Mutex::new(appender)))
+        }
+        AppenderConfig::Custom(config) => {
+            let appender = custom::create(config)?;
             Ok(Arc::new(Mutex::new(appender)))
         }
     }
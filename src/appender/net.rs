@@ -0,0 +1,266 @@
+use std::collections::VecDeque;
+use std::io::{ErrorKind, IoSlice, Write};
+use std::net::{SocketAddr, TcpStream, ToSocketAddrs};
+use std::sync::mpsc::{Receiver, TryRecvError};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::dropped;
+
+type Resolution = std::io::Result<Vec<SocketAddr>>;
+
+/// Resolves the address (`host:port`) by a background thread, as the resolution can't be timed out;
+/// the result is sent to the returned channel. The IP addresses are resolved at once.
+fn resolve(address: &str) -> Receiver<Resolution> {
+    let (sender, receiver) = std::sync::mpsc::sync_channel(1);
+    if let Ok(address) = address.parse::<SocketAddr>() {
+        let _ = sender.send(Ok(vec![address]));
+        return receiver;
+    }
+    let address = address.to_string();
+    let resolver = sender.clone();
+    let spawned = std::thread::Builder::new()
+        .name("naive-logger-resolver".to_string())
+        .spawn(move || {
+            let _ = resolver.send(address.to_socket_addrs().map(Iterator::collect));
+        });
+    if let Err(e) = spawned {
+        let _ = sender.send(Err(e));
+    }
+    receiver
+}
+
+/// Connects to the address (`host:port`), trying each resolved address with the connect timeout,
+/// which also limits the resolution; the write timeout is set on the connected stream.
+pub fn connect(address: &str, connect_timeout: Duration, write_timeout: Duration) -> std::io::Result<TcpStream> {
+    let addresses = resolve(address)
+        .recv_timeout(connect_timeout)
+        .unwrap_or_else(|_| Err(std::io::Error::new(ErrorKind::TimedOut, "address resolution timed out")))?;
+    connect_resolved(&addresses, connect_timeout, write_timeout)
+}
+
+fn connect_resolved(addresses: &[SocketAddr], connect_timeout: Duration, write_timeout: Duration) -> std::io::Result<TcpStream> {
+    let mut last_error = std::io::Error::new(ErrorKind::NotFound, "no address resolved");
+    for address in addresses {
+        match TcpStream::connect_timeout(address, connect_timeout) {
+            Ok(stream) => {
+                stream.set_write_timeout(Some(write_timeout))?;
+                let _ = stream.set_nodelay(true);
+                return Ok(stream);
+            }
+            Err(e) => last_error = e,
+        }
+    }
+    Err(last_error)
+}
+
+/// The delay before the first reconnection; doubled after each failure up to [`MAX_BACKOFF`].
+const MIN_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+/// The maximum number of the chunks written at once.
+const MAX_CHUNKS_PER_WRITE: usize = 64;
+
+/// A TCP connection buffering the data while it's down, and reconnecting with backoff.
+/// The reconnection is attempted when sending after the backoff has elapsed;
+/// the address is resolved in the background meanwhile, so that the sending isn't blocked by the DNS.
+pub struct BufferedConnection {
+    address: String,
+    /// The resolved addresses, resolved again after a connection failure in case they've changed.
+    addresses: Vec<SocketAddr>,
+    resolution: Option<Receiver<Resolution>>,
+    connect_timeout: Duration,
+    write_timeout: Duration,
+    pub(super) stream: Option<TcpStream>,
+    backoff: Duration,
+    pub(super) next_attempt: Instant,
+    /// The chunks not sent yet, with the targets of their records to report if dropped.
+    pub(super) buffer: VecDeque<(Arc<str>, Vec<u8>)>,
+    pub(super) buffered_bytes: u64,
+    max_buffered_bytes: u64,
+}
+
+impl BufferedConnection {
+    /// Creates the connection without connecting; the endpoint may be started later than the program.
+    pub fn new(address: String, connect_timeout: Duration, write_timeout: Duration, max_buffered_bytes: u64) -> Self {
+        Self {
+            address,
+            addresses: vec![],
+            resolution: None,
+            connect_timeout,
+            write_timeout,
+            stream: None,
+            backoff: MIN_BACKOFF,
+            next_attempt: Instant::now(),
+            buffer: VecDeque::new(),
+            buffered_bytes: 0,
+            max_buffered_bytes,
+        }
+    }
+
+    /// Buffers the chunk of the record of the target, dropping the oldest ones if the buffer is full.
+    pub fn push(&mut self, target: Arc<str>, chunk: Vec<u8>) {
+        self.buffered_bytes += chunk.len() as u64;
+        self.buffer.push_back((target, chunk));
+        while self.buffered_bytes > self.max_buffered_bytes {
+            let Some((target, chunk)) = self.buffer.pop_front() else {
+                break;
+            };
+            self.buffered_bytes -= chunk.len() as u64;
+            dropped::count(&target, 1);
+        }
+    }
+
+    /// Sends the buffered chunks if connected, or reconnected after the backoff.
+    pub fn send_buffered(&mut self) {
+        if self.buffer.is_empty() || !self.connect_if_needed() {
+            return;
+        }
+        let Some(stream) = &mut self.stream else {
+            return;
+        };
+        // the chunks are only removed from the buffer after being written entirely;
+        // a partially written one is sent again as a whole after reconnecting
+        let mut offset = 0;
+        while !self.buffer.is_empty() {
+            let slices: Vec<IoSlice> = self
+                .buffer
+                .iter()
+                .take(MAX_CHUNKS_PER_WRITE)
+                .enumerate()
+                .map(|(i, (_, chunk))| IoSlice::new(if i == 0 { &chunk[offset..] } else { chunk }))
+                .collect();
+            let mut written = match stream.write_vectored(&slices) {
+                Ok(0) => break,
+                Ok(n) => n,
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(_) => break,
+            };
+            while let Some((_, chunk)) = self.buffer.front() {
+                let rest = chunk.len() - offset;
+                if written < rest {
+                    offset += written;
+                    break;
+                }
+                written -= rest;
+                offset = 0;
+                self.buffered_bytes -= chunk.len() as u64;
+                self.buffer.pop_front();
+            }
+        }
+        if !self.buffer.is_empty() {
+            self.disconnect();
+        }
+    }
+
+    pub fn flush(&mut self) {
+        // wait for the pending resolution, so that the buffer can be sent
+        if let Some(resolution) = &self.resolution {
+            if let Ok(result) = resolution.recv_timeout(self.connect_timeout) {
+                self.resolved(result);
+            }
+        }
+        self.send_buffered();
+        if let Some(stream) = &mut self.stream {
+            let _ = stream.flush();
+        }
+    }
+
+    /// Connects if not connected and the backoff has elapsed; returns whether connected.
+    fn connect_if_needed(&mut self) -> bool {
+        if self.stream.is_some() {
+            return true;
+        }
+        if Instant::now() < self.next_attempt {
+            return false;
+        }
+        if self.addresses.is_empty() {
+            let resolution = self.resolution.get_or_insert_with(|| resolve(&self.address));
+            match resolution.try_recv() {
+                Ok(result) => self.resolved(result),
+                Err(TryRecvError::Empty) => return false,
+                Err(TryRecvError::Disconnected) => self.resolved(Ok(vec![])),
+            }
+            if self.addresses.is_empty() {
+                return false;
+            }
+        }
+        match connect_resolved(&self.addresses, self.connect_timeout, self.write_timeout) {
+            Ok(stream) => {
+                self.stream = Some(stream);
+                self.backoff = MIN_BACKOFF;
+                true
+            }
+            Err(_) => {
+                self.addresses.clear();
+                self.disconnect();
+                false
+            }
+        }
+    }
+
+    /// Takes the result of the pending resolution; the failure is retried after the backoff.
+    fn resolved(&mut self, result: Resolution) {
+        self.resolution = None;
+        match result {
+            Ok(addresses) if !addresses.is_empty() => self.addresses = addresses,
+            _ => self.disconnect(),
+        }
+    }
+
+    fn disconnect(&mut self) {
+        self.stream = None;
+        self.next_attempt = Instant::now() + self.backoff;
+        self.backoff = (self.backoff * 2).min(MAX_BACKOFF);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+    use std::net::TcpListener;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use super::BufferedConnection;
+
+    fn read_all(listener: &TcpListener, connection: BufferedConnection) -> Vec<u8> {
+        let (mut stream, _) = listener.accept().unwrap();
+        stream.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+        drop(connection);
+        let mut data = vec![];
+        stream.read_to_end(&mut data).unwrap();
+        data
+    }
+
+    #[test]
+    fn test_send_buffered() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let mut connection = BufferedConnection::new(address, Duration::from_secs(1), Duration::from_secs(1), 1 << 20);
+        let mut expected = vec![];
+        for i in 0..200 {
+            let chunk = format!("{}\n", i).into_bytes();
+            expected.extend_from_slice(&chunk);
+            connection.push(Arc::from("test"), chunk);
+        }
+        connection.send_buffered();
+        assert!(connection.stream.is_some());
+        assert!(connection.buffer.is_empty());
+        assert_eq!(connection.buffered_bytes, 0);
+        assert_eq!(read_all(&listener, connection), expected);
+    }
+
+    #[test]
+    fn test_resolve() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = format!("localhost:{}", listener.local_addr().unwrap().port());
+        let mut connection = BufferedConnection::new(address, Duration::from_secs(5), Duration::from_secs(1), 1 << 20);
+        connection.push(Arc::from("test"), b"aaa\n".to_vec());
+        // the buffer is sent once resolved, at the latest when flushed
+        connection.send_buffered();
+        connection.flush();
+        assert!(connection.buffer.is_empty());
+        assert_eq!(read_all(&listener, connection), b"aaa\n");
+    }
+}
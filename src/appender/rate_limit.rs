@@ -0,0 +1,73 @@
+use std::time::Instant;
+
+use crate::sync::Mutex;
+
+/// Limits the rate of the records passed to an appender by a token bucket,
+/// so that a flooding target can't fill the disk or the network.
+pub struct RateLimiter {
+    /// The tokens refilled per second.
+    rate: f64,
+    /// The capacity of the bucket.
+    burst: f64,
+    state: Mutex<State>,
+}
+
+struct State {
+    tokens: f64,
+    refilled_at: Instant,
+}
+
+impl RateLimiter {
+    /// Creates a limiter with a full bucket; `burst` of `0` means the same as `rate`.
+    pub fn new(rate: u32, burst: u32) -> Self {
+        let burst = if burst == 0 { rate } else { burst };
+        Self {
+            rate: rate as f64,
+            burst: burst as f64,
+            state: Mutex::new(State {
+                tokens: burst as f64,
+                refilled_at: Instant::now(),
+            }),
+        }
+    }
+
+    /// Takes a token if available; otherwise the record should be dropped.
+    pub fn acquire(&self) -> bool {
+        self.acquire_at(Instant::now())
+    }
+
+    fn acquire_at(&self, now: Instant) -> bool {
+        let mut state = self.state.lock();
+        let elapsed = now.saturating_duration_since(state.refilled_at).as_secs_f64();
+        state.tokens = (state.tokens + elapsed * self.rate).min(self.burst);
+        state.refilled_at = now;
+        if state.tokens < 1.0 {
+            return false;
+        }
+        state.tokens -= 1.0;
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn test_acquire() {
+        let limiter = super::RateLimiter::new(10, 3);
+        let now = Instant::now();
+        assert_eq!((0..5).filter(|_| limiter.acquire_at(now)).count(), 3);
+
+        // refilled by one token per 100ms
+        let now = now + Duration::from_millis(250);
+        assert_eq!((0..5).filter(|_| limiter.acquire_at(now)).count(), 2);
+
+        // never above the burst size
+        let now = now + Duration::from_secs(60);
+        assert_eq!((0..5).filter(|_| limiter.acquire_at(now)).count(), 3);
+
+        let limiter = super::RateLimiter::new(2, 0);
+        assert_eq!((0..5).filter(|_| limiter.acquire()).count(), 2);
+    }
+}
@@ -0,0 +1,178 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::SecondsFormat;
+use rusqlite::Connection;
+
+use crate::{Datetime, dropped, Error};
+use crate::appender::Appender;
+use crate::config::SqliteAppenderConfig;
+use crate::record::OwnedRecord;
+
+/// The time to wait for the database locked by another connection.
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Inserts the records into a table of an SQLite database, in a transaction per batch;
+/// the database is in the WAL mode, so that it can be queried while being written.
+pub struct SqliteAppender {
+    connection: Connection,
+    insert_sql: String,
+}
+
+impl TryFrom<&SqliteAppenderConfig> for SqliteAppender {
+    type Error = Error;
+
+    fn try_from(config: &SqliteAppenderConfig) -> Result<Self, Self::Error> {
+        let table = &config.table;
+        if !is_identifier(table) {
+            return Err(Error::from(format!(
+                "table '{}' should consist of ASCII letters, digits and underscores, and not start with a digit",
+                table
+            )));
+        }
+        if let Some(dir) = config.path.parent().filter(|x| !x.as_os_str().is_empty()) {
+            std::fs::create_dir_all(dir)
+                .map_err(|e| Error::from(format!("failed to create directory '{}': {}", dir.display(), e)))?;
+        }
+        let connection = Connection::open(&config.path)
+            .map_err(|e| Error::from(format!("failed to open database '{}': {}", config.path.display(), e)))?;
+        connection
+            .busy_timeout(BUSY_TIMEOUT)
+            .and_then(|_| connection.pragma_update(None, "journal_mode", "WAL"))
+            .and_then(|_| {
+                connection.execute_batch(&format!(
+                    "CREATE TABLE IF NOT EXISTS {table} (
+                        id INTEGER PRIMARY KEY,
+                        timestamp TEXT NOT NULL,
+                        level TEXT NOT NULL,
+                        target TEXT NOT NULL,
+                        message TEXT NOT NULL,
+                        kvs TEXT
+                    );
+                    CREATE INDEX IF NOT EXISTS {table}_timestamp ON {table} (timestamp);"
+                ))
+            })
+            .map_err(|e| Error::from(format!("failed to create table '{}': {}", table, e)))?;
+        Ok(Self {
+            connection,
+            insert_sql: format!("INSERT INTO {table} (timestamp, level, target, message, kvs) VALUES (?1, ?2, ?3, ?4, ?5)"),
+        })
+    }
+}
+
+impl Appender for SqliteAppender {
+    fn append(&mut self, datetime: &Datetime, record: &OwnedRecord) -> Result<(), Error> {
+        if self.insert(datetime, record).is_err() {
+            dropped::count(&record.target, 1);
+        }
+        Ok(())
+    }
+
+    fn append_batch(&mut self, records: &[(Datetime, Arc<OwnedRecord>)]) -> Result<(), Error> {
+        let result = self.connection.unchecked_transaction().and_then(|transaction| {
+            for (datetime, record) in records {
+                self.insert(datetime, record)?;
+            }
+            transaction.commit()
+        });
+        // the transaction is rolled back if failed, so none of the records is inserted
+        if result.is_err() {
+            for (_, record) in records {
+                dropped::count(&record.target, 1);
+            }
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+impl SqliteAppender {
+    fn insert(&self, datetime: &Datetime, record: &OwnedRecord) -> rusqlite::Result<()> {
+        let kvs = if record.kvs.is_empty() {
+            None
+        } else {
+            let map: serde_json::Map<String, serde_json::Value> = record.kvs.iter().cloned().collect();
+            Some(serde_json::Value::Object(map).to_string())
+        };
+        let mut statement = self.connection.prepare_cached(&self.insert_sql)?;
+        statement.execute((
+            datetime.to_rfc3339_opts(SecondsFormat::Millis, false),
+            record.level.as_str(),
+            &*record.target,
+            &record.message,
+            kvs,
+        ))?;
+        Ok(())
+    }
+}
+
+fn is_identifier(s: &str) -> bool {
+    s.chars().next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && s.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use log::RecordBuilder;
+
+    use crate::appender::Appender;
+    use crate::config::SqliteAppenderConfig;
+    use crate::encoder::tests::*;
+    use crate::record::OwnedRecord;
+
+    #[test]
+    fn test_append_batch() {
+        let dir = std::env::temp_dir().join(format!("__test_sqlite_{}", std::process::id()));
+        let config = SqliteAppenderConfig {
+            enabled: true,
+            queue: Default::default(),
+            filters: vec![],
+            path: dir.join("logs.db"),
+            table: "app_logs".to_string(),
+        };
+        let mut appender = super::SqliteAppender::try_from(&config).unwrap();
+        let kvs = [("user_id", log::kv::Value::from(42))];
+        let mut builder = RecordBuilder::new();
+        prepare_test_log_record(&mut builder);
+        let with_kvs = OwnedRecord::from(&builder.args(format_args!("{}", TEST_MESSAGE)).key_values(&kvs).build());
+        let mut builder = RecordBuilder::new();
+        prepare_test_log_record(&mut builder);
+        let without_kvs = OwnedRecord::from(&builder.args(format_args!("second")).build());
+        let datetime = test_datetime();
+        appender.append_batch(&[(datetime, Arc::new(with_kvs)), (datetime, Arc::new(without_kvs))]).unwrap();
+
+        let connection = rusqlite::Connection::open(&config.path).unwrap();
+        let mut statement = connection
+            .prepare("SELECT level, target, message, json_extract(kvs, '$.user_id') FROM app_logs ORDER BY id")
+            .unwrap();
+        let rows: Vec<(String, String, String, Option<i64>)> = statement
+            .query_map((), |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)))
+            .unwrap()
+            .map(Result::unwrap)
+            .collect();
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0], (TEST_LEVEL.to_string(), TEST_TARGET.to_string(), TEST_MESSAGE.to_string(), Some(42)));
+        assert_eq!(rows[1].2, "second");
+        assert_eq!(rows[1].3, None);
+
+        drop(appender);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_invalid_table() {
+        let config = SqliteAppenderConfig {
+            enabled: true,
+            queue: Default::default(),
+            filters: vec![],
+            path: "unused.db".into(),
+            table: "logs; DROP TABLE users".to_string(),
+        };
+        assert!(super::SqliteAppender::try_from(&config).is_err());
+    }
+}
@@ -0,0 +1,165 @@
+use std::io::Write;
+#[cfg(unix)]
+use std::os::unix::net::UnixDatagram;
+use std::path::PathBuf;
+
+use log::Level;
+
+use crate::{Datetime, dropped, encoder, Error};
+use crate::appender::Appender;
+use crate::config::SyslogAppenderConfig;
+use crate::encoder::Encoder;
+use crate::record::OwnedRecord;
+
+/// Writes the records to the local syslog daemon, one datagram per record in the BSD syslog format
+/// (`<PRI>Mmm dd hh:mm:ss TAG[PID]: MSG`) like `syslog(3)`.
+pub struct SyslogAppender {
+    encoder: Box<dyn Encoder + Send + Sync>,
+    #[cfg(unix)]
+    socket: Option<UnixDatagram>,
+    path: PathBuf,
+    facility: u8,
+    tag: String,
+}
+
+impl TryFrom<&SyslogAppenderConfig> for SyslogAppender {
+    type Error = Error;
+
+    fn try_from(config: &SyslogAppenderConfig) -> Result<Self, Self::Error> {
+        if cfg!(not(unix)) {
+            return Err(Error::from("syslog appender is only supported on Unix"));
+        }
+        let encoder = encoder::from_config(&config.common.encoder)
+            .map_err(|e| e.concat("failed to create encoder"))?;
+        let encoder = encoder::with_redaction(encoder, config.common.redact.as_ref());
+        let app_name = config.app_name.clone().unwrap_or_else(crate::program_name);
+        let mut appender = Self {
+            encoder,
+            #[cfg(unix)]
+            socket: None,
+            path: config.socket.clone(),
+            facility: config.facility as u8,
+            tag: format!("{}[{}]", app_name, std::process::id()),
+        };
+        appender
+            .connect()
+            .map_err(|e| Error::from(format!("failed to connect to syslog socket '{}': {}", config.socket.display(), e)))?;
+        Ok(appender)
+    }
+}
+
+impl Appender for SyslogAppender {
+    fn append(&mut self, datetime: &Datetime, record: &OwnedRecord) -> Result<(), Error> {
+        let mut buffer = vec![];
+        write!(
+            buffer,
+            "<{}>{} {}: ",
+            self.facility * 8 + severity(record.level),
+            datetime.format("%b %e %H:%M:%S"),
+            self.tag
+        )
+        .unwrap();
+        self.encoder.encode(datetime, record, &mut buffer);
+
+        // the daemon may have been restarted, so reconnect once before giving up the record
+        if self.send(&buffer).is_err() && (self.connect().is_err() || self.send(&buffer).is_err()) {
+            dropped::count(&record.target, 1);
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+impl SyslogAppender {
+    #[cfg(unix)]
+    fn connect(&mut self) -> std::io::Result<()> {
+        self.socket = None;
+        let socket = UnixDatagram::unbound()?;
+        socket.connect(&self.path)?;
+        self.socket = Some(socket);
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    fn send(&self, buf: &[u8]) -> std::io::Result<()> {
+        match &self.socket {
+            None => Err(std::io::ErrorKind::NotConnected.into()),
+            Some(socket) => socket.send(buf).map(|_| ()),
+        }
+    }
+
+    #[cfg(not(unix))]
+    fn connect(&mut self) -> std::io::Result<()> {
+        Err(std::io::ErrorKind::Unsupported.into())
+    }
+
+    #[cfg(not(unix))]
+    fn send(&self, _buf: &[u8]) -> std::io::Result<()> {
+        Err(std::io::ErrorKind::Unsupported.into())
+    }
+}
+
+/// Maps the level to the syslog severity; both `debug` and `trace` are mapped to `debug`.
+fn severity(level: Level) -> u8 {
+    match level {
+        Level::Error => 3,
+        Level::Warn => 4,
+        Level::Info => 6,
+        Level::Debug | Level::Trace => 7,
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use std::os::unix::net::UnixDatagram;
+
+    use log::RecordBuilder;
+
+    use crate::appender::Appender;
+    use crate::config::{AppenderCommonProperties, EncoderConfig, PatternEncoderConfig, SyslogAppenderConfig, SyslogFacility};
+    use crate::encoder::tests::*;
+    use crate::record::OwnedRecord;
+
+    #[test]
+    fn test_append() {
+        let path = std::env::temp_dir().join(format!("__test_syslog_{}.sock", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let server = UnixDatagram::bind(&path).unwrap();
+
+        let config = SyslogAppenderConfig {
+            enabled: true,
+            queue: Default::default(),
+            filters: vec![],
+            common: AppenderCommonProperties {
+                encoder: EncoderConfig::Pattern(PatternEncoderConfig {
+                    pattern: "{message}".to_string(),
+                    ..Default::default()
+                }),
+                redact: None,
+            },
+            socket: path.clone(),
+            facility: SyslogFacility::Local0,
+            app_name: Some("myapp".to_string()),
+        };
+        let mut appender = super::SyslogAppender::try_from(&config).unwrap();
+        let mut builder = RecordBuilder::new();
+        prepare_test_log_record(&mut builder);
+        let record = OwnedRecord::from(&builder.level(log::Level::Warn).args(format_args!("{}", TEST_MESSAGE)).build());
+        let datetime = test_datetime();
+        appender.append(&datetime, &record).unwrap();
+
+        let mut buf = [0; 1024];
+        let n = server.recv(&mut buf).unwrap();
+        let expected = format!(
+            "<132>{} myapp[{}]: {}",
+            datetime.format("%b %e %H:%M:%S"),
+            std::process::id(),
+            TEST_MESSAGE
+        );
+        assert_eq!(std::str::from_utf8(&buf[..n]).unwrap(), expected);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,145 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::{Datetime, encoder, Error};
+use crate::appender::{Appender, EncodedRecord};
+use crate::appender::net::BufferedConnection;
+use crate::config::TcpAppenderConfig;
+use crate::encoder::Encoder;
+use crate::record::OwnedRecord;
+
+/// Streams the encoded lines to a TCP endpoint.
+/// The lines are buffered while the connection is down, and sent after reconnected,
+/// which is attempted with the records appended or flushed after the backoff.
+pub struct TcpAppender {
+    encoder: Arc<dyn Encoder + Send + Sync>,
+    connection: BufferedConnection,
+}
+
+impl TryFrom<&TcpAppenderConfig> for TcpAppender {
+    type Error = Error;
+
+    fn try_from(config: &TcpAppenderConfig) -> Result<Self, Self::Error> {
+        let encoder = encoder::from_config(&config.common.encoder)
+            .map_err(|e| e.concat("failed to create encoder"))?;
+        let encoder = Arc::from(encoder::with_redaction(encoder, config.common.redact.as_ref()));
+        if config.connect_timeout == 0 || config.write_timeout == 0 {
+            return Err(Error::from("connect_timeout and write_timeout must be positive"));
+        }
+        let connection = BufferedConnection::new(
+            format!("{}:{}", config.host, config.port),
+            Duration::from_secs(config.connect_timeout),
+            Duration::from_secs(config.write_timeout),
+            config.max_buffered_bytes,
+        );
+        Ok(Self { encoder, connection })
+    }
+}
+
+impl Appender for TcpAppender {
+    fn append(&mut self, datetime: &Datetime, record: &OwnedRecord) -> Result<(), Error> {
+        self.push(datetime, record);
+        self.connection.send_buffered();
+        Ok(())
+    }
+
+    fn append_batch(&mut self, records: &[(Datetime, Arc<OwnedRecord>)]) -> Result<(), Error> {
+        for (datetime, record) in records {
+            self.push(datetime, record);
+        }
+        self.connection.send_buffered();
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), Error> {
+        self.connection.flush();
+        Ok(())
+    }
+
+    fn entry_encoder(&self) -> Option<Arc<dyn Encoder + Send + Sync>> {
+        Some(self.encoder.clone())
+    }
+
+    fn append_encoded(&mut self, entries: &[EncodedRecord]) -> Result<(), Error> {
+        for entry in entries {
+            self.connection.push(entry.target.clone(), entry.content.clone());
+        }
+        self.connection.send_buffered();
+        Ok(())
+    }
+}
+
+impl TcpAppender {
+    fn push(&mut self, datetime: &Datetime, record: &OwnedRecord) {
+        let mut line = vec![];
+        self.encoder.encode_entry(datetime, record, &mut line);
+        self.connection.push(record.target.clone(), line);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{BufRead, BufReader};
+    use std::net::TcpListener;
+    use std::time::Instant;
+
+    use log::RecordBuilder;
+
+    use crate::appender::Appender;
+    use crate::config::{AppenderCommonProperties, EncoderConfig, PatternEncoderConfig, TcpAppenderConfig};
+    use crate::encoder::tests::*;
+    use crate::record::OwnedRecord;
+
+    fn tcp_appender(port: u16, max_buffered_bytes: u64) -> super::TcpAppender {
+        let config = TcpAppenderConfig {
+            enabled: true,
+            queue: Default::default(),
+            filters: vec![],
+            common: AppenderCommonProperties {
+                encoder: EncoderConfig::Pattern(PatternEncoderConfig {
+                    pattern: "{message}".to_string(),
+                    ..Default::default()
+                }),
+                redact: None,
+            },
+            host: "127.0.0.1".to_string(),
+            port,
+            max_buffered_bytes,
+            connect_timeout: 1,
+            write_timeout: 1,
+        };
+        super::TcpAppender::try_from(&config).unwrap()
+    }
+
+    fn record(message: &str) -> OwnedRecord {
+        let mut builder = RecordBuilder::new();
+        prepare_test_log_record(&mut builder);
+        OwnedRecord::from(&builder.args(format_args!("{}", message)).build())
+    }
+
+    #[test]
+    fn test_buffer_and_reconnect() {
+        // reserve a port, then close the listener so that the connection is refused
+        let port = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+        let mut appender = tcp_appender(port, 8);
+        for message in ["aaa", "bbb", "ccc"] {
+            appender.append(&test_datetime(), &record(message)).unwrap();
+        }
+        assert!(appender.connection.stream.is_none());
+        assert_eq!(appender.connection.buffered_bytes, 8);
+        assert_eq!(appender.connection.buffer.front().unwrap().1, b"bbb\n");
+
+        let listener = TcpListener::bind(("127.0.0.1", port)).unwrap();
+        appender.connection.next_attempt = Instant::now();
+        appender.append(&test_datetime(), &record("ddd")).unwrap();
+        assert!(appender.connection.stream.is_some());
+        assert!(appender.connection.buffer.is_empty());
+
+        // `bbb` is dropped as `ddd` is buffered before sent
+        let (stream, _) = listener.accept().unwrap();
+        stream.set_read_timeout(Some(std::time::Duration::from_secs(5))).unwrap();
+        drop(appender);
+        let lines: Vec<_> = BufReader::new(stream).lines().map(Result::unwrap).collect();
+        assert_eq!(lines, ["ccc", "ddd"]);
+    }
+}
@@ -0,0 +1,333 @@
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::{Arc, Weak};
+use std::time::Duration;
+
+use crate::{Datetime, encoder, Error};
+use crate::appender::Appender;
+use crate::config::WebSocketAppenderConfig;
+use crate::encoder::Encoder;
+use crate::record::OwnedRecord;
+use crate::sync::Mutex;
+
+/// Appended to the key of the client to compute the accept value of the handshake, by RFC 6455.
+const ACCEPT_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+/// Keeps a stalled client from blocking the handshakes of the others.
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(5);
+
+type Clients = Mutex<Vec<TcpStream>>;
+
+/// Serves the encoded records to the WebSocket clients, one text message per record.
+/// The clients are accepted by a background thread, and receive only the records appended after connected;
+/// the messages from the clients are ignored, and a client is disconnected if a write fails or times out.
+pub struct WebSocketAppender {
+    encoder: Box<dyn Encoder + Send + Sync>,
+    clients: Arc<Clients>,
+}
+
+impl TryFrom<&WebSocketAppenderConfig> for WebSocketAppender {
+    type Error = Error;
+
+    fn try_from(config: &WebSocketAppenderConfig) -> Result<Self, Self::Error> {
+        let encoder = encoder::from_config(&config.common.encoder)
+            .map_err(|e| e.concat("failed to create encoder"))?;
+        let encoder = encoder::with_redaction(encoder, config.common.redact.as_ref());
+        if config.write_timeout == 0 {
+            return Err(Error::from("write_timeout must be positive"));
+        }
+        let listener = TcpListener::bind(&config.bind)
+            .map_err(|e| Error::from(format!("failed to bind '{}': {}", config.bind, e)))?;
+        let clients = Arc::new(Mutex::new(vec![]));
+        let weak = Arc::downgrade(&clients);
+        let max_clients = config.max_clients;
+        let write_timeout = Duration::from_secs(config.write_timeout);
+        std::thread::Builder::new()
+            .name("naive-logger-websocket".to_string())
+            .spawn(move || accept(listener, weak, max_clients, write_timeout))
+            .map_err(|e| Error::from(format!("failed to spawn websocket server: {}", e)))?;
+        Ok(Self { encoder, clients })
+    }
+}
+
+impl Appender for WebSocketAppender {
+    fn append(&mut self, datetime: &Datetime, record: &OwnedRecord) -> Result<(), Error> {
+        let mut frames = vec![];
+        self.push_frame(datetime, record, &mut frames);
+        self.send(&frames);
+        Ok(())
+    }
+
+    fn append_batch(&mut self, records: &[(Datetime, Arc<OwnedRecord>)]) -> Result<(), Error> {
+        let mut frames = vec![];
+        for (datetime, record) in records {
+            self.push_frame(datetime, record, &mut frames);
+        }
+        self.send(&frames);
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+impl WebSocketAppender {
+    fn push_frame(&self, datetime: &Datetime, record: &OwnedRecord, frames: &mut Vec<u8>) {
+        let mut message = vec![];
+        self.encoder.encode(datetime, record, &mut message);
+        if self.encoder.is_binary() {
+            return write_frame(frames, BINARY_OPCODE, &message);
+        }
+        // the text messages must be valid UTF-8
+        let message = match String::from_utf8(message) {
+            Ok(message) => message,
+            Err(e) => String::from_utf8_lossy(e.as_bytes()).into_owned(),
+        };
+        write_frame(frames, TEXT_OPCODE, message.as_bytes());
+    }
+
+    /// Sends the frames to all the clients, dropping the failed ones.
+    fn send(&self, frames: &[u8]) {
+        if frames.is_empty() {
+            return;
+        }
+        self.clients.lock().retain_mut(|client| client.write_all(frames).is_ok());
+    }
+}
+
+/// Accepts the clients; stops at the first connection after the appender is dropped.
+fn accept(listener: TcpListener, clients: Weak<Clients>, max_clients: usize, write_timeout: Duration) {
+    for stream in listener.incoming().flatten() {
+        let Some(clients) = clients.upgrade() else {
+            return;
+        };
+        let full = clients.lock().len() >= max_clients;
+        if let Ok(stream) = handshake(stream, full, write_timeout) {
+            clients.lock().push(stream);
+        }
+    }
+}
+
+/// Upgrades the HTTP connection to WebSocket; rejects it with `503` if there are too many clients.
+fn handshake(stream: TcpStream, full: bool, write_timeout: Duration) -> std::io::Result<TcpStream> {
+    stream.set_read_timeout(Some(HANDSHAKE_TIMEOUT))?;
+    stream.set_write_timeout(Some(write_timeout))?;
+    let mut reader = BufReader::new(&stream);
+    let mut key = None;
+    let mut upgrade = false;
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 || line.trim().is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            let name = name.trim();
+            if name.eq_ignore_ascii_case("sec-websocket-key") {
+                key = Some(value.trim().to_string());
+            } else if name.eq_ignore_ascii_case("upgrade") {
+                upgrade = value.trim().eq_ignore_ascii_case("websocket");
+            }
+        }
+    }
+    let mut writer = &stream;
+    let key = match key {
+        Some(key) if upgrade && !full => key,
+        _ => {
+            let status = if full { "503 Service Unavailable" } else { "400 Bad Request" };
+            write!(writer, "HTTP/1.1 {}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n", status)?;
+            return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, status));
+        }
+    };
+    write!(
+        writer,
+        "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Accept: {}\r\n\r\n",
+        accept_value(&key)
+    )?;
+    let _ = stream.set_nodelay(true);
+    Ok(stream)
+}
+
+/// The `Sec-WebSocket-Accept` value for the `Sec-WebSocket-Key` of the client.
+fn accept_value(key: &str) -> String {
+    base64(&sha1(format!("{}{}", key, ACCEPT_GUID).as_bytes()))
+}
+
+const TEXT_OPCODE: u8 = 0x1;
+const BINARY_OPCODE: u8 = 0x2;
+
+/// Writes an unmasked frame of the whole message, as sent by the servers.
+fn write_frame(buf: &mut Vec<u8>, opcode: u8, payload: &[u8]) {
+    // FIN and the opcode
+    buf.push(0x80 | opcode);
+    match payload.len() {
+        len @ 0..=125 => buf.push(len as u8),
+        len @ 126..=0xffff => {
+            buf.push(126);
+            buf.extend_from_slice(&(len as u16).to_be_bytes());
+        }
+        len => {
+            buf.push(127);
+            buf.extend_from_slice(&(len as u64).to_be_bytes());
+        }
+    }
+    buf.extend_from_slice(payload);
+}
+
+fn sha1(data: &[u8]) -> [u8; 20] {
+    let mut h: [u32; 5] = [0x67452301, 0xEFCDAB89, 0x98BADCFE, 0x10325476, 0xC3D2E1F0];
+    let mut message = data.to_vec();
+    message.push(0x80);
+    while message.len() % 64 != 56 {
+        message.push(0);
+    }
+    message.extend_from_slice(&((data.len() as u64) * 8).to_be_bytes());
+    for chunk in message.chunks(64) {
+        let mut w = [0u32; 80];
+        for (i, word) in chunk.chunks(4).enumerate() {
+            w[i] = u32::from_be_bytes(word.try_into().unwrap());
+        }
+        for i in 16..80 {
+            w[i] = (w[i - 3] ^ w[i - 8] ^ w[i - 14] ^ w[i - 16]).rotate_left(1);
+        }
+        let [mut a, mut b, mut c, mut d, mut e] = h;
+        for (i, w) in w.iter().enumerate() {
+            let (f, k) = match i {
+                0..=19 => ((b & c) | (!b & d), 0x5A827999),
+                20..=39 => (b ^ c ^ d, 0x6ED9EBA1),
+                40..=59 => ((b & c) | (b & d) | (c & d), 0x8F1BBCDC),
+                _ => (b ^ c ^ d, 0xCA62C1D6),
+            };
+            let temp = a.rotate_left(5).wrapping_add(f).wrapping_add(e).wrapping_add(k).wrapping_add(*w);
+            e = d;
+            d = c;
+            c = b.rotate_left(30);
+            b = a;
+            a = temp;
+        }
+        for (h, x) in h.iter_mut().zip([a, b, c, d, e]) {
+            *h = h.wrapping_add(x);
+        }
+    }
+    let mut result = [0; 20];
+    for (bytes, h) in result.chunks_mut(4).zip(h) {
+        bytes.copy_from_slice(&h.to_be_bytes());
+    }
+    result
+}
+
+fn base64(data: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut result = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let n = chunk.iter().enumerate().fold(0u32, |n, (i, &x)| n | (x as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                result.push(ALPHABET[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                result.push('=');
+            }
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpStream;
+    use std::time::{Duration, Instant};
+
+    use log::RecordBuilder;
+
+    use crate::appender::Appender;
+    use crate::config::{AppenderCommonProperties, EncoderConfig, PatternEncoderConfig, WebSocketAppenderConfig};
+    use crate::encoder::tests::*;
+    use crate::record::OwnedRecord;
+
+    #[test]
+    fn test_accept_value() {
+        // the example of RFC 6455
+        assert_eq!(super::accept_value("dGhlIHNhbXBsZSBub25jZQ=="), "s3pPLMBiTxaQ9kYGzzhZRbK+xOo=");
+        assert_eq!(super::base64(b"ab"), "YWI=");
+        assert_eq!(super::base64(b"a"), "YQ==");
+    }
+
+    #[test]
+    fn test_write_frame() {
+        let mut buf = vec![];
+        super::write_frame(&mut buf, super::TEXT_OPCODE, b"hi");
+        assert_eq!(buf, b"\x81\x02hi");
+        let mut buf = vec![];
+        super::write_frame(&mut buf, super::TEXT_OPCODE, &[b'x'; 200]);
+        assert_eq!(buf[..4], [0x81, 126, 0, 200]);
+        assert_eq!(buf.len(), 204);
+        let mut buf = vec![];
+        super::write_frame(&mut buf, super::BINARY_OPCODE, &[0, 1]);
+        assert_eq!(buf, [0x82, 2, 0, 1]);
+    }
+
+    #[test]
+    fn test_append() {
+        // bind a free port first, as the appender doesn't expose the bound address
+        let port = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+        let config = WebSocketAppenderConfig {
+            enabled: true,
+            queue: Default::default(),
+            filters: vec![],
+            common: AppenderCommonProperties {
+                encoder: EncoderConfig::Pattern(PatternEncoderConfig {
+                    pattern: "{level} {message}".to_string(),
+                    ..Default::default()
+                }),
+                redact: None,
+            },
+            bind: format!("127.0.0.1:{}", port),
+            max_clients: 1,
+            write_timeout: 1,
+        };
+        let mut appender = super::WebSocketAppender::try_from(&config).unwrap();
+
+        let mut stream = TcpStream::connect(("127.0.0.1", port)).unwrap();
+        stream
+            .write_all(b"GET /logs HTTP/1.1\r\nHost: localhost\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\nSec-WebSocket-Version: 13\r\n\r\n")
+            .unwrap();
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+        let mut response = String::new();
+        loop {
+            let mut line = String::new();
+            reader.read_line(&mut line).unwrap();
+            if line == "\r\n" {
+                break;
+            }
+            response.push_str(&line);
+        }
+        assert!(response.starts_with("HTTP/1.1 101 Switching Protocols\r\n"), "{}", response);
+        assert!(response.contains("Sec-WebSocket-Accept: s3pPLMBiTxaQ9kYGzzhZRbK+xOo=\r\n"));
+
+        // the second client is rejected
+        let mut rejected = TcpStream::connect(("127.0.0.1", port)).unwrap();
+        rejected
+            .write_all(b"GET / HTTP/1.1\r\nUpgrade: websocket\r\nSec-WebSocket-Key: eA==\r\n\r\n")
+            .unwrap();
+        let mut response = String::new();
+        rejected.read_to_string(&mut response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 503 Service Unavailable\r\n"), "{}", response);
+
+        // the client is registered after the response is written
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while appender.clients.lock().is_empty() && Instant::now() < deadline {
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        let mut builder = RecordBuilder::new();
+        prepare_test_log_record(&mut builder);
+        let record = OwnedRecord::from(&builder.args(format_args!("hello")).build());
+        appender.append(&test_datetime(), &record).unwrap();
+
+        let mut frame = [0; 2];
+        reader.read_exact(&mut frame).unwrap();
+        assert_eq!(frame[0], 0x81);
+        let mut payload = vec![0; frame[1] as usize];
+        reader.read_exact(&mut payload).unwrap();
+        assert_eq!(payload, format!("{} hello", TEST_LEVEL).as_bytes());
+    }
+}
@@ -0,0 +1,551 @@
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{sync_channel, Receiver, SyncSender, TrySendError};
+use std::sync::Arc;
+
+use crate::appender::{Appender, EncodedRecord};
+use crate::appender::rate_limit::RateLimiter;
+use crate::config::{ErrorPolicy, OverflowPolicy, QueueConfig};
+use crate::dropped;
+use crate::encoder::Encoder;
+use crate::filter::Filter;
+use crate::record::OwnedRecord;
+use crate::sync::Mutex;
+use crate::{Datetime, Error};
+
+enum Message {
+    Append(Datetime, Arc<OwnedRecord>),
+    AppendEncoded(EncodedRecord),
+    Flush(SyncSender<()>),
+}
+
+/// Owns a dedicated thread which drives the appender, so that the logging threads only push
+/// the records to the bounded channel; encoded by the logging threads if the appender writes the encoded ones.
+/// If not asynchronous, the appender is driven by the logging threads instead.
+pub struct AppenderWorker {
+    inner: Inner,
+    filters: Vec<Arc<dyn Filter>>,
+    rate_limiter: Option<RateLimiter>,
+    errors: Arc<ErrorHandler>,
+}
+
+enum Inner {
+    Bounded {
+        sender: SyncSender<Message>,
+        overflow_policy: OverflowPolicy,
+        /// The [`Appender::entry_encoder`] of the appender.
+        encoder: Option<Arc<dyn Encoder + Send + Sync>>,
+    },
+    Sync(Arc<Mutex<dyn Appender + Send>>),
+}
+
+impl AppenderWorker {
+    pub fn spawn(
+        name: &str,
+        appender: Arc<Mutex<dyn Appender + Send>>,
+        config: &QueueConfig,
+        filters: Vec<Arc<dyn Filter>>,
+        error_policy: ErrorPolicy,
+    ) -> Result<Self, Error> {
+        let errors = Arc::new(ErrorHandler {
+            name: name.to_string(),
+            policy: error_policy,
+            failed: AtomicBool::new(false),
+            failure: Mutex::new(None),
+        });
+        let rate_limiter = match config.max_records_per_second {
+            0 => None,
+            rate => Some(RateLimiter::new(rate, config.burst_size)),
+        };
+        if !config.async_ {
+            return Ok(Self {
+                inner: Inner::Sync(appender),
+                filters,
+                rate_limiter,
+                errors,
+            });
+        }
+        if config.queue_size == 0 {
+            return Err(Error::from("queue_size must be positive"));
+        }
+        let (sender, receiver) = sync_channel(config.queue_size);
+        let inner = Inner::Bounded {
+            sender,
+            overflow_policy: config.overflow_policy,
+            encoder: appender.lock().entry_encoder(),
+        };
+        let worker_errors = errors.clone();
+        std::thread::Builder::new()
+            .name(format!("naive-logger-{}", name))
+            .spawn(move || run(appender, receiver, &worker_errors))
+            .map_err(|e| Error::from(format!("failed to spawn worker thread: {}", e)))?;
+        Ok(Self {
+            inner,
+            filters,
+            rate_limiter,
+            errors,
+        })
+    }
+
+    /// Appends the record if it passes all the filters; they are evaluated by the logging threads.
+    /// The records above the rate limit are dropped and counted.
+    pub fn append(&self, datetime: &Datetime, record: &Arc<OwnedRecord>) {
+        if !self.filters.iter().all(|filter| filter.filter(record)) {
+            return;
+        }
+        if self.rate_limiter.as_ref().is_some_and(|limiter| !limiter.acquire()) {
+            dropped::count(&record.target, 1);
+            return;
+        }
+        self.errors.panic_if_failed();
+        match &self.inner {
+            Inner::Bounded { sender, overflow_policy, encoder } => {
+                let message = match encoder {
+                    Some(encoder) => Message::AppendEncoded(EncodedRecord::new(&**encoder, datetime, record)),
+                    None => Message::Append(*datetime, record.clone()),
+                };
+                match overflow_policy {
+                    OverflowPolicy::Block => {
+                        let _ = sender.send(message);
+                    }
+                    OverflowPolicy::Drop => {
+                        if let Err(TrySendError::Full(_)) = sender.try_send(message) {
+                            dropped::count(&record.target, 1);
+                        }
+                    }
+                }
+            }
+            Inner::Sync(appender) => {
+                let result = catch_unwind(AssertUnwindSafe(|| {
+                    let mut guard = appender.lock();
+                    guard.append(datetime, record)
+                }));
+                match result {
+                    Ok(Ok(())) => {}
+                    Ok(Err(e)) => {
+                        self.errors.handle(e);
+                        self.errors.panic_if_failed();
+                    }
+                    Err(_) => dropped::count(&record.target, 1),
+                }
+            }
+        }
+    }
+
+    /// Requests the worker to flush the appender after the pending records are written.
+    /// The returned receiver will be notified when it's done.
+    pub fn request_flush(&self) -> Receiver<()> {
+        self.errors.panic_if_failed();
+        let (ack_sender, ack_receiver) = sync_channel(1);
+        match &self.inner {
+            // the flush requests are never dropped, as the callers wait for them
+            Inner::Bounded { sender, .. } => {
+                let _ = sender.send(Message::Flush(ack_sender));
+            }
+            Inner::Sync(appender) => {
+                flush(appender, &self.errors);
+                let _ = ack_sender.send(());
+                self.errors.panic_if_failed();
+            }
+        }
+        ack_receiver
+    }
+}
+
+/// The maximum number of records written in one batch.
+const MAX_BATCH_SIZE: usize = 1024;
+
+/// Handles the errors returned by the appender, by the policy.
+struct ErrorHandler {
+    name: String,
+    policy: ErrorPolicy,
+    /// Whether `failure` is set, checked by the logging threads without locking.
+    failed: AtomicBool,
+    /// The error to panic with, if the policy is `panic`.
+    failure: Mutex<Option<Error>>,
+}
+
+impl ErrorHandler {
+    fn handle(&self, error: Error) {
+        match self.policy {
+            ErrorPolicy::Ignore => {}
+            ErrorPolicy::ReportToStderr => eprintln!("naive-logger: appender '{}': {}", self.name, error),
+            ErrorPolicy::Panic => {
+                self.failure.lock().get_or_insert(error);
+                self.failed.store(true, Ordering::Release);
+            }
+        }
+    }
+
+    /// Panics with the error kept by the `panic` policy, on the logging thread rather than the worker,
+    /// so that it reaches the application.
+    fn panic_if_failed(&self) {
+        if !self.failed.load(Ordering::Acquire) {
+            return;
+        }
+        if let Some(error) = self.failure.lock().take() {
+            self.failed.store(false, Ordering::Release);
+            panic!("naive-logger: appender '{}': {}", self.name, error);
+        }
+    }
+}
+
+/// The records drained from the queue; only one of them is used by an appender,
+/// depending on whether it writes the encoded records.
+#[derive(Default)]
+struct Batch {
+    records: Vec<(Datetime, Arc<OwnedRecord>)>,
+    entries: Vec<EncodedRecord>,
+}
+
+impl Batch {
+    fn len(&self) -> usize {
+        self.records.len() + self.entries.len()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+fn run(appender: Arc<Mutex<dyn Appender + Send>>, receiver: Receiver<Message>, errors: &ErrorHandler) {
+    let mut batch = Batch::default();
+    while let Ok(message) = receiver.recv() {
+        let mut next = Some(message);
+        while let Some(message) = next.take() {
+            match message {
+                Message::Append(datetime, record) => {
+                    batch.records.push((datetime, record));
+                    if batch.len() < MAX_BATCH_SIZE {
+                        next = receiver.try_recv().ok();
+                    }
+                }
+                Message::AppendEncoded(entry) => {
+                    batch.entries.push(entry);
+                    if batch.len() < MAX_BATCH_SIZE {
+                        next = receiver.try_recv().ok();
+                    }
+                }
+                Message::Flush(ack) => {
+                    append_batch(&appender, &mut batch, errors);
+                    flush(&appender, errors);
+                    let _ = ack.send(());
+                    next = receiver.try_recv().ok();
+                }
+            }
+        }
+        append_batch(&appender, &mut batch, errors);
+    }
+}
+
+fn flush(appender: &Arc<Mutex<dyn Appender + Send>>, errors: &ErrorHandler) {
+    let result = catch_unwind(AssertUnwindSafe(|| {
+        let mut guard = appender.lock();
+        guard.flush()
+    }));
+    if let Ok(Err(e)) = result {
+        errors.handle(e);
+    }
+}
+
+fn append_batch(appender: &Arc<Mutex<dyn Appender + Send>>, batch: &mut Batch, errors: &ErrorHandler) {
+    if batch.is_empty() {
+        return;
+    }
+    // keep the worker alive if the appender panics; the panic is reported by the panic hook,
+    // and the records of the batch are counted as dropped, as it's unknown which ones are written
+    let result = catch_unwind(AssertUnwindSafe(|| {
+        let mut guard = appender.lock();
+        let mut result = Ok(());
+        if !batch.records.is_empty() {
+            result = guard.append_batch(&batch.records);
+        }
+        if !batch.entries.is_empty() {
+            result = result.and(guard.append_encoded(&batch.entries));
+        }
+        result
+    }));
+    match result {
+        Ok(Ok(())) => {}
+        Ok(Err(e)) => errors.handle(e),
+        Err(_) => {
+            let targets = batch.records.iter().map(|(_, record)| &record.target);
+            for target in targets.chain(batch.entries.iter().map(|entry| &entry.target)) {
+                dropped::count(target, 1);
+            }
+        }
+    }
+    batch.records.clear();
+    batch.entries.clear();
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use log::RecordBuilder;
+
+    use crate::appender::Appender;
+    use crate::encoder::tests::*;
+    use crate::record::OwnedRecord;
+    use crate::sync::Mutex;
+    use crate::{Datetime, Error};
+
+    #[derive(Default)]
+    struct TestAppender {
+        messages: Vec<String>,
+        flushed: bool,
+    }
+
+    impl Appender for TestAppender {
+        fn append(&mut self, _datetime: &Datetime, record: &OwnedRecord) -> Result<(), Error> {
+            match record.message.as_str() {
+                "panic" => panic!("test appender panics"),
+                "error" => return Err(Error::from("test appender fails")),
+                _ => {}
+            }
+            self.messages.push(record.message.clone());
+            Ok(())
+        }
+
+        fn flush(&mut self) -> Result<(), Error> {
+            self.flushed = true;
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_append_and_flush() {
+        let appender = Arc::new(Mutex::new(TestAppender::default()));
+        let worker = super::AppenderWorker::spawn("test", appender.clone(), &Default::default(), vec![], Default::default()).unwrap();
+
+        let datetime = test_datetime();
+        for i in 0..3 {
+            let mut builder = RecordBuilder::new();
+            prepare_test_log_record(&mut builder);
+            let record = OwnedRecord::from(&builder.args(format_args!("message {}", i)).build());
+            worker.append(&datetime, &Arc::new(record));
+        }
+        worker.request_flush().recv().unwrap();
+
+        let guard = appender.lock();
+        assert_eq!(guard.messages, vec!["message 0", "message 1", "message 2"]);
+        assert!(guard.flushed);
+    }
+
+    /// Writes the entries encoded by the logging threads.
+    #[derive(Default)]
+    struct EncodedTestAppender {
+        entries: Vec<Vec<u8>>,
+        encoded_on: Vec<Option<String>>,
+    }
+
+    struct ThreadNameEncoder;
+
+    impl crate::encoder::Encoder for ThreadNameEncoder {
+        fn encode(&self, _datetime: &Datetime, record: &OwnedRecord, buf: &mut Vec<u8>) {
+            buf.extend_from_slice(record.message.as_bytes());
+            buf.push(b'@');
+            buf.extend_from_slice(std::thread::current().name().unwrap_or_default().as_bytes());
+        }
+    }
+
+    impl Appender for EncodedTestAppender {
+        fn append(&mut self, _datetime: &Datetime, _record: &OwnedRecord) -> Result<(), Error> {
+            unreachable!("the records are encoded before queued")
+        }
+
+        fn flush(&mut self) -> Result<(), Error> {
+            Ok(())
+        }
+
+        fn entry_encoder(&self) -> Option<Arc<dyn crate::encoder::Encoder + Send + Sync>> {
+            Some(Arc::new(ThreadNameEncoder))
+        }
+
+        fn append_encoded(&mut self, entries: &[crate::appender::EncodedRecord]) -> Result<(), Error> {
+            for entry in entries {
+                self.entries.push(entry.content.clone());
+                self.encoded_on.push(std::thread::current().name().map(String::from));
+            }
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_append_encoded() {
+        let appender = Arc::new(Mutex::new(EncodedTestAppender::default()));
+        let worker = super::AppenderWorker::spawn("test", appender.clone(), &Default::default(), vec![], Default::default()).unwrap();
+
+        let datetime = test_datetime();
+        std::thread::Builder::new()
+            .name("logging".to_string())
+            .spawn(move || {
+                let record = OwnedRecord::from(&RecordBuilder::new().args(format_args!("message")).build());
+                worker.append(&datetime, &Arc::new(record));
+                worker.request_flush().recv().unwrap();
+            })
+            .unwrap()
+            .join()
+            .unwrap();
+
+        // encoded by the logging thread, and written by the worker
+        let guard = appender.lock();
+        assert_eq!(guard.entries, vec![b"message@logging\n".to_vec()]);
+        assert_eq!(guard.encoded_on, vec![Some("naive-logger-test".to_string())]);
+    }
+
+    #[test]
+    fn test_survive_panic() {
+        let appender = Arc::new(Mutex::new(TestAppender::default()));
+        let worker = super::AppenderWorker::spawn("test", appender.clone(), &Default::default(), vec![], Default::default()).unwrap();
+
+        let datetime = test_datetime();
+        for message in ["before", "panic", "after"] {
+            let mut builder = RecordBuilder::new();
+            prepare_test_log_record(&mut builder);
+            let record = OwnedRecord::from(&builder.args(format_args!("{}", message)).build());
+            worker.append(&datetime, &Arc::new(record));
+            // the rest of the batch is dropped when the appender panics
+            worker.request_flush().recv().unwrap();
+        }
+
+        let guard = appender.lock();
+        assert_eq!(guard.messages, vec!["before", "after"]);
+        assert!(guard.flushed);
+    }
+
+    #[test]
+    fn test_drop_on_overflow() {
+        use crate::config::{OverflowPolicy, QueueConfig};
+
+        let appender = Arc::new(Mutex::new(TestAppender::default()));
+        let config = QueueConfig {
+            async_: true,
+            queue_size: 2,
+            overflow_policy: OverflowPolicy::Drop,
+            ..Default::default()
+        };
+        let worker = super::AppenderWorker::spawn("test", appender.clone(), &config, vec![], Default::default()).unwrap();
+
+        let datetime = test_datetime();
+        let guard = appender.lock();
+        for i in 0..10 {
+            let mut builder = RecordBuilder::new();
+            prepare_test_log_record(&mut builder);
+            let record = OwnedRecord::from(&builder.args(format_args!("message {}", i)).build());
+            worker.append(&datetime, &Arc::new(record));
+            if i == 0 {
+                // let the worker take the first record and wait for the appender
+                std::thread::sleep(std::time::Duration::from_millis(100));
+            }
+        }
+        drop(guard);
+        worker.request_flush().recv().unwrap();
+
+        let guard = appender.lock();
+        assert_eq!(guard.messages, vec!["message 0", "message 1", "message 2"]);
+        drop(guard);
+
+        let config = QueueConfig {
+            async_: true,
+            queue_size: 0,
+            ..Default::default()
+        };
+        assert!(super::AppenderWorker::spawn("test", appender, &config, vec![], Default::default()).is_err());
+    }
+
+    #[test]
+    fn test_sync() {
+        use crate::config::QueueConfig;
+
+        let appender = Arc::new(Mutex::new(TestAppender::default()));
+        let config = QueueConfig {
+            async_: false,
+            ..Default::default()
+        };
+        let worker = super::AppenderWorker::spawn("test", appender.clone(), &config, vec![], Default::default()).unwrap();
+
+        let datetime = test_datetime();
+        for message in ["before", "panic", "after"] {
+            let mut builder = RecordBuilder::new();
+            prepare_test_log_record(&mut builder);
+            let record = OwnedRecord::from(&builder.args(format_args!("{}", message)).build());
+            worker.append(&datetime, &Arc::new(record));
+        }
+        assert_eq!(appender.lock().messages, vec!["before", "after"]);
+        worker.request_flush().recv().unwrap();
+        assert!(appender.lock().flushed);
+    }
+
+    #[test]
+    fn test_rate_limit() {
+        use crate::config::QueueConfig;
+
+        let appender = Arc::new(Mutex::new(TestAppender::default()));
+        let config = QueueConfig {
+            max_records_per_second: 1,
+            burst_size: 3,
+            ..Default::default()
+        };
+        let worker = super::AppenderWorker::spawn("test", appender.clone(), &config, vec![], Default::default()).unwrap();
+
+        let datetime = test_datetime();
+        for i in 0..10 {
+            let mut builder = RecordBuilder::new();
+            prepare_test_log_record(&mut builder);
+            let record = OwnedRecord::from(&builder.args(format_args!("message {}", i)).build());
+            worker.append(&datetime, &Arc::new(record));
+        }
+        worker.request_flush().recv().unwrap();
+
+        let guard = appender.lock();
+        assert_eq!(guard.messages, vec!["message 0", "message 1", "message 2"]);
+    }
+
+    #[test]
+    fn test_error_policy() {
+        use crate::config::{ErrorPolicy, QueueConfig};
+
+        let datetime = test_datetime();
+        let record = |message: &str| {
+            let mut builder = RecordBuilder::new();
+            prepare_test_log_record(&mut builder);
+            Arc::new(OwnedRecord::from(&builder.args(format_args!("{}", message)).build()))
+        };
+
+        // the worker keeps going, and so does the logging thread
+        let appender = Arc::new(Mutex::new(TestAppender::default()));
+        let worker = super::AppenderWorker::spawn("test", appender.clone(), &Default::default(), vec![], ErrorPolicy::Ignore)
+            .unwrap();
+        for message in ["before", "error", "after"] {
+            worker.append(&datetime, &record(message));
+        }
+        worker.request_flush().recv().unwrap();
+        assert_eq!(appender.lock().messages, vec!["before", "after"]);
+
+        // the next call of the logging thread panics
+        let appender = Arc::new(Mutex::new(TestAppender::default()));
+        let worker = super::AppenderWorker::spawn("test", appender.clone(), &Default::default(), vec![], ErrorPolicy::Panic)
+            .unwrap();
+        worker.append(&datetime, &record("error"));
+        worker.request_flush().recv().unwrap();
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| worker.append(&datetime, &record("after"))));
+        let message = result.unwrap_err().downcast::<String>().unwrap();
+        assert_eq!(*message, "naive-logger: appender 'test': test appender fails");
+        // panicked only once
+        worker.append(&datetime, &record("again"));
+        worker.request_flush().recv().unwrap();
+        assert_eq!(appender.lock().messages, vec!["again"]);
+
+        // the failed call panics if not asynchronous
+        let config = QueueConfig {
+            async_: false,
+            ..Default::default()
+        };
+        let appender = Arc::new(Mutex::new(TestAppender::default()));
+        let worker = super::AppenderWorker::spawn("test", appender.clone(), &config, vec![], ErrorPolicy::Panic).unwrap();
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| worker.append(&datetime, &record("error"))));
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,3 @@
+fn main() {
+    naive_logger::logcat::main();
+}
@@ -0,0 +1,12 @@
+//! Prints a commented starter config: `naive-logger-init-config [yaml|toml|json]`.
+
+fn main() {
+    let format = std::env::args().nth(1).unwrap_or_else(|| "yaml".to_string());
+    match naive_logger::example_config(&format) {
+        Some(config) => print!("{}", config),
+        None => {
+            eprintln!("usage: naive-logger-init-config [yaml|toml|json]");
+            std::process::exit(2);
+        }
+    }
+}
@@ -0,0 +1,268 @@
+//! The `naive-logger-check` command, enabled by the `cli` feature:
+//! validates a config file without initializing the logger, for the deployment pipelines.
+//!
+//! Besides the deserialization, it checks the patterns, the writability of the log files,
+//! and the appenders referenced by the loggers; all the errors found are reported.
+//! The custom processors, filters, appenders and encoders, and the forward targets are registered by the programs at runtime,
+//! so they're not checked.
+
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+use crate::config::{
+    AppenderConfig, Config, EncoderConfig, FileAppenderConfig, FilterConfig, LoggerConfig, ProcessorConfig, Timezone,
+};
+use crate::appender::{validate_cleanup_pattern, Compressor, PathPattern};
+use crate::{clock, encoder, filter, heartbeat, processor};
+
+/// Runs the command with the process arguments.
+pub fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let [path] = args.as_slice() else {
+        eprintln!("usage: naive-logger-check <config>");
+        std::process::exit(2);
+    };
+    let errors = check(path);
+    if errors.is_empty() {
+        println!("{}: OK", path);
+        return;
+    }
+    for error in &errors {
+        eprintln!("{}: {}", path, error);
+    }
+    std::process::exit(1);
+}
+
+/// Checks the config file; returns the errors found.
+fn check<P: AsRef<Path>>(path: P) -> Vec<String> {
+    match crate::load_config(path) {
+        Ok(mut config) => {
+            config.add_default_root_appender();
+            check_config(&config)
+        }
+        Err(e) => vec![e.to_string()],
+    }
+}
+
+fn check_config(config: &Config) -> Vec<String> {
+    let mut errors = vec![];
+
+    let mut names: Vec<_> = config.appenders.keys().collect();
+    names.sort();
+    let mut paths = HashMap::new();
+    for name in names {
+        check_appender(name, &config.appenders[name], config.timezone, &mut paths, &mut errors);
+    }
+
+    let appenders: HashSet<&str> = config.appenders.keys().map(String::as_str).collect();
+    if config.root.appenders.is_empty() {
+        errors.push("root logger: must have at least one appender".to_string());
+    }
+    check_logger_appenders("root logger", &config.root, &appenders, &mut errors);
+    for (i, logger) in config.loggers.iter().enumerate() {
+        let name = format!("logger #{} ('{}')", i, logger.target);
+        check_logger_appenders(&name, logger, &appenders, &mut errors);
+    }
+
+    for (i, config) in config.processors.iter().enumerate() {
+        if matches!(config, ProcessorConfig::Custom(_)) {
+            continue;
+        }
+        if let Err(e) = processor::from_config(config) {
+            errors.push(format!("processor #{}: {}", i, e));
+        }
+    }
+
+    if let Some(heartbeat) = &config.heartbeat {
+        if let Err(e) = heartbeat::check(heartbeat) {
+            errors.push(e.to_string());
+        }
+    }
+
+    errors
+}
+
+fn check_logger_appenders(
+    name: &str,
+    config: &LoggerConfig,
+    appenders: &HashSet<&str>,
+    errors: &mut Vec<String>,
+) {
+    for appender in &config.appenders {
+        if !appenders.contains(appender.as_str()) {
+            errors.push(format!("{}: no appender '{}'", name, appender));
+        }
+    }
+}
+
+/// Checks the appender, and the inner ones of a `level_router` named like `<name>/<level range>`.
+fn check_appender(
+    name: &str,
+    appender: &AppenderConfig,
+    timezone: Timezone,
+    paths: &mut HashMap<PathBuf, String>,
+    errors: &mut Vec<String>,
+) {
+    if !appender.enabled() {
+        return;
+    }
+    for (i, config) in appender.filters().iter().enumerate() {
+        if matches!(config, FilterConfig::Custom(_)) {
+            continue;
+        }
+        if let Err(e) = filter::from_config(config) {
+            errors.push(format!("appender '{}': invalid filter #{}: {}", name, i, e));
+        }
+    }
+    let common = match appender {
+        AppenderConfig::Console(config) => &config.common,
+        AppenderConfig::File(config) => {
+            if let Some(other) = paths.insert(config.path_or_pattern().to_path_buf(), name.to_string()) {
+                errors.push(format!(
+                    "appender '{}': path '{}' is also used by appender '{}'",
+                    name,
+                    config.path_or_pattern().display(),
+                    other
+                ));
+            }
+            if let Err(e) = check_file(config, timezone) {
+                errors.push(format!("appender '{}': {}", name, e));
+            }
+            if let Some(kind) = config.compress_backups {
+                if let Err(e) = Compressor::new(kind, config.compression_level) {
+                    errors.push(format!("appender '{}': {}", name, e));
+                }
+            }
+            &config.common
+        }
+        AppenderConfig::Syslog(config) => &config.common,
+        AppenderConfig::Tcp(config) => &config.common,
+        AppenderConfig::Http(config) => &config.common,
+        AppenderConfig::Email(config) => &config.common,
+        AppenderConfig::WebSocket(config) => &config.common,
+        AppenderConfig::Kafka(config) => {
+            if cfg!(not(feature = "kafka")) {
+                errors.push(format!("appender '{}': kafka appender requires the `kafka` feature", name));
+            }
+            &config.common
+        }
+        AppenderConfig::Sqlite(_) => {
+            if cfg!(not(feature = "sqlite")) {
+                errors.push(format!("appender '{}': sqlite appender requires the `sqlite` feature", name));
+            }
+            return;
+        }
+        AppenderConfig::Fluentd(_) => {
+            if cfg!(not(feature = "fluentd")) {
+                errors.push(format!("appender '{}': fluentd appender requires the `fluentd` feature", name));
+            }
+            return;
+        }
+        AppenderConfig::LevelRouter(config) => {
+            if config.routes.is_empty() {
+                errors.push(format!("appender '{}': routes must not be empty", name));
+            }
+            for (levels, config) in &config.routes {
+                check_appender(&format!("{}/{}", name, levels), config, timezone, paths, errors);
+            }
+            return;
+        }
+        // the custom kinds are registered by the application, so they can't be checked here
+        AppenderConfig::Forward(_)
+        | AppenderConfig::Journald(_)
+        | AppenderConfig::Custom(_) => return,
+    };
+    if matches!(common.encoder, EncoderConfig::Custom(_)) {
+        return;
+    }
+    if let Err(e) = encoder::from_config(&common.encoder) {
+        errors.push(format!("appender '{}': invalid encoder: {}", name, e));
+    }
+}
+
+/// Checks whether the log file can be written, without creating it or its directory;
+/// the current one (in the timezone) is checked if the path pattern is set.
+fn check_file(config: &FileAppenderConfig, timezone: Timezone) -> Result<(), String> {
+    let path = match PathPattern::from_config(config).map_err(|e| e.to_string())? {
+        None => config.path.clone(),
+        Some(pattern) => pattern.format(&clock::now(timezone)),
+    };
+    if let Some(cleanup) = &config.cleanup {
+        validate_cleanup_pattern(&cleanup.pattern).map_err(|e| e.to_string())?;
+    }
+    let path = &path;
+    if path.file_name().is_none() {
+        return Err(format!("path '{}' has no file name", path.display()));
+    }
+    if path.exists() {
+        return std::fs::OpenOptions::new()
+            .append(true)
+            .open(path)
+            .map(|_| ())
+            .map_err(|e| format!("log file '{}' is not writable: {}", path.display(), e));
+    }
+
+    // the directory will be created, so check the nearest existing ancestor
+    let mut dir = path.parent().map(Path::to_path_buf).unwrap_or_default();
+    while !dir.as_os_str().is_empty() && !dir.exists() {
+        dir = dir.parent().map(Path::to_path_buf).unwrap_or_default();
+    }
+    if dir.as_os_str().is_empty() {
+        dir = PathBuf::from(".");
+    }
+    if !dir.is_dir() {
+        return Err(format!("'{}' is not a directory", dir.display()));
+    }
+    let probe = dir.join(format!(".naive-logger-check-{}", std::process::id()));
+    std::fs::File::create(&probe)
+        .map_err(|e| format!("directory '{}' is not writable: {}", dir.display(), e))?;
+    let _ = std::fs::remove_file(&probe);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::Config;
+
+    #[test]
+    fn test_check_config() {
+        let config = r#"
+        {
+            "appenders": {
+                "console": {
+                    "kind": "console",
+                    "encoder": {"kind": "pattern", "pattern": "{unknown}"},
+                    "filters": [{"kind": "regex", "pattern": "("}, {"kind": "custom", "name": "unregistered"}]
+                },
+                "file": {"kind": "file", "encoder": {"kind": "json"}, "path": "__test_check/dir/check.log"},
+                "file2": {"kind": "file", "encoder": {"kind": "json"}, "path": "__test_check/dir/check.log"},
+                "disabled": {"kind": "file", "enabled": false, "encoder": {"kind": "json"}, "path": "__test_check/dir/check.log"},
+                "split": {"kind": "level_router", "routes": {
+                    "error": {"kind": "file", "encoder": {"kind": "json"}, "path": "__test_check/dir/check.log"}
+                }}
+            },
+            "root": {"appenders": ["console", "missing"]},
+            "loggers": [{"target": "myapp::", "appenders": ["file", "other"]}],
+            "heartbeat": {"interval_secs": 0}
+        }
+        "#;
+        let config: Config = serde_json::from_str(config).unwrap();
+        let errors = super::check_config(&config);
+        assert_eq!(errors.len(), 7, "{:?}", errors);
+        assert!(errors[0].starts_with("appender 'console': invalid filter #0: invalid pattern '('"));
+        assert!(errors[1].starts_with("appender 'console': invalid encoder: "));
+        assert!(errors[2].starts_with("appender 'file2': path '__test_check/dir/check.log' is also used by appender 'file'"));
+        assert!(errors[3].starts_with("appender 'split/error': path '__test_check/dir/check.log' is also used by appender 'file2'"));
+        assert_eq!(errors[4], "root logger: no appender 'missing'");
+        assert_eq!(errors[5], "logger #0 ('myapp::'): no appender 'other'");
+        assert_eq!(errors[6], "heartbeat interval must be positive");
+        assert!(!std::path::Path::new("__test_check").exists());
+    }
+
+    #[test]
+    fn test_check_file() {
+        let errors = super::check("__test_missing.yaml");
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].starts_with("failed to read config file"));
+    }
+}
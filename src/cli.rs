@@ -0,0 +1,175 @@
+//! The common logging flags for the command line tools, enabled by the `clap` feature.
+//!
+//! Flatten [`LogArgs`] into the arguments of the program, and initialize the logger with it;
+//! the flags overlay the config file (or a default console config if none):
+//!
+//! ```no_run
+//! use clap::Parser;
+//!
+//! #[derive(Parser)]
+//! struct Cli {
+//!     #[command(flatten)]
+//!     log: naive_logger::cli::LogArgs,
+//! }
+//!
+//! let cli = Cli::parse();
+//! cli.log.init(None::<&str>).unwrap();
+//! ```
+
+use std::path::{Path, PathBuf};
+
+use clap::{ArgAction, Args, ValueEnum};
+use log::LevelFilter;
+
+use crate::config::{
+    AppenderCommonProperties, AppenderConfig, Config, EncoderConfig, FileAppenderConfig, JsonEncoderConfig,
+    PatternEncoderConfig,
+};
+use crate::Error;
+
+/// The config used if no config file is given: the `info` messages to the console.
+const DEFAULT_CONFIG: &str = r#"
+{
+    "appenders": {"console": {"kind": "console", "encoder": {"kind": "pattern"}}},
+    "root": {"level": "info", "appenders": ["console"]},
+    "loggers": []
+}
+"#;
+
+/// The name of the file appender added by `--log-file`.
+const LOG_FILE_APPENDER: &str = "cli_log_file";
+
+#[derive(Args, Debug, Default)]
+pub struct LogArgs {
+    /// Increase the logging verbosity; can be repeated
+    #[arg(short = 'v', long = "verbose", action = ArgAction::Count, global = true)]
+    pub verbose: u8,
+    /// Decrease the logging verbosity; can be repeated
+    #[arg(short = 'q', long = "quiet", action = ArgAction::Count, global = true, conflicts_with = "verbose")]
+    pub quiet: u8,
+    /// Also write the log messages to the file
+    #[arg(long = "log-file", value_name = "PATH", global = true)]
+    pub log_file: Option<PathBuf>,
+    /// The format of the log messages
+    #[arg(long = "log-format", value_name = "FORMAT", global = true)]
+    pub log_format: Option<LogFormat>,
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum LogFormat {
+    Pattern,
+    Json,
+}
+
+impl LogArgs {
+    /// Initializes the logger with the config file (or the default console config if none),
+    /// overlaid by the flags.
+    pub fn init<P: AsRef<Path>>(&self, config_file: Option<P>) -> Result<(), Error> {
+        let mut config = match config_file {
+            Some(path) => crate::load_config(path)?,
+            None => serde_json::from_str(DEFAULT_CONFIG).unwrap(),
+        };
+        self.overlay(&mut config);
+        crate::init_from_config(config)
+    }
+
+    /// Applies the flags to the config:
+    /// `-v`/`-q` raise/lower the root logger level by one step each,
+    /// `--log-file` adds a file appender to the root logger,
+    /// and `--log-format` replaces the encoders of all the appenders.
+    fn overlay(&self, config: &mut Config) {
+        let level = config.root.level as i32 + self.verbose as i32 - self.quiet as i32;
+        let max = LevelFilter::max() as i32;
+        config.root.level = LevelFilter::iter()
+            .nth(level.clamp(0, max) as usize)
+            .unwrap();
+
+        if let Some(path) = &self.log_file {
+            let appender = AppenderConfig::File(FileAppenderConfig {
+                path: path.clone(),
+                ..Default::default()
+            });
+            config.appenders.insert(LOG_FILE_APPENDER.to_string(), appender);
+            config.root.appenders.push(LOG_FILE_APPENDER.to_string());
+        }
+
+        if let Some(format) = self.log_format {
+            // the default root appender would be added after the overlay, with the default format
+            config.add_default_root_appender();
+            for appender in config.appenders.values_mut() {
+                if let Some(common) = common_mut(appender) {
+                    common.encoder = match format {
+                        LogFormat::Pattern => EncoderConfig::Pattern(PatternEncoderConfig::default()),
+                        LogFormat::Json => EncoderConfig::Json(JsonEncoderConfig::default()),
+                    };
+                }
+            }
+        }
+    }
+}
+
+/// Gets the common properties; none for the appenders which don't encode the records.
+fn common_mut(appender: &mut AppenderConfig) -> Option<&mut AppenderCommonProperties> {
+    match appender {
+        AppenderConfig::Console(config) => Some(&mut config.common),
+        AppenderConfig::File(config) => Some(&mut config.common),
+        AppenderConfig::Syslog(config) => Some(&mut config.common),
+        AppenderConfig::Tcp(config) => Some(&mut config.common),
+        AppenderConfig::Http(config) => Some(&mut config.common),
+        AppenderConfig::Kafka(config) => Some(&mut config.common),
+        AppenderConfig::Email(config) => Some(&mut config.common),
+        AppenderConfig::WebSocket(config) => Some(&mut config.common),
+        AppenderConfig::Forward(_)
+        | AppenderConfig::Journald(_)
+        | AppenderConfig::Fluentd(_)
+        | AppenderConfig::Sqlite(_)
+        | AppenderConfig::LevelRouter(_)
+        | AppenderConfig::Custom(_) => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+    use log::LevelFilter;
+
+    use crate::config::{AppenderConfig, Config, EncoderConfig};
+
+    #[derive(Parser)]
+    struct Cli {
+        #[command(flatten)]
+        log: super::LogArgs,
+    }
+
+    fn overlay(args: &[&str]) -> Config {
+        let cli = Cli::try_parse_from(std::iter::once("test").chain(args.iter().copied())).unwrap();
+        let mut config = serde_json::from_str(super::DEFAULT_CONFIG).unwrap();
+        cli.log.overlay(&mut config);
+        config
+    }
+
+    #[test]
+    fn test_overlay() {
+        assert_eq!(overlay(&[]).root.level, LevelFilter::Info);
+        assert_eq!(overlay(&["-v"]).root.level, LevelFilter::Debug);
+        assert_eq!(overlay(&["-vvvv"]).root.level, LevelFilter::Trace);
+        assert_eq!(overlay(&["-qq"]).root.level, LevelFilter::Error);
+        assert_eq!(overlay(&["-qqqq"]).root.level, LevelFilter::Off);
+        assert!(Cli::try_parse_from(["test", "-v", "-q"]).is_err());
+
+        let mut config = overlay(&["--log-file", "app.log", "--log-format", "json"]);
+        assert_eq!(config.root.appenders, vec!["console", super::LOG_FILE_APPENDER]);
+        assert!(matches!(config.appenders[super::LOG_FILE_APPENDER], AppenderConfig::File(_)));
+        for appender in config.appenders.values_mut() {
+            let common = super::common_mut(appender).unwrap();
+            assert!(matches!(common.encoder, EncoderConfig::Json(_)));
+        }
+
+        // a config file without the root section
+        let cli = Cli::try_parse_from(["test", "--log-format", "json"]).unwrap();
+        let mut config: Config = serde_json::from_str("{}").unwrap();
+        cli.log.overlay(&mut config);
+        let appender = config.appenders.get_mut(crate::config::DEFAULT_ROOT_APPENDER).unwrap();
+        assert!(matches!(super::common_mut(appender).unwrap().encoder, EncoderConfig::Json(_)));
+    }
+}
@@ -0,0 +1,69 @@
+use std::sync::atomic::{AtomicI32, AtomicI64, Ordering};
+
+use chrono::{DateTime, FixedOffset, Local, Offset, TimeZone, Utc};
+
+use crate::config::Timezone;
+use crate::Datetime;
+
+/// The local offset is looked up again at the first call of each minute,
+/// as the offset only changes at minute boundaries.
+const REFRESH_INTERVAL_SECS: i64 = 60;
+
+static LOCAL_OFFSET_SECS: AtomicI32 = AtomicI32::new(0);
+static NEXT_REFRESH_SECS: AtomicI64 = AtomicI64::new(i64::MIN);
+
+/// Gets the current datetime in the timezone.
+pub fn now(timezone: Timezone) -> Datetime {
+    let utc = Utc::now();
+    let offset = match timezone {
+        Timezone::Local => return local_now(utc),
+        Timezone::Utc => Utc.fix(),
+        #[cfg(feature = "tz")]
+        Timezone::Named(tz) => tz.offset_from_utc_datetime(&utc.naive_utc()).fix(),
+    };
+    DateTime::from_naive_utc_and_offset(utc.naive_utc(), offset)
+}
+
+/// Gets the local datetime, with the cached local offset
+/// instead of looking up the timezone database every time like `chrono::Local::now()`.
+fn local_now(utc: DateTime<Utc>) -> Datetime {
+    let secs = utc.timestamp();
+    if secs >= NEXT_REFRESH_SECS.load(Ordering::Acquire) {
+        let offset = Local.offset_from_utc_datetime(&utc.naive_utc()).fix();
+        LOCAL_OFFSET_SECS.store(offset.local_minus_utc(), Ordering::Relaxed);
+        let next_refresh = (secs.div_euclid(REFRESH_INTERVAL_SECS) + 1) * REFRESH_INTERVAL_SECS;
+        NEXT_REFRESH_SECS.store(next_refresh, Ordering::Release);
+        return DateTime::from_naive_utc_and_offset(utc.naive_utc(), offset);
+    }
+    let offset = FixedOffset::east_opt(LOCAL_OFFSET_SECS.load(Ordering::Relaxed)).unwrap();
+    DateTime::from_naive_utc_and_offset(utc.naive_utc(), offset)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::Timezone;
+
+    #[test]
+    fn test_now() {
+        for _ in 0..2 {
+            let expected = chrono::Local::now();
+            let datetime = super::now(Timezone::Local);
+            assert_eq!(datetime.offset(), expected.offset());
+            assert!((datetime - expected).num_seconds().abs() < 1);
+        }
+    }
+
+    #[test]
+    fn test_now_in_timezone() {
+        let datetime = super::now(Timezone::Utc);
+        assert_eq!(datetime.offset().local_minus_utc(), 0);
+
+        // no daylight saving time in Shanghai
+        #[cfg(feature = "tz")]
+        {
+            let datetime = super::now(Timezone::Named(chrono_tz::Asia::Shanghai));
+            assert_eq!(datetime.offset().local_minus_utc(), 8 * 60 * 60);
+            assert!((datetime.to_utc() - chrono::Utc::now()).num_seconds().abs() < 1);
+        }
+    }
+}
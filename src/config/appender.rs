@@ -1,15 +1,20 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
-use log::LevelFilter;
+use indexmap::IndexMap;
+use log::{Level, LevelFilter};
 use serde::Deserialize;
 
-use crate::config::EncoderConfig;
+use crate::config::{EncoderConfig, FilterConfig, RedactConfig};
 
 const DEFAULT_STDERR_LEVEL: LevelFilter = LevelFilter::Off;
 fn default_stderr_level() -> LevelFilter {
     DEFAULT_STDERR_LEVEL
 }
 
+fn default_enabled() -> bool {
+    true
+}
+
 #[derive(Deserialize)]
 #[serde(deny_unknown_fields)]
 #[serde(tag = "kind")]
@@ -18,34 +23,859 @@ pub enum AppenderConfig {
     Console(ConsoleAppenderConfig),
     #[serde(rename = "file")]
     File(FileAppenderConfig),
+    #[serde(rename = "forward")]
+    Forward(ForwardAppenderConfig),
+    #[serde(rename = "syslog")]
+    Syslog(SyslogAppenderConfig),
+    #[serde(rename = "journald")]
+    Journald(JournaldAppenderConfig),
+    #[serde(rename = "tcp")]
+    Tcp(TcpAppenderConfig),
+    #[serde(rename = "http")]
+    Http(HttpAppenderConfig),
+    #[serde(rename = "kafka")]
+    Kafka(KafkaAppenderConfig),
+    #[serde(rename = "fluentd")]
+    Fluentd(FluentdAppenderConfig),
+    #[serde(rename = "email")]
+    Email(EmailAppenderConfig),
+    #[serde(rename = "sqlite")]
+    Sqlite(SqliteAppenderConfig),
+    #[serde(rename = "websocket")]
+    WebSocket(WebSocketAppenderConfig),
+    #[serde(rename = "level_router")]
+    LevelRouter(LevelRouterAppenderConfig),
+    #[serde(rename = "custom")]
+    Custom(CustomAppenderConfig),
 }
 
-#[derive(Deserialize)]
+impl AppenderConfig {
+    /// Whether the appender is enabled; the disabled appenders are not constructed,
+    /// and the references to them are ignored.
+    pub fn enabled(&self) -> bool {
+        match self {
+            AppenderConfig::Console(config) => config.enabled,
+            AppenderConfig::File(config) => config.enabled,
+            AppenderConfig::Forward(config) => config.enabled,
+            AppenderConfig::Syslog(config) => config.enabled,
+            AppenderConfig::Journald(config) => config.enabled,
+            AppenderConfig::Tcp(config) => config.enabled,
+            AppenderConfig::Http(config) => config.enabled,
+            AppenderConfig::Kafka(config) => config.enabled,
+            AppenderConfig::Fluentd(config) => config.enabled,
+            AppenderConfig::Email(config) => config.enabled,
+            AppenderConfig::Sqlite(config) => config.enabled,
+            AppenderConfig::WebSocket(config) => config.enabled,
+            AppenderConfig::LevelRouter(config) => config.enabled,
+            AppenderConfig::Custom(config) => config.enabled,
+        }
+    }
+
+    pub fn filters(&self) -> &[FilterConfig] {
+        match self {
+            AppenderConfig::Console(config) => &config.filters,
+            AppenderConfig::File(config) => &config.filters,
+            AppenderConfig::Forward(config) => &config.filters,
+            AppenderConfig::Syslog(config) => &config.filters,
+            AppenderConfig::Journald(config) => &config.filters,
+            AppenderConfig::Tcp(config) => &config.filters,
+            AppenderConfig::Http(config) => &config.filters,
+            AppenderConfig::Kafka(config) => &config.filters,
+            AppenderConfig::Fluentd(config) => &config.filters,
+            AppenderConfig::Email(config) => &config.filters,
+            AppenderConfig::Sqlite(config) => &config.filters,
+            AppenderConfig::WebSocket(config) => &config.filters,
+            AppenderConfig::LevelRouter(config) => &config.filters,
+            AppenderConfig::Custom(config) => &config.filters,
+        }
+    }
+
+    pub fn queue(&self) -> &QueueConfig {
+        match self {
+            AppenderConfig::Console(config) => &config.queue,
+            AppenderConfig::File(config) => &config.queue,
+            AppenderConfig::Forward(config) => &config.queue,
+            AppenderConfig::Syslog(config) => &config.queue,
+            AppenderConfig::Journald(config) => &config.queue,
+            AppenderConfig::Tcp(config) => &config.queue,
+            AppenderConfig::Http(config) => &config.queue,
+            AppenderConfig::Kafka(config) => &config.queue,
+            AppenderConfig::Fluentd(config) => &config.queue,
+            AppenderConfig::Email(config) => &config.queue,
+            AppenderConfig::Sqlite(config) => &config.queue,
+            AppenderConfig::WebSocket(config) => &config.queue,
+            AppenderConfig::LevelRouter(config) => &config.queue,
+            AppenderConfig::Custom(config) => &config.queue,
+        }
+    }
+
+    /// The file appenders, either itself or the inner ones of a `level_router`, which are enabled;
+    /// for detecting the paths used by multiple appenders.
+    pub fn file_configs(&self) -> Vec<&FileAppenderConfig> {
+        match self {
+            _ if !self.enabled() => vec![],
+            AppenderConfig::File(config) => vec![config],
+            AppenderConfig::LevelRouter(config) => config.routes.values().flat_map(AppenderConfig::file_configs).collect(),
+            _ => vec![],
+        }
+    }
+}
+
+/// How the records are passed to the appender, available to all the appenders.
+#[derive(Clone, Copy, Deserialize)]
+pub struct QueueConfig {
+    /// Whether the records are queued and written by a dedicated thread of the appender;
+    /// otherwise they are written on the logging threads, with the appender locked.
+    #[serde(rename = "async", default = "default_async")]
+    pub async_: bool,
+    /// The maximum number of the queued records.
+    #[serde(default = "default_queue_size")]
+    pub queue_size: usize,
+    #[serde(default)]
+    pub overflow_policy: OverflowPolicy,
+    /// The maximum rate of the records passed to the appender; `0` means unlimited.
+    /// The excess records are dropped and counted.
+    #[serde(default)]
+    pub max_records_per_second: u32,
+    /// The number of the records which can be passed at once above the rate;
+    /// `0` means the same as `max_records_per_second`.
+    #[serde(default)]
+    pub burst_size: u32,
+}
+
+fn default_async() -> bool {
+    true
+}
+
+fn default_queue_size() -> usize {
+    8192
+}
+
+impl Default for QueueConfig {
+    fn default() -> Self {
+        Self {
+            async_: default_async(),
+            queue_size: default_queue_size(),
+            overflow_policy: OverflowPolicy::default(),
+            max_records_per_second: 0,
+            burst_size: 0,
+        }
+    }
+}
+
+/// What to do with the records logged when the bounded queue is full.
+#[derive(Clone, Copy, Default, PartialEq, Deserialize)]
+pub enum OverflowPolicy {
+    /// The logging threads wait for the room, so that no record is lost.
+    #[default]
+    #[serde(rename = "block")]
+    Block,
+    /// The records are dropped and counted, so that the logging threads never wait.
+    #[serde(rename = "drop")]
+    Drop,
+}
+
+#[derive(Default, Deserialize)]
 #[serde(deny_unknown_fields)]
 pub struct AppenderCommonProperties {
     pub encoder: EncoderConfig,
+    #[serde(default)]
+    pub redact: Option<RedactConfig>,
 }
 
 #[derive(Deserialize)]
 #[serde(deny_unknown_fields)]
 pub struct ConsoleAppenderConfig {
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    #[serde(flatten)]
+    pub queue: QueueConfig,
+    /// The records are written only if they pass all the filters.
+    #[serde(default)]
+    pub filters: Vec<FilterConfig>,
     #[serde(flatten)]
     pub common: AppenderCommonProperties,
     #[serde(default = "default_stderr_level")]
     pub stderr_level: LevelFilter,
+    #[serde(default)]
+    pub color: ColorMode,
+}
+
+impl Default for ConsoleAppenderConfig {
+    fn default() -> Self {
+        Self {
+            enabled: default_enabled(),
+            queue: QueueConfig::default(),
+            filters: vec![],
+            common: AppenderCommonProperties::default(),
+            stderr_level: default_stderr_level(),
+            color: ColorMode::default(),
+        }
+    }
+}
+
+/// Whether the console appender keeps the ANSI color codes of the encoded records.
+#[derive(Clone, Copy, Default, PartialEq, Deserialize)]
+pub enum ColorMode {
+    /// Only if the stream is a terminal, decided for stdout and stderr separately;
+    /// overridden by the environment variables `NO_COLOR` and `CLICOLOR_FORCE`.
+    #[default]
+    #[serde(rename = "auto")]
+    Auto,
+    #[serde(rename = "always")]
+    Always,
+    #[serde(rename = "never")]
+    Never,
 }
 
 #[derive(Deserialize)]
 #[serde(deny_unknown_fields)]
 pub struct FileAppenderConfig {
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    #[serde(flatten)]
+    pub queue: QueueConfig,
+    /// The records are written only if they pass all the filters.
+    #[serde(default)]
+    pub filters: Vec<FilterConfig>,
     #[serde(flatten)]
     pub common: AppenderCommonProperties,
-    #[serde(deserialize_with = "super::util::deserialize_str_with_env_var")]
+    /// Either `path` or `path_pattern` should be set.
+    #[serde(default, deserialize_with = "super::util::deserialize_str_with_env_var")]
     pub path: PathBuf,
+    /// The path with the `strftime` specifiers, e.g. `logs/app-%Y-%m-%d.log`, formatted by the time of the records;
+    /// the records are written to another file when the formatted path changes.
+    #[serde(default, deserialize_with = "super::util::deserialize_opt_str_with_env_var")]
+    pub path_pattern: Option<PathBuf>,
     #[serde(default, deserialize_with = "super::util::deserialize_file_size")]
     pub max_file_size: u64,
     #[serde(default)]
     pub max_backup_index: usize,
+    /// How the backups are named; they are kept up to `max_backup_index + 1` either way.
+    #[serde(default)]
+    pub backup_naming: BackupNaming,
+    /// Deletes the oldest backups when the total size of the log file and its backups exceeds it; `0` means no limit.
+    #[serde(default, deserialize_with = "super::util::deserialize_file_size")]
+    pub max_total_size: u64,
+    /// In seconds; deletes the backups modified longer ago than it. `0` means no limit.
+    #[serde(default, deserialize_with = "super::util::deserialize_duration_secs")]
+    pub max_backup_age: u64,
+    /// Rotates when the records fall into another interval (aligned in the configured timezone, e.g. at the midnight
+    /// for `1d`) than the last written one; combined with `max_file_size`, whichever comes first. `0` means no limit.
+    #[serde(default, deserialize_with = "super::util::deserialize_duration_secs")]
+    pub rotation_interval: u64,
+    /// Compresses the backups in a background thread after the rotation; requires the cargo feature of the codec.
+    #[serde(default)]
+    pub compress_backups: Option<BackupCompression>,
+    /// The compression level, `6` of `0..=9` for gzip and `3` of `1..=22` for zstd by default.
+    #[serde(default)]
+    pub compression_level: Option<i32>,
+    #[serde(default)]
+    pub cleanup: Option<CleanupConfig>,
+    /// In seconds; the writes are buffered and flushed by a background thread at this interval.
+    #[serde(default, deserialize_with = "super::util::deserialize_duration_secs")]
+    pub flush_interval: u64,
+    /// The writes are buffered and flushed after this number of records.
+    /// If neither is set, the writes are flushed after each record (or batch).
+    #[serde(default)]
+    pub flush_every_n_records: usize,
+    /// Whether the file is shared by multiple processes: it's opened for appending,
+    /// and the rotations are serialized by an advisory lock on `<file name>.lock`.
+    #[serde(default)]
+    pub shared: bool,
+    /// Whether to reopen the path if the file is moved away, e.g. rotated by logrotate;
+    /// checked at most once per second. The files are also reopened by [`crate::reopen_files`].
+    #[serde(default)]
+    pub reopen: bool,
+    /// The path of a symlink maintained to point at the current file, e.g. `logs/current.log`,
+    /// for tailing the file of `path_pattern`.
+    #[serde(default, deserialize_with = "super::util::deserialize_opt_str_with_env_var")]
+    pub create_symlink: Option<PathBuf>,
+    /// When the writes are synced to the disk by `fsync`, trading the throughput for the durability.
+    #[serde(default)]
+    pub sync: SyncPolicy,
+    /// Runs after each backup is created (and compressed, if enabled), e.g. to ship it elsewhere.
+    #[serde(default)]
+    pub on_rotate: Option<RotateHookConfig>,
+    /// The permission bits set on the log files (including the existing one) on Unix, e.g. `0640`,
+    /// instead of the defaults by the umask.
+    #[serde(default, deserialize_with = "super::util::deserialize_opt_mode")]
+    pub file_mode: Option<u32>,
+    /// The permission bits set on the directories created for the log files on Unix, e.g. `0750`.
+    #[serde(default, deserialize_with = "super::util::deserialize_opt_mode")]
+    pub dir_mode: Option<u32>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+#[serde(tag = "kind")]
+pub enum RotateHookConfig {
+    #[serde(rename = "command")]
+    Command(CommandRotateHookConfig),
+    #[serde(rename = "custom")]
+    Custom(CustomRotateHookConfig),
+}
+
+/// Runs the command by the shell, with the environment variables `NAIVE_LOGGER_BACKUP_FILE`
+/// and `NAIVE_LOGGER_LOG_FILE` set to the paths.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CommandRotateHookConfig {
+    pub command: String,
+}
+
+/// Calls the hook registered by [`crate::register_rotate_hook`].
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CustomRotateHookConfig {
+    pub name: String,
+}
+
+/// When the file appender syncs the writes to the disk, deserialized from `none`, `every_record`,
+/// `on_error`, or `interval(<duration>)` like `interval(5s)`.
+#[derive(Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(try_from = "String")]
+pub enum SyncPolicy {
+    /// Left to the operating system.
+    #[default]
+    None,
+    /// After each record (or batch).
+    EveryRecord,
+    /// In seconds, by a background thread, if written since the last sync.
+    Interval(u64),
+    /// After an `error` record is written, so that the records explaining a crash survive it.
+    OnError,
+}
+
+impl TryFrom<String> for SyncPolicy {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        match s.as_str() {
+            "none" => Ok(SyncPolicy::None),
+            "every_record" => Ok(SyncPolicy::EveryRecord),
+            "on_error" => Ok(SyncPolicy::OnError),
+            _ => {
+                let interval = s
+                    .strip_prefix("interval(")
+                    .and_then(|x| x.strip_suffix(')'))
+                    .ok_or_else(|| format!("unknown sync policy '{}'", s))?;
+                let secs = super::util::deserialize_duration_secs(
+                    serde::de::value::StrDeserializer::<serde::de::value::Error>::new(interval),
+                )
+                .map_err(|e| format!("invalid sync interval '{}': {}", interval, e))?;
+                if secs == 0 {
+                    return Err("sync interval must be positive".to_string());
+                }
+                Ok(SyncPolicy::Interval(secs))
+            }
+        }
+    }
+}
+
+/// The same as deserialized from only the `path`, e.g. `FileAppenderConfig { path, ..Default::default() }`.
+impl Default for FileAppenderConfig {
+    fn default() -> Self {
+        Self {
+            enabled: default_enabled(),
+            queue: QueueConfig::default(),
+            filters: vec![],
+            common: AppenderCommonProperties::default(),
+            path: PathBuf::new(),
+            path_pattern: None,
+            max_file_size: 0,
+            max_backup_index: 0,
+            backup_naming: BackupNaming::default(),
+            max_total_size: 0,
+            max_backup_age: 0,
+            rotation_interval: 0,
+            compress_backups: None,
+            compression_level: None,
+            cleanup: None,
+            flush_interval: 0,
+            flush_every_n_records: 0,
+            shared: false,
+            reopen: false,
+            create_symlink: None,
+            sync: SyncPolicy::default(),
+            on_rotate: None,
+            file_mode: None,
+            dir_mode: None,
+        }
+    }
+}
+
+impl FileAppenderConfig {
+    /// The path, or the path pattern if set; for identifying the log files in the messages.
+    pub fn path_or_pattern(&self) -> &Path {
+        self.path_pattern.as_deref().unwrap_or(&self.path)
+    }
+}
+
+#[derive(Clone, Copy, Default, PartialEq, Deserialize)]
+pub enum BackupNaming {
+    /// `app.log.0` for the newest, shifting the older ones to the next indexes at each rotation.
+    #[default]
+    #[serde(rename = "index")]
+    Index,
+    /// `app.log.2024-07-31T12-00-00` by the time of the rotation, so that the backups are never renamed.
+    #[serde(rename = "timestamp")]
+    Timestamp,
+}
+
+#[derive(Clone, Copy, Deserialize)]
+pub enum BackupCompression {
+    #[serde(rename = "gzip")]
+    Gzip,
+    #[serde(rename = "zstd")]
+    Zstd,
+}
+
+impl BackupCompression {
+    pub fn name(&self) -> &'static str {
+        match self {
+            BackupCompression::Gzip => "gzip",
+            BackupCompression::Zstd => "zstd",
+        }
+    }
+}
+
+/// Deletes the stale files matching the pattern when the appender is created,
+/// e.g. the ones left by the renamed services or the old naming schemes.
+/// If neither `max_age_days` nor `max_count` is set, all the matching files are deleted.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CleanupConfig {
+    /// The file name pattern relative to the directory of the log file, with the wildcards `*` and `?`,
+    /// e.g. `old-service-*.log`; the log file and its backups never match.
+    pub pattern: String,
+    /// Only delete the files modified more than this number of days ago.
+    #[serde(default)]
+    pub max_age_days: Option<u64>,
+    /// Only delete the files other than the newest ones of this number.
+    #[serde(default)]
+    pub max_count: Option<usize>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ForwardAppenderConfig {
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    #[serde(flatten)]
+    pub queue: QueueConfig,
+    /// The records are written only if they pass all the filters.
+    #[serde(default)]
+    pub filters: Vec<FilterConfig>,
+    /// The name of the `log::Log` implementation registered by `register_forward_target()`.
+    pub name: String,
+}
+
+/// An appender created by the factory registered by `register_appender_kind()`;
+/// the other properties are passed to the factory.
+#[derive(Deserialize)]
+pub struct CustomAppenderConfig {
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    #[serde(flatten)]
+    pub queue: QueueConfig,
+    /// The records are written only if they pass all the filters.
+    #[serde(default)]
+    pub filters: Vec<FilterConfig>,
+    /// The registered kind.
+    pub name: String,
+    #[serde(flatten)]
+    pub properties: serde_json::Map<String, serde_json::Value>,
+}
+
+fn default_syslog_socket() -> PathBuf {
+    PathBuf::from("/dev/log")
+}
+
+/// Writes to the local syslog daemon through its Unix datagram socket; only supported on Unix.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SyslogAppenderConfig {
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    #[serde(flatten)]
+    pub queue: QueueConfig,
+    /// The records are written only if they pass all the filters.
+    #[serde(default)]
+    pub filters: Vec<FilterConfig>,
+    #[serde(flatten)]
+    pub common: AppenderCommonProperties,
+    #[serde(default = "default_syslog_socket")]
+    pub socket: PathBuf,
+    #[serde(default)]
+    pub facility: SyslogFacility,
+    /// The tag of the messages; the name of the program by default.
+    #[serde(default)]
+    pub app_name: Option<String>,
+}
+
+#[derive(Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SyslogFacility {
+    Kern,
+    #[default]
+    User,
+    Mail,
+    Daemon,
+    Auth,
+    Syslog,
+    Lpr,
+    News,
+    Uucp,
+    Cron,
+    Authpriv,
+    Ftp,
+    Local0 = 16,
+    Local1,
+    Local2,
+    Local3,
+    Local4,
+    Local5,
+    Local6,
+    Local7,
+}
+
+fn default_journald_socket() -> PathBuf {
+    PathBuf::from("/run/systemd/journal/socket")
+}
+
+/// Submits the records to systemd-journald by its native protocol; only supported on Unix.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct JournaldAppenderConfig {
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    #[serde(flatten)]
+    pub queue: QueueConfig,
+    /// The records are written only if they pass all the filters.
+    #[serde(default)]
+    pub filters: Vec<FilterConfig>,
+    #[serde(default = "default_journald_socket")]
+    pub socket: PathBuf,
+    /// The `SYSLOG_IDENTIFIER` field; the name of the program by default.
+    #[serde(default)]
+    pub syslog_identifier: Option<String>,
+}
+
+const DEFAULT_MAX_BUFFERED_BYTES: u64 = 1024 * 1024;
+fn default_max_buffered_bytes() -> u64 {
+    DEFAULT_MAX_BUFFERED_BYTES
+}
+
+const DEFAULT_NETWORK_TIMEOUT_SECS: u64 = 5;
+fn default_network_timeout() -> u64 {
+    DEFAULT_NETWORK_TIMEOUT_SECS
+}
+
+/// Streams the encoded lines to a TCP endpoint, reconnecting with backoff if the connection is down.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TcpAppenderConfig {
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    #[serde(flatten)]
+    pub queue: QueueConfig,
+    /// The records are written only if they pass all the filters.
+    #[serde(default)]
+    pub filters: Vec<FilterConfig>,
+    #[serde(flatten)]
+    pub common: AppenderCommonProperties,
+    pub host: String,
+    pub port: u16,
+    /// The lines are buffered in memory while the connection is down;
+    /// the oldest ones are dropped if the buffer exceeds this size.
+    #[serde(default = "default_max_buffered_bytes", deserialize_with = "super::util::deserialize_file_size")]
+    pub max_buffered_bytes: u64,
+    /// In seconds; also applied to each address resolved from the host.
+    #[serde(default = "default_network_timeout", deserialize_with = "super::util::deserialize_duration_secs")]
+    pub connect_timeout: u64,
+    /// In seconds; the connection is considered down if a write takes longer.
+    #[serde(default = "default_network_timeout", deserialize_with = "super::util::deserialize_duration_secs")]
+    pub write_timeout: u64,
+}
+
+const DEFAULT_WEBSOCKET_MAX_CLIENTS: usize = 16;
+fn default_websocket_max_clients() -> usize {
+    DEFAULT_WEBSOCKET_MAX_CLIENTS
+}
+
+/// Serves the encoded records to the WebSocket clients in real time, e.g. for a browser-based live log viewer.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct WebSocketAppenderConfig {
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    #[serde(flatten)]
+    pub queue: QueueConfig,
+    /// The records are written only if they pass all the filters.
+    #[serde(default)]
+    pub filters: Vec<FilterConfig>,
+    #[serde(flatten)]
+    pub common: AppenderCommonProperties,
+    /// The address to listen on, like `127.0.0.1:9001`.
+    pub bind: String,
+    /// The more clients are rejected.
+    #[serde(default = "default_websocket_max_clients")]
+    pub max_clients: usize,
+    /// In seconds; a client is disconnected if a write takes longer, so that it can't stall the others.
+    #[serde(default = "default_network_timeout", deserialize_with = "super::util::deserialize_duration_secs")]
+    pub write_timeout: u64,
+}
+
+/// Routes the records to the inner appenders by their levels, e.g. the `error` ones to `error.log`
+/// and the others to `app.log`. The inner appenders are written by this one, so their queue settings are ignored.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LevelRouterAppenderConfig {
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    #[serde(flatten)]
+    pub queue: QueueConfig,
+    /// The records are written only if they pass all the filters.
+    #[serde(default)]
+    pub filters: Vec<FilterConfig>,
+    /// The inner appenders by the level ranges; a record is passed to all the ones whose ranges contain its level.
+    pub routes: IndexMap<LevelRange, AppenderConfig>,
+}
+
+/// The levels from the severest to the least severe one, inclusive, deserialized from a level like `error`,
+/// or two levels like `trace..warn` in either order.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+#[serde(try_from = "String")]
+pub struct LevelRange {
+    pub severest: Level,
+    pub least_severe: Level,
+}
+
+impl LevelRange {
+    pub fn contains(&self, level: Level) -> bool {
+        self.severest <= level && level <= self.least_severe
+    }
+}
+
+impl TryFrom<String> for LevelRange {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        let parse = |x: &str| x.trim().parse::<Level>().map_err(|_| format!("invalid level range '{}'", s));
+        let (a, b) = match s.split_once("..") {
+            Some((a, b)) => (parse(a)?, parse(b)?),
+            None => (parse(&s)?, parse(&s)?),
+        };
+        Ok(Self {
+            severest: a.min(b),
+            least_severe: a.max(b),
+        })
+    }
+}
+
+impl std::fmt::Display for LevelRange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let severest = self.severest.as_str().to_lowercase();
+        if self.severest == self.least_severe {
+            write!(f, "{}", severest)
+        } else {
+            write!(f, "{}..{}", self.least_severe.as_str().to_lowercase(), severest)
+        }
+    }
+}
+
+const DEFAULT_FLUENTD_PORT: u16 = 24224;
+fn default_fluentd_port() -> u16 {
+    DEFAULT_FLUENTD_PORT
+}
+
+/// Ships the records to fluentd or fluent-bit by the forward protocol, as the structured events of the tag.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FluentdAppenderConfig {
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    #[serde(flatten)]
+    pub queue: QueueConfig,
+    /// The records are written only if they pass all the filters.
+    #[serde(default)]
+    pub filters: Vec<FilterConfig>,
+    pub host: String,
+    #[serde(default = "default_fluentd_port")]
+    pub port: u16,
+    pub tag: String,
+    /// The same as the one of the TCP appender.
+    #[serde(default = "default_max_buffered_bytes", deserialize_with = "super::util::deserialize_file_size")]
+    pub max_buffered_bytes: u64,
+    #[serde(default = "default_network_timeout", deserialize_with = "super::util::deserialize_duration_secs")]
+    pub connect_timeout: u64,
+    #[serde(default = "default_network_timeout", deserialize_with = "super::util::deserialize_duration_secs")]
+    pub write_timeout: u64,
+}
+
+const DEFAULT_HTTP_BATCH_SIZE: usize = 100;
+fn default_http_batch_size() -> usize {
+    DEFAULT_HTTP_BATCH_SIZE
+}
+
+const DEFAULT_HTTP_MAX_DELAY_SECS: u64 = 1;
+fn default_http_max_delay() -> u64 {
+    DEFAULT_HTTP_MAX_DELAY_SECS
+}
+
+fn default_http_content_type() -> String {
+    "application/x-ndjson".to_string()
+}
+
+/// POSTs the encoded records in batches to an HTTP endpoint, as the lines of the body.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct HttpAppenderConfig {
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    #[serde(flatten)]
+    pub queue: QueueConfig,
+    /// The records are written only if they pass all the filters.
+    #[serde(default)]
+    pub filters: Vec<FilterConfig>,
+    #[serde(flatten)]
+    pub common: AppenderCommonProperties,
+    /// Like `http://host[:port][/path]`; HTTPS is not supported.
+    pub url: String,
+    /// The extra headers of the requests, e.g. for the authorization.
+    #[serde(default)]
+    pub headers: IndexMap<String, String>,
+    #[serde(default = "default_http_content_type")]
+    pub content_type: String,
+    /// The maximum number of records in a request.
+    #[serde(default = "default_http_batch_size")]
+    pub batch_size: usize,
+    /// In seconds; the maximum time a record waits for its batch to be sent.
+    #[serde(default = "default_http_max_delay", deserialize_with = "super::util::deserialize_duration_secs")]
+    pub max_delay: u64,
+    #[serde(default = "default_network_timeout", deserialize_with = "super::util::deserialize_duration_secs")]
+    pub connect_timeout: u64,
+    /// In seconds; applied to both sending the request and receiving the response.
+    #[serde(default = "default_network_timeout", deserialize_with = "super::util::deserialize_duration_secs")]
+    pub write_timeout: u64,
+}
+
+const DEFAULT_SMTP_PORT: u16 = 25;
+fn default_smtp_port() -> u16 {
+    DEFAULT_SMTP_PORT
+}
+
+fn default_email_subject() -> String {
+    "[{program}@{hostname}] {count} error(s): {message}".to_string()
+}
+
+const DEFAULT_EMAIL_THROTTLE_SECS: u64 = 300;
+fn default_email_throttle() -> u64 {
+    DEFAULT_EMAIL_THROTTLE_SECS
+}
+
+const DEFAULT_EMAIL_MAX_RECORDS: usize = 100;
+fn default_email_max_records() -> usize {
+    DEFAULT_EMAIL_MAX_RECORDS
+}
+
+/// Mails the `error` records by SMTP, at most one mail per `throttle`.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct EmailAppenderConfig {
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    #[serde(flatten)]
+    pub queue: QueueConfig,
+    /// The records are written only if they pass all the filters.
+    #[serde(default)]
+    pub filters: Vec<FilterConfig>,
+    #[serde(flatten)]
+    pub common: AppenderCommonProperties,
+    /// The SMTP relay; neither TLS nor authentication is supported.
+    pub host: String,
+    #[serde(default = "default_smtp_port")]
+    pub port: u16,
+    pub from: String,
+    pub to: Vec<String>,
+    /// With the placeholders `{program}`, `{hostname}`, `{count}` and `{message}` (of the first record).
+    #[serde(default = "default_email_subject")]
+    pub subject: String,
+    /// In seconds; the minimum interval between the mails, the records in between are sent together.
+    #[serde(default = "default_email_throttle", deserialize_with = "super::util::deserialize_duration_secs")]
+    pub throttle: u64,
+    /// The maximum number of records in a mail; the rest are only counted.
+    #[serde(default = "default_email_max_records")]
+    pub max_records: usize,
+    #[serde(default = "default_network_timeout", deserialize_with = "super::util::deserialize_duration_secs")]
+    pub connect_timeout: u64,
+    /// In seconds; applied to both sending the commands and receiving the replies.
+    #[serde(default = "default_network_timeout", deserialize_with = "super::util::deserialize_duration_secs")]
+    pub write_timeout: u64,
+}
+
+fn default_sqlite_table() -> String {
+    "logs".to_string()
+}
+
+/// Inserts the records into a table of an SQLite database; requires the `sqlite` feature.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SqliteAppenderConfig {
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    #[serde(flatten)]
+    pub queue: QueueConfig,
+    /// The records are written only if they pass all the filters.
+    #[serde(default)]
+    pub filters: Vec<FilterConfig>,
+    pub path: PathBuf,
+    /// Created if not existing.
+    #[serde(default = "default_sqlite_table")]
+    pub table: String,
+}
+
+/// How many replicas should acknowledge a record before it's considered delivered.
+#[derive(Clone, Copy, Default, Deserialize)]
+pub enum KafkaAcks {
+    /// Not waiting for the acknowledgement, i.e. `acks=0`.
+    #[serde(rename = "none")]
+    None,
+    /// Only the partition leader, i.e. `acks=1`.
+    #[serde(rename = "leader")]
+    Leader,
+    /// All the in-sync replicas, i.e. `acks=all`.
+    #[default]
+    #[serde(rename = "all")]
+    All,
+}
+
+/// Publishes each encoded record as a message to a Kafka topic; requires the `kafka` feature.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct KafkaAppenderConfig {
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    #[serde(flatten)]
+    pub queue: QueueConfig,
+    /// The records are written only if they pass all the filters.
+    #[serde(default)]
+    pub filters: Vec<FilterConfig>,
+    #[serde(flatten)]
+    pub common: AppenderCommonProperties,
+    /// The bootstrap brokers, like `host:port`.
+    pub brokers: Vec<String>,
+    pub topic: String,
+    /// The key-value pair whose value is used as the message key, so that the records with the same value
+    /// are published to the same partition; the records without it are distributed by the default partitioner.
+    #[serde(default)]
+    pub key: Option<String>,
+    #[serde(default)]
+    pub acks: KafkaAcks,
+    /// The extra librdkafka properties of the producer, e.g. `security.protocol`.
+    #[serde(default)]
+    pub properties: IndexMap<String, String>,
 }
 
 #[cfg(test)]
@@ -56,10 +886,123 @@ mod tests {
     fn test_deserialize() {
         let s = r#"{"kind": "console", "encoder": {"kind": "pattern"}, "stderr_level": "error"}"#;
         let config: AppenderConfig = serde_json::from_str(s).unwrap();
-        assert!(matches!(config, AppenderConfig::Console(_)));
+        assert!(matches!(&config, AppenderConfig::Console(x) if x.color == ColorMode::Auto));
+        assert!(config.queue().async_ && config.queue().queue_size == 8192);
+        let s = r#"{"kind": "console", "encoder": {"kind": "pattern"}, "color": "never"}"#;
+        let config: AppenderConfig = serde_json::from_str(s).unwrap();
+        assert!(matches!(&config, AppenderConfig::Console(x) if x.color == ColorMode::Never));
+
+        let s = r#"{"kind": "console", "encoder": {"kind": "pattern"}, "queue_size": 100, "overflow_policy": "drop"}"#;
+        let config: AppenderConfig = serde_json::from_str(s).unwrap();
+        assert!(config.queue().queue_size == 100 && config.queue().overflow_policy == OverflowPolicy::Drop);
+        let s = r#"{"kind": "console", "encoder": {"kind": "pattern"}, "async": false}"#;
+        let config: AppenderConfig = serde_json::from_str(s).unwrap();
+        assert!(!config.queue().async_);
+        let s = r#"{"kind": "console", "encoder": {"kind": "pattern"}, "max_records_per_second": 100, "burst_size": 500}"#;
+        let config: AppenderConfig = serde_json::from_str(s).unwrap();
+        assert!(config.queue().max_records_per_second == 100 && config.queue().burst_size == 500);
+        let s = r#"{"kind": "console", "encoder": {"kind": "pattern"}, "queue": 100}"#;
+        assert!(serde_json::from_str::<AppenderConfig>(s).is_err());
+
+        let s = r#"{"kind": "file", "encoder": {"kind": "json"}, "redact": {"keys": ["password"]}, "path": "log.txt", "max_file_size": "1G", "max_backup_index": 2, "rotation_interval": "1d", "max_total_size": "1G", "max_backup_age": "7d"}"#;
+        let config: AppenderConfig = serde_json::from_str(s).unwrap();
+        assert!(matches!(config, AppenderConfig::File(x) if x.rotation_interval == 86400 && x.max_backup_age == 7 * 86400));
+        let s = r#"{"kind": "file", "encoder": {"kind": "json"}, "path": "log.txt", "file_mode": "0640", "dir_mode": "0750"}"#;
+        let config: AppenderConfig = serde_json::from_str(s).unwrap();
+        assert!(matches!(config, AppenderConfig::File(x) if x.file_mode == Some(0o640) && x.dir_mode == Some(0o750)));
+        let s = r#"{"kind": "file", "encoder": {"kind": "json"}, "path": "log.txt", "backup_naming": "timestamp"}"#;
+        let config: AppenderConfig = serde_json::from_str(s).unwrap();
+        assert!(matches!(config, AppenderConfig::File(x) if x.backup_naming == BackupNaming::Timestamp));
+
+        let s = r#"{"kind": "file", "encoder": {"kind": "json"}, "path_pattern": "logs/app-%Y-%m-%d.log", "flush_interval": "1s", "flush_every_n_records": 100, "shared": true, "reopen": true, "create_symlink": "logs/current.log"}"#;
+        let config: AppenderConfig = serde_json::from_str(s).unwrap();
+        assert!(matches!(config, AppenderConfig::File(x) if x.path_or_pattern() == Path::new("logs/app-%Y-%m-%d.log") && x.flush_interval == 1 && x.shared && x.reopen && x.create_symlink.is_some()));
+
+        let s = r#"{"kind": "file", "encoder": {"kind": "json"}, "path": "log.txt", "sync": "interval(5s)"}"#;
+        let config: AppenderConfig = serde_json::from_str(s).unwrap();
+        assert!(matches!(config, AppenderConfig::File(x) if x.sync == SyncPolicy::Interval(5)));
+        let s = r#"{"kind": "file", "encoder": {"kind": "json"}, "path": "log.txt", "sync": "on_error"}"#;
+        let config: AppenderConfig = serde_json::from_str(s).unwrap();
+        assert!(matches!(config, AppenderConfig::File(x) if x.sync == SyncPolicy::OnError));
+        let s = r#"{"kind": "file", "encoder": {"kind": "json"}, "path": "log.txt", "sync": "interval(0)"}"#;
+        assert!(serde_json::from_str::<AppenderConfig>(s).is_err());
+        let s = r#"{"kind": "file", "encoder": {"kind": "json"}, "path": "log.txt", "on_rotate": {"kind": "command", "command": "gzip $NAIVE_LOGGER_BACKUP_FILE"}}"#;
+        let config: AppenderConfig = serde_json::from_str(s).unwrap();
+        assert!(matches!(config, AppenderConfig::File(x) if matches!(&x.on_rotate, Some(RotateHookConfig::Command(x)) if x.command.starts_with("gzip"))));
+
+        let s = r#"{"kind": "file", "encoder": {"kind": "json"}, "path": "log.txt", "cleanup": {"pattern": "old-*.log", "max_count": 3}}"#;
+        let config: AppenderConfig = serde_json::from_str(s).unwrap();
+        assert!(matches!(config, AppenderConfig::File(x) if x.cleanup.as_ref().is_some_and(|x| x.max_count == Some(3))));
+
+        let s = r#"{"kind": "file", "encoder": {"kind": "json"}, "path": "log.txt", "compress_backups": "zstd", "compression_level": 9}"#;
+        let config: AppenderConfig = serde_json::from_str(s).unwrap();
+        assert!(matches!(config, AppenderConfig::File(x) if matches!(x.compress_backups, Some(BackupCompression::Zstd))));
+
+        let s = r#"{"kind": "syslog", "encoder": {"kind": "pattern", "pattern": "{message}"}, "facility": "local3"}"#;
+        let config: AppenderConfig = serde_json::from_str(s).unwrap();
+        assert!(matches!(config, AppenderConfig::Syslog(x) if x.facility as u8 == 19 && x.socket.as_path() == std::path::Path::new("/dev/log")));
+
+        let s = r#"{"kind": "journald"}"#;
+        let config: AppenderConfig = serde_json::from_str(s).unwrap();
+        assert!(matches!(config, AppenderConfig::Journald(x) if x.syslog_identifier.is_none()));
+
+        let s = r#"{"kind": "tcp", "encoder": {"kind": "json"}, "host": "localhost", "port": 5170, "max_buffered_bytes": "4M"}"#;
+        let config: AppenderConfig = serde_json::from_str(s).unwrap();
+        assert!(matches!(config, AppenderConfig::Tcp(x) if x.max_buffered_bytes == 4 * 1024 * 1024 && x.write_timeout == 5));
+
+        let s = r#"{"kind": "http", "encoder": {"kind": "json"}, "url": "http://localhost:3100/ingest", "headers": {"X-Token": "t"}, "max_delay": "5s"}"#;
+        let config: AppenderConfig = serde_json::from_str(s).unwrap();
+        assert!(matches!(config, AppenderConfig::Http(x) if x.headers["X-Token"] == "t" && x.max_delay == 5 && x.batch_size == 100));
+
+        let s = r#"{"kind": "email", "encoder": {"kind": "pattern"}, "host": "localhost", "from": "app@example.com", "to": ["ops@example.com"], "throttle": "10m"}"#;
+        let config: AppenderConfig = serde_json::from_str(s).unwrap();
+        assert!(matches!(config, AppenderConfig::Email(x) if x.port == 25 && x.throttle == 600 && x.subject.contains("{count}")));
+
+        let s = r#"{"kind": "websocket", "encoder": {"kind": "json"}, "bind": "127.0.0.1:9001"}"#;
+        let config: AppenderConfig = serde_json::from_str(s).unwrap();
+        assert!(matches!(config, AppenderConfig::WebSocket(x) if x.max_clients == 16 && x.write_timeout == 5));
+
+        let s = r#"{"kind": "level_router", "routes": {
+            "error": {"kind": "file", "encoder": {"kind": "pattern"}, "path": "error.log"},
+            "trace..warn": {"kind": "file", "encoder": {"kind": "pattern"}, "path": "app.log"}
+        }}"#;
+        let config: AppenderConfig = serde_json::from_str(s).unwrap();
+        let AppenderConfig::LevelRouter(x) = &config else {
+            panic!("not a level_router appender");
+        };
+        let ranges: Vec<_> = x.routes.keys().map(ToString::to_string).collect();
+        assert_eq!(ranges, ["error", "trace..warn"]);
+        assert!(x.routes.keys().nth(1).unwrap().contains(log::Level::Info));
+        assert!(!x.routes.keys().nth(1).unwrap().contains(log::Level::Error));
+        assert_eq!(config.file_configs().len(), 2);
+
+        let s = r#"{"kind": "level_router", "routes": {"warn..fatal": {"kind": "console", "encoder": {"kind": "pattern"}}}}"#;
+        assert!(serde_json::from_str::<AppenderConfig>(s).is_err());
+
+        let s = r#"{"kind": "sqlite", "path": "logs.db"}"#;
+        let config: AppenderConfig = serde_json::from_str(s).unwrap();
+        assert!(matches!(config, AppenderConfig::Sqlite(x) if x.table == "logs"));
+
+        let s = r#"{"kind": "fluentd", "host": "localhost", "tag": "app.access"}"#;
+        let config: AppenderConfig = serde_json::from_str(s).unwrap();
+        assert!(matches!(config, AppenderConfig::Fluentd(x) if x.port == 24224 && x.tag == "app.access"));
+
+        let s = r#"{"kind": "kafka", "encoder": {"kind": "json"}, "brokers": ["localhost:9092"], "topic": "logs", "key": "user_id", "acks": "leader"}"#;
+        let config: AppenderConfig = serde_json::from_str(s).unwrap();
+        assert!(matches!(config, AppenderConfig::Kafka(x) if x.topic == "logs" && matches!(x.acks, KafkaAcks::Leader)));
+
+        let s = r#"{"kind": "forward", "name": "legacy"}"#;
+        let config: AppenderConfig = serde_json::from_str(s).unwrap();
+        assert!(config.enabled());
+        assert!(matches!(config, AppenderConfig::Forward(x) if x.name == "legacy"));
+
+        let s = r#"{"kind": "custom", "name": "my_kind", "enabled": false, "async": false, "url": "x"}"#;
+        let config: AppenderConfig = serde_json::from_str(s).unwrap();
+        assert!(!config.enabled() && !config.queue().async_);
+        assert!(matches!(config, AppenderConfig::Custom(x) if x.name == "my_kind" && x.properties.len() == 1 && x.properties["url"] == "x"));
 
-        let s = r#"{"kind": "file", "encoder": {"kind": "json"}, "path": "log.txt", "max_file_size": "1G", "max_backup_index": 2}"#;
+        let s = r#"{"kind": "console", "enabled": false, "encoder": {"kind": "pattern"}}"#;
         let config: AppenderConfig = serde_json::from_str(s).unwrap();
-        assert!(matches!(config, AppenderConfig::File(_)));
+        assert!(!config.enabled());
     }
 }
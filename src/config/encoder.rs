@@ -1,3 +1,7 @@
+use std::collections::HashMap;
+
+use indexmap::IndexMap;
+use log::Level;
 use serde::Deserialize;
 
 const DEFAULT_PATTERN: &str =
@@ -14,6 +18,21 @@ pub enum EncoderConfig {
     Pattern(PatternEncoderConfig),
     #[serde(rename = "json")]
     Json(JsonEncoderConfig),
+    #[serde(rename = "msgpack")]
+    Msgpack(MsgpackEncoderConfig),
+    #[serde(rename = "cbor")]
+    Cbor(CborEncoderConfig),
+    #[serde(rename = "cef")]
+    Cef(CefEncoderConfig),
+    #[serde(rename = "custom")]
+    Custom(CustomEncoderConfig),
+}
+
+/// The `pattern` encoder with the default pattern.
+impl Default for EncoderConfig {
+    fn default() -> Self {
+        EncoderConfig::Pattern(PatternEncoderConfig::default())
+    }
 }
 
 #[derive(Deserialize)]
@@ -21,11 +40,161 @@ pub enum EncoderConfig {
 pub struct PatternEncoderConfig {
     #[serde(default = "default_pattern")]
     pub pattern: String,
+    /// Renders the durations, byte sizes and timestamps in the key-value pairs in a friendly way.
+    #[serde(default)]
+    pub friendly_values: bool,
+    /// The names to display for the levels instead of their own, e.g. `WARNING` for `warn`.
+    #[serde(default)]
+    pub level_names: HashMap<Level, String>,
+}
+
+impl Default for PatternEncoderConfig {
+    fn default() -> Self {
+        Self {
+            pattern: default_pattern(),
+            friendly_values: false,
+            level_names: HashMap::new(),
+        }
+    }
+}
+
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+pub struct JsonEncoderConfig {
+    /// The names to display for the levels instead of their own, e.g. `WARNING` for `warn`.
+    #[serde(default)]
+    pub level_names: HashMap<Level, String>,
+    /// If set, output as the `schema_version` field, so the downstream parsers can tell the field layouts apart.
+    #[serde(default)]
+    pub schema_version: Option<String>,
+    /// Whether to output the `logger` field, e.g. `naive-logger/0.1.0`.
+    #[serde(default)]
+    pub logger_field: bool,
+    /// Whether to output the `thread_id` and `thread_name` fields of the threads generating the records.
+    #[serde(default)]
+    pub thread_fields: bool,
+    /// Whether to output the indented multi-line JSON for reading, rather than a line per record.
+    #[serde(default)]
+    pub pretty: bool,
+    #[serde(default)]
+    pub timestamp_format: TimestampFormat,
+    /// Whether the string timestamps are in UTC rather than the configured timezone.
+    #[serde(default)]
+    pub timestamp_utc: bool,
+    /// Whether to output the key-value pairs as the top-level fields rather than nested in `args`.
+    #[serde(default)]
+    pub flatten_args: bool,
+    /// What to do with the flattened key-value pairs clashing with the built-in fields.
+    #[serde(default)]
+    pub arg_conflict: ArgConflictPolicy,
+    /// The fields added to every record, e.g. the service name and the environment;
+    /// the environment variables in the values are substituted.
+    #[serde(default, deserialize_with = "super::util::deserialize_map_with_env_var")]
+    pub static_fields: IndexMap<String, String>,
+    /// The built-in fields to output, e.g. `["timestamp", "level", "message", "args"]`; empty means all.
+    #[serde(default)]
+    pub include_fields: Vec<String>,
+    /// The built-in fields not to output, e.g. `["module", "file", "line"]`.
+    #[serde(default)]
+    pub exclude_fields: Vec<String>,
+}
+
+#[derive(Clone, Copy, Default, PartialEq, Deserialize)]
+pub enum ArgConflictPolicy {
+    /// Output the key-value pair with the key prefixed by `arg_`, e.g. `arg_level`.
+    #[default]
+    #[serde(rename = "prefix")]
+    Prefix,
+    /// Output the key-value pair in place of the built-in field.
+    #[serde(rename = "overwrite")]
+    Overwrite,
+    /// Drop the key-value pair.
+    #[serde(rename = "skip")]
+    Skip,
+}
+
+/// How the JSON encoder outputs the timestamps, deserialized from `millis`, `seconds`, `nanos`, `rfc3339`,
+/// or a `strftime` format like `%Y-%m-%d %H:%M:%S%.3f`.
+#[derive(Clone, Default, PartialEq, Deserialize)]
+#[serde(try_from = "String")]
+pub enum TimestampFormat {
+    /// The Unix timestamp in milliseconds.
+    #[default]
+    Millis,
+    Seconds,
+    Nanos,
+    /// Like `2024-07-31T12:34:56.789+08:00`.
+    Rfc3339,
+    Strftime(String),
+}
+
+impl TryFrom<String> for TimestampFormat {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        match s.as_str() {
+            "millis" => Ok(TimestampFormat::Millis),
+            "seconds" => Ok(TimestampFormat::Seconds),
+            "nanos" => Ok(TimestampFormat::Nanos),
+            "rfc3339" => Ok(TimestampFormat::Rfc3339),
+            _ if s.contains('%') => {
+                let invalid = chrono::format::StrftimeItems::new(&s).any(|x| matches!(x, chrono::format::Item::Error));
+                if invalid {
+                    return Err(format!("invalid timestamp format '{}'", s));
+                }
+                Ok(TimestampFormat::Strftime(s))
+            }
+            _ => Err(format!("unknown timestamp format '{}'", s)),
+        }
+    }
+}
+
+/// Encodes the records as the MessagePack maps of the same fields as the `json` encoder,
+/// each prefixed by its length as a 4-byte big-endian integer.
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+pub struct MsgpackEncoderConfig {
+    /// The names to output for the levels instead of their own, e.g. `WARNING` for `warn`.
+    #[serde(default)]
+    pub level_names: HashMap<Level, String>,
 }
 
+/// Encodes the records as the CBOR maps of the same fields as the `json` encoder, one after another;
+/// requires the `cbor` feature.
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+pub struct CborEncoderConfig {
+    /// The names to output for the levels instead of their own, e.g. `WARNING` for `warn`.
+    #[serde(default)]
+    pub level_names: HashMap<Level, String>,
+}
+
+/// Formats the records in the Common Event Format for the SIEM systems like ArcSight,
+/// with the key-value pairs as the extensions.
 #[derive(Deserialize)]
 #[serde(deny_unknown_fields)]
-pub struct JsonEncoderConfig;
+pub struct CefEncoderConfig {
+    /// The `Device Vendor` header field.
+    pub vendor: String,
+    /// The `Device Product` header field.
+    pub product: String,
+    /// The `Device Version` header field.
+    #[serde(default)]
+    pub version: String,
+    /// The `Severity` header fields (`0..=10`) of the levels instead of the defaults.
+    #[serde(default)]
+    pub severities: HashMap<Level, u8>,
+}
+
+/// An encoder created by the factory registered by `register_encoder_kind()`;
+/// the other properties are passed to the factory.
+#[derive(Deserialize)]
+pub struct CustomEncoderConfig {
+    /// The registered kind.
+    pub name: String,
+    #[serde(flatten)]
+    pub properties: serde_json::Map<String, serde_json::Value>,
+}
 
 #[cfg(test)]
 mod tests {
@@ -40,5 +209,36 @@ mod tests {
         let s = r#"{"kind": "json"}"#;
         let config: EncoderConfig = serde_json::from_str(s).unwrap();
         assert!(matches!(config, EncoderConfig::Json(_)));
+
+        let s = r#"{"kind": "json", "timestamp_format": "%Y-%m-%d %H:%M:%S", "timestamp_utc": true}"#;
+        let config: EncoderConfig = serde_json::from_str(s).unwrap();
+        assert!(matches!(config, EncoderConfig::Json(x) if x.timestamp_format == TimestampFormat::Strftime("%Y-%m-%d %H:%M:%S".to_string()) && x.timestamp_utc));
+        for s in [r#"{"kind": "json", "timestamp_format": "micros"}"#, r#"{"kind": "json", "timestamp_format": "%Q"}"#] {
+            assert!(serde_json::from_str::<EncoderConfig>(s).is_err());
+        }
+
+        let s = r#"{"kind": "msgpack"}"#;
+        let config: EncoderConfig = serde_json::from_str(s).unwrap();
+        assert!(matches!(config, EncoderConfig::Msgpack(_)));
+
+        let s = r#"{"kind": "cbor", "level_names": {"warn": "WARNING"}}"#;
+        let config: EncoderConfig = serde_json::from_str(s).unwrap();
+        assert!(matches!(config, EncoderConfig::Cbor(x) if x.level_names[&Level::Warn] == "WARNING"));
+
+        let s = r#"{"kind": "custom", "name": "proprietary", "version": 2}"#;
+        let config: EncoderConfig = serde_json::from_str(s).unwrap();
+        assert!(matches!(config, EncoderConfig::Custom(x) if x.name == "proprietary" && x.properties["version"] == 2));
+
+        let s = r#"{"kind": "cef", "vendor": "Acme", "product": "myapp", "severities": {"error": 9}}"#;
+        let config: EncoderConfig = serde_json::from_str(s).unwrap();
+        assert!(matches!(config, EncoderConfig::Cef(x) if x.version.is_empty() && x.severities[&Level::Error] == 9));
+
+        let s = r#"{"kind": "json", "level_names": {"warn": "WARNING", "ERROR": "SEVERE"}}"#;
+        let config: EncoderConfig = serde_json::from_str(s).unwrap();
+        let EncoderConfig::Json(config) = config else {
+            panic!("expecting json encoder config");
+        };
+        assert_eq!(config.level_names[&Level::Warn], "WARNING");
+        assert_eq!(config.level_names[&Level::Error], "SEVERE");
     }
 }
@@ -0,0 +1,56 @@
+use log::LevelFilter;
+use serde::Deserialize;
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+#[serde(tag = "kind")]
+pub enum FilterConfig {
+    #[serde(rename = "threshold")]
+    Threshold(ThresholdFilterConfig),
+    #[serde(rename = "regex")]
+    Regex(RegexFilterConfig),
+    #[serde(rename = "custom")]
+    Custom(CustomFilterConfig),
+}
+
+/// Passes the records at least as severe as the level.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ThresholdFilterConfig {
+    pub level: LevelFilter,
+}
+
+/// Passes the records whose messages match the pattern, or the other ones if `exclude`.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RegexFilterConfig {
+    pub pattern: String,
+    #[serde(default)]
+    pub exclude: bool,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CustomFilterConfig {
+    pub name: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_deserialize() {
+        let s = r#"{"kind": "threshold", "level": "warn"}"#;
+        let config: FilterConfig = serde_json::from_str(s).unwrap();
+        assert!(matches!(config, FilterConfig::Threshold(x) if x.level == LevelFilter::Warn));
+
+        let s = r#"{"kind": "regex", "pattern": "^health", "exclude": true}"#;
+        let config: FilterConfig = serde_json::from_str(s).unwrap();
+        assert!(matches!(config, FilterConfig::Regex(x) if x.pattern == "^health" && x.exclude));
+
+        let s = r#"{"kind": "custom", "name": "my_filter"}"#;
+        let config: FilterConfig = serde_json::from_str(s).unwrap();
+        assert!(matches!(config, FilterConfig::Custom(x) if x.name == "my_filter"));
+    }
+}
@@ -19,9 +19,10 @@ pub struct LoggerConfig {
     pub appenders: Vec<String>,
 }
 
-#[derive(Clone, Copy, Deserialize)]
+#[derive(Clone, Copy, Default, Deserialize)]
 #[serde(deny_unknown_fields)]
 pub enum LoggerTargetMatcher {
+    #[default]
     #[serde(rename = "prefix")]
     Prefix,
     #[serde(rename = "prefix_inverse")]
@@ -29,11 +30,6 @@ pub enum LoggerTargetMatcher {
     #[serde(rename = "exact")]
     Exact,
 }
-impl Default for LoggerTargetMatcher {
-    fn default() -> Self {
-        Self::Prefix
-    }
-}
 
 #[cfg(test)]
 mod tests {
@@ -1,22 +1,338 @@
+//! The configuration model, deserialized from the config files (see `docs/configuration.md`),
+//! or constructed by the code, e.g. from the presets like [`Config::json_stdout`].
+
 use std::collections::HashMap;
+use std::path::Path;
 
+use log::{Level, LevelFilter};
+use serde::de::DeserializeOwned;
 use serde::Deserialize;
 
 pub use appender::*;
 pub use encoder::*;
+pub use filter::*;
 pub use logger::*;
+pub use processor::*;
 
 mod appender;
 mod encoder;
+mod filter;
 mod logger;
+mod processor;
 mod util;
 
 #[derive(Deserialize)]
 #[serde(deny_unknown_fields)]
 pub struct Config {
+    /// The variables referenced in the other string values as `${vars.<name>}`.
+    #[serde(default)]
+    pub vars: HashMap<String, String>,
+    #[serde(default)]
     pub appenders: HashMap<String, AppenderConfig>,
+    /// If omitted, the `info` messages are written to [`DEFAULT_ROOT_APPENDER`].
+    #[serde(default = "default_root")]
     pub root: LoggerConfig,
+    #[serde(default)]
     pub loggers: Vec<LoggerConfig>,
+    /// Determines the appender [`DEFAULT_ROOT_APPENDER`] if it's not configured.
+    #[serde(default)]
+    pub preset: Option<ConfigPreset>,
+    #[serde(default)]
+    pub backtrace: BacktraceConfig,
+    #[serde(default)]
+    pub processors: Vec<ProcessorConfig>,
+    #[serde(default)]
+    pub heartbeat: Option<HeartbeatConfig>,
+    /// Whether to log the startup info after the initialization, see `naive_logger::log_startup_info`.
+    #[serde(default)]
+    pub startup_info: bool,
+    /// The messages longer than this are truncated before being appended.
+    #[serde(default)]
+    pub max_message_bytes: Option<usize>,
+    /// The records larger than this (the message, the keys and the values) are truncated
+    /// before being appended, by dropping the key-value pairs from the last one, then the message.
+    #[serde(default)]
+    pub max_record_bytes: Option<usize>,
+    /// The timezone of the timestamps of all the records.
+    #[serde(default)]
+    pub timezone: Timezone,
+    /// How the errors of the appenders (e.g. failing to write the log file) are handled.
+    #[serde(default)]
+    pub error_policy: ErrorPolicy,
+}
+
+/// The formats of the config files.
+#[derive(Clone, Copy)]
+pub(crate) enum ConfigFormat {
+    Json,
+    Toml,
+    Yaml,
+}
+
+impl ConfigFormat {
+    fn deserialize<T: DeserializeOwned>(self, s: &str) -> Result<T, String> {
+        match self {
+            ConfigFormat::Json => serde_json::from_str(s).map_err(|e| e.to_string()),
+            ConfigFormat::Toml => toml::from_str(s).map_err(|e| e.to_string()),
+            ConfigFormat::Yaml => serde_yaml::from_str(s).map_err(|e| e.to_string()),
+        }
+    }
+}
+
+/// Deserializes the config; if it has `vars`, the references to them in the other string values
+/// are resolved before the deserialization.
+pub(crate) fn parse(s: &str, format: ConfigFormat) -> Result<Config, String> {
+    let mut value: serde_json::Value = format.deserialize(s)?;
+    let vars = match value.get("vars") {
+        None => return format.deserialize(s),
+        Some(vars) => HashMap::<String, String>::deserialize(vars)
+            .map_err(|e| format!("vars: {}", e))?,
+    };
+    if let serde_json::Value::Object(map) = &mut value {
+        for (key, value) in map.iter_mut() {
+            if key != "vars" {
+                resolve_vars(value, &vars)?;
+            }
+        }
+    }
+    Config::deserialize(value).map_err(|e| e.to_string())
+}
+
+fn resolve_vars(value: &mut serde_json::Value, vars: &HashMap<String, String>) -> Result<(), String> {
+    match value {
+        serde_json::Value::String(s) => *s = util::resolve_vars(s, vars)?,
+        serde_json::Value::Array(values) => {
+            for value in values {
+                resolve_vars(value, vars)?;
+            }
+        }
+        serde_json::Value::Object(map) => {
+            for value in map.values_mut() {
+                resolve_vars(value, vars)?;
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+/// The appender of the root logger synthesized when the `root` section is omitted:
+/// a console appender with the default pattern, unless an appender of this name is configured.
+pub const DEFAULT_ROOT_APPENDER: &str = "default_console";
+
+fn default_root() -> LoggerConfig {
+    LoggerConfig {
+        target: String::new(),
+        target_matcher: LoggerTargetMatcher::Prefix,
+        level: LevelFilter::Info,
+        appenders: vec![DEFAULT_ROOT_APPENDER.to_string()],
+    }
+}
+
+/// The log file size to rotate at in the presets.
+const PRESET_MAX_FILE_SIZE: u64 = 100 * 1024 * 1024;
+/// The number of the backup files kept in the presets is this value plus one.
+const PRESET_MAX_BACKUP_INDEX: usize = 4;
+
+impl Config {
+    /// The messages at the level or severer to the console, by the default pattern,
+    /// and to the log file, as JSON lines rotated at 100 MiB with 5 backups kept.
+    pub fn console_and_rotating_file<P: AsRef<Path>>(path: P, level: LevelFilter) -> Self {
+        let mut appenders = HashMap::new();
+        let encoder = EncoderConfig::Pattern(PatternEncoderConfig::default());
+        appenders.insert("console".to_string(), console_appender(encoder));
+        appenders.insert(
+            "file".to_string(),
+            AppenderConfig::File(FileAppenderConfig {
+                common: AppenderCommonProperties {
+                    encoder: EncoderConfig::Json(JsonEncoderConfig::default()),
+                    redact: None,
+                },
+                path: path.as_ref().to_path_buf(),
+                max_file_size: PRESET_MAX_FILE_SIZE,
+                max_backup_index: PRESET_MAX_BACKUP_INDEX,
+                ..Default::default()
+            }),
+        );
+        Self::with_root(appenders, level)
+    }
+
+    /// The messages at the level or severer to stdout, as JSON lines.
+    pub fn json_stdout(level: LevelFilter) -> Self {
+        let mut appenders = HashMap::new();
+        let encoder = EncoderConfig::Json(JsonEncoderConfig::default());
+        appenders.insert("console".to_string(), console_appender(encoder));
+        Self::with_root(appenders, level)
+    }
+
+    /// Routes all the messages to all the appenders by the root logger.
+    fn with_root(appenders: HashMap<String, AppenderConfig>, level: LevelFilter) -> Self {
+        let mut names: Vec<String> = appenders.keys().cloned().collect();
+        names.sort();
+        Self {
+            vars: HashMap::new(),
+            appenders,
+            root: LoggerConfig {
+                target: String::new(),
+                target_matcher: LoggerTargetMatcher::Prefix,
+                level,
+                appenders: names,
+            },
+            loggers: vec![],
+            preset: None,
+            backtrace: BacktraceConfig::default(),
+            processors: vec![],
+            heartbeat: None,
+            startup_info: false,
+            max_message_bytes: None,
+            max_record_bytes: None,
+            timezone: Timezone::Local,
+            error_policy: ErrorPolicy::default(),
+        }
+    }
+
+    /// Adds the console appender [`DEFAULT_ROOT_APPENDER`] (as specified by the preset if any),
+    /// if it's referenced by the loggers but not configured.
+    pub(crate) fn add_default_root_appender(&mut self) {
+        let referenced = std::iter::once(&self.root)
+            .chain(&self.loggers)
+            .any(|logger| logger.appenders.iter().any(|x| x == DEFAULT_ROOT_APPENDER));
+        if referenced && !self.appenders.contains_key(DEFAULT_ROOT_APPENDER) {
+            let appender = match self.preset {
+                None => console_appender(EncoderConfig::Pattern(PatternEncoderConfig::default())),
+                Some(preset) => preset.appender(),
+            };
+            self.appenders.insert(DEFAULT_ROOT_APPENDER.to_string(), appender);
+        }
+    }
+}
+
+fn console_appender(encoder: EncoderConfig) -> AppenderConfig {
+    AppenderConfig::Console(ConsoleAppenderConfig {
+        common: AppenderCommonProperties {
+            encoder,
+            redact: None,
+        },
+        stderr_level: LevelFilter::Off,
+        ..Default::default()
+    })
+}
+
+/// The curated console outputs, which the appender [`DEFAULT_ROOT_APPENDER`] is expanded to.
+#[derive(Clone, Copy, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub enum ConfigPreset {
+    /// JSON lines to stdout, for the log collectors of the containers.
+    #[serde(rename = "k8s-json-stdout")]
+    K8sJsonStdout,
+    /// Colored short lines with the friendly values, for the local development.
+    #[serde(rename = "dev-pretty")]
+    DevPretty,
+}
+
+const DEV_PRETTY_PATTERN: &str =
+    "{colorStart}{datetime(%H:%M:%S%.3f)} {level} {target}{colorEnd} {message}{kv( )(=)}";
+
+impl ConfigPreset {
+    fn appender(self) -> AppenderConfig {
+        match self {
+            ConfigPreset::K8sJsonStdout => {
+                console_appender(EncoderConfig::Json(JsonEncoderConfig::default()))
+            }
+            ConfigPreset::DevPretty => {
+                console_appender(EncoderConfig::Pattern(PatternEncoderConfig {
+                    pattern: DEV_PRETTY_PATTERN.to_string(),
+                    friendly_values: true,
+                    ..Default::default()
+                }))
+            }
+        }
+    }
+}
+
+/// Controls whether to capture the backtrace for the error records.
+#[derive(Clone, Copy, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub enum BacktraceConfig {
+    #[default]
+    #[serde(rename = "off")]
+    Off,
+    /// Captured if enabled by `RUST_LIB_BACKTRACE` or `RUST_BACKTRACE`.
+    #[serde(rename = "env")]
+    Env,
+    #[serde(rename = "always")]
+    Always,
+}
+
+/// How the errors of the appenders are handled; the records involved are lost in any case.
+#[derive(Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub enum ErrorPolicy {
+    #[serde(rename = "ignore")]
+    Ignore,
+    /// Printed to stderr, as the appender may be the one the logs would be written to.
+    #[default]
+    #[serde(rename = "report_to_stderr")]
+    ReportToStderr,
+    /// Panics the logging thread appending the next record to the appender (or the failed one, if not `async`).
+    #[serde(rename = "panic")]
+    Panic,
+}
+
+/// The timezone of the timestamps, deserialized from `utc`, `local` or an IANA name like `Asia/Shanghai`;
+/// the IANA names require the `tz` feature.
+#[derive(Clone, Copy, Default, Deserialize)]
+#[serde(try_from = "String")]
+pub enum Timezone {
+    Utc,
+    #[default]
+    Local,
+    #[cfg(feature = "tz")]
+    Named(chrono_tz::Tz),
+}
+
+impl TryFrom<String> for Timezone {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        match s.as_str() {
+            "utc" => Ok(Timezone::Utc),
+            "local" => Ok(Timezone::Local),
+            #[cfg(feature = "tz")]
+            _ => s
+                .parse()
+                .map(Timezone::Named)
+                .map_err(|_| format!("unknown timezone '{}'", s)),
+            #[cfg(not(feature = "tz"))]
+            _ => Err(format!("timezone '{}' requires the `tz` feature", s)),
+        }
+    }
+}
+
+/// Logs a message periodically with the uptime and the record counts,
+/// so that a quiet service can be distinguished from a dead one.
+#[derive(Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct HeartbeatConfig {
+    pub interval_secs: u64,
+    #[serde(default = "default_heartbeat_level")]
+    pub level: Level,
+    #[serde(default = "default_heartbeat_target")]
+    pub target: String,
+    #[serde(default = "default_heartbeat_message")]
+    pub message: String,
+}
+
+fn default_heartbeat_level() -> Level {
+    Level::Info
+}
+
+fn default_heartbeat_target() -> String {
+    "naive_logger::heartbeat".to_string()
+}
+
+fn default_heartbeat_message() -> String {
+    "heartbeat".to_string()
 }
 
 #[cfg(test)]
@@ -60,9 +376,104 @@ mod tests {
                     "target_matcher": "prefix_inverse",
                     "level": "warn"
                 }
-            ]
+            ],
+            "backtrace": "env",
+            "error_policy": "panic",
+            "heartbeat": {
+                "interval_secs": 60
+            },
+            "startup_info": true,
+            "max_message_bytes": 65536,
+            "timezone": "utc"
         }
         "#;
-        let _config: Config = serde_json::from_str(config).unwrap();
+        let config: Config = serde_json::from_str(config).unwrap();
+        assert!(matches!(config.backtrace, BacktraceConfig::Env));
+        assert!(matches!(config.error_policy, ErrorPolicy::Panic));
+        assert!(
+            matches!(config.heartbeat, Some(x) if x.interval_secs == 60 && x.level == Level::Info && x.message == "heartbeat")
+        );
+        assert!(config.startup_info);
+        assert_eq!(config.max_message_bytes, Some(65536));
+        assert_eq!(config.max_record_bytes, None);
+        assert!(matches!(config.timezone, Timezone::Utc));
+
+        let config = serde_json::from_str::<Config>(r#"{"timezone": "Asia/Shanghai"}"#);
+        #[cfg(feature = "tz")]
+        assert!(matches!(config.unwrap().timezone, Timezone::Named(chrono_tz::Asia::Shanghai)));
+        #[cfg(not(feature = "tz"))]
+        assert!(config.is_err());
+        assert!(serde_json::from_str::<Config>(r#"{"timezone": "Mars/Olympus"}"#).is_err());
+    }
+
+    #[test]
+    fn test_parse_vars() {
+        let config = r#"
+        [vars]
+        log_dir = "/var/log/myapp"
+
+        [appenders.main]
+        kind = "file"
+        path = "${vars.log_dir}/main.log"
+        encoder = { kind = "json" }
+
+        [appenders.audit]
+        kind = "file"
+        path = "$${vars.log_dir}/audit.log"
+        encoder = { kind = "json" }
+        "#;
+        let config = parse(config, ConfigFormat::Toml).unwrap();
+        assert!(matches!(&config.appenders["main"], AppenderConfig::File(x) if x.path == Path::new("/var/log/myapp/main.log")));
+        assert!(matches!(&config.appenders["audit"], AppenderConfig::File(x) if x.path == Path::new("${vars.log_dir}/audit.log")));
+
+        let config = r#"{"vars": {}, "appenders": {"main": {"kind": "file", "path": "${vars.x}", "encoder": {"kind": "json"}}}}"#;
+        let result = parse(config, ConfigFormat::Json);
+        assert!(matches!(result, Err(e) if e.contains("unknown variable 'x'")));
+    }
+
+    #[test]
+    fn test_default_root() {
+        let config = r#"
+        [[loggers]]
+        target = "myapp::"
+        level = "debug"
+        "#;
+        let mut config: Config = toml::from_str(config).unwrap();
+        assert_eq!(config.root.level, LevelFilter::Info);
+        config.add_default_root_appender();
+        assert!(matches!(config.appenders[DEFAULT_ROOT_APPENDER], AppenderConfig::Console(_)));
+
+        let config = r#"{"appenders": {"default_console": {"kind": "console", "encoder": {"kind": "json"}}}}"#;
+        let mut config: Config = serde_json::from_str(config).unwrap();
+        config.add_default_root_appender();
+        assert!(matches!(&config.appenders[DEFAULT_ROOT_APPENDER], AppenderConfig::Console(x) if matches!(x.common.encoder, EncoderConfig::Json(_))));
+
+        let mut config: Config = toml::from_str(r#"preset = "k8s-json-stdout""#).unwrap();
+        config.add_default_root_appender();
+        assert!(matches!(&config.appenders[DEFAULT_ROOT_APPENDER], AppenderConfig::Console(x) if matches!(x.common.encoder, EncoderConfig::Json(_))));
+
+        let config = r#"
+        preset = "dev-pretty"
+        [root]
+        level = "debug"
+        appenders = ["default_console"]
+        "#;
+        let mut config: Config = toml::from_str(config).unwrap();
+        config.add_default_root_appender();
+        assert!(matches!(&config.appenders[DEFAULT_ROOT_APPENDER], AppenderConfig::Console(x) if matches!(&x.common.encoder, EncoderConfig::Pattern(x) if x.friendly_values)));
+    }
+
+    #[test]
+    fn test_presets() {
+        let config = Config::console_and_rotating_file("logs/app.log", LevelFilter::Debug);
+        assert_eq!(config.root.level, LevelFilter::Debug);
+        assert_eq!(config.root.appenders, vec!["console", "file"]);
+        assert!(matches!(&config.appenders["file"], AppenderConfig::File(x) if x.max_file_size == PRESET_MAX_FILE_SIZE));
+
+        let config = Config::json_stdout(LevelFilter::Info);
+        assert_eq!(config.root.appenders, vec!["console"]);
+        assert!(
+            matches!(&config.appenders["console"], AppenderConfig::Console(x) if matches!(x.common.encoder, EncoderConfig::Json(_)))
+        );
     }
 }
@@ -0,0 +1,114 @@
+use indexmap::IndexMap;
+use serde::Deserialize;
+
+fn default_hostname_key() -> String {
+    "hostname".to_string()
+}
+
+fn default_pid_key() -> String {
+    "pid".to_string()
+}
+
+fn default_redact_mask() -> String {
+    "***".to_string()
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+#[serde(tag = "kind")]
+pub enum ProcessorConfig {
+    #[serde(rename = "hostname")]
+    Hostname(HostnameProcessorConfig),
+    #[serde(rename = "pid")]
+    Pid(PidProcessorConfig),
+    #[serde(rename = "env")]
+    Env(EnvProcessorConfig),
+    #[serde(rename = "remove")]
+    Remove(RemoveProcessorConfig),
+    #[serde(rename = "redact")]
+    Redact(RedactConfig),
+    #[serde(rename = "custom")]
+    Custom(CustomProcessorConfig),
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct HostnameProcessorConfig {
+    #[serde(default = "default_hostname_key")]
+    pub key: String,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PidProcessorConfig {
+    #[serde(default = "default_pid_key")]
+    pub key: String,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct EnvProcessorConfig {
+    /// The map from the keys to the names of the environment variables.
+    pub vars: IndexMap<String, String>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RemoveProcessorConfig {
+    pub keys: Vec<String>,
+}
+
+/// Masks the values of the key-value pairs, by a deny-list and/or an allow-list of the keys.
+/// The keys are matched case-insensitively.
+#[derive(Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RedactConfig {
+    /// The keys whose values are always masked.
+    #[serde(default)]
+    pub keys: Vec<String>,
+    /// If specified, the values of the keys not in the list are masked too.
+    #[serde(default)]
+    pub allow_keys: Option<Vec<String>>,
+    #[serde(default = "default_redact_mask")]
+    pub mask: String,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CustomProcessorConfig {
+    pub name: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_deserialize() {
+        let s = r#"{"kind": "hostname"}"#;
+        let config: ProcessorConfig = serde_json::from_str(s).unwrap();
+        assert!(matches!(config, ProcessorConfig::Hostname(x) if x.key == "hostname"));
+
+        let s = r#"{"kind": "pid", "key": "process_id"}"#;
+        let config: ProcessorConfig = serde_json::from_str(s).unwrap();
+        assert!(matches!(config, ProcessorConfig::Pid(x) if x.key == "process_id"));
+
+        let s = r#"{"kind": "env", "vars": {"pod": "POD_NAME"}}"#;
+        let config: ProcessorConfig = serde_json::from_str(s).unwrap();
+        assert!(matches!(config, ProcessorConfig::Env(x) if x.vars["pod"] == "POD_NAME"));
+
+        let s = r#"{"kind": "remove", "keys": ["password"]}"#;
+        let config: ProcessorConfig = serde_json::from_str(s).unwrap();
+        assert!(matches!(config, ProcessorConfig::Remove(x) if x.keys == vec!["password"]));
+
+        let s = r#"{"kind": "redact", "keys": ["password"], "allow_keys": ["user"]}"#;
+        let config: ProcessorConfig = serde_json::from_str(s).unwrap();
+        assert!(
+            matches!(config, ProcessorConfig::Redact(x) if x.keys == vec!["password"] && x.allow_keys == Some(vec!["user".to_string()]) && x.mask == "***")
+        );
+
+        let s = r#"{"kind": "custom", "name": "my_processor"}"#;
+        let config: ProcessorConfig = serde_json::from_str(s).unwrap();
+        assert!(matches!(config, ProcessorConfig::Custom(x) if x.name == "my_processor"));
+    }
+}
@@ -1,7 +1,10 @@
+use std::collections::HashMap;
+use std::ffi::OsString;
 use std::fmt::Formatter;
 
+use indexmap::IndexMap;
 use serde::de::{Error, Unexpected, Visitor as VisitorTrait};
-use serde::Deserializer;
+use serde::{Deserialize, Deserializer};
 
 pub fn deserialize_file_size<'de, D: Deserializer<'de>>(de: D) -> Result<u64, D::Error> {
     struct Visitor;
@@ -53,13 +56,106 @@ pub fn deserialize_file_size<'de, D: Deserializer<'de>>(de: D) -> Result<u64, D:
     de.deserialize_any(Visitor)
 }
 
-pub fn deserialize_str_with_env_var<'de, D: Deserializer<'de>, T: From<String>>(
+/// Deserializes the duration in seconds, from a number of seconds or a string like `30s`, `10m`, `1h` or `1d`.
+pub fn deserialize_duration_secs<'de, D: Deserializer<'de>>(de: D) -> Result<u64, D::Error> {
+    struct Visitor;
+    impl<'de> VisitorTrait<'de> for Visitor {
+        type Value = u64;
+
+        fn expecting(&self, formatter: &mut Formatter) -> std::fmt::Result {
+            write!(formatter, "a positive number followed by an optional unit (s/m/h/d)")
+        }
+
+        fn visit_i64<E>(self, v: i64) -> Result<Self::Value, E>
+        where
+            E: Error,
+        {
+            if v < 0 {
+                return Err(Error::invalid_value(Unexpected::Signed(v), &self));
+            }
+            Ok(v as _)
+        }
+
+        fn visit_u64<E>(self, v: u64) -> Result<Self::Value, E>
+        where
+            E: Error,
+        {
+            Ok(v)
+        }
+
+        fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
+        where
+            E: Error,
+        {
+            let (n, unit) = match v.char_indices().last() {
+                Some((i, 's')) => (&v[..i], 1),
+                Some((i, 'm')) => (&v[..i], 60),
+                Some((i, 'h')) => (&v[..i], 60 * 60),
+                Some((i, 'd')) => (&v[..i], 24 * 60 * 60),
+                _ => (v, 1),
+            };
+            n.parse::<u64>()
+                .map_err(Error::custom)?
+                .checked_mul(unit)
+                .ok_or_else(|| Error::custom(format!("duration '{}' is too large", v)))
+        }
+    }
+    de.deserialize_any(Visitor)
+}
+
+/// Deserializes the Unix permission bits, from an octal string like `"0640"` or `"640"`,
+/// or a number taken as it is (e.g. `0o640` in TOML).
+pub fn deserialize_opt_mode<'de, D: Deserializer<'de>>(de: D) -> Result<Option<u32>, D::Error> {
+    struct Visitor;
+    impl<'de> VisitorTrait<'de> for Visitor {
+        type Value = u32;
+
+        fn expecting(&self, formatter: &mut Formatter) -> std::fmt::Result {
+            write!(formatter, "an octal string of the permission bits like \"0640\"")
+        }
+
+        fn visit_i64<E>(self, v: i64) -> Result<Self::Value, E>
+        where
+            E: Error,
+        {
+            if !(0..=0o7777).contains(&v) {
+                return Err(Error::invalid_value(Unexpected::Signed(v), &self));
+            }
+            Ok(v as _)
+        }
+
+        fn visit_u64<E>(self, v: u64) -> Result<Self::Value, E>
+        where
+            E: Error,
+        {
+            if v > 0o7777 {
+                return Err(Error::invalid_value(Unexpected::Unsigned(v), &self));
+            }
+            Ok(v as _)
+        }
+
+        fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
+        where
+            E: Error,
+        {
+            match u32::from_str_radix(v.strip_prefix("0o").unwrap_or(v), 8) {
+                Ok(mode) if mode <= 0o7777 => Ok(mode),
+                _ => Err(Error::invalid_value(Unexpected::Str(v), &self)),
+            }
+        }
+    }
+    de.deserialize_any(Visitor).map(Some)
+}
+
+/// Deserializes the string with the environment variables substituted; the result is an `OsString`,
+/// as the variables may not be valid UTF-8.
+pub fn deserialize_str_with_env_var<'de, D: Deserializer<'de>, T: From<OsString>>(
     de: D,
 ) -> Result<T, D::Error> {
     struct Visitor<T> {
         _phantom: std::marker::PhantomData<T>,
     }
-    impl<'de, T: From<String>> VisitorTrait<'de> for Visitor<T> {
+    impl<'de, T: From<OsString>> VisitorTrait<'de> for Visitor<T> {
         type Value = T;
 
         fn expecting(&self, formatter: &mut Formatter) -> std::fmt::Result {
@@ -70,26 +166,33 @@ pub fn deserialize_str_with_env_var<'de, D: Deserializer<'de>, T: From<String>>(
         where
             E: Error
         {
+            let expanded;
+            let s = if cfg!(windows) {
+                expanded = expand_percent_vars(s);
+                expanded.as_str()
+            } else {
+                s
+            };
             enum State {
                 Normal,
                 DollarMet,
                 LeftBracketMet,
             }
             let mut state = State::Normal;
-            let mut result = String::new();
+            let mut result = OsString::new();
             let mut var_name = String::new();
             for char in s.chars() {
                 match state {
                     State::Normal => {
                         if char != '$' {
-                            result.push(char);
+                            result.push(char.encode_utf8(&mut [0; 4]));
                         } else {
                             state = State::DollarMet;
                         }
                     }
                     State::DollarMet => {
                         if char == '$' {
-                            result.push('$');
+                            result.push("$");
                             state = State::Normal;
                         } else if char == '{' {
                             state = State::LeftBracketMet;
@@ -101,10 +204,16 @@ pub fn deserialize_str_with_env_var<'de, D: Deserializer<'de>, T: From<String>>(
                         if char != '}' {
                             var_name.push(char);
                         } else {
-                            let value = std::env::var(&var_name).map_err(|_| {
-                                Error::custom(format!("environment variable `{}` not found", var_name))
-                            })?;
-                            result.push_str(&value);
+                            // `${VAR:-default}` falls back to the default if the variable is unset or empty
+                            let value = match var_name.split_once(":-") {
+                                Some((name, default)) => std::env::var_os(name)
+                                    .filter(|x| !x.is_empty())
+                                    .unwrap_or_else(|| default.into()),
+                                None => std::env::var_os(&var_name).ok_or_else(|| {
+                                    Error::custom(format!("environment variable `{}` not found", var_name))
+                                })?,
+                            };
+                            result.push(&value);
                             var_name.clear();
                             state = State::Normal;
                         }
@@ -120,6 +229,89 @@ pub fn deserialize_str_with_env_var<'de, D: Deserializer<'de>, T: From<String>>(
     de.deserialize_str(visitor)
 }
 
+pub fn deserialize_opt_str_with_env_var<'de, D: Deserializer<'de>, T: From<OsString>>(
+    de: D,
+) -> Result<Option<T>, D::Error> {
+    deserialize_str_with_env_var(de).map(Some)
+}
+
+/// Deserializes the map of strings, with the environment variables in the values substituted
+/// the same as [`deserialize_str_with_env_var`].
+pub fn deserialize_map_with_env_var<'de, D: Deserializer<'de>>(de: D) -> Result<IndexMap<String, String>, D::Error> {
+    let map = IndexMap::<String, String>::deserialize(de)?;
+    map.into_iter()
+        .map(|(key, value)| {
+            let value: OsString = deserialize_str_with_env_var(
+                serde::de::value::StrDeserializer::<serde::de::value::Error>::new(&value),
+            )
+            .map_err(|e| D::Error::custom(format!("invalid value of '{}': {}", key, e)))?;
+            let value = value
+                .into_string()
+                .map_err(|_| D::Error::custom(format!("value of '{}' should be valid UTF-8", key)))?;
+            Ok((key, value))
+        })
+        .collect()
+}
+
+/// Expands the Windows-style environment variables `%VAR%`; the unset ones are kept as they are,
+/// like `cmd` does. The `$` in the values are escaped for the following `${}` substitution.
+fn expand_percent_vars(s: &str) -> String {
+    let mut result = String::new();
+    let mut rest = s;
+    while let Some(start) = rest.find('%') {
+        result.push_str(&rest[..start]);
+        let Some(len) = rest[start + 1..].find('%') else {
+            rest = &rest[start..];
+            break;
+        };
+        let name = &rest[start + 1..start + 1 + len];
+        match std::env::var(name) {
+            Ok(value) if !name.is_empty() => {
+                result.push_str(&value.replace('$', "$$"));
+                rest = &rest[start + len + 2..];
+            }
+            _ => {
+                // keep the first '%', the second one may start another reference
+                result.push('%');
+                rest = &rest[start + 1..];
+            }
+        }
+    }
+    result.push_str(rest);
+    result
+}
+
+/// Replaces the references `${vars.<name>}` in the string by the variables;
+/// the other references and the escaped `$$` are kept for the environment variable substitution.
+pub fn resolve_vars(s: &str, vars: &HashMap<String, String>) -> Result<String, String> {
+    const PREFIX: &str = "${vars.";
+    let mut result = String::new();
+    let mut rest = s;
+    while let Some(i) = rest.find('$') {
+        result.push_str(&rest[..i]);
+        rest = &rest[i..];
+        if rest.starts_with("$$") {
+            result.push_str("$$");
+            rest = &rest[2..];
+        } else if let Some(reference) = rest.strip_prefix(PREFIX) {
+            let end = reference
+                .find('}')
+                .ok_or_else(|| format!("unclosed reference in '{}'", s))?;
+            let name = &reference[..end];
+            let value = vars
+                .get(name)
+                .ok_or_else(|| format!("unknown variable '{}'", name))?;
+            result.push_str(value);
+            rest = &reference[end + 1..];
+        } else {
+            result.push('$');
+            rest = &rest[1..];
+        }
+    }
+    result.push_str(rest);
+    Ok(result)
+}
+
 #[cfg(test)]
 mod tests {
     use serde::Deserialize;
@@ -166,16 +358,105 @@ mod tests {
         let result: Result<Config, _> = serde_json::from_str(config);
         assert!(result.is_err());
     }
-    
+
+    #[test]
+    fn test_deserialize_duration_secs() {
+        #[derive(Deserialize)]
+        struct Config {
+            #[serde(deserialize_with = "super::deserialize_duration_secs")]
+            duration: u64,
+        }
+
+        let cases = [
+            (r#"30"#, 30),
+            (r#""30""#, 30),
+            (r#""30s""#, 30),
+            (r#""10m""#, 600),
+            (r#""1h""#, 3600),
+            (r#""7d""#, 7 * 86400),
+        ];
+        for (input, expected) in cases {
+            let config = format!(r#"{{"duration": {}}}"#, input);
+            let config: Config = serde_json::from_str(&config).unwrap();
+            assert_eq!(config.duration, expected);
+        }
+
+        for input in [r#"-1"#, r#""1w""#, r#""h""#, r#""18446744073709551615d""#] {
+            let config = format!(r#"{{"duration": {}}}"#, input);
+            assert!(serde_json::from_str::<Config>(&config).is_err());
+        }
+
+        let result = serde_json::from_str::<Config>(r#"{"duration": "213503982334602d"}"#);
+        assert!(result.err().unwrap().to_string().starts_with("duration '213503982334602d' is too large"));
+    }
+
+    #[test]
+    fn test_deserialize_opt_mode() {
+        #[derive(Deserialize)]
+        struct Config {
+            #[serde(default, deserialize_with = "super::deserialize_opt_mode")]
+            mode: Option<u32>,
+        }
+
+        let cases = [(r#""0640""#, 0o640), (r#""750""#, 0o750), (r#""0o600""#, 0o600), (r#"416"#, 0o640)];
+        for (input, expected) in cases {
+            let config = format!(r#"{{"mode": {}}}"#, input);
+            let config: Config = serde_json::from_str(&config).unwrap();
+            assert_eq!(config.mode, Some(expected));
+        }
+        assert_eq!(serde_json::from_str::<Config>("{}").unwrap().mode, None);
+
+        for input in [r#""0648""#, r#""17777""#, r#"-1"#] {
+            let config = format!(r#"{{"mode": {}}}"#, input);
+            assert!(serde_json::from_str::<Config>(&config).is_err());
+        }
+    }
+
+    #[test]
+    fn test_expand_percent_vars() {
+        let name = env!("CARGO_PKG_NAME");
+        let cases = [
+            ("%CARGO_PKG_NAME%\\app.log", format!("{}\\app.log", name)),
+            ("50%%__TEST_UNSET_VAR%%CARGO_PKG_NAME%", format!("50%%__TEST_UNSET_VAR%{}", name)),
+            ("100%", "100%".to_string()),
+        ];
+        for (input, expected) in cases {
+            assert_eq!(super::expand_percent_vars(input), expected);
+        }
+    }
+
     #[test]
     fn test_deserialize_str_with_env_var() {
         #[derive(Deserialize)]
         struct Config {
             #[serde(deserialize_with = "super::deserialize_str_with_env_var")]
-            name: String,
+            name: std::ffi::OsString,
         }
         let config = r#"{"name": "$$${CARGO_PKG_NAME}${CARGO_PKG_VERSION}$$"}"#;
         let config: Config = serde_json::from_str(config).unwrap();
-        assert_eq!(config.name, format!("${}{}$", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION")));
+        assert_eq!(config.name, *format!("${}{}$", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION")));
+
+        let config = r#"{"name": "${__TEST_UNSET_VAR:-/var/log}/${CARGO_PKG_NAME:-x}"}"#;
+        let config: Config = serde_json::from_str(config).unwrap();
+        assert_eq!(config.name, *format!("/var/log/{}", env!("CARGO_PKG_NAME")));
+
+        let config = r#"{"name": "${__TEST_UNSET_VAR}"}"#;
+        assert!(serde_json::from_str::<Config>(config).is_err());
+    }
+
+    #[test]
+    fn test_deserialize_map_with_env_var() {
+        #[derive(Deserialize)]
+        struct Config {
+            #[serde(deserialize_with = "super::deserialize_map_with_env_var")]
+            fields: indexmap::IndexMap<String, String>,
+        }
+        let config = r#"{"fields": {"service": "${CARGO_PKG_NAME}", "env": "${__TEST_UNSET_VAR:-dev}"}}"#;
+        let config: Config = serde_json::from_str(config).unwrap();
+        assert_eq!(config.fields["service"], env!("CARGO_PKG_NAME"));
+        assert_eq!(config.fields["env"], "dev");
+
+        let config = r#"{"fields": {"env": "${__TEST_UNSET_VAR}"}}"#;
+        assert!(serde_json::from_str::<Config>(config).is_err());
     }
 }
@@ -0,0 +1,369 @@
+//! The mapped diagnostic context of the current thread.
+//!
+//! The entries in the context are attached to every record logged by the current thread,
+//! as if they were passed as the key-value pairs of the record.
+//! If the record has a key-value pair with the same key, the one of the record wins.
+//!
+//! The context is organized as a stack of scopes. The entries are inserted into the innermost scope,
+//! and shadow the entries of the same keys in the outer scopes until the scope is popped.
+//!
+//! ```
+//! naive_logger::context::insert("request_id", 42);
+//! log::info!("handling request"); // with `request_id=42`
+//! {
+//!     let _scope = naive_logger::context::push_scope();
+//!     naive_logger::context::insert("operation", "query");
+//!     log::info!("querying"); // with `request_id=42|operation="query"`
+//! }
+//! naive_logger::context::remove("request_id");
+//! ```
+//!
+//! The context doesn't follow the work sent to the other threads automatically;
+//! capture it with [`Context::current`] and attach it in the other thread,
+//! or spawn the thread with [`spawn_with_context`].
+
+use std::cell::{Cell, RefCell};
+use std::marker::PhantomData;
+
+use indexmap::IndexMap;
+
+type Scope = IndexMap<String, serde_json::Value>;
+
+thread_local! {
+    /// The scopes with their ids, which identify them for the guards, as the depths are reused after popped;
+    /// the outermost one is never popped.
+    static CONTEXT: RefCell<Vec<(u64, Scope)>> = RefCell::new(vec![(0, Scope::new())]);
+    static NEXT_SCOPE_ID: Cell<u64> = const { Cell::new(1) };
+}
+
+/// Inserts an entry into the innermost scope of the current thread,
+/// replacing the previous value of the same key in that scope.
+pub fn insert<K: Into<String>, V: Into<serde_json::Value>>(key: K, value: V) {
+    CONTEXT.with_borrow_mut(|context| {
+        innermost(context).insert(key.into(), value.into());
+    });
+}
+
+/// Removes an entry from the innermost scope containing the key, returning its value.
+pub fn remove(key: &str) -> Option<serde_json::Value> {
+    CONTEXT.with_borrow_mut(|context| {
+        context
+            .iter_mut()
+            .rev()
+            .find_map(|(_, scope)| scope.shift_remove(key))
+    })
+}
+
+/// Removes all the entries from every scope of the current thread.
+pub fn clear() {
+    CONTEXT.with_borrow_mut(|context| context.iter_mut().for_each(|(_, scope)| scope.clear()));
+}
+
+/// Inserts an entry into the innermost scope of the current thread,
+/// which will be restored to the previous value when the returned guard is dropped.
+#[must_use]
+pub fn insert_scoped<K: Into<String>, V: Into<serde_json::Value>>(key: K, value: V) -> ContextGuard {
+    let key = key.into();
+    let (previous, scope_id) = CONTEXT.with_borrow_mut(|context| {
+        (innermost(context).insert(key.clone(), value.into()), context.last().unwrap().0)
+    });
+    ContextGuard {
+        key,
+        previous,
+        scope_id,
+        _not_send: PhantomData,
+    }
+}
+
+/// Pushes a new scope, which will be popped with all its entries when the returned guard is dropped.
+#[must_use]
+pub fn push_scope() -> ScopeGuard {
+    let id = NEXT_SCOPE_ID.replace(NEXT_SCOPE_ID.get() + 1);
+    CONTEXT.with_borrow_mut(|context| context.push((id, Scope::new())));
+    ScopeGuard {
+        id,
+        _not_send: PhantomData,
+    }
+}
+
+fn innermost(context: &mut [(u64, Scope)]) -> &mut Scope {
+    &mut context.last_mut().unwrap().1
+}
+
+/// Pops the scope when dropped; see [`push_scope`].
+pub struct ScopeGuard {
+    id: u64,
+    _not_send: PhantomData<*const ()>,
+}
+
+impl Drop for ScopeGuard {
+    fn drop(&mut self) {
+        // the inner scopes are popped too, if their guards are leaked or dropped out of order;
+        // nothing to pop if the scope is already popped with an outer one
+        CONTEXT.with_borrow_mut(|context| {
+            if let Some(i) = context.iter().position(|(id, _)| *id == self.id) {
+                context.truncate(i);
+            }
+        });
+    }
+}
+
+/// Restores the context entry when dropped; see [`insert_scoped`].
+pub struct ContextGuard {
+    key: String,
+    previous: Option<serde_json::Value>,
+    /// The id of the scope the entry is inserted into.
+    scope_id: u64,
+    _not_send: PhantomData<*const ()>,
+}
+
+impl Drop for ContextGuard {
+    fn drop(&mut self) {
+        let key = std::mem::take(&mut self.key);
+        let previous = self.previous.take();
+        CONTEXT.with_borrow_mut(|context| {
+            // nothing to restore if the scope is already popped
+            let Some((_, scope)) = context.iter_mut().find(|(id, _)| *id == self.scope_id) else {
+                return;
+            };
+            match previous {
+                None => {
+                    scope.shift_remove(&key);
+                }
+                Some(value) => {
+                    scope.insert(key, value);
+                }
+            }
+        });
+    }
+}
+
+/// A snapshot of the context entries, which can be attached to another thread.
+#[derive(Clone, Default)]
+pub struct Context {
+    entries: Scope,
+}
+
+impl Context {
+    /// Captures the entries visible in the current thread.
+    pub fn current() -> Self {
+        CONTEXT.with_borrow(|context| {
+            let mut entries = Scope::new();
+            for (_, scope) in context {
+                for (key, value) in scope {
+                    entries.insert(key.clone(), value.clone());
+                }
+            }
+            Self { entries }
+        })
+    }
+
+    /// Pushes a new scope with the captured entries into the context of the current thread.
+    #[must_use]
+    pub fn attach(&self) -> ScopeGuard {
+        let guard = push_scope();
+        CONTEXT.with_borrow_mut(|context| innermost(context).clone_from(&self.entries));
+        guard
+    }
+
+    /// Wraps the closure to run it with the captured entries attached,
+    /// which is handy for the tasks sent to thread pools.
+    pub fn wrap<F: FnOnce() -> T, T>(self, f: F) -> impl FnOnce() -> T {
+        move || {
+            let _guard = self.attach();
+            f()
+        }
+    }
+}
+
+/// Spawns a thread inheriting the context entries of the current thread.
+pub fn spawn_with_context<F, T>(f: F) -> std::thread::JoinHandle<T>
+where
+    F: FnOnce() -> T + Send + 'static,
+    T: Send + 'static,
+{
+    std::thread::spawn(Context::current().wrap(f))
+}
+
+/// Merges the context entries into the key-value pairs of a record.
+pub(crate) fn merge_into(kvs: &mut Vec<(String, serde_json::Value)>) {
+    if CONTEXT.with_borrow(|context| context.iter().all(|(_, scope)| scope.is_empty())) {
+        return;
+    }
+    let mut merged: Vec<_> = Context::current()
+        .entries
+        .into_iter()
+        .filter(|(key, _)| kvs.iter().all(|(x, _)| x != key))
+        .collect();
+    merged.append(kvs);
+    *kvs = merged;
+}
+
+#[cfg(test)]
+mod tests {
+    fn snapshot() -> Vec<(String, serde_json::Value)> {
+        let mut kvs = vec![];
+        super::merge_into(&mut kvs);
+        kvs
+    }
+
+    #[test]
+    fn test_insert_and_remove() {
+        super::insert("a", 1);
+        super::insert("b", "x");
+        super::insert("a", 2);
+        assert_eq!(
+            snapshot(),
+            vec![("a".to_string(), 2.into()), ("b".to_string(), "x".into())]
+        );
+        assert_eq!(super::remove("a"), Some(2.into()));
+        assert_eq!(snapshot(), vec![("b".to_string(), "x".into())]);
+        super::clear();
+        assert!(snapshot().is_empty());
+    }
+
+    #[test]
+    fn test_insert_scoped() {
+        super::insert("a", 1);
+        {
+            let _guard1 = super::insert_scoped("a", 2);
+            let _guard2 = super::insert_scoped("b", 3);
+            assert_eq!(
+                snapshot(),
+                vec![("a".to_string(), 2.into()), ("b".to_string(), 3.into())]
+            );
+        }
+        assert_eq!(snapshot(), vec![("a".to_string(), 1.into())]);
+        super::clear();
+    }
+
+    #[test]
+    fn test_push_scope() {
+        super::insert("a", 1);
+        super::insert("b", 2);
+        {
+            let _scope = super::push_scope();
+            super::insert("b", 3);
+            super::insert("c", 4);
+            assert_eq!(
+                snapshot(),
+                vec![
+                    ("a".to_string(), 1.into()),
+                    ("b".to_string(), 3.into()),
+                    ("c".to_string(), 4.into()),
+                ]
+            );
+            {
+                let _scope = super::push_scope();
+                super::insert("a", 5);
+                assert_eq!(super::remove("c"), Some(4.into()));
+                assert_eq!(
+                    snapshot(),
+                    vec![
+                        ("a".to_string(), 5.into()),
+                        ("b".to_string(), 3.into()),
+                    ]
+                );
+            }
+            assert_eq!(
+                snapshot(),
+                vec![("a".to_string(), 1.into()), ("b".to_string(), 3.into())]
+            );
+        }
+        assert_eq!(
+            snapshot(),
+            vec![("a".to_string(), 1.into()), ("b".to_string(), 2.into())]
+        );
+        super::clear();
+    }
+
+    #[test]
+    fn test_insert_scoped_across_scopes() {
+        super::insert("a", 1);
+        let guard = super::insert_scoped("a", 2);
+        let scope = super::push_scope();
+        super::insert("a", 3);
+        // restored into the scope inserted into, rather than the innermost one
+        drop(guard);
+        assert_eq!(snapshot(), vec![("a".to_string(), 3.into())]);
+        drop(scope);
+        assert_eq!(snapshot(), vec![("a".to_string(), 1.into())]);
+
+        // the popped scope is not restored
+        let scope = super::push_scope();
+        let guard = super::insert_scoped("b", 4);
+        drop(scope);
+        drop(guard);
+        assert_eq!(snapshot(), vec![("a".to_string(), 1.into())]);
+        super::clear();
+    }
+
+    #[test]
+    fn test_guards_dropped_out_of_order() {
+        // the scope of the entry is popped and another one is pushed at the same depth
+        let scope = super::push_scope();
+        let guard = super::insert_scoped("a", 1);
+        drop(scope);
+        let _scope = super::push_scope();
+        super::insert("a", 2);
+        drop(guard);
+        assert_eq!(snapshot(), vec![("a".to_string(), 2.into())]);
+
+        // the scope is popped with an outer one, then another one is pushed at the same depth
+        let outer = super::push_scope();
+        let inner = super::push_scope();
+        drop(outer);
+        let _other = super::push_scope();
+        let _scope = super::push_scope();
+        super::insert("b", 3);
+        drop(inner);
+        assert_eq!(
+            snapshot(),
+            vec![("a".to_string(), 2.into()), ("b".to_string(), 3.into())]
+        );
+        super::clear();
+    }
+
+    #[test]
+    fn test_spawn_with_context() {
+        super::insert("a", 1);
+        let _scope = super::push_scope();
+        super::insert("b", 2);
+        let kvs = super::spawn_with_context(|| {
+            super::insert("c", 3);
+            snapshot()
+        })
+        .join()
+        .unwrap();
+        assert_eq!(
+            kvs,
+            vec![
+                ("a".to_string(), 1.into()),
+                ("b".to_string(), 2.into()),
+                ("c".to_string(), 3.into()),
+            ]
+        );
+        assert_eq!(
+            snapshot(),
+            vec![("a".to_string(), 1.into()), ("b".to_string(), 2.into())]
+        );
+        super::clear();
+    }
+
+    #[test]
+    fn test_merge_into() {
+        super::insert("a", 1);
+        super::insert("b", 2);
+        let mut kvs = vec![("b".to_string(), 3.into()), ("c".to_string(), 4.into())];
+        super::merge_into(&mut kvs);
+        assert_eq!(
+            kvs,
+            vec![
+                ("a".to_string(), 1.into()),
+                ("b".to_string(), 3.into()),
+                ("c".to_string(), 4.into()),
+            ]
+        );
+        super::clear();
+    }
+}
@@ -0,0 +1,86 @@
+//! Accounting of the records dropped by the pipeline, e.g. in the batch when an appender panics.
+//!
+//! The counts are reported periodically as `warn` records through the logger,
+//! so that the data loss is visible rather than silent.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Once, OnceLock};
+use std::time::Duration;
+
+use log::{Level, Record};
+
+use crate::sync::Mutex;
+
+/// The interval to report the dropped records.
+const REPORT_INTERVAL: Duration = Duration::from_secs(10);
+
+/// The target of the reports.
+const REPORT_TARGET: &str = "naive_logger::dropped";
+
+type Counts = Mutex<HashMap<Arc<str>, u64>>;
+
+fn counts() -> &'static Counts {
+    static COUNTS: OnceLock<Counts> = OnceLock::new();
+    COUNTS.get_or_init(Default::default)
+}
+
+/// Counts the dropped records of the target; the reporter thread is spawned at the first call.
+pub(crate) fn count(target: &Arc<str>, n: u64) {
+    *counts().lock().entry(target.clone()).or_default() += n;
+
+    static REPORTER: Once = Once::new();
+    REPORTER.call_once(|| {
+        let _ = std::thread::Builder::new()
+            .name("naive-logger-dropped".to_string())
+            .spawn(|| loop {
+                std::thread::sleep(REPORT_INTERVAL);
+                report();
+            });
+    });
+}
+
+/// Takes the counts since the last call, ordered by the targets.
+fn take() -> Vec<(Arc<str>, u64)> {
+    let mut counts: Vec<_> = std::mem::take(&mut *counts().lock()).into_iter().collect();
+    counts.sort();
+    counts
+}
+
+fn report() {
+    for (target, n) in take() {
+        let kvs: &[(&str, log::kv::Value)] =
+            &[("dropped", n.into()), ("dropped_target", (&*target).into())];
+        log::logger().log(
+            &Record::builder()
+                .level(Level::Warn)
+                .target(REPORT_TARGET)
+                .args(format_args!(
+                    "dropped {} records from target {} in the last {}s",
+                    n,
+                    target,
+                    REPORT_INTERVAL.as_secs()
+                ))
+                .key_values(&kvs)
+                .build(),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    #[test]
+    fn test_count() {
+        let a: Arc<str> = Arc::from("__test_dropped::a");
+        let b: Arc<str> = Arc::from("__test_dropped::b");
+        super::count(&b, 1);
+        super::count(&a, 2);
+        super::count(&b, 3);
+        let counts: Vec<_> = super::take()
+            .into_iter()
+            .filter(|(target, _)| target.starts_with("__test_dropped::"))
+            .collect();
+        assert_eq!(counts, vec![(a, 2), (b, 4)]);
+    }
+}
@@ -0,0 +1,67 @@
+use crate::{Datetime, Error};
+use crate::config::CborEncoderConfig;
+use crate::encoder::{Encoder, level_names, StructuredRecord};
+use crate::record::OwnedRecord;
+
+/// Encodes the records as the CBOR maps; a CBOR data item delimits itself, so they're simply concatenated.
+pub struct CborEncoder {
+    level_names: [String; 5],
+}
+
+impl TryFrom<&CborEncoderConfig> for CborEncoder {
+    type Error = Error;
+
+    fn try_from(config: &CborEncoderConfig) -> Result<Self, Self::Error> {
+        Ok(Self {
+            level_names: level_names(&config.level_names),
+        })
+    }
+}
+
+impl Encoder for CborEncoder {
+    fn encode(&self, datetime: &Datetime, record: &OwnedRecord, buf: &mut Vec<u8>) {
+        // writing to a vector never fails
+        ciborium::into_writer(&StructuredRecord::new(datetime, record, &self.level_names), buf).unwrap();
+    }
+
+    fn is_binary(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use log::RecordBuilder;
+
+    use crate::config::CborEncoderConfig;
+    use crate::encoder::Encoder;
+    use crate::encoder::tests::*;
+    use crate::record::OwnedRecord;
+
+    #[test]
+    fn test_encode() {
+        let encoder = super::CborEncoder::try_from(&CborEncoderConfig::default()).unwrap();
+        let mut builder = RecordBuilder::new();
+        prepare_test_log_record(&mut builder);
+        let mut kvs = Vec::new();
+        prepare_test_kvs(&mut kvs);
+        let record = OwnedRecord::from(&builder.args(format_args!("{}", TEST_MESSAGE)).key_values(&kvs).build());
+        let mut buf = vec![];
+        encoder.encode_entry(&test_datetime(), &record, &mut buf);
+        let len = buf.len();
+        encoder.encode_entry(&test_datetime(), &record, &mut buf);
+        assert_eq!(buf.len(), 2 * len);
+
+        let mut reader = buf.as_slice();
+        for _ in 0..2 {
+            let value: serde_json::Value = ciborium::from_reader(&mut reader).unwrap();
+            assert_eq!(value["timestamp"], TEST_TIMESTAMP);
+            assert_eq!(value["level"], TEST_LEVEL.as_str());
+            assert_eq!(value["target"], TEST_TARGET);
+            assert_eq!(value["message"], TEST_MESSAGE);
+            assert_eq!(value["args"][TEST_KV0.0], TEST_KV0.1);
+            assert_eq!(value["args"][TEST_KV3.0], serde_json::json!(TEST_KV3.1));
+        }
+        assert!(reader.is_empty());
+    }
+}
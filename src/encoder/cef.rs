@@ -0,0 +1,157 @@
+use log::Level;
+
+use crate::{Datetime, Error};
+use crate::config::CefEncoderConfig;
+use crate::encoder::Encoder;
+use crate::record::OwnedRecord;
+
+/// The version of the format, the first header field.
+const CEF_VERSION: u8 = 0;
+
+/// The severities of the levels by default, indexed by `level as usize - 1`.
+const DEFAULT_SEVERITIES: [u8; 5] = [8, 6, 3, 1, 0];
+
+const MAX_SEVERITY: u8 = 10;
+
+/// Formats the records like
+/// `CEF:0|<vendor>|<product>|<version>|<target>|<message>|<severity>|rt=<timestamp> <key>=<value>...`.
+pub struct CefEncoder {
+    /// The escaped header fields before the signature ID, ended with `|`.
+    prefix: String,
+    severities: [u8; 5],
+}
+
+impl TryFrom<&CefEncoderConfig> for CefEncoder {
+    type Error = Error;
+
+    fn try_from(config: &CefEncoderConfig) -> Result<Self, Self::Error> {
+        let mut severities = DEFAULT_SEVERITIES;
+        for (level, severity) in &config.severities {
+            if *severity > MAX_SEVERITY {
+                return Err(Error::from(format!(
+                    "severity of {} should be 0 to {}",
+                    level.as_str().to_lowercase(),
+                    MAX_SEVERITY
+                )));
+            }
+            severities[*level as usize - 1] = *severity;
+        }
+        let mut prefix = format!("CEF:{}|", CEF_VERSION);
+        for field in [&config.vendor, &config.product, &config.version] {
+            escape_header(field, &mut prefix);
+            prefix.push('|');
+        }
+        Ok(Self { prefix, severities })
+    }
+}
+
+impl Encoder for CefEncoder {
+    fn encode(&self, datetime: &Datetime, record: &OwnedRecord, buf: &mut Vec<u8>) {
+        let mut s = self.prefix.clone();
+        escape_header(&record.target, &mut s);
+        s.push('|');
+        escape_header(&record.message, &mut s);
+        s.push('|');
+        s.push_str(&self.severity(record.level).to_string());
+        s.push_str("|rt=");
+        s.push_str(&datetime.timestamp_millis().to_string());
+        for (key, value) in &record.kvs {
+            s.push(' ');
+            s.extend(key.chars().map(|c| if c.is_ascii_alphanumeric() { c } else { '_' }));
+            s.push('=');
+            match value {
+                serde_json::Value::String(value) => escape_extension(value, &mut s),
+                value => escape_extension(&value.to_string(), &mut s),
+            }
+        }
+        buf.extend_from_slice(s.as_bytes());
+    }
+}
+
+impl CefEncoder {
+    fn severity(&self, level: Level) -> u8 {
+        self.severities[level as usize - 1]
+    }
+}
+
+/// Escapes the pipes and the backslashes of a header field; the line breaks are replaced by spaces,
+/// as they can't be escaped in the headers.
+fn escape_header(s: &str, buf: &mut String) {
+    for c in s.chars() {
+        match c {
+            '|' | '\\' => {
+                buf.push('\\');
+                buf.push(c);
+            }
+            '\r' | '\n' => buf.push(' '),
+            c => buf.push(c),
+        }
+    }
+}
+
+/// Escapes the equal signs, the backslashes and the line breaks of an extension value.
+fn escape_extension(s: &str, buf: &mut String) {
+    for c in s.chars() {
+        match c {
+            '=' | '\\' => {
+                buf.push('\\');
+                buf.push(c);
+            }
+            '\r' => buf.push_str("\\r"),
+            '\n' => buf.push_str("\\n"),
+            c => buf.push(c),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use log::{Level, RecordBuilder};
+
+    use crate::config::CefEncoderConfig;
+    use crate::encoder::Encoder;
+    use crate::encoder::tests::*;
+    use crate::record::OwnedRecord;
+
+    #[test]
+    fn test_encode() {
+        let config: CefEncoderConfig =
+            serde_json::from_str(r#"{"vendor": "Acme|Corp", "product": "myapp", "version": "1.0"}"#).unwrap();
+        let encoder = super::CefEncoder::try_from(&config).unwrap();
+        let kvs: &[(&str, log::kv::Value)] = &[
+            ("user.id", 42.into()),
+            ("query", "a=b\nc\\d".into()),
+        ];
+        let mut builder = RecordBuilder::new();
+        prepare_test_log_record(&mut builder);
+        let record = OwnedRecord::from(
+            &builder
+                .level(Level::Warn)
+                .args(format_args!("login failed | retrying"))
+                .key_values(&kvs)
+                .build(),
+        );
+        let mut buf = vec![];
+        encoder.encode(&test_datetime(), &record, &mut buf);
+        assert_eq!(
+            String::from_utf8(buf).unwrap(),
+            format!(
+                r"CEF:0|Acme\|Corp|myapp|1.0|{}|login failed \| retrying|6|rt={} user_id=42 query=a\=b\nc\\d",
+                TEST_TARGET, TEST_TIMESTAMP
+            )
+        );
+    }
+
+    #[test]
+    fn test_severities() {
+        let config: CefEncoderConfig =
+            serde_json::from_str(r#"{"vendor": "Acme", "product": "myapp", "severities": {"error": 10}}"#).unwrap();
+        let encoder = super::CefEncoder::try_from(&config).unwrap();
+        assert_eq!(encoder.severity(Level::Error), 10);
+        assert_eq!(encoder.severity(Level::Info), 3);
+
+        let config: CefEncoderConfig =
+            serde_json::from_str(r#"{"vendor": "Acme", "product": "myapp", "severities": {"error": 11}}"#).unwrap();
+        assert!(super::CefEncoder::try_from(&config).is_err());
+    }
+}
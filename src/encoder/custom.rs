@@ -0,0 +1,84 @@
+use std::collections::HashMap;
+use std::sync::{Arc, OnceLock};
+
+use crate::config::CustomEncoderConfig;
+use crate::encoder::Encoder;
+use crate::sync::Mutex;
+use crate::Error;
+
+type Factory = dyn Fn(&serde_json::Value) -> Result<Box<dyn Encoder + Send + Sync>, Error> + Send + Sync;
+
+type Registry = Mutex<HashMap<String, Arc<Factory>>>;
+
+fn registry() -> &'static Registry {
+    static REGISTRY: OnceLock<Registry> = OnceLock::new();
+    REGISTRY.get_or_init(Default::default)
+}
+
+pub(crate) fn register<E, F>(kind: &str, factory: F)
+where
+    E: Encoder + Send + Sync + 'static,
+    F: Fn(&serde_json::Value) -> Result<E, Error> + Send + Sync + 'static,
+{
+    let factory = move |properties: &serde_json::Value| -> Result<Box<dyn Encoder + Send + Sync>, Error> {
+        Ok(Box::new(factory(properties)?))
+    };
+    registry().lock().insert(kind.to_string(), Arc::new(factory));
+}
+
+/// Creates the encoder by the factory registered as the kind, with the other properties of the config.
+pub(crate) fn create(config: &CustomEncoderConfig) -> Result<Box<dyn Encoder + Send + Sync>, Error> {
+    let factory = registry()
+        .lock()
+        .get(&config.name)
+        .cloned()
+        .ok_or_else(|| Error::from(format!("no encoder kind registered as '{}'", config.name)))?;
+    // called with the registry unlocked, in case the factory registers another kind
+    factory(&serde_json::Value::Object(config.properties.clone()))
+}
+
+#[cfg(test)]
+mod tests {
+    use log::RecordBuilder;
+    use serde::Deserialize;
+
+    use crate::config::EncoderConfig;
+    use crate::encoder::Encoder;
+    use crate::encoder::tests::*;
+    use crate::record::OwnedRecord;
+    use crate::{Datetime, Error};
+
+    struct PrefixEncoder(String);
+
+    impl Encoder for PrefixEncoder {
+        fn encode(&self, _datetime: &Datetime, record: &OwnedRecord, buf: &mut Vec<u8>) {
+            buf.extend_from_slice(self.0.as_bytes());
+            buf.extend_from_slice(record.message.as_bytes());
+        }
+    }
+
+    #[test]
+    fn test_create() {
+        #[derive(Deserialize)]
+        struct Properties {
+            prefix: String,
+        }
+        super::register("test_prefix", |properties: &serde_json::Value| {
+            let properties = Properties::deserialize(properties).map_err(|e| Error::from(e.to_string()))?;
+            Ok(PrefixEncoder(properties.prefix))
+        });
+
+        let config: EncoderConfig =
+            serde_json::from_str(r#"{"kind": "custom", "name": "test_prefix", "prefix": ">> "}"#).unwrap();
+        let encoder = crate::encoder::from_config(&config).unwrap();
+        let record = OwnedRecord::from(&RecordBuilder::new().args(format_args!("{}", TEST_MESSAGE)).build());
+        let mut buf = vec![];
+        encoder.encode_entry(&test_datetime(), &record, &mut buf);
+        assert_eq!(String::from_utf8(buf).unwrap(), format!(">> {}\n", TEST_MESSAGE));
+
+        let config: EncoderConfig = serde_json::from_str(r#"{"kind": "custom", "name": "test_prefix"}"#).unwrap();
+        assert!(crate::encoder::from_config(&config).is_err());
+        let config: EncoderConfig = serde_json::from_str(r#"{"kind": "custom", "name": "test_missing"}"#).unwrap();
+        assert!(crate::encoder::from_config(&config).is_err());
+    }
+}
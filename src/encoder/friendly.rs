@@ -0,0 +1,144 @@
+//! Friendly rendering of the common value types in the pattern encoder.
+//!
+//! The types are recognized by the shapes of their serialized values,
+//! or by the suffixes of the keys:
+//!
+//! * durations: `std::time::Duration`, or the numbers whose keys end with `_ms` or `_secs`
+//! * byte sizes: the numbers whose keys end with `_bytes`
+//! * timestamps: `std::time::SystemTime`, or the numbers of Unix seconds whose keys end with `_at`
+
+use std::fmt::Write;
+
+use chrono::{DateTime, SecondsFormat};
+use serde_json::Value;
+
+const KIB: f64 = 1024.0;
+const BYTE_UNITS: [&str; 6] = ["B", "KiB", "MiB", "GiB", "TiB", "PiB"];
+
+/// Renders the value in a friendly way if its type is recognized.
+pub fn render(key: &str, value: &Value) -> Option<String> {
+    if let Value::Object(map) = value {
+        if map.len() != 2 {
+            return None;
+        }
+        if let (Some(secs), Some(nanos)) = (map.get("secs"), map.get("nanos")) {
+            return Some(duration(secs.as_f64()? + nanos.as_f64()? / 1e9));
+        }
+        if let (Some(secs), Some(nanos)) = (map.get("secs_since_epoch"), map.get("nanos_since_epoch")) {
+            return timestamp(secs.as_i64()?, nanos.as_u64()? as u32);
+        }
+        return None;
+    }
+
+    let number = value.as_f64()?;
+    if key.ends_with("_ms") {
+        Some(duration(number / 1e3))
+    } else if key.ends_with("_secs") {
+        Some(duration(number))
+    } else if key.ends_with("_bytes") {
+        Some(bytes(number))
+    } else if key.ends_with("_at") {
+        let nanos = (number.fract() * 1e9).round() as u32;
+        timestamp(number.trunc() as i64, nanos)
+    } else {
+        None
+    }
+}
+
+/// Formats the duration like `1h2m3.5s` from a minute on, with the zero parts left out,
+/// or in the largest unit of `s`, `ms`, `µs` and `ns` in which it's at least `1`.
+fn duration(secs: f64) -> String {
+    if secs < 0.0 {
+        return format!("-{}", duration(-secs));
+    }
+    if secs == 0.0 {
+        return "0s".to_string();
+    }
+    // rounded to the displayed precision first, so that e.g. `59.96` carries into `1m` rather than `60s`
+    let tenths = (secs * 10.0).round();
+    if tenths >= 600.0 {
+        let tenths = tenths as u64;
+        let (hours, minutes, tenths) = (tenths / 36000, tenths / 600 % 60, tenths % 600);
+        let mut result = String::new();
+        if hours > 0 {
+            write!(result, "{}h", hours).unwrap();
+        }
+        if minutes > 0 {
+            write!(result, "{}m", minutes).unwrap();
+        }
+        if tenths > 0 {
+            write!(result, "{}s", decimal(tenths as f64 / 10.0)).unwrap();
+        }
+        return result;
+    }
+    for (scale, unit) in [(1.0, "s"), (1e3, "ms"), (1e6, "µs")] {
+        let value = (secs * scale * 10.0).round() / 10.0;
+        if value >= 1.0 {
+            return format!("{}{}", decimal(value), unit);
+        }
+    }
+    format!("{}ns", decimal(secs * 1e9))
+}
+
+fn bytes(mut value: f64) -> String {
+    let mut unit = 0;
+    while value >= KIB && unit < BYTE_UNITS.len() - 1 {
+        value /= KIB;
+        unit += 1;
+    }
+    format!("{}{}", decimal(value), BYTE_UNITS[unit])
+}
+
+fn timestamp(secs: i64, nanos: u32) -> Option<String> {
+    let datetime = DateTime::from_timestamp(secs, nanos)?;
+    Some(datetime.to_rfc3339_opts(SecondsFormat::AutoSi, true))
+}
+
+/// Formats the number with at most one decimal place.
+fn decimal(value: f64) -> String {
+    let s = format!("{:.1}", value);
+    match s.strip_suffix(".0") {
+        Some(s) => s.to_string(),
+        None => s,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, SystemTime};
+
+    use serde_json::json;
+
+    use super::render;
+
+    #[test]
+    fn test_render() {
+        let value = serde_json::to_value(Duration::from_micros(12300)).unwrap();
+        assert_eq!(render("elapsed", &value).as_deref(), Some("12.3ms"));
+        assert_eq!(render("elapsed_ms", &json!(250)).as_deref(), Some("250ms"));
+        assert_eq!(render("timeout_secs", &json!(90)).as_deref(), Some("1m30s"));
+        assert_eq!(render("latency_secs", &json!(0.0000005)).as_deref(), Some("500ns"));
+        assert_eq!(render("timeout_secs", &json!(60)).as_deref(), Some("1m"));
+        assert_eq!(render("timeout_secs", &json!(120)).as_deref(), Some("2m"));
+        assert_eq!(render("timeout_secs", &json!(3600)).as_deref(), Some("1h"));
+        assert_eq!(render("timeout_secs", &json!(3690)).as_deref(), Some("1h1m30s"));
+        assert_eq!(render("timeout_secs", &json!(3600.5)).as_deref(), Some("1h0.5s"));
+        // carried into the next unit when rounded
+        assert_eq!(render("timeout_secs", &json!(119.96)).as_deref(), Some("2m"));
+        assert_eq!(render("latency_secs", &json!(0.99996)).as_deref(), Some("1s"));
+        assert_eq!(render("latency_secs", &json!(0.0009999)).as_deref(), Some("1ms"));
+        assert_eq!(render("latency_secs", &json!(0)).as_deref(), Some("0s"));
+        assert_eq!(render("offset_ms", &json!(-1500)).as_deref(), Some("-1.5s"));
+
+        assert_eq!(render("body_bytes", &json!(512)).as_deref(), Some("512B"));
+        assert_eq!(render("body_bytes", &json!(4404019)).as_deref(), Some("4.2MiB"));
+
+        let value = serde_json::to_value(SystemTime::UNIX_EPOCH + Duration::from_secs(1722400496)).unwrap();
+        assert_eq!(render("now", &value).as_deref(), Some("2024-07-31T04:34:56Z"));
+        assert_eq!(render("created_at", &json!(1722400496.5)).as_deref(), Some("2024-07-31T04:34:56.500Z"));
+
+        assert_eq!(render("count", &json!(42)), None);
+        assert_eq!(render("elapsed_ms", &json!("fast")), None);
+        assert_eq!(render("user", &json!({"id": 42, "name": "foo"})), None);
+    }
+}
@@ -1,58 +1,220 @@
+use chrono::{SecondsFormat, Utc};
 use indexmap::IndexMap;
-use log::{Level, Record};
-use log::kv::{Key, Value, VisitSource};
 use serde::Serialize;
 
 use crate::{Datetime, Error};
-use crate::config::JsonEncoderConfig;
-use crate::encoder::Encoder;
+use crate::config::{ArgConflictPolicy, JsonEncoderConfig, TimestampFormat};
+use crate::encoder::{Arg, args, Encoder, level_names};
+use crate::record::OwnedRecord;
 
-#[derive(Default)]
-pub struct JsonEncoder;
+/// The value of the `logger` field.
+const LOGGER: &str = concat!("naive-logger/", env!("CARGO_PKG_VERSION"));
+
+/// The fields output by the encoder, which the static fields can't use;
+/// the ones output can be chosen by `include_fields` and `exclude_fields`.
+const BUILTIN_FIELDS: [&str; 15] = [
+    "schema_version",
+    "logger",
+    "timestamp",
+    "level",
+    "target",
+    "module",
+    "file",
+    "line",
+    "message",
+    "args",
+    "backtrace",
+    "trace_id",
+    "span_id",
+    "thread_id",
+    "thread_name",
+];
+
+/// The prefix of the flattened keys clashing with the built-in fields, by [`ArgConflictPolicy::Prefix`].
+const CONFLICT_PREFIX: &str = "arg_";
+
+pub struct JsonEncoder {
+    level_names: [String; 5],
+    schema_version: Option<String>,
+    logger_field: bool,
+    thread_fields: bool,
+    pretty: bool,
+    timestamp_format: TimestampFormat,
+    timestamp_utc: bool,
+    flatten_args: bool,
+    arg_conflict: ArgConflictPolicy,
+    static_fields: IndexMap<String, String>,
+    /// The built-in fields not to output.
+    excluded: Vec<&'static str>,
+}
 
 impl TryFrom<&JsonEncoderConfig> for JsonEncoder {
     type Error = Error;
 
-    fn try_from(_config: &JsonEncoderConfig) -> Result<Self, Self::Error> {
-        Ok(Self)
+    fn try_from(config: &JsonEncoderConfig) -> Result<Self, Self::Error> {
+        if let Some(key) = config.static_fields.keys().find(|x| BUILTIN_FIELDS.contains(&x.as_str())) {
+            return Err(Error::from(format!("static field '{}' clashes with a built-in field", key)));
+        }
+        if let Some(name) = config
+            .include_fields
+            .iter()
+            .chain(&config.exclude_fields)
+            .find(|x| !BUILTIN_FIELDS.contains(&x.as_str()))
+        {
+            return Err(Error::from(format!("unknown field '{}'", name)));
+        }
+        let excluded = BUILTIN_FIELDS
+            .into_iter()
+            .filter(|name| {
+                !config.include_fields.is_empty() && !config.include_fields.iter().any(|x| x == name)
+                    || config.exclude_fields.iter().any(|x| x == name)
+            })
+            .collect();
+        Ok(Self {
+            level_names: level_names(&config.level_names),
+            schema_version: config.schema_version.clone(),
+            logger_field: config.logger_field,
+            thread_fields: config.thread_fields,
+            pretty: config.pretty,
+            timestamp_format: config.timestamp_format.clone(),
+            timestamp_utc: config.timestamp_utc,
+            flatten_args: config.flatten_args,
+            arg_conflict: config.arg_conflict,
+            static_fields: config.static_fields.clone(),
+            excluded,
+        })
     }
 }
 
-impl Encoder for JsonEncoder {
-    fn encode(&self, datetime: &Datetime, record: &Record) -> String {
-        #[derive(Default)]
-        struct Visitor<'a>(IndexMap<Key<'a>, Value<'a>>);
-        impl<'a> VisitSource<'a> for Visitor<'a> {
-            fn visit_pair(&mut self, key: Key<'a>, value: Value<'a>) -> Result<(), log::kv::Error> {
-                self.0.insert(key, value);
-                Ok(())
+#[derive(Serialize)]
+#[serde(untagged)]
+enum Timestamp {
+    Number(i64),
+    String(String),
+}
+
+impl JsonEncoder {
+    fn includes(&self, field: &str) -> bool {
+        !self.excluded.contains(&field)
+    }
+
+    fn timestamp(&self, datetime: &Datetime) -> Timestamp {
+        match &self.timestamp_format {
+            TimestampFormat::Millis => Timestamp::Number(datetime.timestamp_millis()),
+            TimestampFormat::Seconds => Timestamp::Number(datetime.timestamp()),
+            // out of range after the year 2262
+            TimestampFormat::Nanos => Timestamp::Number(datetime.timestamp_nanos_opt().unwrap_or(i64::MAX)),
+            TimestampFormat::Rfc3339 if self.timestamp_utc => {
+                Timestamp::String(datetime.with_timezone(&Utc).to_rfc3339_opts(SecondsFormat::Millis, true))
+            }
+            TimestampFormat::Rfc3339 => Timestamp::String(datetime.to_rfc3339_opts(SecondsFormat::Millis, false)),
+            TimestampFormat::Strftime(format) if self.timestamp_utc => {
+                Timestamp::String(datetime.with_timezone(&Utc).format(format).to_string())
             }
+            TimestampFormat::Strftime(format) => Timestamp::String(datetime.format(format).to_string()),
         }
-        let mut visitor = Visitor::default();
-        record.key_values().visit(&mut visitor).unwrap();
+    }
+}
 
+impl Encoder for JsonEncoder {
+    fn encode(&self, datetime: &Datetime, record: &OwnedRecord, buf: &mut Vec<u8>) {
         #[derive(Serialize)]
         struct X<'a> {
-            timestamp: i64,
-            level: Level,
-            target: &'a str,
-            module: Option<&'a str>,
-            file: Option<&'a str>,
-            line: Option<u32>,
-            message: &'a std::fmt::Arguments<'a>,
-            args: IndexMap<Key<'a>, Value<'a>>,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            schema_version: Option<&'a str>,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            logger: Option<&'a str>,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            timestamp: Option<Timestamp>,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            level: Option<&'a str>,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            target: Option<&'a str>,
+            // the outer `None` if excluded, and the inner one output as `null`
+            #[serde(skip_serializing_if = "Option::is_none")]
+            module: Option<Option<&'a str>>,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            file: Option<Option<&'a str>>,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            line: Option<Option<u32>>,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            message: Option<&'a str>,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            args: Option<IndexMap<&'a str, Arg<'a>>>,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            backtrace: Option<&'a str>,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            trace_id: Option<&'a str>,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            span_id: Option<&'a str>,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            thread_id: Option<u64>,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            thread_name: Option<&'a str>,
+            #[serde(flatten)]
+            static_fields: &'a IndexMap<String, String>,
         }
+        let field = |name: &str| self.includes(name);
         let x = X {
-            timestamp: datetime.timestamp_millis(),
-            level: record.level(),
-            target: record.target(),
-            module: record.module_path(),
-            file: record.file(),
-            line: record.line(),
-            message: record.args(),
-            args: visitor.0,
+            schema_version: self.schema_version.as_deref().filter(|_| field("schema_version")),
+            logger: (self.logger_field && field("logger")).then_some(LOGGER),
+            timestamp: field("timestamp").then(|| self.timestamp(datetime)),
+            level: field("level").then(|| self.level_names[record.level as usize - 1].as_str()),
+            target: field("target").then_some(&record.target),
+            module: field("module").then_some(record.module_path.as_deref()),
+            file: field("file").then_some(record.file.as_deref()),
+            line: field("line").then_some(record.line),
+            message: field("message").then_some(&record.message),
+            args: (!self.flatten_args && field("args")).then(|| args(record)),
+            backtrace: record.backtrace.as_deref().filter(|_| field("backtrace")),
+            trace_id: record.trace_id.as_deref().filter(|_| field("trace_id")),
+            span_id: record.span_id.as_deref().filter(|_| field("span_id")),
+            thread_id: record.thread_id.filter(|_| self.thread_fields && field("thread_id")),
+            thread_name: record.thread_name.as_deref().filter(|_| self.thread_fields && field("thread_name")),
+            static_fields: &self.static_fields,
         };
-        serde_json::to_string(&x).unwrap()
+        if self.flatten_args {
+            self.write(buf, &self.flatten(x, record));
+        } else {
+            self.write(buf, &x);
+        }
+    }
+}
+
+impl JsonEncoder {
+    fn write<T: Serialize>(&self, buf: &mut Vec<u8>, value: &T) {
+        if self.pretty {
+            serde_json::to_writer_pretty(buf, value).unwrap();
+        } else {
+            serde_json::to_writer(buf, value).unwrap();
+        }
+    }
+
+    /// Adds the key-value pairs to the built-in fields, resolving the clashes by the policy.
+    fn flatten<T: Serialize>(&self, fields: T, record: &OwnedRecord) -> serde_json::Value {
+        let mut value = serde_json::to_value(fields).unwrap();
+        let object = value.as_object_mut().unwrap();
+        if !self.includes("args") {
+            return value;
+        }
+        let builtins: Vec<String> = object.keys().cloned().collect();
+        for (key, arg) in args(record) {
+            let arg = serde_json::to_value(arg).unwrap();
+            if !builtins.iter().any(|x| x == key) {
+                object.insert(key.to_string(), arg);
+                continue;
+            }
+            match self.arg_conflict {
+                ArgConflictPolicy::Prefix => {
+                    object.insert(format!("{}{}", CONFLICT_PREFIX, key), arg);
+                }
+                ArgConflictPolicy::Overwrite => {
+                    object.insert(key.to_string(), arg);
+                }
+                ArgConflictPolicy::Skip => {}
+            }
+        }
+        value
     }
 }
 
@@ -62,6 +224,12 @@ mod tests {
 
     use crate::encoder::Encoder;
     use crate::encoder::tests::*;
+    use crate::config::JsonEncoderConfig;
+    use crate::record::OwnedRecord;
+
+    fn json_encoder(config: &JsonEncoderConfig) -> super::JsonEncoder {
+        super::JsonEncoder::try_from(config).unwrap()
+    }
 
     #[test]
     fn test_encode() {
@@ -70,14 +238,19 @@ mod tests {
         prepare_test_log_record(&mut builder);
         let mut kvs = Vec::new();
         prepare_test_kvs(&mut kvs);
-        let encoder = super::JsonEncoder;
-        let result = encoder.encode(
+        let encoder = json_encoder(&Default::default());
+        let mut buf = vec![];
+        encoder.encode(
             &datetime,
-            &builder
-                .args(format_args!("{}", TEST_MESSAGE))
-                .key_values(&kvs)
-                .build(),
+            &OwnedRecord::from(
+                &builder
+                    .args(format_args!("{}", TEST_MESSAGE))
+                    .key_values(&kvs)
+                    .build(),
+            ),
+            &mut buf,
         );
+        let result = String::from_utf8(buf).unwrap();
 
         let mut expected = serde_json::Map::new();
         expected.insert("timestamp".to_string(), TEST_TIMESTAMP.into());
@@ -97,4 +270,177 @@ mod tests {
 
         assert_eq!(result, expected);
     }
+
+    #[test]
+    fn test_encode_error_chain() {
+        let mut builder = RecordBuilder::new();
+        prepare_test_log_record(&mut builder);
+        let mut record = OwnedRecord::from(&builder.args(format_args!("{}", TEST_MESSAGE)).build());
+        record.kvs.push(("error".to_string(), "top".into()));
+        record.error_chains.push((
+            "error".to_string(),
+            vec!["top".to_string(), "root".to_string()],
+        ));
+        let result = encode_json(&Default::default(), &record);
+        assert_eq!(result["args"]["error"], serde_json::json!(["top", "root"]));
+    }
+
+    #[test]
+    fn test_encode_nested_kv() {
+        #[derive(serde::Serialize)]
+        struct User {
+            id: u32,
+            name: &'static str,
+        }
+
+        let user = User { id: 42, name: "foo" };
+        let kvs: &[(&str, log::kv::Value)] = &[("user", log::kv::Value::from_serde(&user))];
+        let record = OwnedRecord::from(&RecordBuilder::new().key_values(&kvs).build());
+        let result = encode_json(&Default::default(), &record);
+        assert_eq!(result["args"]["user"], serde_json::json!({"id": 42, "name": "foo"}));
+    }
+
+    #[test]
+    fn test_encode_level_names() {
+        let config: JsonEncoderConfig =
+            serde_json::from_str(r#"{"level_names": {"warn": "WARNING"}}"#).unwrap();
+        for (level, expected) in [(log::Level::Warn, "WARNING"), (log::Level::Error, "ERROR")] {
+            let record = OwnedRecord::from(&RecordBuilder::new().level(level).build());
+            assert_eq!(encode_json(&config, &record)["level"], expected);
+        }
+    }
+
+    #[test]
+    fn test_encode_timestamp_format() {
+        let record = OwnedRecord::from(&RecordBuilder::new().build());
+        let cases = [
+            (r#"{}"#, serde_json::json!(TEST_TIMESTAMP)),
+            (r#"{"timestamp_format": "seconds"}"#, serde_json::json!(TEST_TIMESTAMP / 1000)),
+            (r#"{"timestamp_format": "nanos"}"#, serde_json::json!(TEST_TIMESTAMP * 1_000_000)),
+            (r#"{"timestamp_format": "rfc3339", "timestamp_utc": true}"#, serde_json::json!("2024-07-31T04:34:56.789Z")),
+            (r#"{"timestamp_format": "%Y-%m-%d %H:%M:%S%.3f", "timestamp_utc": true}"#, serde_json::json!("2024-07-31 04:34:56.789")),
+        ];
+        for (config, expected) in cases {
+            let config: JsonEncoderConfig = serde_json::from_str(config).unwrap();
+            let result = encode_json(&config, &record);
+            assert_eq!(result["timestamp"], expected);
+        }
+
+        let config: JsonEncoderConfig = serde_json::from_str(r#"{"timestamp_format": "rfc3339"}"#).unwrap();
+        let result = encode_json(&config, &record);
+        let expected = test_datetime().to_rfc3339_opts(chrono::SecondsFormat::Millis, false);
+        assert_eq!(result["timestamp"], expected.as_str());
+    }
+
+    #[test]
+    fn test_encode_flatten_args() {
+        let kvs: &[(&str, log::kv::Value)] = &[("user_id", 42.into()), ("level", "high".into())];
+        let record = OwnedRecord::from(&RecordBuilder::new().key_values(&kvs).build());
+        let cases = [
+            ("prefix", serde_json::json!("INFO"), serde_json::json!("high")),
+            ("overwrite", serde_json::json!("high"), serde_json::Value::Null),
+            ("skip", serde_json::json!("INFO"), serde_json::Value::Null),
+        ];
+        for (policy, level, prefixed) in cases {
+            let config = format!(r#"{{"flatten_args": true, "arg_conflict": "{}"}}"#, policy);
+            let config: JsonEncoderConfig = serde_json::from_str(&config).unwrap();
+            let result = encode_json(&config, &record);
+            assert!(result.get("args").is_none());
+            assert_eq!(result["user_id"], 42);
+            assert_eq!(result["level"], level, "{}", policy);
+            assert_eq!(result["arg_level"], prefixed, "{}", policy);
+        }
+    }
+
+    #[test]
+    fn test_encode_static_fields() {
+        let kvs: &[(&str, log::kv::Value)] = &[("region", "eu".into())];
+        let record = OwnedRecord::from(&RecordBuilder::new().key_values(&kvs).build());
+        let config: JsonEncoderConfig =
+            serde_json::from_str(r#"{"static_fields": {"service": "${CARGO_PKG_NAME}", "region": "us"}}"#).unwrap();
+        let mut buf = vec![];
+        json_encoder(&config).encode(&test_datetime(), &record, &mut buf);
+        let result = String::from_utf8(buf).unwrap();
+        let expected = format!(r#""args":{{"region":"eu"}},"service":"{}","region":"us"}}"#, env!("CARGO_PKG_NAME"));
+        assert!(result.ends_with(&expected), "{}", result);
+
+        // the static fields take precedence over the flattened key-value pairs, as the built-in ones
+        let config: JsonEncoderConfig =
+            serde_json::from_str(r#"{"static_fields": {"region": "us"}, "flatten_args": true}"#).unwrap();
+        let result = encode_json(&config, &record);
+        assert_eq!(result["region"], "us");
+        assert_eq!(result["arg_region"], "eu");
+
+        let config: JsonEncoderConfig = serde_json::from_str(r#"{"static_fields": {"level": "x"}}"#).unwrap();
+        assert!(super::JsonEncoder::try_from(&config).is_err());
+    }
+
+    #[test]
+    fn test_encode_fields() {
+        let kvs: &[(&str, log::kv::Value)] = &[("user_id", 42.into())];
+        let record = OwnedRecord::from(&RecordBuilder::new().args(format_args!("hi")).key_values(&kvs).build());
+        let cases = [
+            (r#"{"exclude_fields": ["module", "file", "line"]}"#, vec!["timestamp", "level", "target", "message", "args"]),
+            (r#"{"include_fields": ["level", "message", "args"]}"#, vec!["level", "message", "args"]),
+            (r#"{"include_fields": ["level", "message", "args"], "exclude_fields": ["args"]}"#, vec!["level", "message"]),
+            (r#"{"exclude_fields": ["args"], "flatten_args": true}"#, vec!["timestamp", "level", "target", "module", "file", "line", "message"]),
+        ];
+        for (config, expected) in cases {
+            let config: JsonEncoderConfig = serde_json::from_str(config).unwrap();
+            let result = encode_json(&config, &record);
+            assert_eq!(result.as_object().unwrap().keys().collect::<Vec<_>>(), expected);
+        }
+
+        let config: JsonEncoderConfig = serde_json::from_str(r#"{"exclude_fields": ["lines"]}"#).unwrap();
+        assert!(super::JsonEncoder::try_from(&config).is_err());
+    }
+
+    #[test]
+    fn test_encode_thread_fields() {
+        let mut record = OwnedRecord::from(&RecordBuilder::new().build());
+        record.thread_id = Some(7);
+        record.thread_name = Some("worker-1".into());
+        let result = encode_json(&Default::default(), &record);
+        assert!(result.get("thread_id").is_none());
+        assert!(result.get("thread_name").is_none());
+
+        let config: JsonEncoderConfig = serde_json::from_str(r#"{"thread_fields": true}"#).unwrap();
+        let result = encode_json(&config, &record);
+        assert_eq!(result["thread_id"], 7);
+        assert_eq!(result["thread_name"], "worker-1");
+
+        record.thread_name = None;
+        let result = encode_json(&config, &record);
+        assert_eq!(result["thread_id"], 7);
+        assert!(result.get("thread_name").is_none());
+    }
+
+    #[test]
+    fn test_encode_pretty() {
+        let record = OwnedRecord::from(&RecordBuilder::new().args(format_args!("{}", TEST_MESSAGE)).build());
+        let config: JsonEncoderConfig = serde_json::from_str(r#"{"pretty": true}"#).unwrap();
+        let mut buf = vec![];
+        json_encoder(&config).encode(&test_datetime(), &record, &mut buf);
+        let result = String::from_utf8(buf).unwrap();
+        assert!(result.starts_with(&format!("{{\n  \"timestamp\": {},\n", TEST_TIMESTAMP)), "{}", result);
+        assert!(result.ends_with("\n}"));
+        let result: serde_json::Value = serde_json::from_str(&result).unwrap();
+        assert_eq!(result["message"], TEST_MESSAGE);
+    }
+
+    #[test]
+    fn test_encode_schema_version() {
+        let record = OwnedRecord::from(&RecordBuilder::new().build());
+        let result = encode_json(&Default::default(), &record);
+        assert!(result.get("schema_version").is_none());
+        assert!(result.get("logger").is_none());
+
+        let config: JsonEncoderConfig =
+            serde_json::from_str(r#"{"schema_version": "2", "logger_field": true}"#).unwrap();
+        let mut buf = vec![];
+        json_encoder(&config).encode(&test_datetime(), &record, &mut buf);
+        let result = String::from_utf8(buf).unwrap();
+        let expected = format!(r#"{{"schema_version":"2","logger":"naive-logger/{}","#, env!("CARGO_PKG_VERSION"));
+        assert!(result.starts_with(&expected));
+    }
 }
@@ -1,18 +1,51 @@
-use log::Record;
+use std::collections::HashMap;
+
+use indexmap::IndexMap;
+use log::Level;
+use serde::Serialize;
 
 use crate::{Datetime, Error};
-use crate::config::EncoderConfig;
+use crate::config::{EncoderConfig, RedactConfig};
 use crate::encoder::json::JsonEncoder;
 use crate::encoder::pattern::PatternEncoder;
+use crate::processor::{Processor, Redactor};
+use crate::record::OwnedRecord;
 
+#[cfg(feature = "cbor")]
+mod cbor;
+mod cef;
+mod custom;
+mod friendly;
 mod json;
-mod pattern;
+#[cfg(feature = "msgpack")]
+mod msgpack;
+pub(crate) mod pattern;
+
+pub(crate) use custom::register as register_encoder_kind;
 
+/// Encodes the records for the appenders; the custom ones can be registered by [`crate::register_encoder_kind`].
+///
+/// The datetime of a record is in the configured timezone.
 pub trait Encoder {
-    fn encode(&self, datetime: &Datetime, record: &Record) -> String;
+    /// Encodes the record and appends the result to the buffer.
+    fn encode(&self, datetime: &Datetime, record: &OwnedRecord, buf: &mut Vec<u8>);
+
+    /// Whether the output is binary and delimits itself, like the length-prefixed MessagePack frames;
+    /// then it's not followed by a newline, and sent as binary messages by the WebSocket appender.
+    fn is_binary(&self) -> bool {
+        false
+    }
+
+    /// Encodes the record as an entry of a stream like a log file, followed by a newline unless binary.
+    fn encode_entry(&self, datetime: &Datetime, record: &OwnedRecord, buf: &mut Vec<u8>) {
+        self.encode(datetime, record, buf);
+        if !self.is_binary() {
+            buf.push(b'\n');
+        }
+    }
 }
 
-pub fn from_config(config: &EncoderConfig) -> Result<Box<dyn Encoder + Send>, Error> {
+pub fn from_config(config: &EncoderConfig) -> Result<Box<dyn Encoder + Send + Sync>, Error> {
     match config {
         EncoderConfig::Pattern(config) => {
             let encoder = PatternEncoder::try_from(config)?;
@@ -22,11 +55,148 @@ pub fn from_config(config: &EncoderConfig) -> Result<Box<dyn Encoder + Send>, Er
             let encoder = JsonEncoder::try_from(config)?;
             Ok(Box::new(encoder))
         }
+        #[cfg(feature = "msgpack")]
+        EncoderConfig::Msgpack(config) => {
+            let encoder = msgpack::MsgpackEncoder::try_from(config)?;
+            Ok(Box::new(encoder))
+        }
+        #[cfg(not(feature = "msgpack"))]
+        EncoderConfig::Msgpack(_) => Err(Error::from("msgpack encoder requires the `msgpack` feature")),
+        #[cfg(feature = "cbor")]
+        EncoderConfig::Cbor(config) => {
+            let encoder = cbor::CborEncoder::try_from(config)?;
+            Ok(Box::new(encoder))
+        }
+        #[cfg(not(feature = "cbor"))]
+        EncoderConfig::Cbor(_) => Err(Error::from("cbor encoder requires the `cbor` feature")),
+        EncoderConfig::Cef(config) => {
+            let encoder = cef::CefEncoder::try_from(config)?;
+            Ok(Box::new(encoder))
+        }
+        EncoderConfig::Custom(config) => custom::create(config),
+    }
+}
+
+/// Creates the encoder, with the color codes of the `pattern` encoder (`{colorStart}`, `{colorEnd}`
+/// and the styles of the key-value pairs) left out unless `color`; the other encoders are not affected.
+pub fn from_config_with_color(config: &EncoderConfig, color: bool) -> Result<Box<dyn Encoder + Send + Sync>, Error> {
+    match config {
+        EncoderConfig::Pattern(config) => {
+            let encoder = PatternEncoder::try_from(config)?.with_color(color);
+            Ok(Box::new(encoder))
+        }
+        _ => from_config(config),
+    }
+}
+
+/// A value of the `args` of the structured encoders; the errors are output as the messages of their chains.
+#[derive(Serialize)]
+#[serde(untagged)]
+enum Arg<'a> {
+    Value(&'a serde_json::Value),
+    ErrorChain(&'a [String]),
+}
+
+fn args(record: &OwnedRecord) -> IndexMap<&str, Arg<'_>> {
+    let mut args: IndexMap<&str, Arg> = record
+        .kvs
+        .iter()
+        .map(|(key, value)| (key.as_str(), Arg::Value(value)))
+        .collect();
+    for (key, chain) in &record.error_chains {
+        if let Some(arg) = args.get_mut(key.as_str()) {
+            *arg = Arg::ErrorChain(chain);
+        }
+    }
+    args
+}
+
+/// The fields of a record output by the binary encoders, the same as the ones of the `json` encoder.
+#[cfg(any(feature = "msgpack", feature = "cbor"))]
+#[derive(Serialize)]
+struct StructuredRecord<'a> {
+    timestamp: i64,
+    level: &'a str,
+    target: &'a str,
+    module: Option<&'a str>,
+    file: Option<&'a str>,
+    line: Option<u32>,
+    message: &'a str,
+    args: IndexMap<&'a str, Arg<'a>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    backtrace: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    trace_id: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    span_id: Option<&'a str>,
+}
+
+#[cfg(any(feature = "msgpack", feature = "cbor"))]
+impl<'a> StructuredRecord<'a> {
+    fn new(datetime: &Datetime, record: &'a OwnedRecord, level_names: &'a [String; 5]) -> Self {
+        Self {
+            timestamp: datetime.timestamp_millis(),
+            level: &level_names[record.level as usize - 1],
+            target: &record.target,
+            module: record.module_path.as_deref(),
+            file: record.file.as_deref(),
+            line: record.line,
+            message: &record.message,
+            args: args(record),
+            backtrace: record.backtrace.as_deref(),
+            trace_id: record.trace_id.as_deref(),
+            span_id: record.span_id.as_deref(),
+        }
+    }
+}
+
+/// The names to display for the levels, indexed by `level as usize - 1`;
+/// the levels not in the config keep their own names.
+fn level_names(config: &HashMap<Level, String>) -> [String; 5] {
+    let mut names = Level::iter().map(|level| match config.get(&level) {
+        Some(name) => name.clone(),
+        None => level.as_str().to_string(),
+    });
+    std::array::from_fn(|_| names.next().unwrap())
+}
+
+/// Wraps the encoder to mask the values of the key-value pairs before encoding.
+pub fn with_redaction(
+    encoder: Box<dyn Encoder + Send + Sync>,
+    config: Option<&RedactConfig>,
+) -> Box<dyn Encoder + Send + Sync> {
+    match config {
+        None => encoder,
+        Some(config) => Box::new(RedactEncoder {
+            inner: encoder,
+            redactor: Redactor::from(config),
+        }),
+    }
+}
+
+struct RedactEncoder {
+    inner: Box<dyn Encoder + Send + Sync>,
+    redactor: Redactor,
+}
+
+impl Encoder for RedactEncoder {
+    fn is_binary(&self) -> bool {
+        self.inner.is_binary()
+    }
+
+    fn encode(&self, datetime: &Datetime, record: &OwnedRecord, buf: &mut Vec<u8>) {
+        let redact = record.kvs.iter().any(|(key, _)| self.redactor.should_redact(key));
+        if !redact {
+            return self.inner.encode(datetime, record, buf);
+        }
+        let mut record = record.clone();
+        self.redactor.process(&mut record);
+        self.inner.encode(datetime, &record, buf);
     }
 }
 
 #[cfg(test)]
-mod tests {
+pub(crate) mod tests {
     use chrono::DateTime;
     use log::{Level, RecordBuilder};
     use log::kv::{Source, Value};
@@ -58,6 +228,16 @@ mod tests {
         kvs.push(Box::new((TEST_KV3.0, Value::from_serde(&TEST_KV3.1))));
     }
 
+    /// Encodes the record with the JSON encoder of the config at [`test_datetime`], and parses the result.
+    pub fn encode_json(config: &crate::config::JsonEncoderConfig, record: &crate::record::OwnedRecord) -> serde_json::Value {
+        use super::Encoder;
+
+        let encoder = super::json::JsonEncoder::try_from(config).unwrap();
+        let mut buf = vec![];
+        encoder.encode(&test_datetime(), record, &mut buf);
+        serde_json::from_slice(&buf).unwrap()
+    }
+
     pub fn prepare_test_log_record(builder: &mut RecordBuilder) {
         builder
             .target(TEST_TARGET)
@@ -67,4 +247,33 @@ mod tests {
             .line(Some(TEST_LINE))
             .build();
     }
+
+    #[test]
+    fn test_with_redaction() {
+        use crate::config::{EncoderConfig, PatternEncoderConfig, RedactConfig};
+        use crate::record::OwnedRecord;
+
+        let encoder = super::from_config(&EncoderConfig::Pattern(PatternEncoderConfig {
+            pattern: "{message}{kv(|)(=)}".to_string(),
+            ..Default::default()
+        }))
+        .unwrap();
+        let config: RedactConfig = serde_json::from_str(r#"{"keys": ["string"]}"#).unwrap();
+        let encoder = super::with_redaction(encoder, Some(&config));
+
+        let mut kvs = Vec::new();
+        prepare_test_kvs(&mut kvs);
+        let record = OwnedRecord::from(
+            &RecordBuilder::new()
+                .args(format_args!("{}", TEST_MESSAGE))
+                .key_values(&kvs)
+                .build(),
+        );
+        let mut buf = vec![];
+        encoder.encode(&test_datetime(), &record, &mut buf);
+        let result = String::from_utf8(buf).unwrap();
+        assert!(result.contains(r#"string="***""#), "{}", result);
+        assert!(!result.contains("hello"));
+        assert_eq!(record.kvs[1], (TEST_KV1.0.to_string(), TEST_KV1.1.into()));
+    }
 }
@@ -0,0 +1,73 @@
+use crate::{Datetime, Error};
+use crate::config::MsgpackEncoderConfig;
+use crate::encoder::{Encoder, level_names, StructuredRecord};
+use crate::record::OwnedRecord;
+
+/// The length of the big-endian length prefix of the frames.
+const LENGTH_PREFIX_LEN: usize = 4;
+
+pub struct MsgpackEncoder {
+    level_names: [String; 5],
+}
+
+impl TryFrom<&MsgpackEncoderConfig> for MsgpackEncoder {
+    type Error = Error;
+
+    fn try_from(config: &MsgpackEncoderConfig) -> Result<Self, Self::Error> {
+        Ok(Self {
+            level_names: level_names(&config.level_names),
+        })
+    }
+}
+
+impl Encoder for MsgpackEncoder {
+    fn encode(&self, datetime: &Datetime, record: &OwnedRecord, buf: &mut Vec<u8>) {
+        let start = buf.len();
+        buf.extend_from_slice(&[0; LENGTH_PREFIX_LEN]);
+        // writing to a vector never fails
+        rmp_serde::encode::write_named(buf, &StructuredRecord::new(datetime, record, &self.level_names)).unwrap();
+        let len = (buf.len() - start - LENGTH_PREFIX_LEN) as u32;
+        buf[start..start + LENGTH_PREFIX_LEN].copy_from_slice(&len.to_be_bytes());
+    }
+
+    fn is_binary(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use log::RecordBuilder;
+
+    use crate::config::MsgpackEncoderConfig;
+    use crate::encoder::Encoder;
+    use crate::encoder::tests::*;
+    use crate::record::OwnedRecord;
+
+    #[test]
+    fn test_encode() {
+        let encoder = super::MsgpackEncoder::try_from(&MsgpackEncoderConfig::default()).unwrap();
+        let mut builder = RecordBuilder::new();
+        prepare_test_log_record(&mut builder);
+        let mut kvs = Vec::new();
+        prepare_test_kvs(&mut kvs);
+        let mut record = OwnedRecord::from(&builder.args(format_args!("{}", TEST_MESSAGE)).key_values(&kvs).build());
+        record.error_chains.push((TEST_KV1.0.to_string(), vec!["top".to_string(), "root".to_string()]));
+        let mut buf = vec![];
+        encoder.encode_entry(&test_datetime(), &record, &mut buf);
+        encoder.encode_entry(&test_datetime(), &record, &mut buf);
+
+        let len = u32::from_be_bytes(buf[..4].try_into().unwrap()) as usize;
+        assert_eq!(buf.len(), 2 * (4 + len));
+        let value: serde_json::Value = rmp_serde::from_slice(&buf[4..4 + len]).unwrap();
+        assert_eq!(value["timestamp"], TEST_TIMESTAMP);
+        assert_eq!(value["level"], TEST_LEVEL.as_str());
+        assert_eq!(value["target"], TEST_TARGET);
+        assert_eq!(value["line"], TEST_LINE);
+        assert_eq!(value["message"], TEST_MESSAGE);
+        assert_eq!(value["args"][TEST_KV0.0], TEST_KV0.1);
+        assert_eq!(value["args"][TEST_KV1.0], serde_json::json!(["top", "root"]));
+        assert_eq!(value["args"][TEST_KV3.0], serde_json::json!(TEST_KV3.1));
+        assert!(value.get("backtrace").is_none());
+    }
+}
@@ -1,12 +1,11 @@
-use std::fmt::Write;
+use std::borrow::Cow;
+use std::io::Write;
 use std::mem::swap;
 
-use log::kv::VisitSource;
-use log::Record;
-
 use crate::{Datetime, Error};
 use crate::config::PatternEncoderConfig;
-use crate::encoder::Encoder;
+use crate::encoder::{Encoder, friendly, level_names};
+use crate::record::OwnedRecord;
 
 const DEFAULT_DATETIME_FORMAT: &str = "%Y-%m-%dT%H:%M:%S%.3f%z";
 
@@ -14,6 +13,9 @@ const UNKNOWN_MODULE: &str = "<unknown>";
 const UNKNOWN_FILE: &str = "<unknown>";
 const UNKNOWN_LINE: u32 = 0;
 
+/// Replaces the last character kept of the truncated placeholders.
+const ELLIPSIS: &str = "…";
+
 const ANSI_COLOR_RESET: &str = "\x1b[0m";
 const ANSI_COLOR_RED: &str = "\x1b[31m";
 const ANSI_COLOR_GREEN: &str = "\x1b[32m";
@@ -21,6 +23,92 @@ const ANSI_COLOR_YELLOW: &str = "\x1b[33m";
 const ANSI_COLOR_CYAN: &str = "\x1b[34m";
 const ANSI_COLOR_MAGENTA: &str = "\x1b[35m";
 
+/// Gets the escape sequence of the style of the key-value pairs,
+/// which is a `+`-joined list of the style names, e.g. `bold+cyan`; empty for no style.
+fn parse_style(s: &str) -> Result<String, &'static str> {
+    let mut style = String::new();
+    for name in s.split('+').filter(|x| !x.is_empty()) {
+        style.push_str(match name {
+            "bold" => "\x1b[1m",
+            "dim" => "\x1b[2m",
+            "italic" => "\x1b[3m",
+            "underline" => "\x1b[4m",
+            "red" => ANSI_COLOR_RED,
+            "green" => ANSI_COLOR_GREEN,
+            "yellow" => ANSI_COLOR_YELLOW,
+            "blue" => "\x1b[34m",
+            "magenta" => ANSI_COLOR_MAGENTA,
+            "cyan" => "\x1b[36m",
+            _ => return Err("unknown style name"),
+        });
+    }
+    Ok(style)
+}
+
+/// Gets the maximum length in characters of the truncated placeholders,
+/// given as `max=<n>` or `.<n>`, e.g. `{message(max=200)}` or `{target(.20)}`; none if no argument.
+fn parse_max_len<S: AsRef<str>>(args: &[S]) -> Result<Option<usize>, &'static str> {
+    let Some(arg) = args.first().map(|x| x.as_ref()) else {
+        return Ok(None);
+    };
+    if args.len() > 1 {
+        return Err("expecting at most one argument");
+    }
+    let max_len = arg
+        .strip_prefix("max=")
+        .or_else(|| arg.strip_prefix('.'))
+        .and_then(|x| x.parse::<usize>().ok())
+        .ok_or("expecting a truncation argument like 'max=200' or '.200'")?;
+    if max_len == 0 {
+        return Err("maximum length must be positive");
+    }
+    Ok(Some(max_len))
+}
+
+/// Writes the string, truncated to at most `max_len` characters with an ellipsis at the end.
+fn write_truncated(buf: &mut Vec<u8>, s: &str, max_len: Option<usize>) {
+    if let Some(max_len) = max_len {
+        if s.char_indices().nth(max_len).is_some() {
+            let (end, _) = s.char_indices().nth(max_len - 1).unwrap();
+            buf.extend_from_slice(&s.as_bytes()[..end]);
+            buf.extend_from_slice(ELLIPSIS.as_bytes());
+            return;
+        }
+    }
+    buf.extend_from_slice(s.as_bytes());
+}
+
+/// Gets the format of `{level}`: `upper`, `lower`, `short` (the first character, e.g. `E` for `ERROR`),
+/// or `upper`/`lower` followed by the width to pad or truncate to, e.g. `upper5`; the name as is if no argument.
+fn parse_level_format<S: AsRef<str>>(args: &[S]) -> Result<(LevelFormat, usize), &'static str> {
+    let Some(arg) = args.first().map(|x| x.as_ref()) else {
+        return Ok((LevelFormat::Name, 0));
+    };
+    if args.len() > 1 {
+        return Err("expecting at most one argument");
+    }
+    if arg == "short" {
+        return Ok((LevelFormat::Short, 0));
+    }
+    let (format, width) = if let Some(width) = arg.strip_prefix("upper") {
+        (LevelFormat::Upper, width)
+    } else if let Some(width) = arg.strip_prefix("lower") {
+        (LevelFormat::Lower, width)
+    } else {
+        return Err("expecting 'upper', 'lower' or 'short', optionally followed by a width like 'upper5'");
+    };
+    if width.is_empty() {
+        return Ok((format, 0));
+    }
+    if !width.bytes().all(|b| b.is_ascii_digit()) {
+        return Err("expecting a positive width like 'upper5'");
+    }
+    match width.parse::<usize>() {
+        Ok(width) if width > 0 => Ok((format, width)),
+        _ => Err("expecting a positive width like 'upper5'"),
+    }
+}
+
 fn level2color(level: log::Level) -> &'static str {
     match level {
         log::Level::Error => ANSI_COLOR_RED,
@@ -33,6 +121,10 @@ fn level2color(level: log::Level) -> &'static str {
 
 pub struct PatternEncoder {
     placeholders: Vec<Placeholder>,
+    friendly_values: bool,
+    level_names: [String; 5],
+    /// Whether to write the color codes; see [`PatternEncoder::with_color`].
+    color: bool,
 }
 
 enum Placeholder {
@@ -42,18 +134,47 @@ enum Placeholder {
     Datetime {
         format: String,
     },
-    Level,
-    Target,
-    Module,
-    File,
+    Level {
+        format: LevelFormat,
+        /// The width to pad the name to with spaces; 0 for no padding.
+        width: usize,
+    },
+    /// The text placeholders are truncated to `max_len` characters if given.
+    Target {
+        max_len: Option<usize>,
+    },
+    Module {
+        max_len: Option<usize>,
+    },
+    File {
+        max_len: Option<usize>,
+    },
     Line,
-    Message,
+    Message {
+        max_len: Option<usize>,
+    },
     KeyValuePairs {
         pair_separator: String,
         kv_separator: String,
+        /// The escape sequences to style the keys and the values; empty for no style.
+        key_style: String,
+        value_style: String,
     },
     ColorStart,
     ColorEnd,
+    TraceId,
+    SpanId,
+    Thread,
+    ThreadName,
+    Backtrace,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum LevelFormat {
+    Name,
+    Upper,
+    Lower,
+    Short,
 }
 
 impl TryFrom<&PatternEncoderConfig> for PatternEncoder {
@@ -62,13 +183,28 @@ impl TryFrom<&PatternEncoderConfig> for PatternEncoder {
     fn try_from(config: &PatternEncoderConfig) -> Result<Self, Self::Error> {
         let placeholders =
             parse_placeholders(&config.pattern).map_err(|e| e.concat("invalid pattern"))?;
-        Ok(Self { placeholders })
+        Ok(Self {
+            placeholders,
+            friendly_values: config.friendly_values,
+            level_names: level_names(&config.level_names),
+            color: true,
+        })
+    }
+}
+
+impl PatternEncoder {
+    /// Leaves out the color codes unless `color`, i.e. `{colorStart}`, `{colorEnd}` and the styles of the key-value pairs,
+    /// e.g. for the console not being a terminal.
+    pub fn with_color(mut self, color: bool) -> Self {
+        self.color = color;
+        self
     }
 }
 
 fn parse_placeholders(s: &str) -> Result<Vec<Placeholder>, Error> {
     let mut placeholders = vec![];
 
+    #[allow(clippy::enum_variant_names)]
     enum State {
         CollectLiteral,            // until '{'
         CollectPlaceholder,        // until '(' or '}'
@@ -173,160 +309,258 @@ impl<S1: AsRef<str>, S2: AsRef<str>> TryFrom<(S1, &[S2])> for Placeholder {
         let args = tuple.1;
 
         match name {
-            x if x == "datetime" => {
+            "datetime" => {
                 if args.len() > 1 {
                     return Err("expecting at most one argument");
                 }
                 let format = args
-                    .get(0)
+                    .first()
                     .map(|x| x.as_ref())
                     .unwrap_or(DEFAULT_DATETIME_FORMAT);
                 Ok(Placeholder::Datetime {
                     format: format.to_string(),
                 })
             }
-            x if x == "level" => {
+            "level" => {
+                let (format, width) = parse_level_format(args)?;
+                Ok(Placeholder::Level { format, width })
+            }
+            "target" => Ok(Placeholder::Target {
+                max_len: parse_max_len(args)?,
+            }),
+            "module" => Ok(Placeholder::Module {
+                max_len: parse_max_len(args)?,
+            }),
+            "file" => Ok(Placeholder::File {
+                max_len: parse_max_len(args)?,
+            }),
+            "line" => {
                 if !args.is_empty() {
                     return Err("expecting no argument");
                 }
-                Ok(Placeholder::Level)
+                Ok(Placeholder::Line)
             }
-            x if x == "target" => {
-                if !args.is_empty() {
-                    return Err("expecting no argument");
+            "message" => Ok(Placeholder::Message {
+                max_len: parse_max_len(args)?,
+            }),
+            "kv" => {
+                if !(2..=4).contains(&args.len()) {
+                    return Err("expecting two to four arguments");
                 }
-                Ok(Placeholder::Target)
+                let pair_separator = args[0].as_ref();
+                let kv_separator = args[1].as_ref();
+                let style = |i: usize| args.get(i).map_or(Ok(String::new()), |x| parse_style(x.as_ref()));
+                Ok(Placeholder::KeyValuePairs {
+                    pair_separator: pair_separator.to_string(),
+                    kv_separator: kv_separator.to_string(),
+                    key_style: style(2)?,
+                    value_style: style(3)?,
+                })
             }
-            x if x == "module" => {
+            "colorStart" => {
                 if !args.is_empty() {
                     return Err("expecting no argument");
                 }
-                Ok(Placeholder::Module)
+                Ok(Placeholder::ColorStart)
             }
-            x if x == "file" => {
+            "colorEnd" => {
                 if !args.is_empty() {
                     return Err("expecting no argument");
                 }
-                Ok(Placeholder::File)
+                Ok(Placeholder::ColorEnd)
             }
-            x if x == "line" => {
+            "traceId" => {
                 if !args.is_empty() {
                     return Err("expecting no argument");
                 }
-                Ok(Placeholder::Line)
+                Ok(Placeholder::TraceId)
             }
-            x if x == "message" => {
+            "spanId" => {
                 if !args.is_empty() {
                     return Err("expecting no argument");
                 }
-                Ok(Placeholder::Message)
+                Ok(Placeholder::SpanId)
             }
-            x if x == "kv" => {
-                if args.len() != 2 {
-                    return Err("expecting exactly two arguments");
+            "thread" => {
+                if !args.is_empty() {
+                    return Err("expecting no argument");
                 }
-                let pair_separator = args[0].as_ref();
-                let kv_separator = args[1].as_ref();
-                Ok(Placeholder::KeyValuePairs {
-                    pair_separator: pair_separator.to_string(),
-                    kv_separator: kv_separator.to_string(),
-                })
+                Ok(Placeholder::Thread)
             }
-            x if x == "colorStart" => {
+            "threadName" => {
                 if !args.is_empty() {
                     return Err("expecting no argument");
                 }
-                Ok(Placeholder::ColorStart)
+                Ok(Placeholder::ThreadName)
             }
-            x if x == "colorEnd" => {
+            "backtrace" => {
                 if !args.is_empty() {
                     return Err("expecting no argument");
                 }
-                Ok(Placeholder::ColorEnd)
-            }
-            _ => {
-                return Err("unknown placeholder name");
+                Ok(Placeholder::Backtrace)
             }
+            _ => Err("unknown placeholder name"),
         }
     }
 }
 
 impl Encoder for PatternEncoder {
-    fn encode(&self, datetime: &Datetime, record: &Record) -> String {
-        let mut result = String::new();
+    fn encode(&self, datetime: &Datetime, record: &OwnedRecord, buf: &mut Vec<u8>) {
+        // the level color to restore after styling the key-value pairs, between `{colorStart}` and `{colorEnd}`
+        let mut color = "";
         for placeholder in &self.placeholders {
             match placeholder {
                 Placeholder::Literal { content } => {
-                    write!(result, "{}", content).unwrap();
+                    buf.extend_from_slice(content.as_bytes());
                 }
                 Placeholder::Datetime { format } => {
-                    write!(result, "{}", datetime.format(format)).unwrap();
+                    write!(buf, "{}", datetime.format(format)).unwrap();
                 }
-                Placeholder::Level => {
-                    write!(result, "{}", record.level()).unwrap();
+                Placeholder::Level { format, width } => {
+                    let name = &self.level_names[record.level as usize - 1];
+                    let name = match format {
+                        LevelFormat::Name => Cow::Borrowed(name.as_str()),
+                        LevelFormat::Upper => Cow::Owned(name.to_uppercase()),
+                        LevelFormat::Lower => Cow::Owned(name.to_lowercase()),
+                        // may be the same for the custom names starting with the same character
+                        LevelFormat::Short => Cow::Borrowed(&name[..name.chars().next().map_or(0, char::len_utf8)]),
+                    };
+                    if *width > 0 {
+                        write!(buf, "{:<width$.width$}", name, width = *width).unwrap();
+                    } else {
+                        buf.extend_from_slice(name.as_bytes());
+                    }
                 }
-                Placeholder::Target => {
-                    write!(result, "{}", record.target()).unwrap();
+                Placeholder::Target { max_len } => {
+                    write_truncated(buf, &record.target, *max_len);
                 }
-                Placeholder::Module => {
-                    let module = record.module_path().unwrap_or(UNKNOWN_MODULE);
-                    write!(result, "{}", module).unwrap();
+                Placeholder::Module { max_len } => {
+                    let module = record.module_path.as_deref().unwrap_or(UNKNOWN_MODULE);
+                    write_truncated(buf, module, *max_len);
                 }
-                Placeholder::File => {
-                    let file = record.file().unwrap_or(UNKNOWN_FILE);
-                    write!(result, "{}", file).unwrap();
+                Placeholder::File { max_len } => {
+                    let file = record.file.as_deref().unwrap_or(UNKNOWN_FILE);
+                    write_truncated(buf, file, *max_len);
                 }
                 Placeholder::Line => {
-                    let line = record.line().unwrap_or(UNKNOWN_LINE);
-                    write!(result, "{}", line).unwrap();
+                    let line = record.line.unwrap_or(UNKNOWN_LINE);
+                    write!(buf, "{}", line).unwrap();
                 }
-                Placeholder::Message => {
-                    write!(result, "{}", record.args()).unwrap();
+                Placeholder::Message { max_len } => {
+                    write_truncated(buf, &record.message, *max_len);
                 }
                 Placeholder::KeyValuePairs {
-                    kv_separator,
                     pair_separator,
+                    kv_separator,
+                    key_style,
+                    value_style,
                 } => {
-                    struct Visitor<'a> {
-                        pair_separator: &'a str,
-                        kv_separator: &'a str,
-                        result: &'a mut String,
-                    }
-                    impl<'a> VisitSource<'a> for Visitor<'a> {
-                        fn visit_pair(
-                            &mut self,
-                            key: log::kv::Key,
-                            value: log::kv::Value,
-                        ) -> Result<(), log::kv::Error> {
-                            write!(
-                                self.result,
-                                "{}{}{}{}",
-                                self.pair_separator,
-                                key,
-                                self.kv_separator,
-                                serde_json::to_string(&value).unwrap()
-                            )
-                            .unwrap();
-                            Ok(())
-                        }
-                    }
-                    let mut visitor = Visitor {
+                    let format = KvFormat {
                         pair_separator,
                         kv_separator,
-                        result: &mut result,
+                        key_style: if self.color { key_style } else { "" },
+                        value_style: if self.color { value_style } else { "" },
+                        color,
                     };
-                    record.key_values().visit(&mut visitor).unwrap();
+                    for (key, value) in &record.kvs {
+                        self.write_kv(buf, key, value, &format);
+                    }
+                }
+                Placeholder::ColorStart if self.color => {
+                    color = level2color(record.level);
+                    write!(buf, "{}", color).unwrap();
+                }
+                Placeholder::ColorEnd if self.color => {
+                    color = "";
+                    write!(buf, "{}", ANSI_COLOR_RESET).unwrap();
+                }
+                Placeholder::ColorStart | Placeholder::ColorEnd => {}
+                Placeholder::TraceId => {
+                    if let Some(trace_id) = &record.trace_id {
+                        buf.extend_from_slice(trace_id.as_bytes());
+                    }
                 }
-                Placeholder::ColorStart => {
-                    write!(result, "{}", level2color(record.level())).unwrap();
+                Placeholder::SpanId => {
+                    if let Some(span_id) = &record.span_id {
+                        buf.extend_from_slice(span_id.as_bytes());
+                    }
+                }
+                Placeholder::Thread => {
+                    if let Some(thread_id) = record.thread_id {
+                        write!(buf, "{}", thread_id).unwrap();
+                    }
+                }
+                Placeholder::ThreadName => {
+                    if let Some(thread_name) = &record.thread_name {
+                        buf.extend_from_slice(thread_name.as_bytes());
+                    }
                 }
-                Placeholder::ColorEnd => {
-                    write!(result, "{}", ANSI_COLOR_RESET).unwrap();
+                Placeholder::Backtrace => {
+                    if let Some(backtrace) = &record.backtrace {
+                        buf.push(b'\n');
+                        buf.extend_from_slice(backtrace.trim_end().as_bytes());
+                    }
                 }
             }
         }
+    }
+}
+
+/// How to write the key-value pairs.
+struct KvFormat<'a> {
+    pair_separator: &'a str,
+    kv_separator: &'a str,
+    key_style: &'a str,
+    value_style: &'a str,
+    /// The color to restore after the styled keys and values.
+    color: &'a str,
+}
+
+impl KvFormat<'_> {
+    fn write_styled<F: FnOnce(&mut Vec<u8>)>(&self, buf: &mut Vec<u8>, style: &str, f: F) {
+        if style.is_empty() {
+            return f(buf);
+        }
+        buf.extend_from_slice(style.as_bytes());
+        f(buf);
+        buf.extend_from_slice(ANSI_COLOR_RESET.as_bytes());
+        buf.extend_from_slice(self.color.as_bytes());
+    }
 
-        result
+    fn write_key(&self, buf: &mut Vec<u8>, key: &str) {
+        buf.extend_from_slice(self.pair_separator.as_bytes());
+        self.write_styled(buf, self.key_style, |buf| buf.extend_from_slice(key.as_bytes()));
+        buf.extend_from_slice(self.kv_separator.as_bytes());
+    }
+}
+
+impl PatternEncoder {
+    /// Writes the key-value pair; the nested objects are flattened as dotted keys,
+    /// e.g. `user.id=42`, and the other values are written as JSON.
+    fn write_kv(&self, buf: &mut Vec<u8>, key: &str, value: &serde_json::Value, format: &KvFormat) {
+        if self.friendly_values {
+            if let Some(value) = friendly::render(key, value) {
+                format.write_key(buf, key);
+                format.write_styled(buf, format.value_style, |buf| {
+                    buf.extend_from_slice(value.as_bytes())
+                });
+                return;
+            }
+        }
+        match value {
+            serde_json::Value::Object(map) if !map.is_empty() => {
+                for (sub_key, value) in map {
+                    let key = format!("{}.{}", key, sub_key);
+                    self.write_kv(buf, &key, value, format);
+                }
+            }
+            _ => {
+                format.write_key(buf, key);
+                format.write_styled(buf, format.value_style, |buf| {
+                    serde_json::to_writer(&mut *buf, value).unwrap()
+                });
+            }
+        }
     }
 }
 
@@ -337,6 +571,7 @@ mod tests {
     use crate::encoder::Encoder;
     use crate::encoder::pattern::DEFAULT_DATETIME_FORMAT;
     use crate::encoder::tests::*;
+    use crate::record::OwnedRecord;
 
     #[test]
     fn test_parse_placeholder() {
@@ -362,28 +597,46 @@ mod tests {
 
         let tuple = ("level", empty);
         let placeholder = super::Placeholder::try_from(tuple).unwrap();
-        assert!(matches!(placeholder, super::Placeholder::Level));
-        let tuple = ("level", &[""][..]);
-        let result = super::Placeholder::try_from(tuple);
-        assert!(result.is_err());
+        assert!(matches!(placeholder, super::Placeholder::Level { format: super::LevelFormat::Name, width: 0 }));
+        for (arg, expected_format, expected_width) in [
+            ("upper", super::LevelFormat::Upper, 0),
+            ("lower", super::LevelFormat::Lower, 0),
+            ("short", super::LevelFormat::Short, 0),
+            ("upper5", super::LevelFormat::Upper, 5),
+            ("lower7", super::LevelFormat::Lower, 7),
+        ] {
+            let tuple = ("level", &[arg][..]);
+            let placeholder = super::Placeholder::try_from(tuple).unwrap();
+            assert!(
+                matches!(placeholder, super::Placeholder::Level { format, width } if format == expected_format && width == expected_width)
+            );
+        }
+        for arg in ["", "short5", "upper0", "upper+5", "upper-5", "upper 5", "upper5x", "upperx", "title"] {
+            let tuple = ("level", &[arg][..]);
+            let result = super::Placeholder::try_from(tuple);
+            assert!(result.is_err());
+        }
 
         let tuple = ("target", empty);
         let placeholder = super::Placeholder::try_from(tuple).unwrap();
-        assert!(matches!(placeholder, super::Placeholder::Target));
+        assert!(matches!(placeholder, super::Placeholder::Target { max_len: None }));
+        let tuple = ("target", &[".20"][..]);
+        let placeholder = super::Placeholder::try_from(tuple).unwrap();
+        assert!(matches!(placeholder, super::Placeholder::Target { max_len: Some(20) }));
         let tuple = ("target", &[""][..]);
         let result = super::Placeholder::try_from(tuple);
         assert!(result.is_err());
 
         let tuple = ("module", empty);
         let placeholder = super::Placeholder::try_from(tuple).unwrap();
-        assert!(matches!(placeholder, super::Placeholder::Module));
+        assert!(matches!(placeholder, super::Placeholder::Module { max_len: None }));
         let tuple = ("module", &[""][..]);
         let result = super::Placeholder::try_from(tuple);
         assert!(result.is_err());
 
         let tuple = ("file", empty);
         let placeholder = super::Placeholder::try_from(tuple).unwrap();
-        assert!(matches!(placeholder, super::Placeholder::File));
+        assert!(matches!(placeholder, super::Placeholder::File { max_len: None }));
         let tuple = ("file", &[""][..]);
         let result = super::Placeholder::try_from(tuple);
         assert!(result.is_err());
@@ -397,19 +650,59 @@ mod tests {
 
         let tuple = ("message", empty);
         let placeholder = super::Placeholder::try_from(tuple).unwrap();
-        assert!(matches!(placeholder, super::Placeholder::Message));
+        assert!(matches!(placeholder, super::Placeholder::Message { max_len: None }));
+        let tuple = ("message", &["max=200"][..]);
+        let placeholder = super::Placeholder::try_from(tuple).unwrap();
+        assert!(matches!(placeholder, super::Placeholder::Message { max_len: Some(200) }));
         let tuple = ("message", &[""][..]);
         let result = super::Placeholder::try_from(tuple);
         assert!(result.is_err());
+        for arg in ["200", "max=", ".0", "max=-1", "max=1.5"] {
+            let tuple = ("message", &[arg][..]);
+            let result = super::Placeholder::try_from(tuple);
+            assert!(result.is_err());
+        }
+        let tuple = ("message", &[".20", ".20"][..]);
+        let result = super::Placeholder::try_from(tuple);
+        assert!(result.is_err());
 
         let tuple = ("kv", &["|", "="][..]);
         let placeholder = super::Placeholder::try_from(tuple).unwrap();
         assert!(
-            matches!(placeholder, super::Placeholder::KeyValuePairs { pair_separator, kv_separator } if pair_separator == "|" && kv_separator == "=")
+            matches!(placeholder, super::Placeholder::KeyValuePairs { pair_separator, kv_separator, .. } if pair_separator == "|" && kv_separator == "=")
+        );
+        let tuple = ("kv", &["|", "=", "dim"][..]);
+        let placeholder = super::Placeholder::try_from(tuple).unwrap();
+        assert!(
+            matches!(placeholder, super::Placeholder::KeyValuePairs { key_style, value_style, .. } if key_style == "\x1b[2m" && value_style.is_empty())
         );
         let tuple = ("kv", empty);
         let result = super::Placeholder::try_from(tuple);
         assert!(result.is_err());
+        let tuple = ("kv", &["|", "=", "", "", ""][..]);
+        let result = super::Placeholder::try_from(tuple);
+        assert!(result.is_err());
+
+        let tuple = ("backtrace", empty);
+        let placeholder = super::Placeholder::try_from(tuple).unwrap();
+        assert!(matches!(placeholder, super::Placeholder::Backtrace));
+
+        let tuple = ("traceId", empty);
+        let placeholder = super::Placeholder::try_from(tuple).unwrap();
+        assert!(matches!(placeholder, super::Placeholder::TraceId));
+        let tuple = ("spanId", &[""][..]);
+        let result = super::Placeholder::try_from(tuple);
+        assert!(result.is_err());
+
+        let tuple = ("thread", empty);
+        let placeholder = super::Placeholder::try_from(tuple).unwrap();
+        assert!(matches!(placeholder, super::Placeholder::Thread));
+        let tuple = ("threadName", empty);
+        let placeholder = super::Placeholder::try_from(tuple).unwrap();
+        assert!(matches!(placeholder, super::Placeholder::ThreadName));
+        let tuple = ("threadName", &[""][..]);
+        let result = super::Placeholder::try_from(tuple);
+        assert!(result.is_err());
     }
 
     #[test]
@@ -422,20 +715,20 @@ mod tests {
         );
         assert!(matches!(&result[2], super::Placeholder::Literal { content } if content == "|"));
         assert!(matches!(&result[3], super::Placeholder::ColorStart));
-        assert!(matches!(&result[4], super::Placeholder::Level));
+        assert!(matches!(&result[4], super::Placeholder::Level { format: super::LevelFormat::Name, width: 0 }));
         assert!(matches!(&result[5], super::Placeholder::ColorEnd));
         assert!(matches!(&result[6], super::Placeholder::Literal { content } if content == "|"));
-        assert!(matches!(&result[7], super::Placeholder::Target));
+        assert!(matches!(&result[7], super::Placeholder::Target { max_len: None }));
         assert!(matches!(&result[8], super::Placeholder::Literal { content } if content == "|"));
-        assert!(matches!(&result[9], super::Placeholder::Module));
+        assert!(matches!(&result[9], super::Placeholder::Module { max_len: None }));
         assert!(matches!(&result[10], super::Placeholder::Literal { content } if content == "|"));
-        assert!(matches!(&result[11], super::Placeholder::File));
+        assert!(matches!(&result[11], super::Placeholder::File { max_len: None }));
         assert!(matches!(&result[12], super::Placeholder::Literal { content } if content == ":"));
         assert!(matches!(&result[13], super::Placeholder::Line));
         assert!(matches!(&result[14], super::Placeholder::Literal { content } if content == "|"));
-        assert!(matches!(&result[15], super::Placeholder::Message));
+        assert!(matches!(&result[15], super::Placeholder::Message { max_len: None }));
         assert!(
-            matches!(&result[16], super::Placeholder::KeyValuePairs { pair_separator, kv_separator } if pair_separator == "|" && kv_separator == "=")
+            matches!(&result[16], super::Placeholder::KeyValuePairs { pair_separator, kv_separator, .. } if pair_separator == "|" && kv_separator == "=")
         );
         assert!(matches!(&result[17], super::Placeholder::Literal { content } if content == " --"));
 
@@ -484,20 +777,23 @@ mod tests {
                     content: "|".to_string(),
                 },
                 super::Placeholder::ColorStart,
-                super::Placeholder::Level,
+                super::Placeholder::Level {
+                    format: super::LevelFormat::Name,
+                    width: 0,
+                },
                 super::Placeholder::ColorEnd,
                 super::Placeholder::Literal {
                     content: "|".to_string(),
                 },
-                super::Placeholder::Target,
+                super::Placeholder::Target { max_len: None },
                 super::Placeholder::Literal {
                     content: "|".to_string(),
                 },
-                super::Placeholder::Module,
+                super::Placeholder::Module { max_len: None },
                 super::Placeholder::Literal {
                     content: "|".to_string(),
                 },
-                super::Placeholder::File,
+                super::Placeholder::File { max_len: None },
                 super::Placeholder::Literal {
                     content: ":".to_string(),
                 },
@@ -505,20 +801,30 @@ mod tests {
                 super::Placeholder::Literal {
                     content: "|".to_string(),
                 },
-                super::Placeholder::Message,
+                super::Placeholder::Message { max_len: None },
                 super::Placeholder::KeyValuePairs {
                     pair_separator: "|".to_string(),
                     kv_separator: "=".to_string(),
+                    key_style: String::new(),
+                    value_style: String::new(),
                 },
             ],
+            friendly_values: false,
+            level_names: super::level_names(&Default::default()),
+            color: true,
         };
-        let result = encoder.encode(
+        let mut buf = vec![];
+        encoder.encode(
             &datetime,
-            &builder
-                .args(format_args!("{}", TEST_MESSAGE))
-                .key_values(&kvs)
-                .build(),
+            &OwnedRecord::from(
+                &builder
+                    .args(format_args!("{}", TEST_MESSAGE))
+                    .key_values(&kvs)
+                    .build(),
+            ),
+            &mut buf,
         );
+        let result = String::from_utf8(buf).unwrap();
 
         assert_eq!(
             result,
@@ -544,4 +850,178 @@ mod tests {
             )
         );
     }
+
+    #[test]
+    fn test_encode_nested_kv() {
+        let encoder = super::PatternEncoder {
+            placeholders: vec![super::Placeholder::KeyValuePairs {
+                pair_separator: " ".to_string(),
+                kv_separator: "=".to_string(),
+                key_style: String::new(),
+                value_style: String::new(),
+            }],
+            friendly_values: false,
+            level_names: super::level_names(&Default::default()),
+            color: true,
+        };
+        let mut record = OwnedRecord::from(&RecordBuilder::new().build());
+        record.kvs.push((
+            "user".to_string(),
+            serde_json::json!({"id": 42, "name": "foo", "address": {"city": "bar"}, "tags": ["a"], "extra": {}}),
+        ));
+        let mut buf = vec![];
+        encoder.encode(&test_datetime(), &record, &mut buf);
+        assert_eq!(
+            String::from_utf8(buf).unwrap(),
+            r#" user.id=42 user.name="foo" user.address.city="bar" user.tags=["a"] user.extra={}"#
+        );
+    }
+
+    #[test]
+    fn test_encode_friendly_values() {
+        let encoder = super::PatternEncoder {
+            placeholders: vec![super::Placeholder::KeyValuePairs {
+                pair_separator: " ".to_string(),
+                kv_separator: "=".to_string(),
+                key_style: String::new(),
+                value_style: String::new(),
+            }],
+            friendly_values: true,
+            level_names: super::level_names(&Default::default()),
+            color: true,
+        };
+        let mut record = OwnedRecord::from(&RecordBuilder::new().build());
+        record.kvs.push((
+            "elapsed".to_string(),
+            serde_json::to_value(std::time::Duration::from_micros(12300)).unwrap(),
+        ));
+        record.kvs.push(("body_bytes".to_string(), 4404019.into()));
+        record.kvs.push(("user".to_string(), serde_json::json!({"id": 42})));
+        let mut buf = vec![];
+        encoder.encode(&test_datetime(), &record, &mut buf);
+        assert_eq!(
+            String::from_utf8(buf).unwrap(),
+            " elapsed=12.3ms body_bytes=4.2MiB user.id=42"
+        );
+    }
+
+    #[test]
+    fn test_encode_truncated() {
+        let config = crate::config::PatternEncoderConfig {
+            pattern: "{target(.4)}|{message(max=6)}".to_string(),
+            ..Default::default()
+        };
+        let encoder = super::PatternEncoder::try_from(&config).unwrap();
+        for (target, message, expected) in [
+            ("app", "hello", "app|hello"),
+            ("myapp", "héllo!", "mya…|héllo!"),
+            ("myapp::db", "héllo, world", "mya…|héllo…"),
+        ] {
+            let record = OwnedRecord::from(&RecordBuilder::new().target(target).args(format_args!("{}", message)).build());
+            let mut buf = vec![];
+            encoder.encode(&test_datetime(), &record, &mut buf);
+            assert_eq!(String::from_utf8(buf).unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn test_encode_thread() {
+        let config = crate::config::PatternEncoderConfig {
+            pattern: "[{threadName}#{thread}]".to_string(),
+            ..Default::default()
+        };
+        let encoder = super::PatternEncoder::try_from(&config).unwrap();
+        let mut record = OwnedRecord::from(&RecordBuilder::new().build());
+        record.thread_id = Some(7);
+        record.thread_name = Some("worker-1".into());
+        let mut buf = vec![];
+        encoder.encode(&test_datetime(), &record, &mut buf);
+        assert_eq!(String::from_utf8(buf).unwrap(), "[worker-1#7]");
+
+        record.thread_id = None;
+        record.thread_name = None;
+        let mut buf = vec![];
+        encoder.encode(&test_datetime(), &record, &mut buf);
+        assert_eq!(String::from_utf8(buf).unwrap(), "[#]");
+    }
+
+    #[test]
+    fn test_encode_level_format() {
+        let config: crate::config::PatternEncoderConfig = serde_json::from_str(
+            r#"{"pattern": "{level(lower)}|{level(short)}|{level(upper5)}|", "level_names": {"warn": "Warning", "error": "CRITICAL"}}"#,
+        )
+        .unwrap();
+        let encoder = super::PatternEncoder::try_from(&config).unwrap();
+        for (level, expected) in [
+            (log::Level::Error, "critical|C|CRITI|"),
+            (log::Level::Info, "info|I|INFO |"),
+            (log::Level::Warn, "warning|W|WARNI|"),
+        ] {
+            let record = OwnedRecord::from(&RecordBuilder::new().level(level).build());
+            let mut buf = vec![];
+            encoder.encode(&test_datetime(), &record, &mut buf);
+            assert_eq!(String::from_utf8(buf).unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn test_encode_level_names() {
+        let config: crate::config::PatternEncoderConfig =
+            serde_json::from_str(r#"{"pattern": "{level}", "level_names": {"error": "SEVERE"}}"#).unwrap();
+        let encoder = super::PatternEncoder::try_from(&config).unwrap();
+        for (level, expected) in [(log::Level::Error, "SEVERE"), (log::Level::Info, "INFO")] {
+            let record = OwnedRecord::from(&RecordBuilder::new().level(level).build());
+            let mut buf = vec![];
+            encoder.encode(&test_datetime(), &record, &mut buf);
+            assert_eq!(String::from_utf8(buf).unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn test_encode_styled_kv() {
+        let config = crate::config::PatternEncoderConfig {
+            pattern: "{colorStart}{message}{kv( )(=)(dim)(bold+cyan)}{colorEnd}".to_string(),
+            ..Default::default()
+        };
+        let encoder = super::PatternEncoder::try_from(&config).unwrap();
+        let mut record = OwnedRecord::from(&RecordBuilder::new().level(log::Level::Warn).args(format_args!("m")).build());
+        record.kvs.push(("a".to_string(), 1.into()));
+        let mut buf = vec![];
+        encoder.encode(&test_datetime(), &record, &mut buf);
+        assert_eq!(
+            String::from_utf8(buf).unwrap(),
+            "\x1b[33mm \x1b[2ma\x1b[0m\x1b[33m=\x1b[1m\x1b[36m1\x1b[0m\x1b[33m\x1b[0m"
+        );
+
+        // the value style is optional
+        let config = crate::config::PatternEncoderConfig {
+            pattern: "{kv( )(=)(dim)}".to_string(),
+            ..Default::default()
+        };
+        let encoder = super::PatternEncoder::try_from(&config).unwrap();
+        let mut buf = vec![];
+        encoder.encode(&test_datetime(), &record, &mut buf);
+        assert_eq!(String::from_utf8(buf).unwrap(), " \x1b[2ma\x1b[0m=1");
+
+        let config = crate::config::PatternEncoderConfig {
+            pattern: "{kv( )(=)(dim)(blink)}".to_string(),
+            ..Default::default()
+        };
+        assert!(super::PatternEncoder::try_from(&config).is_err());
+    }
+
+    #[test]
+    fn test_encode_without_color() {
+        let config = crate::config::PatternEncoderConfig {
+            pattern: "{colorStart}{message}{kv( )(=)(dim)(bold+cyan)}{colorEnd}".to_string(),
+            ..Default::default()
+        };
+        let encoder = super::PatternEncoder::try_from(&config).unwrap().with_color(false);
+        // the escape sequences in the message are not the encoder's, so they're kept
+        let mut record = OwnedRecord::from(&RecordBuilder::new().args(format_args!("\x1b[1mm")).build());
+        record.kvs.push(("a".to_string(), 1.into()));
+        let mut buf = vec![];
+        encoder.encode(&test_datetime(), &record, &mut buf);
+        assert_eq!(String::from_utf8(buf).unwrap(), "\x1b[1mm a=1");
+    }
 }
@@ -0,0 +1,132 @@
+//! The C API, enabled by the `ffi` feature, for the Rust libraries embedded in C/C++ hosts
+//! to have the host configure the logging; see `include/naive_logger.h`.
+//!
+//! The levels are passed as integers, from `1` (error) to `5` (trace).
+//! The panics are caught and reported as failures, as they must not unwind into the host.
+
+use std::ffi::{c_char, c_int, CStr};
+use std::panic::AssertUnwindSafe;
+
+use log::{Level, Record};
+
+/// Initializes the logger with the config file; returns `0` on success, or `-1` on failure,
+/// with the error printed to stderr.
+///
+/// # Safety
+///
+/// `path` must be null or a valid nul-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn naive_logger_init_from_file(path: *const c_char) -> c_int {
+    let Some(path) = to_str(path) else {
+        eprintln!("naive_logger_init_from_file: invalid path");
+        return -1;
+    };
+    match crate::init(path) {
+        Ok(_) => 0,
+        Err(e) => {
+            eprintln!("naive_logger_init_from_file: {}", e);
+            -1
+        }
+    }
+}
+
+/// Logs the message with the level and target; returns `0` on success,
+/// or `-1` if the arguments are invalid or the logging panicked.
+///
+/// # Safety
+///
+/// `target` and `message` must be null or valid nul-terminated strings.
+#[no_mangle]
+pub unsafe extern "C" fn naive_logger_log(
+    level: c_int,
+    target: *const c_char,
+    message: *const c_char,
+) -> c_int {
+    let Some(level) = to_level(level) else {
+        return -1;
+    };
+    let (Some(target), Some(message)) = (to_str(target), to_str(message)) else {
+        return -1;
+    };
+    catch_panic("naive_logger_log", || {
+        log::logger().log(
+            &Record::builder()
+                .level(level)
+                .target(target)
+                .args(format_args!("{}", message))
+                .build(),
+        )
+    })
+}
+
+/// Flushes all the appenders, waiting until the pending messages are written;
+/// returns `0` on success, or `-1` if the flushing panicked.
+#[no_mangle]
+pub extern "C" fn naive_logger_flush() -> c_int {
+    catch_panic("naive_logger_flush", || log::logger().flush())
+}
+
+/// Runs the function; returns `0`, or `-1` if it panicked, with the panic printed to stderr.
+fn catch_panic<F: FnOnce()>(name: &str, f: F) -> c_int {
+    match std::panic::catch_unwind(AssertUnwindSafe(f)) {
+        Ok(()) => 0,
+        Err(e) => {
+            let message = e
+                .downcast_ref::<&str>()
+                .copied()
+                .or_else(|| e.downcast_ref::<String>().map(String::as_str))
+                .unwrap_or("unknown panic");
+            eprintln!("{}: panicked: {}", name, message);
+            -1
+        }
+    }
+}
+
+unsafe fn to_str<'a>(s: *const c_char) -> Option<&'a str> {
+    if s.is_null() {
+        return None;
+    }
+    CStr::from_ptr(s).to_str().ok()
+}
+
+fn to_level(level: c_int) -> Option<Level> {
+    match level {
+        1 => Some(Level::Error),
+        2 => Some(Level::Warn),
+        3 => Some(Level::Info),
+        4 => Some(Level::Debug),
+        5 => Some(Level::Trace),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ptr::null;
+
+    use log::Level;
+
+    #[test]
+    fn test_to_level() {
+        assert_eq!(super::to_level(1), Some(Level::Error));
+        assert_eq!(super::to_level(5), Some(Level::Trace));
+        assert_eq!(super::to_level(0), None);
+        assert_eq!(super::to_level(6), None);
+    }
+
+    #[test]
+    fn test_invalid_arguments() {
+        unsafe {
+            assert_eq!(super::naive_logger_init_from_file(null()), -1);
+            assert_eq!(super::naive_logger_init_from_file(c"__test_missing.yaml".as_ptr()), -1);
+            assert_eq!(super::naive_logger_log(3, null(), c"message".as_ptr()), -1);
+            assert_eq!(super::naive_logger_log(0, c"target".as_ptr(), c"message".as_ptr()), -1);
+        }
+    }
+
+    #[test]
+    fn test_catch_panic() {
+        assert_eq!(super::catch_panic("test", || {}), 0);
+        assert_eq!(super::catch_panic("test", || panic!("boom")), -1);
+    }
+}
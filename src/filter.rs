@@ -0,0 +1,112 @@
+//! The filters deciding which records are written by an appender, configured by its `filters`;
+//! a record is written only if it passes all of them.
+
+use std::collections::HashMap;
+use std::sync::{Arc, OnceLock};
+
+use regex::Regex;
+
+use crate::Error;
+use crate::config::FilterConfig;
+use crate::record::OwnedRecord;
+use crate::sync::Mutex;
+
+pub trait Filter: Send + Sync {
+    /// Returns `false` to keep the record from the appender.
+    fn filter(&self, record: &OwnedRecord) -> bool;
+}
+
+impl<F: Fn(&OwnedRecord) -> bool + Send + Sync> Filter for F {
+    fn filter(&self, record: &OwnedRecord) -> bool {
+        self(record)
+    }
+}
+
+type Registry = Mutex<HashMap<String, Arc<dyn Filter>>>;
+
+fn registry() -> &'static Registry {
+    static REGISTRY: OnceLock<Registry> = OnceLock::new();
+    REGISTRY.get_or_init(Default::default)
+}
+
+pub(crate) fn register<F: Filter + 'static>(name: &str, filter: F) {
+    registry().lock().insert(name.to_string(), Arc::new(filter));
+}
+
+pub(crate) fn from_config(config: &FilterConfig) -> Result<Arc<dyn Filter>, Error> {
+    match config {
+        FilterConfig::Threshold(config) => {
+            let level = config.level;
+            Ok(Arc::new(move |record: &OwnedRecord| record.level <= level))
+        }
+        FilterConfig::Regex(config) => {
+            let regex = Regex::new(&config.pattern)
+                .map_err(|e| Error::from(format!("invalid pattern '{}': {}", config.pattern, e)))?;
+            let exclude = config.exclude;
+            Ok(Arc::new(move |record: &OwnedRecord| regex.is_match(&record.message) != exclude))
+        }
+        FilterConfig::Custom(config) => registry()
+            .lock()
+            .get(&config.name)
+            .cloned()
+            .ok_or_else(|| Error::from(format!("no filter registered as '{}'", config.name))),
+    }
+}
+
+/// Creates the filters of an appender, in the configuration order.
+pub(crate) fn from_configs(configs: &[FilterConfig]) -> Result<Vec<Arc<dyn Filter>>, Error> {
+    configs
+        .iter()
+        .enumerate()
+        .map(|(i, config)| from_config(config).map_err(|e| e.concat(format!("failed to create filter #{}", i))))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use log::{Level, LevelFilter, RecordBuilder};
+
+    use crate::config::{FilterConfig, RegexFilterConfig, ThresholdFilterConfig};
+    use crate::record::OwnedRecord;
+
+    fn record(level: Level, message: &str) -> OwnedRecord {
+        OwnedRecord::from(&RecordBuilder::new().level(level).args(format_args!("{}", message)).build())
+    }
+
+    #[test]
+    fn test_from_config() {
+        let filter = super::from_config(&FilterConfig::Threshold(ThresholdFilterConfig {
+            level: LevelFilter::Warn,
+        }))
+        .unwrap();
+        assert!(filter.filter(&record(Level::Error, "x")));
+        assert!(filter.filter(&record(Level::Warn, "x")));
+        assert!(!filter.filter(&record(Level::Info, "x")));
+
+        let regex = |pattern: &str, exclude: bool| {
+            super::from_config(&FilterConfig::Regex(RegexFilterConfig {
+                pattern: pattern.to_string(),
+                exclude,
+            }))
+        };
+        let filter = regex("^health check", false).unwrap();
+        assert!(filter.filter(&record(Level::Info, "health check passed")));
+        assert!(!filter.filter(&record(Level::Info, "user logged in")));
+        let filter = regex("^health check", true).unwrap();
+        assert!(!filter.filter(&record(Level::Info, "health check passed")));
+        assert!(filter.filter(&record(Level::Info, "user logged in")));
+        assert!(regex("(", false).is_err());
+    }
+
+    #[test]
+    fn test_custom() {
+        super::register("test_no_secrets", |record: &OwnedRecord| !record.message.contains("secret"));
+        let config: FilterConfig = serde_json::from_str(r#"{"kind": "custom", "name": "test_no_secrets"}"#).unwrap();
+        let filter = super::from_config(&config).unwrap();
+        assert!(!filter.filter(&record(Level::Info, "the secret is 42")));
+        assert!(filter.filter(&record(Level::Info, "nothing to see")));
+
+        let config: FilterConfig = serde_json::from_str(r#"{"kind": "custom", "name": "test_missing"}"#).unwrap();
+        assert!(super::from_config(&config).is_err());
+    }
+}
@@ -0,0 +1,73 @@
+use std::time::{Duration, Instant};
+
+use log::{Level, Record};
+
+use crate::config::HeartbeatConfig;
+use crate::{Error, LogImplementation};
+
+/// Checks the config before the logger is installed, as the heartbeat thread is spawned after that.
+pub fn check(config: &HeartbeatConfig) -> Result<(), Error> {
+    if config.interval_secs == 0 {
+        return Err(Error::from("heartbeat interval must be positive"));
+    }
+    Ok(())
+}
+
+/// Spawns the thread logging the heartbeat messages through the installed logger.
+pub fn spawn(config: HeartbeatConfig, log_impl: &'static LogImplementation) -> Result<(), Error> {
+    let start = Instant::now();
+    std::thread::Builder::new()
+        .name("naive-logger-heartbeat".to_string())
+        .spawn(move || loop {
+            std::thread::sleep(Duration::from_secs(config.interval_secs));
+            beat(&config, start, log_impl.record_counts());
+        })
+        .map_err(|e| Error::from(format!("failed to spawn heartbeat thread: {}", e)))?;
+    Ok(())
+}
+
+fn beat(config: &HeartbeatConfig, start: Instant, counts: [u64; 5]) {
+    let kvs = kvs(start, counts);
+    let kvs: Vec<(&str, log::kv::Value)> = kvs
+        .iter()
+        .map(|(key, value)| (*key, log::kv::Value::from(*value)))
+        .collect();
+    log::logger().log(
+        &Record::builder()
+            .level(config.level)
+            .target(&config.target)
+            .args(format_args!("{}", config.message))
+            .key_values(&kvs)
+            .build(),
+    );
+}
+
+/// The uptime in seconds, and the numbers of the records logged since the initialization.
+fn kvs(start: Instant, counts: [u64; 5]) -> Vec<(&'static str, u64)> {
+    vec![
+        ("uptime_secs", start.elapsed().as_secs()),
+        ("records", counts.iter().sum()),
+        ("error_records", counts[Level::Error as usize - 1]),
+        ("warn_records", counts[Level::Warn as usize - 1]),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn test_kvs() {
+        let start = Instant::now() - Duration::from_secs(90);
+        let kvs = super::kvs(start, [1, 2, 3, 4, 5]);
+        assert_eq!(
+            kvs,
+            vec![
+                ("uptime_secs", 90),
+                ("records", 15),
+                ("error_records", 1),
+                ("warn_records", 2),
+            ]
+        );
+    }
+}
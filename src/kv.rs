@@ -0,0 +1,113 @@
+//! Structured key-value literals: [`kv!`](crate::kv) builds a `serde_json::Value` from the nested
+//! map/array literals with bare keys, to be captured by the `log` macros with `:serde`:
+//!
+//! ```
+//! let name = "foo";
+//! log::info!(user:serde = naive_logger::kv!({ id: 42, name: name, roles: ["admin", "dev"] }); "logged in");
+//! ```
+//!
+//! The pattern encoder flattens the maps as dotted keys (`user.id=42|user.name="foo"|...`),
+//! and the JSON encoder writes them as nested objects.
+//! The keys must be identifiers; the leaves can be any expressions implementing `Serialize`.
+
+use serde::Serialize;
+
+#[doc(hidden)]
+pub use serde_json::{Map, Value};
+
+#[macro_export]
+macro_rules! kv {
+    ({ $($body:tt)* }) => {
+        $crate::kv::__kv_object!([] $($body)*)
+    };
+    ([ $($body:tt)* ]) => {
+        $crate::kv::__kv_array!([] $($body)*)
+    };
+    ($value:expr) => {
+        $crate::kv::to_value(&$value)
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __kv_object {
+    ([$(($key:ident, $value:expr))*]) => {{
+        #[allow(unused_mut)]
+        let mut map = $crate::kv::Map::new();
+        $(map.insert(stringify!($key).to_string(), $value);)*
+        $crate::kv::Value::Object(map)
+    }};
+    ([$($done:tt)*] $key:ident : { $($value:tt)* } $(, $($rest:tt)*)?) => {
+        $crate::kv::__kv_object!([$($done)* ($key, $crate::kv!({ $($value)* }))] $($($rest)*)?)
+    };
+    ([$($done:tt)*] $key:ident : [ $($value:tt)* ] $(, $($rest:tt)*)?) => {
+        $crate::kv::__kv_object!([$($done)* ($key, $crate::kv!([ $($value)* ]))] $($($rest)*)?)
+    };
+    ([$($done:tt)*] $key:ident : $value:expr $(, $($rest:tt)*)?) => {
+        $crate::kv::__kv_object!([$($done)* ($key, $crate::kv!($value))] $($($rest)*)?)
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __kv_array {
+    ([$($value:expr),*]) => {
+        $crate::kv::Value::Array(vec![$($value),*])
+    };
+    ([$($done:expr),*] { $($value:tt)* } $(, $($rest:tt)*)?) => {
+        $crate::kv::__kv_array!([$($done,)* $crate::kv!({ $($value)* })] $($($rest)*)?)
+    };
+    ([$($done:expr),*] [ $($value:tt)* ] $(, $($rest:tt)*)?) => {
+        $crate::kv::__kv_array!([$($done,)* $crate::kv!([ $($value)* ])] $($($rest)*)?)
+    };
+    ([$($done:expr),*] $value:expr $(, $($rest:tt)*)?) => {
+        $crate::kv::__kv_array!([$($done,)* $crate::kv!($value)] $($($rest)*)?)
+    };
+}
+
+#[doc(hidden)]
+pub use crate::{__kv_array, __kv_object};
+
+/// Converts the leaf value; the values which can't be represented in JSON
+/// (e.g. the maps with non-string keys) are converted to `null`.
+#[doc(hidden)]
+pub fn to_value<T: Serialize + ?Sized>(value: &T) -> Value {
+    serde_json::to_value(value).unwrap_or(Value::Null)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    #[test]
+    fn test_kv() {
+        let name = "foo";
+        let value = crate::kv!({
+            id: 42,
+            name: name,
+            tags: ["a", { nested: [1, 2] }, []],
+            empty: {},
+            score: 1.5 * 2.0,
+        });
+        assert_eq!(
+            value,
+            json!({
+                "id": 42,
+                "name": "foo",
+                "tags": ["a", {"nested": [1, 2]}, []],
+                "empty": {},
+                "score": 3.0,
+            })
+        );
+        assert_eq!(crate::kv!([1, "x"]), json!([1, "x"]));
+        assert_eq!(crate::kv!(Some(1)), json!(1));
+    }
+
+    #[test]
+    fn test_kv_capture() {
+        let user = crate::kv!({ id: 42 });
+        let kvs = [("user", log::kv::Value::from_serde(&user))];
+        let record = crate::record::OwnedRecord::from(&log::Record::builder().key_values(&kvs).build());
+        assert_eq!(record.kvs, vec![("user".to_string(), json!({"id": 42}))]);
+    }
+}
@@ -1,19 +1,60 @@
+use std::backtrace::{Backtrace, BacktraceStatus};
+use std::borrow::Cow;
 use std::collections::{HashMap, HashSet};
 use std::fmt::{Display, Formatter};
 use std::path::Path;
-use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, OnceLock};
 
-use log::{LevelFilter, Log, Metadata, Record};
+use log::{Level, LevelFilter, Log, Metadata, Record};
 
-use crate::appender::Appender;
-use crate::config::{AppenderConfig, Config, LoggerConfig};
-use crate::logger::Logger;
+use crate::appender::AppenderWorker;
+use crate::config::{
+    AppenderConfig, BacktraceConfig, Config, ConfigFormat, EncoderConfig, ErrorPolicy, JsonEncoderConfig,
+    LoggerConfig, PatternEncoderConfig, Timezone,
+};
+use crate::filter::Filter;
+use crate::logger::{AtomicLevelFilter, Logger};
+use crate::processor::Processor;
+use crate::record::OwnedRecord;
+use crate::sync::RwLock;
+
+pub use crate::appender::Appender;
+pub use crate::encoder::Encoder;
+
+#[cfg(feature = "admin")]
+pub mod admin;
+#[cfg(feature = "cli")]
+pub mod check;
+#[cfg(feature = "clap")]
+pub mod cli;
+pub mod config;
+pub mod context;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+pub mod filter;
+pub mod kv;
+#[cfg(feature = "cli")]
+pub mod logcat;
+pub mod processor;
+pub mod record;
+pub mod scope;
+#[cfg(feature = "slog")]
+pub mod slog_bridge;
+pub mod stopwatch;
+pub mod test;
+#[cfg(feature = "tracing")]
+pub mod tracing_bridge;
 
 mod appender;
-mod config;
+mod clock;
+mod dropped;
 mod encoder;
+mod heartbeat;
 mod logger;
+mod sync;
 
+/// The timestamp of the records; despite the `Local` type, the offset is the one of the configured timezone.
 type Datetime = chrono::DateTime<chrono::Local>;
 
 #[derive(Debug)]
@@ -47,6 +88,23 @@ impl Error {
 }
 
 pub fn init<P: AsRef<Path>>(config_file: P) -> Result<(), Error> {
+    let source = format!("file '{}'", config_file.as_ref().display());
+    init_with_source(load_config(config_file)?, source)
+}
+
+/// Gets a commented starter config in the format (`yaml`, `toml` or `json`; JSON has no comments),
+/// with a console appender and a rotating file appender.
+pub fn example_config(format: &str) -> Option<&'static str> {
+    match format {
+        "yaml" | "yml" => Some(include_str!("templates/starter.yaml")),
+        "toml" => Some(include_str!("templates/starter.toml")),
+        "json" => Some(include_str!("templates/starter.json")),
+        _ => None,
+    }
+}
+
+/// Reads and deserializes the config file, by the deserializer determined by its extension.
+fn load_config<P: AsRef<Path>>(config_file: P) -> Result<Config, Error> {
     let path = config_file.as_ref();
     let content = std::fs::read_to_string(path)
         .map_err(|e| Error::from(format!("failed to read config file: {}", e)))?;
@@ -58,81 +116,313 @@ pub fn init<P: AsRef<Path>>(config_file: P) -> Result<(), Error> {
             let ext = s
                 .to_str()
                 .ok_or_else(|| Error::from("config filename contains invalid UTF-8"))?;
-            match ext {
-                x if x == "json" => init_from_json(content),
-                x if x == "toml" => init_from_toml(content),
-                x if x == "yaml" || x == "yml" => init_from_yaml(content),
-                _ => Err(Error::from(format!(
-                    "unsupported config file extension '{}'",
-                    ext
-                ))),
-            }
+            let format = match ext {
+                "json" => ConfigFormat::Json,
+                "toml" => ConfigFormat::Toml,
+                "yaml" | "yml" => ConfigFormat::Yaml,
+                _ => {
+                    return Err(Error::from(format!(
+                        "unsupported config file extension '{}'",
+                        ext
+                    )))
+                }
+            };
+            deserialize_config(&content, format)
         }
     }
 }
 
+fn deserialize_config(s: &str, format: ConfigFormat) -> Result<Config, Error> {
+    config::parse(s, format).map_err(|e| Error::from(format!("failed to deserialize config: {}", e)))
+}
+
+/// Registers a processor which can be referenced in the configuration as
+/// `{kind = "custom", name = "<name>"}`; it should be called before the initialization.
+pub fn register_processor<P: Processor + 'static>(name: &str, processor: P) {
+    processor::register(name, processor);
+}
+
+/// Registers a filter which can be referenced in the `filters` of the appenders as
+/// `{kind = "custom", name = "<name>"}`; it should be called before the initialization.
+pub fn register_filter<F: Filter + 'static>(name: &str, filter: F) {
+    filter::register(name, filter);
+}
+
+/// Registers another `log::Log` implementation which can be referenced by the appenders as
+/// `{kind = "forward", name = "<name>"}`; it should be called before the initialization.
+/// The records are forwarded to it after being routed and processed by this crate.
+pub fn register_forward_target(name: &str, logger: Box<dyn Log>) {
+    appender::register_forward_target(name, logger);
+}
+
+/// Makes the file appenders reopen their files at the next write, e.g. after rotated by logrotate
+/// with `create` rather than `copytruncate`; it only sets a flag, so it's safe in a signal handler like `SIGHUP`.
+pub fn reopen_files() {
+    appender::request_reopen();
+}
+
+/// Registers a hook which can be referenced in the `on_rotate` of the file appenders as
+/// `{kind = "custom", name = "<name>"}`; it should be called before the initialization.
+/// It's called with the path of each backup, on a background thread.
+pub fn register_rotate_hook<F: Fn(&Path) + Send + Sync + 'static>(name: &str, hook: F) {
+    appender::register_rotate_hook(name, hook);
+}
+
+/// Registers a kind of custom appenders which can be referenced in the configuration as
+/// `{kind = "custom", name = "<kind>", ...}`; it should be called before the initialization.
+/// The factory creates an appender from the other properties (as a JSON object) of each config.
+pub fn register_appender_kind<A, F>(kind: &str, factory: F)
+where
+    A: Appender + Send + 'static,
+    F: Fn(&serde_json::Value) -> Result<A, Error> + Send + Sync + 'static,
+{
+    appender::register_appender_kind(kind, factory);
+}
+
+/// Registers a kind of custom encoders which can be referenced in the `encoder` of the appenders as
+/// `{kind = "custom", name = "<kind>", ...}`; it should be called before the initialization.
+/// The factory creates an encoder from the other properties (as a JSON object) of each config;
+/// it's shared by the logging threads, which encode the records queued for the `async` appenders.
+pub fn register_encoder_kind<E, F>(kind: &str, factory: F)
+where
+    E: Encoder + Send + Sync + 'static,
+    F: Fn(&serde_json::Value) -> Result<E, Error> + Send + Sync + 'static,
+{
+    encoder::register_encoder_kind(kind, factory);
+}
+
 pub fn init_from_json<S: AsRef<str>>(s: S) -> Result<(), Error> {
-    let config = serde_json::from_str(s.as_ref())
-        .map_err(|e| Error::from(format!("failed to deserialize config: {}", e)))?;
-    init_from_config(config)
+    let config = deserialize_config(s.as_ref(), ConfigFormat::Json)?;
+    init_with_source(config, "json string".to_string())
 }
 
 pub fn init_from_toml<S: AsRef<str>>(s: S) -> Result<(), Error> {
-    let config = toml::from_str(s.as_ref())
-        .map_err(|e| Error::from(format!("failed to deserialize config: {}", e)))?;
-    init_from_config(config)
+    let config = deserialize_config(s.as_ref(), ConfigFormat::Toml)?;
+    init_with_source(config, "toml string".to_string())
 }
 
 pub fn init_from_yaml<S: AsRef<str>>(s: S) -> Result<(), Error> {
-    let config = serde_yaml::from_str(s.as_ref())
-        .map_err(|e| Error::from(format!("failed to deserialize config: {}", e)))?;
-    init_from_config(config)
-}
-
-fn init_from_config(config: Config) -> Result<(), Error> {
-    let appenders = construct_appenders(config.appenders)?;
-    let root_logger = Logger::new(&config.root, &appenders, None)
-        .map_err(|e| e.concat("failed to create root logger"))?;
-    let mut loggers = vec![];
-    for (i, config) in config.loggers.iter().enumerate() {
-        let logger = Logger::new(config, &appenders, Some(&root_logger))
-            .map_err(|e| e.concat(format!("failed to create logger #{}'", i)))?;
-        loggers.push(logger);
-    }
-    loggers.push(root_logger);
-    let global_level = get_global_level(std::iter::once(&config.root).chain(&config.loggers));
-
-    let log_impl = LogImplementation {
-        global_level,
-        loggers,
-        appenders: appenders.values().cloned().collect(),
-    };
+    let config = deserialize_config(s.as_ref(), ConfigFormat::Yaml)?;
+    init_with_source(config, "yaml string".to_string())
+}
+
+/// The installed logger, for the runtime controls.
+static INSTANCE: OnceLock<&'static LogImplementation> = OnceLock::new();
+
+/// Initializes the logger with the config constructed by the code,
+/// e.g. `Config::console_and_rotating_file("logs/app.log", LevelFilter::Info)`.
+pub fn init_from_config(config: Config) -> Result<(), Error> {
+    init_with_source(config, "code".to_string())
+}
+
+/// The environment variable overriding the encoders of the console appenders: `json` or `pattern`.
+const FORMAT_ENV: &str = "NAIVE_LOGGER_FORMAT";
+
+/// Initializes the logger; the source of the config is reported by [`log_startup_info`].
+fn init_with_source(mut config: Config, source: String) -> Result<(), Error> {
+    if let Ok(format) = std::env::var(FORMAT_ENV) {
+        override_console_format(&mut config, &format)
+            .map_err(|e| e.concat(format!("invalid {}", FORMAT_ENV)))?;
+    }
+    let heartbeat = config.heartbeat.clone();
+    let startup_info = config.startup_info;
+    let mut log_impl = LogImplementation::try_from(config)?;
+    log_impl.config_source = source;
+    let global_level = log_impl.global_level.get();
     let log_impl = Box::leak(Box::new(log_impl));
 
     log::set_max_level(global_level);
-    log::set_logger(log_impl).map_err(|e| Error::from(format!("failed to set logger: {}", e)))
+    log::set_logger(log_impl).map_err(|e| Error::from(format!("failed to set logger: {}", e)))?;
+    let _ = INSTANCE.set(log_impl);
+    if let Some(heartbeat) = heartbeat {
+        heartbeat::spawn(heartbeat, log_impl)?;
+    }
+    if startup_info {
+        log_impl.log_startup_info();
+    }
+    Ok(())
 }
 
+/// Replaces the encoders of the console appenders by the format, including the default root appender;
+/// the `pattern` encoders are kept as they are when switching to `pattern`.
+fn override_console_format(config: &mut Config, format: &str) -> Result<(), Error> {
+    let json = match format {
+        "" => return Ok(()),
+        "json" => true,
+        "pattern" => false,
+        _ => return Err(Error::from(format!("unknown format '{}'", format))),
+    };
+    config.add_default_root_appender();
+    for appender in config.appenders.values_mut() {
+        let AppenderConfig::Console(config) = appender else {
+            continue;
+        };
+        let encoder = &mut config.common.encoder;
+        match encoder {
+            EncoderConfig::Json(_) if !json => {
+                *encoder = EncoderConfig::Pattern(PatternEncoderConfig::default())
+            }
+            EncoderConfig::Pattern(_) if json => {
+                *encoder = EncoderConfig::Json(JsonEncoderConfig::default())
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+fn instance() -> Result<&'static LogImplementation, Error> {
+    INSTANCE
+        .get()
+        .copied()
+        .ok_or_else(|| Error::from("logger is not initialized"))
+}
+
+/// Gets the levels of the loggers, identified by their targets (`root` for the root logger),
+/// in the matching order.
+pub fn logger_levels() -> Result<Vec<(String, LevelFilter)>, Error> {
+    Ok(instance()?.logger_levels())
+}
+
+/// Changes the level of the logger identified by its target (`root` for the root logger) at runtime;
+/// if multiple loggers have the same target, the first one is changed.
+pub fn set_logger_level(name: &str, level: LevelFilter) -> Result<(), Error> {
+    instance()?.set_logger_level(name, level)
+}
+
+/// Silences the whole process until the guard is dropped, e.g. while running an interactive prompt.
+pub fn quiet() -> Result<LevelGuard, Error> {
+    verbose(LevelFilter::Off)
+}
+
+/// Overrides the levels of all the loggers with the level until the guard is dropped,
+/// e.g. to see the details of a chatty startup phase.
+pub fn verbose(level: LevelFilter) -> Result<LevelGuard, Error> {
+    let log_impl = instance()?;
+    let levels = log_impl.override_levels(level);
+    Ok(LevelGuard { log_impl, levels })
+}
+
+/// Restores the levels of the loggers overridden by [`quiet`] or [`verbose`] when dropped.
+///
+/// The nested guards should be dropped in the reverse order; the levels changed by
+/// [`set_logger_level`] while the guard is alive are also restored.
+#[must_use = "the levels are restored when the guard is dropped"]
+pub struct LevelGuard {
+    log_impl: &'static LogImplementation,
+    levels: Vec<LevelFilter>,
+}
+
+impl Drop for LevelGuard {
+    fn drop(&mut self) {
+        self.log_impl.restore_levels(&self.levels);
+    }
+}
+
+/// Logs an `info` message with the target `naive_logger`, describing the program
+/// (the executable name and the process ID), the version of this crate, the source of the config,
+/// the appenders and the levels of the loggers; useful in the support bundles.
+///
+/// It can also be logged automatically after the initialization, by the `startup_info` setting.
+pub fn log_startup_info() -> Result<(), Error> {
+    instance()?.log_startup_info();
+    Ok(())
+}
+
+impl TryFrom<Config> for LogImplementation {
+    type Error = Error;
+
+    fn try_from(mut config: Config) -> Result<Self, Self::Error> {
+        if let Some(heartbeat) = &config.heartbeat {
+            heartbeat::check(heartbeat)?;
+        }
+        config.add_default_root_appender();
+        let appenders = construct_appenders(config.appenders, config.error_policy, config.timezone)?;
+        let root_logger = Logger::new(&config.root, &appenders, None)
+            .map_err(|e| e.concat("failed to create root logger"))?;
+        let mut loggers = vec![];
+        for (i, config) in config.loggers.iter().enumerate() {
+            let logger = Logger::new(config, &appenders, Some(&root_logger))
+                .map_err(|e| e.concat(format!("failed to create logger #{}'", i)))?;
+            loggers.push(logger);
+        }
+        loggers.push(root_logger);
+        let global_level = get_global_level(std::iter::once(&config.root).chain(&config.loggers));
+        let mut processors = vec![];
+        for (i, config) in config.processors.iter().enumerate() {
+            let processor = processor::from_config(config)
+                .map_err(|e| e.concat(format!("failed to create processor #{}", i)))?;
+            processors.push(processor);
+        }
+        if config.max_message_bytes.is_some() || config.max_record_bytes.is_some() {
+            processors.push(Arc::new(processor::SizeLimiter {
+                max_message_bytes: config.max_message_bytes,
+                max_record_bytes: config.max_record_bytes,
+            }));
+        }
+
+        let mut appender_names: Vec<_> = appenders
+            .iter()
+            .filter(|(_, appender)| appender.is_some())
+            .map(|(name, _)| name.clone())
+            .collect();
+        appender_names.sort();
+
+        Ok(LogImplementation {
+            global_level: AtomicLevelFilter::new(global_level),
+            loggers,
+            appenders: appenders.values().flatten().cloned().collect(),
+            appender_names,
+            config_source: String::new(),
+            route_cache: RwLock::new(HashMap::new()),
+            backtrace: config.backtrace,
+            processors,
+            record_counts: config.heartbeat.as_ref().map(|_| Default::default()),
+            timezone: config.timezone,
+        })
+    }
+}
+
+/// The file stem of the executable, or empty if unknown.
+fn program_name() -> String {
+    std::env::current_exe()
+        .ok()
+        .and_then(|path| Some(path.file_stem()?.to_string_lossy().into_owned()))
+        .unwrap_or_default()
+}
+
+/// Constructs the appenders; the disabled ones are mapped to `None`.
 fn construct_appenders(
     config_map: HashMap<String, AppenderConfig>,
-) -> Result<HashMap<String, Arc<Mutex<dyn Appender + Send>>>, Error> {
+    error_policy: ErrorPolicy,
+    timezone: Timezone,
+) -> Result<HashMap<String, Option<Arc<AppenderWorker>>>, Error> {
     let mut result = HashMap::new();
     let mut path_set = HashSet::new();
+    let file_configs = config_map.values().flat_map(AppenderConfig::file_configs);
+    let log_files = appender::initial_paths(file_configs, timezone);
     for (name, config) in config_map {
-        if let AppenderConfig::File(config) = &config {
-            let path = config.path.to_str().ok_or_else(|| {
-                Error::from(format!("appender '{}': path contains invalid UTF-8", name))
-            })?;
-            if !path_set.insert(path.to_string()) {
+        if !config.enabled() {
+            result.insert(name, None);
+            continue;
+        }
+        for config in config.file_configs() {
+            if !path_set.insert(config.path_or_pattern().to_path_buf()) {
                 return Err(Error::from(format!(
                     "appenders: path '{}' is used by multiple appenders",
-                    path
+                    config.path_or_pattern().display()
                 )));
             }
+            appender::cleanup_stale_files(config, timezone, &log_files)
+                .map_err(|e| e.concat(format!("failed to create appender '{}'", name)))?;
         }
-        let appender = appender::from_config(&config)
+        let appender = appender::from_config(&config, timezone)
             .map_err(|e| e.concat(format!("failed to create appender '{}'", name)))?;
-        result.insert(name, appender);
+        let filters = filter::from_configs(config.filters())
+            .map_err(|e| e.concat(format!("failed to create appender '{}'", name)))?;
+        let worker = AppenderWorker::spawn(&name, appender, config.queue(), filters, error_policy)
+            .map_err(|e| e.concat(format!("failed to create appender '{}'", name)))?;
+        result.insert(name, Some(Arc::new(worker)));
     }
     Ok(result)
 }
@@ -143,33 +433,428 @@ fn get_global_level<'a, I: Iterator<Item = &'a LoggerConfig>>(it: I) -> LevelFil
         .unwrap_or(LevelFilter::Info)
 }
 
+/// The route cache stops growing when it holds too many targets, in case the targets are generated dynamically;
+/// the routes of the other targets are decided every time.
+const MAX_CACHED_TARGETS: usize = 4096;
+
+/// The index of the logger handling the records of each level, for a specific target.
+type Routes = [Option<usize>; 5];
+
+fn capture_backtrace(config: BacktraceConfig) -> Option<String> {
+    let backtrace = match config {
+        BacktraceConfig::Off => return None,
+        BacktraceConfig::Env => Backtrace::capture(),
+        BacktraceConfig::Always => Backtrace::force_capture(),
+    };
+    match backtrace.status() {
+        BacktraceStatus::Captured => Some(backtrace.to_string()),
+        _ => None,
+    }
+}
+
 struct LogImplementation {
-    global_level: LevelFilter,
+    global_level: AtomicLevelFilter,
+    /// The other loggers in the matching order, followed by the root logger.
     loggers: Vec<Logger>,
-    appenders: Vec<Arc<Mutex<dyn Appender + Send>>>,
+    appenders: Vec<Arc<AppenderWorker>>,
+    appender_names: Vec<String>,
+    /// Where the config is loaded from, e.g. `file 'log.yaml'`.
+    config_source: String,
+    /// Keyed by the static targets without copying them, e.g. the module paths.
+    route_cache: RwLock<HashMap<Cow<'static, str>, Routes>>,
+    backtrace: BacktraceConfig,
+    processors: Vec<Arc<dyn Processor>>,
+    /// The numbers of the records appended, by level; only counted for the heartbeat.
+    record_counts: Option<[AtomicU64; 5]>,
+    timezone: Timezone,
+}
+
+impl LogImplementation {
+    /// Finds the logger handling the record with the given target (also given if static) and level.
+    /// The decisions are cached per target; the cache is cleared when the logger levels change.
+    /// The decisions are made with the cache locked, so that they won't be based on the stale levels.
+    fn route(&self, target: &str, static_target: Option<&'static str>, level: Level) -> Option<&Logger> {
+        let index = level as usize - 1;
+        {
+            let cache = self.route_cache.read();
+            if let Some(routes) = cache.get(target) {
+                return routes[index].map(|i| &self.loggers[i]);
+            }
+            if cache.len() >= MAX_CACHED_TARGETS {
+                return self.routes(target)[index].map(|i| &self.loggers[i]);
+            }
+        }
+
+        let mut cache = self.route_cache.write();
+        let routes = self.routes(target);
+        if cache.len() < MAX_CACHED_TARGETS {
+            let key = match static_target {
+                Some(target) => Cow::Borrowed(target),
+                None => Cow::Owned(target.to_string()),
+            };
+            cache.insert(key, routes);
+        }
+        routes[index].map(|i| &self.loggers[i])
+    }
+
+    fn routes(&self, target: &str) -> Routes {
+        let mut routes: Routes = [None; 5];
+        for (i, level) in Level::iter().enumerate() {
+            routes[i] = self
+                .loggers
+                .iter()
+                .position(|logger| logger.matches(target, level));
+        }
+        routes
+    }
+
+    fn record_counts(&self) -> [u64; 5] {
+        match &self.record_counts {
+            Some(counts) => std::array::from_fn(|i| counts[i].load(Ordering::Relaxed)),
+            None => [0; 5],
+        }
+    }
+
+    fn logger_name(&self, i: usize) -> &str {
+        if i == self.loggers.len() - 1 {
+            "root"
+        } else {
+            self.loggers[i].target()
+        }
+    }
+
+    fn logger_levels(&self) -> Vec<(String, LevelFilter)> {
+        (0..self.loggers.len())
+            .map(|i| (self.logger_name(i).to_string(), self.loggers[i].level()))
+            .collect()
+    }
+
+    fn startup_info(&self) -> Vec<(&'static str, String)> {
+        let program = program_name();
+        let levels: Vec<_> = self
+            .logger_levels()
+            .into_iter()
+            .map(|(name, level)| format!("{}={}", name, level.as_str().to_lowercase()))
+            .collect();
+        vec![
+            ("program", program),
+            ("pid", std::process::id().to_string()),
+            ("naive_logger_version", env!("CARGO_PKG_VERSION").to_string()),
+            ("config_source", self.config_source.clone()),
+            ("appenders", self.appender_names.join(",")),
+            ("levels", levels.join(",")),
+        ]
+    }
+
+    fn log_startup_info(&self) {
+        let info = self.startup_info();
+        let kvs: Vec<(&str, &str)> = info.iter().map(|(key, value)| (*key, value.as_str())).collect();
+        self.log(
+            &Record::builder()
+                .level(Level::Info)
+                .target("naive_logger")
+                .args(format_args!("logger initialized"))
+                .key_values(&kvs)
+                .build(),
+        );
+    }
+
+    fn set_logger_level(&self, name: &str, level: LevelFilter) -> Result<(), Error> {
+        let i = (0..self.loggers.len())
+            .find(|&i| self.logger_name(i) == name)
+            .ok_or_else(|| Error::from(format!("no logger '{}'", name)))?;
+        self.update_levels(|| self.loggers[i].set_level(level));
+        Ok(())
+    }
+
+    /// Sets the levels of all the loggers; returns the previous ones.
+    fn override_levels(&self, level: LevelFilter) -> Vec<LevelFilter> {
+        let levels = self.loggers.iter().map(Logger::level).collect();
+        self.update_levels(|| self.loggers.iter().for_each(|logger| logger.set_level(level)));
+        levels
+    }
+
+    fn restore_levels(&self, levels: &[LevelFilter]) {
+        self.update_levels(|| {
+            for (logger, level) in self.loggers.iter().zip(levels) {
+                logger.set_level(*level);
+            }
+        });
+    }
+
+    /// Changes the levels of the loggers by the function, with the route cache locked;
+    /// then updates the global level and clears the cache.
+    fn update_levels<F: FnOnce()>(&self, f: F) {
+        let mut cache = self.route_cache.write();
+        f();
+        let global_level = self.loggers.iter().map(Logger::level).max().unwrap();
+        self.global_level.set(global_level);
+        log::set_max_level(global_level);
+        cache.clear();
+    }
 }
 
 impl Log for LogImplementation {
     fn enabled(&self, metadata: &Metadata) -> bool {
-        metadata.level() <= self.global_level
+        metadata.level() <= self.global_level.get()
+            && self.route(metadata.target(), None, metadata.level()).is_some()
     }
 
     fn log(&self, record: &Record) {
-        if !self.enabled(record.metadata()) {
+        if record.level() > self.global_level.get() {
             return;
         }
-        let now = chrono::Local::now();
-        for logger in &self.loggers {
-            if logger.handle(&now, record) {
-                return;
+        // the target is the static module path by default
+        let static_target = record.module_path_static().filter(|x| *x == record.target());
+        if let Some(logger) = self.route(record.target(), static_target, record.level()) {
+            let now = clock::now(self.timezone);
+            let mut owned = OwnedRecord::from(record);
+            if record.level() == Level::Error {
+                owned.backtrace = capture_backtrace(self.backtrace);
+            }
+            for processor in &self.processors {
+                if !processor.process(&mut owned) {
+                    return;
+                }
+            }
+            logger.append(&now, &Arc::new(owned));
+            if let Some(counts) = &self.record_counts {
+                counts[record.level() as usize - 1].fetch_add(1, Ordering::Relaxed);
             }
         }
     }
 
     fn flush(&self) {
-        for appender in &self.appenders {
-            let mut guard = appender.lock().unwrap();
-            guard.flush();
+        let acks: Vec<_> = self
+            .appenders
+            .iter()
+            .map(|appender| appender.request_flush())
+            .collect();
+        for ack in acks {
+            let _ = ack.recv();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use log::{Level, LevelFilter, Log, MetadataBuilder};
+
+    use crate::config::Config;
+
+    #[test]
+    fn test_route() {
+        let config = r#"
+        {
+            "appenders": {
+                "console": {
+                    "kind": "console",
+                    "encoder": {
+                        "kind": "pattern"
+                    }
+                }
+            },
+            "root": {
+                "level": "info",
+                "appenders": ["console"]
+            },
+            "loggers": [
+                {
+                    "target": "myapp::profiler",
+                    "target_matcher": "exact",
+                    "level": "trace"
+                },
+                {
+                    "target": "myapp::",
+                    "target_matcher": "prefix_inverse",
+                    "level": "warn"
+                }
+            ]
+        }
+        "#;
+        let config: Config = serde_json::from_str(config).unwrap();
+        let log_impl = super::LogImplementation::try_from(config).unwrap();
+
+        let cases = [
+            ("myapp::profiler", Level::Trace, Some(0)),
+            ("myapp::profiler", Level::Error, Some(0)),
+            ("myapp::handler", Level::Info, Some(2)),
+            ("myapp::handler", Level::Debug, None),
+            ("other", Level::Warn, Some(1)),
+            ("other", Level::Info, Some(2)),
+            ("other", Level::Debug, None),
+        ];
+        for _ in 0..2 {
+            for (target, level, expected) in cases {
+                let logger = log_impl.route(target, Some(target), level);
+                let index = logger.map(|x| {
+                    log_impl
+                        .loggers
+                        .iter()
+                        .position(|y| std::ptr::eq(x, y))
+                        .unwrap()
+                });
+                assert_eq!(index, expected);
+
+                let metadata = MetadataBuilder::new().target(target).level(level).build();
+                assert_eq!(log_impl.enabled(&metadata), expected.is_some());
+            }
+        }
+        assert_eq!(log_impl.route_cache.read().len(), 3);
+        assert!(log_impl.route_cache.read().keys().all(|x| matches!(x, std::borrow::Cow::Borrowed(_))));
+
+        // the cache stops growing when full, and the other targets are still routed
+        for i in 0..super::MAX_CACHED_TARGETS {
+            let target = format!("dynamic::{}", i);
+            assert!(log_impl.route(&target, None, Level::Info).is_some());
+        }
+        assert_eq!(log_impl.route_cache.read().len(), super::MAX_CACHED_TARGETS);
+        for (target, level, expected) in cases {
+            let logger = log_impl.route(target, None, level);
+            assert_eq!(logger.is_some(), expected.is_some());
+        }
+        let logger = log_impl.route("other::uncached", None, Level::Warn);
+        assert!(std::ptr::eq(logger.unwrap(), &log_impl.loggers[1]));
+        assert_eq!(log_impl.route_cache.read().len(), super::MAX_CACHED_TARGETS);
+    }
+
+    #[test]
+    fn test_example_config() {
+        let config: Config = serde_yaml::from_str(super::example_config("yaml").unwrap()).unwrap();
+        assert_eq!(config.appenders.len(), 2);
+        let config: Config = toml::from_str(super::example_config("toml").unwrap()).unwrap();
+        assert_eq!(config.appenders.len(), 2);
+        let config: Config = serde_json::from_str(super::example_config("json").unwrap()).unwrap();
+        assert_eq!(config.appenders.len(), 2);
+        assert!(super::example_config("ini").is_none());
+    }
+
+    #[test]
+    fn test_set_logger_level() {
+        let config = r#"
+        {
+            "appenders": {"console": {"kind": "console", "encoder": {"kind": "pattern"}}},
+            "root": {"level": "info", "appenders": ["console"]},
+            "loggers": [{"target": "myapp::", "level": "warn"}]
+        }
+        "#;
+        let config: Config = serde_json::from_str(config).unwrap();
+        let log_impl = super::LogImplementation::try_from(config).unwrap();
+        let metadata = MetadataBuilder::new().target("myapp::handler").level(Level::Debug).build();
+        assert!(!log_impl.enabled(&metadata));
+
+        log_impl.set_logger_level("myapp::", LevelFilter::Debug).unwrap();
+        assert!(log_impl.enabled(&metadata));
+        assert_eq!(log_impl.global_level.get(), LevelFilter::Debug);
+        assert_eq!(
+            log_impl.logger_levels(),
+            vec![
+                ("myapp::".to_string(), LevelFilter::Debug),
+                ("root".to_string(), LevelFilter::Info),
+            ]
+        );
+
+        log_impl.set_logger_level("root", LevelFilter::Trace).unwrap();
+        assert_eq!(log_impl.global_level.get(), LevelFilter::Trace);
+        assert!(log_impl.set_logger_level("other", LevelFilter::Trace).is_err());
+    }
+
+    #[test]
+    fn test_disabled_appender() {
+        let config = r#"
+        {
+            "appenders": {
+                "console": {"kind": "console", "encoder": {"kind": "pattern"}},
+                "file": {"kind": "file", "enabled": false, "encoder": {"kind": "pattern"}, "path": "__test_disabled/disabled.log"}
+            },
+            "root": {"appenders": ["console", "file"]},
+            "loggers": [{"target": "myapp::", "appenders": ["file"]}]
+        }
+        "#;
+        let config: Config = serde_json::from_str(config).unwrap();
+        let log_impl = super::LogImplementation::try_from(config).unwrap();
+        assert_eq!(log_impl.appenders.len(), 1);
+        assert_eq!(log_impl.appender_names, vec!["console"]);
+        assert!(!std::path::Path::new("__test_disabled").exists());
+    }
+
+    #[test]
+    fn test_override_console_format() {
+        use crate::config::{AppenderConfig, EncoderConfig};
+
+        let config = r#"
+        {
+            "appenders": {
+                "console": {"kind": "console", "encoder": {"kind": "pattern"}},
+                "file": {"kind": "file", "encoder": {"kind": "pattern"}, "path": "__test_format.log"}
+            },
+            "root": {"appenders": ["console", "file"]},
+            "loggers": []
+        }
+        "#;
+        let mut config: Config = serde_json::from_str(config).unwrap();
+        super::override_console_format(&mut config, "json").unwrap();
+        assert!(matches!(&config.appenders["console"], AppenderConfig::Console(x) if matches!(x.common.encoder, EncoderConfig::Json(_))));
+        assert!(matches!(&config.appenders["file"], AppenderConfig::File(x) if matches!(x.common.encoder, EncoderConfig::Pattern(_))));
+
+        super::override_console_format(&mut config, "pattern").unwrap();
+        assert!(matches!(&config.appenders["console"], AppenderConfig::Console(x) if matches!(x.common.encoder, EncoderConfig::Pattern(_))));
+        assert!(super::override_console_format(&mut config, "xml").is_err());
+
+        // the default root appender is added before the override
+        let mut config: Config = serde_json::from_str("{}").unwrap();
+        super::override_console_format(&mut config, "json").unwrap();
+        let appender = &config.appenders[crate::config::DEFAULT_ROOT_APPENDER];
+        assert!(matches!(appender, AppenderConfig::Console(x) if matches!(x.common.encoder, EncoderConfig::Json(_))));
+    }
+
+    #[test]
+    fn test_override_levels() {
+        let config = r#"
+        {
+            "appenders": {"console": {"kind": "console", "encoder": {"kind": "pattern"}}},
+            "root": {"level": "info", "appenders": ["console"]},
+            "loggers": [{"target": "myapp::", "level": "warn"}]
+        }
+        "#;
+        let config: Config = serde_json::from_str(config).unwrap();
+        let log_impl = super::LogImplementation::try_from(config).unwrap();
+        let metadata = |level| MetadataBuilder::new().target("myapp::handler").level(level).build();
+
+        let quiet = log_impl.override_levels(LevelFilter::Off);
+        assert!(!log_impl.enabled(&metadata(Level::Error)));
+        let verbose = log_impl.override_levels(LevelFilter::Debug);
+        assert!(log_impl.enabled(&metadata(Level::Debug)));
+        assert_eq!(log_impl.global_level.get(), LevelFilter::Debug);
+
+        log_impl.restore_levels(&verbose);
+        assert!(!log_impl.enabled(&metadata(Level::Error)));
+        log_impl.restore_levels(&quiet);
+        assert!(log_impl.enabled(&metadata(Level::Warn)));
+        assert!(!log_impl.enabled(&metadata(Level::Debug)));
+        assert_eq!(log_impl.global_level.get(), LevelFilter::Info);
+    }
+
+    #[test]
+    fn test_startup_info() {
+        let config = r#"
+        {
+            "appenders": {
+                "stdout": {"kind": "console", "encoder": {"kind": "pattern"}},
+                "json": {"kind": "console", "encoder": {"kind": "json"}}
+            },
+            "root": {"level": "info", "appenders": ["stdout"]},
+            "loggers": [{"target": "myapp::", "level": "warn", "appenders": ["json"]}]
         }
+        "#;
+        let config: Config = serde_json::from_str(config).unwrap();
+        let mut log_impl = super::LogImplementation::try_from(config).unwrap();
+        log_impl.config_source = "json string".to_string();
+        let info = log_impl.startup_info();
+        let get = |key| info.iter().find(|(x, _)| *x == key).unwrap().1.as_str();
+        assert_eq!(get("naive_logger_version"), env!("CARGO_PKG_VERSION"));
+        assert_eq!(get("config_source"), "json string");
+        assert_eq!(get("appenders"), "json,stdout");
+        assert_eq!(get("levels"), "myapp::=warn,root=info");
     }
 }
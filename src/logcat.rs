@@ -0,0 +1,273 @@
+//! The `naive-logcat` command, enabled by the `cli` feature:
+//! tails a log file written by the `json` encoder, and re-renders the messages by a pattern,
+//! filtered by the level, target and key-value pairs.
+//!
+//! The lines which are not JSON objects are printed as they are.
+
+use std::fs::File;
+use std::io::{stdin, stdout, BufRead, BufReader, Seek, SeekFrom, Write};
+use std::path::PathBuf;
+use std::time::Duration;
+
+use clap::Parser;
+use log::{Level, LevelFilter};
+
+use crate::config::PatternEncoderConfig;
+use crate::encoder::Encoder;
+use crate::encoder::pattern::PatternEncoder;
+use crate::record::OwnedRecord;
+use crate::{Datetime, Error};
+
+const DEFAULT_PATTERN: &str = "{colorStart}{datetime}|{level}|{target}|{message}{kv(|)(=)}{colorEnd}";
+const DEFAULT_PATTERN_NO_COLOR: &str = "{datetime}|{level}|{target}|{message}{kv(|)(=)}";
+
+/// The interval to poll the file for the new lines when following it.
+const FOLLOW_INTERVAL: Duration = Duration::from_millis(200);
+
+/// Tails a JSON log file and pretty-prints the messages.
+#[derive(Parser, Debug)]
+#[command(name = "naive-logcat")]
+struct Args {
+    /// The log file; `-` for stdin
+    file: PathBuf,
+    /// Keep reading the new lines appended to the file
+    #[arg(short = 'f', long)]
+    follow: bool,
+    /// Only print the messages at this level or severer
+    #[arg(long, value_name = "LEVEL")]
+    level: Option<LevelFilter>,
+    /// Only print the messages whose targets start with this prefix
+    #[arg(long, value_name = "PREFIX")]
+    target: Option<String>,
+    /// Only print the messages with this key-value pair; can be repeated
+    #[arg(long = "kv", value_name = "KEY=VALUE", value_parser = parse_kv)]
+    kvs: Vec<(String, String)>,
+    /// The pattern to render the messages, as the one of the `pattern` encoder
+    #[arg(long)]
+    pattern: Option<String>,
+    /// Don't colorize the messages
+    #[arg(long)]
+    no_color: bool,
+}
+
+fn parse_kv(s: &str) -> Result<(String, String), String> {
+    let (key, value) = s
+        .split_once('=')
+        .ok_or_else(|| format!("expecting KEY=VALUE, got '{}'", s))?;
+    Ok((key.to_string(), value.to_string()))
+}
+
+/// Runs the command with the process arguments.
+pub fn main() {
+    let args = Args::parse();
+    if let Err(e) = run(&args) {
+        eprintln!("naive-logcat: {}", e);
+        std::process::exit(1);
+    }
+}
+
+fn run(args: &Args) -> Result<(), Error> {
+    let pattern = match (&args.pattern, args.no_color) {
+        (Some(pattern), _) => pattern.clone(),
+        (None, false) => DEFAULT_PATTERN.to_string(),
+        (None, true) => DEFAULT_PATTERN_NO_COLOR.to_string(),
+    };
+    let encoder = PatternEncoder::try_from(&PatternEncoderConfig {
+        pattern,
+        ..Default::default()
+    })?;
+    let filter = Filter {
+        level: args.level.unwrap_or(LevelFilter::Trace),
+        target: args.target.clone(),
+        kvs: args.kvs.clone(),
+    };
+
+    let mut stdout = stdout().lock();
+    let mut print = |line: &str| -> Result<(), Error> {
+        let mut buf = vec![];
+        match parse_line(line) {
+            Some((datetime, record)) => {
+                if !filter.matches(&record) {
+                    return Ok(());
+                }
+                encoder.encode(&datetime, &record, &mut buf);
+            }
+            None => buf.extend_from_slice(line.as_bytes()),
+        }
+        buf.push(b'\n');
+        stdout
+            .write_all(&buf)
+            .map_err(|e| Error::from(format!("failed to write stdout: {}", e)))
+    };
+
+    if args.file.as_os_str() == "-" {
+        for line in stdin().lock().lines() {
+            let line = line.map_err(|e| Error::from(format!("failed to read stdin: {}", e)))?;
+            print(&line)?;
+        }
+        return Ok(());
+    }
+
+    let open = || {
+        File::open(&args.file).map_err(|e| Error::from(format!("failed to open log file: {}", e)))
+    };
+    let mut reader = BufReader::new(open()?);
+    let mut position = 0;
+    let mut line = String::new();
+    loop {
+        line.clear();
+        let n = reader
+            .read_line(&mut line)
+            .map_err(|e| Error::from(format!("failed to read log file: {}", e)))?;
+        if n > 0 && line.ends_with('\n') {
+            position += n as u64;
+            print(line.trim_end_matches(['\r', '\n']))?;
+            continue;
+        }
+        if !args.follow {
+            if n > 0 {
+                print(&line)?;
+            }
+            return Ok(());
+        }
+
+        // wait for the rest of the partial line, and reopen the file if it's rotated
+        std::thread::sleep(FOLLOW_INTERVAL);
+        let rotated = match (std::fs::metadata(&args.file), reader.get_ref().metadata()) {
+            (Ok(x), Ok(y)) => x.len() < position || !same_file(&x, &y),
+            _ => false,
+        };
+        if rotated {
+            reader = BufReader::new(open()?);
+            position = 0;
+        } else {
+            reader
+                .seek(SeekFrom::Start(position))
+                .map_err(|e| Error::from(format!("failed to read log file: {}", e)))?;
+        }
+    }
+}
+
+#[cfg(unix)]
+fn same_file(x: &std::fs::Metadata, y: &std::fs::Metadata) -> bool {
+    use std::os::unix::fs::MetadataExt;
+    x.dev() == y.dev() && x.ino() == y.ino()
+}
+
+#[cfg(not(unix))]
+fn same_file(_x: &std::fs::Metadata, _y: &std::fs::Metadata) -> bool {
+    true
+}
+
+struct Filter {
+    level: LevelFilter,
+    target: Option<String>,
+    kvs: Vec<(String, String)>,
+}
+
+impl Filter {
+    fn matches(&self, record: &OwnedRecord) -> bool {
+        if record.level > self.level {
+            return false;
+        }
+        if let Some(target) = &self.target {
+            if !record.target.starts_with(target.as_str()) {
+                return false;
+            }
+        }
+        self.kvs.iter().all(|(key, value)| {
+            record.kvs.iter().any(|(x, y)| {
+                x == key
+                    && match y {
+                        serde_json::Value::String(y) => y == value,
+                        y => serde_json::from_str(value).is_ok_and(|x: serde_json::Value| x == *y),
+                    }
+            })
+        })
+    }
+}
+
+/// Parses the line written by the `json` encoder.
+fn parse_line(line: &str) -> Option<(Datetime, OwnedRecord)> {
+    let serde_json::Value::Object(mut object) = serde_json::from_str(line).ok()? else {
+        return None;
+    };
+    let mut take_str = |key: &str| match object.remove(key) {
+        Some(serde_json::Value::String(x)) => Some(x),
+        _ => None,
+    };
+    let level: Level = take_str("level")?.parse().ok()?;
+    let target = take_str("target").unwrap_or_default();
+    let module_path = take_str("module");
+    let file = take_str("file");
+    let message = take_str("message").unwrap_or_default();
+    let backtrace = take_str("backtrace");
+    let trace_id = take_str("trace_id");
+    let span_id = take_str("span_id");
+    let thread_name = take_str("thread_name");
+    let datetime = chrono::DateTime::from_timestamp_millis(object.get("timestamp")?.as_i64()?)?;
+    let line = object.get("line").and_then(|x| x.as_u64()).map(|x| x as u32);
+    let thread_id = object.get("thread_id").and_then(|x| x.as_u64());
+    let kvs = match object.remove("args") {
+        Some(serde_json::Value::Object(args)) => args.into_iter().collect(),
+        _ => vec![],
+    };
+
+    let record = OwnedRecord {
+        level,
+        target: target.into(),
+        module_path: module_path.map(Into::into),
+        file: file.map(Into::into),
+        line,
+        message,
+        kvs,
+        error_chains: vec![],
+        backtrace,
+        trace_id,
+        span_id,
+        thread_id,
+        thread_name: thread_name.map(Into::into),
+    };
+    Some((datetime.into(), record))
+}
+
+#[cfg(test)]
+mod tests {
+    use log::{Level, LevelFilter};
+
+    #[test]
+    fn test_parse_line() {
+        let line = r#"{"timestamp":1722400496789,"level":"WARN","target":"myapp::test","module":"myapp::test","file":"src/main.rs","line":42,"message":"hello","args":{"user":"foo","count":1}}"#;
+        let (datetime, record) = super::parse_line(line).unwrap();
+        assert_eq!(datetime.timestamp_millis(), 1722400496789);
+        assert_eq!(record.level, Level::Warn);
+        assert_eq!(&*record.target, "myapp::test");
+        assert_eq!(record.line, Some(42));
+        assert_eq!(record.message, "hello");
+        assert_eq!(
+            record.kvs,
+            vec![
+                ("user".to_string(), "foo".into()),
+                ("count".to_string(), 1.into()),
+            ]
+        );
+
+        assert!(super::parse_line("plain text").is_none());
+        assert!(super::parse_line(r#"{"level":"INFO"}"#).is_none());
+    }
+
+    #[test]
+    fn test_filter() {
+        let line = r#"{"timestamp":0,"level":"INFO","target":"myapp::test","message":"","args":{"user":"foo","count":1}}"#;
+        let (_, record) = super::parse_line(line).unwrap();
+        let filter = |level, target: Option<&str>, kvs: &[(&str, &str)]| super::Filter {
+            level,
+            target: target.map(String::from),
+            kvs: kvs.iter().map(|(x, y)| (x.to_string(), y.to_string())).collect(),
+        };
+        assert!(filter(LevelFilter::Info, Some("myapp::"), &[("user", "foo"), ("count", "1")]).matches(&record));
+        assert!(!filter(LevelFilter::Warn, None, &[]).matches(&record));
+        assert!(!filter(LevelFilter::Trace, Some("other"), &[]).matches(&record));
+        assert!(!filter(LevelFilter::Trace, None, &[("user", "bar")]).matches(&record));
+    }
+}
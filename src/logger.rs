@@ -1,29 +1,50 @@
 use std::collections::HashMap;
-use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
 
-use log::{LevelFilter, Record};
+use log::{Level, LevelFilter};
 
 use crate::{Datetime, Error};
-use crate::appender::Appender;
+use crate::appender::AppenderWorker;
 use crate::config::{LoggerConfig, LoggerTargetMatcher};
+use crate::record::OwnedRecord;
+
+/// A level filter which can be changed at runtime.
+pub struct AtomicLevelFilter(AtomicUsize);
+
+impl AtomicLevelFilter {
+    pub fn new(level: LevelFilter) -> Self {
+        Self(AtomicUsize::new(level as usize))
+    }
+
+    pub fn get(&self) -> LevelFilter {
+        LevelFilter::iter()
+            .nth(self.0.load(Ordering::Relaxed))
+            .unwrap()
+    }
+
+    pub fn set(&self, level: LevelFilter) {
+        self.0.store(level as usize, Ordering::Relaxed);
+    }
+}
 
 pub struct Logger {
     target: String,
     target_matcher: LoggerTargetMatcher,
-    level: LevelFilter,
-    appenders: Vec<Arc<Mutex<dyn Appender + Send>>>,
+    level: AtomicLevelFilter,
+    appenders: Vec<Arc<AppenderWorker>>,
 }
 
 impl Logger {
     pub fn new(
         config: &LoggerConfig,
-        appenders: &HashMap<String, Arc<Mutex<dyn Appender + Send>>>,
+        appenders: &HashMap<String, Option<Arc<AppenderWorker>>>,
         root_logger: Option<&Logger>,
     ) -> Result<Self, Error> {
         let mut logger = Self {
             target: config.target.clone(),
             target_matcher: config.target_matcher,
-            level: config.level,
+            level: AtomicLevelFilter::new(config.level),
             appenders: vec![],
         };
         if config.appenders.is_empty() {
@@ -36,39 +57,42 @@ impl Logger {
                 let appender = appenders.get(name).ok_or_else(|| {
                     Error::from(format!("no appender '{}'", name))
                 })?;
-                logger.appenders.push(appender.clone());
+                // the references to the disabled appenders are ignored
+                if let Some(appender) = appender {
+                    logger.appenders.push(appender.clone());
+                }
             }
         }
         Ok(logger)
     }
 
-    pub fn handle(&self, datetime: &Datetime, record: &Record) -> bool {
-        if record.level() > self.level {
+    pub fn target(&self) -> &str {
+        &self.target
+    }
+
+    pub fn level(&self) -> LevelFilter {
+        self.level.get()
+    }
+
+    pub fn set_level(&self, level: LevelFilter) {
+        self.level.set(level);
+    }
+
+    pub fn matches(&self, target: &str, level: Level) -> bool {
+        if level > self.level.get() {
             return false;
         }
 
         match self.target_matcher {
-            LoggerTargetMatcher::Prefix => {
-                if !record.target().starts_with(&self.target) {
-                    return false;
-                }
-            }
-            LoggerTargetMatcher::PrefixInverse => {
-                if record.target().starts_with(&self.target) {
-                    return false;
-                }
-            }
-            LoggerTargetMatcher::Exact => {
-                if record.target() != self.target {
-                    return false;
-                }
-            }
+            LoggerTargetMatcher::Prefix => target.starts_with(&self.target),
+            LoggerTargetMatcher::PrefixInverse => !target.starts_with(&self.target),
+            LoggerTargetMatcher::Exact => target == self.target,
         }
+    }
 
+    pub fn append(&self, datetime: &Datetime, record: &Arc<OwnedRecord>) {
         for appender in &self.appenders {
-            let mut guard = appender.lock().unwrap();
-            guard.append(datetime, record);
+            appender.append(datetime, record);
         }
-        true
     }
 }
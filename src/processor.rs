@@ -0,0 +1,315 @@
+//! The processors transform the records before they are sent to the appenders.
+//!
+//! Besides the built-in processors, custom processors can be registered by name
+//! with [`register_processor`](crate::register_processor),
+//! and referenced in the configuration as `{kind = "custom", name = "<name>"}`.
+
+use std::collections::HashMap;
+use std::sync::{Arc, OnceLock};
+
+use crate::Error;
+use crate::config::{ProcessorConfig, RedactConfig};
+use crate::record::OwnedRecord;
+use crate::sync::Mutex;
+
+pub trait Processor: Send + Sync {
+    /// Transforms the record; returns `false` to drop it.
+    fn process(&self, record: &mut OwnedRecord) -> bool;
+}
+
+impl<F: Fn(&mut OwnedRecord) -> bool + Send + Sync> Processor for F {
+    fn process(&self, record: &mut OwnedRecord) -> bool {
+        self(record)
+    }
+}
+
+type Registry = Mutex<HashMap<String, Arc<dyn Processor>>>;
+
+fn registry() -> &'static Registry {
+    static REGISTRY: OnceLock<Registry> = OnceLock::new();
+    REGISTRY.get_or_init(Default::default)
+}
+
+pub(crate) fn register<P: Processor + 'static>(name: &str, processor: P) {
+    registry()
+        .lock()
+        .insert(name.to_string(), Arc::new(processor));
+}
+
+pub(crate) fn from_config(config: &ProcessorConfig) -> Result<Arc<dyn Processor>, Error> {
+    match config {
+        ProcessorConfig::Hostname(config) => {
+            let hostname = hostname().ok_or_else(|| Error::from("failed to get hostname"))?;
+            Ok(Arc::new(SetProcessor(vec![(
+                config.key.clone(),
+                hostname.into(),
+            )])))
+        }
+        ProcessorConfig::Pid(config) => Ok(Arc::new(SetProcessor(vec![(
+            config.key.clone(),
+            std::process::id().into(),
+        )]))),
+        ProcessorConfig::Env(config) => {
+            let kvs = config
+                .vars
+                .iter()
+                .filter_map(|(key, var)| Some((key.clone(), std::env::var(var).ok()?.into())))
+                .collect();
+            Ok(Arc::new(SetProcessor(kvs)))
+        }
+        ProcessorConfig::Remove(config) => {
+            let keys = config.keys.clone();
+            Ok(Arc::new(move |record: &mut OwnedRecord| {
+                record.kvs.retain(|(key, _)| !keys.contains(key));
+                true
+            }))
+        }
+        ProcessorConfig::Redact(config) => Ok(Arc::new(Redactor::from(config))),
+        ProcessorConfig::Custom(config) => registry()
+            .lock()
+            .get(&config.name)
+            .cloned()
+            .ok_or_else(|| Error::from(format!("no processor registered as '{}'", config.name))),
+    }
+}
+
+/// Sets the key-value pairs determined at initialization.
+struct SetProcessor(Vec<(String, serde_json::Value)>);
+
+impl Processor for SetProcessor {
+    fn process(&self, record: &mut OwnedRecord) -> bool {
+        for (key, value) in &self.0 {
+            record.set_kv(key, value.clone());
+        }
+        true
+    }
+}
+
+/// Masks the values of the key-value pairs; see [`RedactConfig`].
+pub(crate) struct Redactor {
+    keys: Vec<String>,
+    allow_keys: Option<Vec<String>>,
+    mask: serde_json::Value,
+}
+
+impl From<&RedactConfig> for Redactor {
+    fn from(config: &RedactConfig) -> Self {
+        Self {
+            keys: config.keys.clone(),
+            allow_keys: config.allow_keys.clone(),
+            mask: config.mask.clone().into(),
+        }
+    }
+}
+
+impl Redactor {
+    pub fn should_redact(&self, key: &str) -> bool {
+        let contains = |keys: &[String]| keys.iter().any(|x| x.eq_ignore_ascii_case(key));
+        if contains(&self.keys) {
+            return true;
+        }
+        match &self.allow_keys {
+            None => false,
+            Some(allow_keys) => !contains(allow_keys),
+        }
+    }
+}
+
+impl Processor for Redactor {
+    fn process(&self, record: &mut OwnedRecord) -> bool {
+        for (key, value) in &mut record.kvs {
+            if self.should_redact(key) {
+                *value = self.mask.clone();
+            }
+        }
+        record.error_chains.retain(|(key, _)| !self.should_redact(key));
+        true
+    }
+}
+
+/// Truncates the giant records; applied after all the configured processors.
+pub(crate) struct SizeLimiter {
+    pub max_message_bytes: Option<usize>,
+    pub max_record_bytes: Option<usize>,
+}
+
+impl SizeLimiter {
+    /// The approximate size of the record: the message, the keys and the JSON values.
+    fn record_bytes(record: &OwnedRecord) -> usize {
+        record.message.len() + record.kvs.iter().map(|(k, v)| k.len() + kv_bytes(v)).sum::<usize>()
+    }
+}
+
+fn kv_bytes(value: &serde_json::Value) -> usize {
+    match value {
+        serde_json::Value::String(x) => x.len(),
+        x => x.to_string().len(),
+    }
+}
+
+/// Truncates the string to at most `max` bytes, at a char boundary.
+fn truncate(s: &mut String, max: usize) {
+    if s.len() > max {
+        let mut i = max;
+        while !s.is_char_boundary(i) {
+            i -= 1;
+        }
+        s.truncate(i);
+    }
+}
+
+impl Processor for SizeLimiter {
+    fn process(&self, record: &mut OwnedRecord) -> bool {
+        let mut markers = vec![];
+        if let Some(max) = self.max_message_bytes {
+            let len = record.message.len();
+            if len > max {
+                truncate(&mut record.message, max);
+                markers.push(("message_truncated", len));
+            }
+        }
+        if let Some(max) = self.max_record_bytes {
+            let size = Self::record_bytes(record);
+            if size > max {
+                // drop the key-value pairs from the last one, then truncate the message
+                let mut kvs_bytes = size - record.message.len();
+                while kvs_bytes > max.saturating_sub(record.message.len()) {
+                    let Some((key, value)) = record.kvs.pop() else {
+                        break;
+                    };
+                    kvs_bytes -= key.len() + kv_bytes(&value);
+                    record.error_chains.retain(|(x, _)| *x != key);
+                }
+                truncate(&mut record.message, max - kvs_bytes);
+                markers.push(("record_truncated", size));
+            }
+        }
+        for (key, size) in markers {
+            record.set_kv(key, size.into());
+        }
+        true
+    }
+}
+
+pub(crate) fn hostname() -> Option<String> {
+    if let Ok(hostname) = std::env::var("HOSTNAME").or_else(|_| std::env::var("COMPUTERNAME")) {
+        return Some(hostname);
+    }
+    let hostname = std::fs::read_to_string("/proc/sys/kernel/hostname")
+        .or_else(|_| std::fs::read_to_string("/etc/hostname"))
+        .ok()?;
+    Some(hostname.trim().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use log::RecordBuilder;
+
+    use crate::config::ProcessorConfig;
+    use crate::record::OwnedRecord;
+
+    fn process(config: &str, record: &mut OwnedRecord) -> bool {
+        let config: ProcessorConfig = serde_json::from_str(config).unwrap();
+        super::from_config(&config).unwrap().process(record)
+    }
+
+    #[test]
+    fn test_builtin_processors() {
+        let mut record = OwnedRecord::from(&RecordBuilder::new().build());
+        record.kvs.push(("password".to_string(), "secret".into()));
+
+        assert!(process(r#"{"kind": "pid"}"#, &mut record));
+        std::env::set_var("__TEST_PROCESSOR_POD", "pod-0");
+        assert!(process(
+            r#"{"kind": "env", "vars": {"pod": "__TEST_PROCESSOR_POD", "node": "__TEST_PROCESSOR_NODE"}}"#,
+            &mut record
+        ));
+        assert!(process(r#"{"kind": "remove", "keys": ["password"]}"#, &mut record));
+        assert_eq!(
+            record.kvs,
+            vec![
+                ("pid".to_string(), std::process::id().into()),
+                ("pod".to_string(), "pod-0".into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_size_limiter() {
+        use super::Processor;
+
+        let limiter = super::SizeLimiter {
+            max_message_bytes: Some(8),
+            max_record_bytes: Some(16),
+        };
+        let mut record = OwnedRecord::from(&RecordBuilder::new().args(format_args!("héllo world")).build());
+        record.kvs.push(("a".to_string(), "12".into()));
+        record.kvs.push(("b".to_string(), "1234567890".into()));
+        assert!(limiter.process(&mut record));
+        assert_eq!(record.message, "héllo w");
+        assert_eq!(
+            record.kvs,
+            vec![
+                ("a".to_string(), "12".into()),
+                ("message_truncated".to_string(), 12.into()),
+                ("record_truncated".to_string(), 22.into()),
+            ]
+        );
+
+        let limiter = super::SizeLimiter {
+            max_message_bytes: None,
+            max_record_bytes: Some(4),
+        };
+        let mut record = OwnedRecord::from(&RecordBuilder::new().args(format_args!("hello")).build());
+        record.kvs.push(("a".to_string(), 1.into()));
+        assert!(limiter.process(&mut record));
+        assert_eq!(record.message, "hell");
+        assert_eq!(record.kvs, vec![("record_truncated".to_string(), 7.into())]);
+    }
+
+    #[test]
+    fn test_redact() {
+        let mut record = OwnedRecord::from(&RecordBuilder::new().build());
+        record.kvs.push(("user".to_string(), "foo".into()));
+        record.kvs.push(("Password".to_string(), "secret".into()));
+        record.kvs.push(("token".to_string(), "xxx".into()));
+        assert!(process(
+            r#"{"kind": "redact", "keys": ["password"]}"#,
+            &mut record
+        ));
+        assert_eq!(
+            record.kvs,
+            vec![
+                ("user".to_string(), "foo".into()),
+                ("Password".to_string(), "***".into()),
+                ("token".to_string(), "xxx".into()),
+            ]
+        );
+        assert!(process(
+            r#"{"kind": "redact", "allow_keys": ["user"], "mask": "-"}"#,
+            &mut record
+        ));
+        assert_eq!(
+            record.kvs,
+            vec![
+                ("user".to_string(), "foo".into()),
+                ("Password".to_string(), "-".into()),
+                ("token".to_string(), "-".into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_custom_processor() {
+        crate::register_processor("__test_drop_all", |_: &mut OwnedRecord| false);
+        let mut record = OwnedRecord::from(&RecordBuilder::new().build());
+        assert!(!process(
+            r#"{"kind": "custom", "name": "__test_drop_all"}"#,
+            &mut record
+        ));
+
+        let config: ProcessorConfig =
+            serde_json::from_str(r#"{"kind": "custom", "name": "__test_unknown"}"#).unwrap();
+        assert!(super::from_config(&config).is_err());
+    }
+}
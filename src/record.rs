@@ -0,0 +1,350 @@
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, OnceLock};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use log::kv::{Key, Value, VisitSource};
+use log::{Level, Record};
+
+use crate::context;
+use crate::sync::RwLock;
+
+/// The intern table is cleared when it holds too many strings,
+/// in case the strings are generated dynamically.
+const MAX_INTERNED_STRINGS: usize = 4096;
+
+thread_local! {
+    /// The copies got by the current thread, so that the shared table is only locked on the first use of a string;
+    /// the static strings are looked up by their addresses, the others by their contents.
+    static LOCAL_STATICS: RefCell<HashMap<(usize, usize), Arc<str>>> = Default::default();
+    static LOCAL_STRINGS: RefCell<HashSet<Arc<str>>> = Default::default();
+}
+
+/// Gets the shared copy of the static string, like [`intern`] but looked up by its address.
+fn intern_static(s: &'static str) -> Arc<str> {
+    let key = (s.as_ptr() as usize, s.len());
+    LOCAL_STATICS
+        .try_with(|statics| {
+            let mut statics = statics.borrow_mut();
+            if let Some(x) = statics.get(&key) {
+                return x.clone();
+            }
+            if statics.len() >= MAX_INTERNED_STRINGS {
+                statics.clear();
+            }
+            let x = intern_shared(s);
+            statics.insert(key, x.clone());
+            x
+        })
+        .unwrap_or_else(|_| intern_shared(s))
+}
+
+/// Gets the shared copy of the string, as the targets, module paths and file paths
+/// repeat constantly among the records.
+fn intern(s: &str) -> Arc<str> {
+    LOCAL_STRINGS
+        .try_with(|strings| {
+            let mut strings = strings.borrow_mut();
+            if let Some(x) = strings.get(s) {
+                return x.clone();
+            }
+            if strings.len() >= MAX_INTERNED_STRINGS {
+                strings.clear();
+            }
+            let x = intern_shared(s);
+            strings.insert(x.clone());
+            x
+        })
+        // not available when logging in the destructors of the other thread-local variables
+        .unwrap_or_else(|_| intern_shared(s))
+}
+
+fn intern_shared(s: &str) -> Arc<str> {
+    static TABLE: OnceLock<RwLock<HashSet<Arc<str>>>> = OnceLock::new();
+    let table = TABLE.get_or_init(Default::default);
+    if let Some(x) = table.read().get(s) {
+        return x.clone();
+    }
+    let mut table = table.write();
+    if let Some(x) = table.get(s) {
+        return x.clone();
+    }
+    if table.len() >= MAX_INTERNED_STRINGS {
+        table.clear();
+    }
+    let x: Arc<str> = Arc::from(s);
+    table.insert(x.clone());
+    x
+}
+
+/// Gets the id and the name of the current thread. The ids are numbered from 1 in the order
+/// the threads first log, as `std::thread::ThreadId` can't be converted to a number yet.
+fn current_thread() -> (Option<u64>, Option<Arc<str>>) {
+    static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+    thread_local! {
+        static CURRENT: (u64, Option<Arc<str>>) =
+            (NEXT_ID.fetch_add(1, Ordering::Relaxed), std::thread::current().name().map(Arc::from));
+    }
+    // not available when logging in the destructors of the other thread-local variables
+    CURRENT.try_with(|(id, name)| (Some(*id), name.clone())).unwrap_or_default()
+}
+
+/// A log record detached from the borrowed `log::Record`,
+/// so that it can be sent to the appender workers.
+#[derive(Clone)]
+pub struct OwnedRecord {
+    pub level: Level,
+    pub target: Arc<str>,
+    pub module_path: Option<Arc<str>>,
+    pub file: Option<Arc<str>>,
+    pub line: Option<u32>,
+    pub message: String,
+    /// The key-value pairs, materialized when the record is detached: a key and a string or compound value
+    /// take an allocation each. The encoders write them to the output without allocating any further.
+    pub kvs: Vec<(String, serde_json::Value)>,
+    /// The messages of the error chains of the key-value pairs captured as errors,
+    /// from the top-level error to the root cause.
+    pub error_chains: Vec<(String, Vec<String>)>,
+    /// The backtrace captured when the record is generated, if enabled.
+    pub backtrace: Option<String>,
+    /// The trace id of the current OpenTelemetry span, if any.
+    pub trace_id: Option<String>,
+    /// The span id of the current OpenTelemetry span, if any.
+    pub span_id: Option<String>,
+    /// The id of the thread generating the record, if known.
+    pub thread_id: Option<u64>,
+    /// The name of the thread generating the record, if named.
+    pub thread_name: Option<Arc<str>>,
+}
+
+impl OwnedRecord {
+    /// Sets the value of the key-value pair, or appends the pair if the key doesn't exist.
+    pub fn set_kv<K: Into<String> + AsRef<str>>(&mut self, key: K, value: serde_json::Value) {
+        match self.kvs.iter_mut().find(|(x, _)| x == key.as_ref()) {
+            Some((_, x)) => *x = value,
+            None => self.kvs.push((key.into(), value)),
+        }
+    }
+}
+
+impl From<&Record<'_>> for OwnedRecord {
+    fn from(record: &Record) -> Self {
+        #[derive(Default)]
+        struct Visitor {
+            kvs: Vec<(String, serde_json::Value)>,
+            error_chains: Vec<(String, Vec<String>)>,
+        }
+        impl<'a> VisitSource<'a> for Visitor {
+            fn visit_pair(&mut self, key: Key<'a>, value: Value<'a>) -> Result<(), log::kv::Error> {
+                if let Some(mut error) = value.to_borrowed_error() {
+                    let mut chain = vec![error.to_string()];
+                    while let Some(source) = error.source() {
+                        chain.push(source.to_string());
+                        error = source;
+                    }
+                    self.error_chains.push((key.to_string(), chain));
+                }
+                // the values are materialized here, as the records outlive the borrowed ones on the worker threads
+                // (so the pairs are not allocation-free; only their encoding is);
+                // the ones failing to serialize (by the user `Serialize` implementations) are kept as the error messages
+                let value = serde_json::to_value(value)
+                    .unwrap_or_else(|e| serde_json::Value::String(format!("<failed to serialize: {}>", e)));
+                self.kvs.push((key.to_string(), value));
+                Ok(())
+            }
+        }
+        let mut visitor = Visitor::default();
+        record.key_values().visit(&mut visitor).unwrap();
+        context::merge_into(&mut visitor.kvs);
+        let (trace_id, span_id) = current_span_ids().unzip();
+        let (thread_id, thread_name) = current_thread();
+        let module_path = match record.module_path_static() {
+            Some(module_path) => Some(intern_static(module_path)),
+            None => record.module_path().map(intern),
+        };
+        // the target is the module path by default
+        let target = match &module_path {
+            Some(module_path) if **module_path == *record.target() => module_path.clone(),
+            _ => intern(record.target()),
+        };
+        let file = match record.file_static() {
+            Some(file) => Some(intern_static(file)),
+            None => record.file().map(intern),
+        };
+
+        Self {
+            level: record.level(),
+            target,
+            module_path,
+            file,
+            line: record.line(),
+            message: record.args().to_string(),
+            kvs: visitor.kvs,
+            error_chains: visitor.error_chains,
+            backtrace: None,
+            trace_id,
+            span_id,
+            thread_id,
+            thread_name,
+        }
+    }
+}
+
+#[cfg(feature = "opentelemetry")]
+fn current_span_ids() -> Option<(String, String)> {
+    use opentelemetry::trace::TraceContextExt;
+
+    let context = opentelemetry::Context::current();
+    let span = context.span();
+    let span_context = span.span_context();
+    if !span_context.is_valid() {
+        return None;
+    }
+    Some((
+        span_context.trace_id().to_string(),
+        span_context.span_id().to_string(),
+    ))
+}
+
+#[cfg(not(feature = "opentelemetry"))]
+fn current_span_ids() -> Option<(String, String)> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use log::RecordBuilder;
+
+    use crate::encoder::tests::*;
+
+    #[test]
+    fn test_from_record() {
+        let mut builder = RecordBuilder::new();
+        prepare_test_log_record(&mut builder);
+        let mut kvs = Vec::new();
+        prepare_test_kvs(&mut kvs);
+        let record = super::OwnedRecord::from(
+            &builder
+                .args(format_args!("{}", TEST_MESSAGE))
+                .key_values(&kvs)
+                .build(),
+        );
+
+        assert_eq!(record.level, TEST_LEVEL);
+        assert_eq!(&*record.target, TEST_TARGET);
+        assert_eq!(record.module_path.as_deref(), Some(TEST_MODULE));
+        assert_eq!(record.file.as_deref(), Some(TEST_FILE));
+        assert_eq!(record.line, Some(TEST_LINE));
+        assert_eq!(record.message, TEST_MESSAGE);
+        assert_eq!(
+            record.kvs,
+            vec![
+                (TEST_KV0.0.to_string(), TEST_KV0.1.into()),
+                (TEST_KV1.0.to_string(), TEST_KV1.1.into()),
+                (TEST_KV2.0.to_string(), TEST_KV2.1.into()),
+                (TEST_KV3.0.to_string(), TEST_KV3.1.into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_thread() {
+        let record = super::OwnedRecord::from(&RecordBuilder::new().build());
+        let again = super::OwnedRecord::from(&RecordBuilder::new().build());
+        assert!(record.thread_id.is_some());
+        assert_eq!(record.thread_id, again.thread_id);
+
+        let other = std::thread::Builder::new()
+            .name("worker-1".to_string())
+            .spawn(|| super::OwnedRecord::from(&RecordBuilder::new().build()))
+            .unwrap()
+            .join()
+            .unwrap();
+        assert!(other.thread_id.is_some());
+        assert_ne!(other.thread_id, record.thread_id);
+        assert_eq!(other.thread_name.as_deref(), Some("worker-1"));
+    }
+
+    #[cfg(feature = "opentelemetry")]
+    #[test]
+    fn test_span_ids() {
+        use opentelemetry::trace::{
+            SpanContext, SpanId, TraceContextExt, TraceFlags, TraceId, TraceState,
+        };
+
+        let span_context = SpanContext::new(
+            TraceId::from_hex("4bf92f3577b34da6a3ce929d0e0e4736").unwrap(),
+            SpanId::from_hex("00f067aa0ba902b7").unwrap(),
+            TraceFlags::SAMPLED,
+            false,
+            TraceState::default(),
+        );
+        let _guard = opentelemetry::Context::current()
+            .with_remote_span_context(span_context)
+            .attach();
+        let record = super::OwnedRecord::from(&RecordBuilder::new().build());
+        assert_eq!(
+            record.trace_id.as_deref(),
+            Some("4bf92f3577b34da6a3ce929d0e0e4736")
+        );
+        assert_eq!(record.span_id.as_deref(), Some("00f067aa0ba902b7"));
+    }
+
+    #[test]
+    fn test_unserializable_value() {
+        struct Unserializable;
+        impl serde::Serialize for Unserializable {
+            fn serialize<S: serde::Serializer>(&self, _: S) -> Result<S::Ok, S::Error> {
+                Err(serde::ser::Error::custom("not supported"))
+            }
+        }
+
+        let kvs = [("x", log::kv::Value::from_serde(&Unserializable))];
+        let record = super::OwnedRecord::from(&RecordBuilder::new().key_values(&kvs).build());
+        assert_eq!(record.kvs, vec![("x".to_string(), "<failed to serialize: `serde` serialization failed: not supported>".into())]);
+    }
+
+    #[test]
+    fn test_error_chains() {
+        #[derive(Debug)]
+        struct TestError(&'static str, Option<Box<TestError>>);
+        impl std::fmt::Display for TestError {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                write!(f, "{}", self.0)
+            }
+        }
+        impl std::error::Error for TestError {
+            fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+                self.1.as_deref().map(|x| x as _)
+            }
+        }
+
+        let error = TestError("top", Some(Box::new(TestError("root", None))));
+        let kvs: &[(&str, log::kv::Value)] = &[
+            ("error", log::kv::Value::from_dyn_error(&error)),
+            ("number", log::kv::Value::from(1)),
+        ];
+        let record = super::OwnedRecord::from(&RecordBuilder::new().key_values(&kvs).build());
+        assert_eq!(
+            record.error_chains,
+            vec![(
+                "error".to_string(),
+                vec!["top".to_string(), "root".to_string()]
+            )]
+        );
+        assert_eq!(record.kvs[0], ("error".to_string(), "top".into()));
+    }
+
+    #[test]
+    fn test_intern() {
+        let x = super::intern("naive_logger::record::tests");
+        let y = super::intern(&String::from("naive_logger::record::tests"));
+        assert!(std::sync::Arc::ptr_eq(&x, &y));
+        let z = super::intern_static("naive_logger::record::tests");
+        assert!(std::sync::Arc::ptr_eq(&x, &z));
+
+        // the same copy is shared with the other threads
+        let w = std::thread::spawn(|| super::intern("naive_logger::record::tests")).join().unwrap();
+        assert!(std::sync::Arc::ptr_eq(&x, &w));
+    }
+}
@@ -0,0 +1,227 @@
+//! Entry/exit records for tracing the functions (or any blocks) without spans.
+//!
+//! [`log_scope!`](crate::log_scope) logs `enter <name>` at once, and `exit <name>` with the
+//! `elapsed_ms` key-value pair when the scope ends; if a block is given, its value is
+//! taken as a `Result`, and `status` (`ok` or `err`) and `error` are also logged.
+//! The block is evaluated in a closure, so `return` and `?` inside it exit the block (not the
+//! enclosing function) with the result, which is logged the same; and it can't `.await`:
+//!
+//! ```
+//! use log::Level;
+//!
+//! fn load_user(id: u32) -> Result<String, std::io::Error> {
+//!     naive_logger::log_scope!(Level::Debug, "load_user" => {
+//!         let name = std::fs::read_to_string(format!("users/{}", id))?; // `status=err` if failed
+//!         Ok(name)
+//!     })
+//! }
+//!
+//! fn handle() {
+//!     naive_logger::log_scope!(Level::Trace, "handle");
+//!     // ...
+//! } // `exit handle` is logged here
+//! ```
+
+use std::fmt::Display;
+use std::time::Instant;
+
+use log::{Level, Record};
+
+/// The call site of the records.
+#[doc(hidden)]
+pub struct Site {
+    pub target: &'static str,
+    pub file: &'static str,
+    pub line: u32,
+}
+
+#[macro_export]
+macro_rules! log_scope {
+    ($level:expr, $name:expr => $body:block) => {{
+        let scope = $crate::scope::Scope::enter($level, $name, $crate::scope::__site!());
+        #[allow(clippy::redundant_closure_call)]
+        let result = (|| $body)();
+        scope.finish_result(&result);
+        result
+    }};
+    ($level:expr, $name:expr) => {
+        let _scope = $crate::scope::Scope::enter($level, $name, $crate::scope::__site!());
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __site {
+    () => {
+        $crate::scope::Site {
+            target: module_path!(),
+            file: file!(),
+            line: line!(),
+        }
+    };
+}
+
+#[doc(hidden)]
+pub use crate::__site;
+
+/// Logs the exit record when dropped, if not finished explicitly.
+pub struct Scope {
+    level: Level,
+    name: &'static str,
+    site: Site,
+    start: Instant,
+    finished: bool,
+}
+
+impl Scope {
+    pub fn enter(level: Level, name: &'static str, site: Site) -> Self {
+        let scope = Self {
+            level,
+            name,
+            site,
+            start: Instant::now(),
+            finished: false,
+        };
+        scope.log("enter", &[("scope", name.into())]);
+        scope
+    }
+
+    /// Logs the exit record with the status of the result.
+    pub fn finish_result<T, E: Display>(mut self, result: &Result<T, E>) {
+        self.finished = true;
+        let mut kvs = vec![
+            ("scope", self.name.into()),
+            ("elapsed_ms", elapsed_ms(self.start).into()),
+        ];
+        match result {
+            Ok(_) => kvs.push(("status", "ok".into())),
+            Err(e) => {
+                kvs.push(("status", "err".into()));
+                kvs.push(("error", e.to_string().into()));
+            }
+        }
+        self.log("exit", &kvs);
+    }
+
+    fn log(&self, action: &str, kvs: &[(&str, serde_json::Value)]) {
+        log_at(&self.site, self.level, format_args!("{} {}", action, self.name), kvs);
+    }
+}
+
+impl Drop for Scope {
+    fn drop(&mut self) {
+        if !self.finished {
+            let kvs = [
+                ("scope", self.name.into()),
+                ("elapsed_ms", elapsed_ms(self.start).into()),
+            ];
+            self.log("exit", &kvs);
+        }
+    }
+}
+
+/// The milliseconds elapsed since the instant, with the microsecond precision.
+pub(crate) fn elapsed_ms(start: Instant) -> f64 {
+    start.elapsed().as_micros() as f64 / 1000.0
+}
+
+/// Logs the record at the call site, if the level is enabled.
+pub(crate) fn log_at(
+    site: &Site,
+    level: Level,
+    args: std::fmt::Arguments,
+    kvs: &[(&str, serde_json::Value)],
+) {
+    if level > log::max_level() {
+        return;
+    }
+    let kvs: Vec<(&str, log::kv::Value)> = kvs
+        .iter()
+        .map(|(key, value)| (*key, log::kv::Value::from_serde(value)))
+        .collect();
+    log::logger().log(
+        &Record::builder()
+            .level(level)
+            .target(site.target)
+            .module_path_static(Some(site.target))
+            .file_static(Some(site.file))
+            .line(Some(site.line))
+            .args(args)
+            .key_values(&kvs)
+            .build(),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn test_elapsed_ms() {
+        let start = Instant::now() - Duration::from_micros(1500);
+        assert!(super::elapsed_ms(start) >= 1.5);
+    }
+
+    #[test]
+    fn test_log_scope() {
+        use std::sync::OnceLock;
+
+        use log::Level;
+
+        use crate::test::CaptureAppender;
+
+        static CAPTURE: OnceLock<CaptureAppender> = OnceLock::new();
+        let capture = CAPTURE.get_or_init(|| {
+            let capture = CaptureAppender::register("scope_capture");
+            crate::init_from_json(
+                r#"{
+                    "appenders": {"capture": {"kind": "forward", "name": "scope_capture"}},
+                    "root": {"level": "trace", "appenders": ["capture"]}
+                }"#,
+            )
+            .unwrap();
+            capture
+        });
+        let exit_kvs = |name: &str| {
+            let records = capture.matching(Level::Debug, &format!("exit {}", name));
+            assert_eq!(records.len(), 1);
+            records[0].kvs.clone().into_iter().collect::<serde_json::Map<_, _>>()
+        };
+
+        fn parse(s: &str) -> Result<u32, std::num::ParseIntError> {
+            crate::log_scope!(Level::Debug, "scope_parse" => {
+                let n: u32 = s.parse()?;
+                Ok(n * 2)
+            })
+        }
+        fn early(fail: bool) -> Result<u32, String> {
+            crate::log_scope!(Level::Debug, "scope_early" => {
+                if fail {
+                    return Err("early".to_string());
+                }
+                Ok(42)
+            })
+        }
+        assert_eq!(early(true), Err("early".to_string()));
+        let kvs = exit_kvs("scope_early");
+        assert_eq!(kvs["status"], "err");
+        assert_eq!(kvs["error"], "early");
+        assert!(kvs["elapsed_ms"].is_number());
+
+        assert!(parse("x").is_err());
+        let kvs = exit_kvs("scope_parse");
+        assert_eq!(kvs["status"], "err");
+        assert_eq!(kvs["error"], "invalid digit found in string");
+
+        capture.clear();
+        assert_eq!(parse("21"), Ok(42));
+        assert_eq!(exit_kvs("scope_parse")["status"], "ok");
+
+        {
+            crate::log_scope!(Level::Trace, "scope_guard");
+        }
+        let records = capture.matching(Level::Trace, "exit scope_guard");
+        assert_eq!(records.len(), 1);
+        assert!(records[0].kvs.iter().all(|(key, _)| key != "status"));
+    }
+}
@@ -0,0 +1,191 @@
+//! A bridge forwarding the `slog` records into the `log` pipeline, enabled by the `slog` feature.
+//!
+//! The key-value pairs of the records and the loggers are converted into the key-value pairs
+//! of the log records; the ones of the records shadow the ones of the loggers:
+//!
+//! ```no_run
+//! use slog::Drain;
+//!
+//! naive_logger::init("log.yaml").unwrap();
+//! let logger = slog::Logger::root(naive_logger::slog_bridge::SlogDrain::new().fuse(), slog::o!());
+//! slog::info!(logger, "hello"; "user" => "foo");
+//! ```
+
+use std::fmt::Arguments;
+use std::panic::{RefUnwindSafe, UnwindSafe};
+
+use log::{Level, Log, Record};
+use slog::{Drain, Key, OwnedKVList, Serializer, KV};
+
+/// A `slog` drain forwarding the records to the installed logger.
+#[derive(Default)]
+pub struct SlogDrain {
+    /// The logger to forward to; the installed logger if none.
+    logger: Option<&'static dyn Log>,
+}
+
+impl SlogDrain {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+// Required by `slog::Logger::root`; the logger keeps no state broken by the panics.
+impl UnwindSafe for SlogDrain {}
+impl RefUnwindSafe for SlogDrain {}
+
+impl Drain for SlogDrain {
+    type Ok = ();
+    type Err = slog::Never;
+
+    fn log(&self, record: &slog::Record, values: &OwnedKVList) -> Result<(), slog::Never> {
+        let logger = self.logger.unwrap_or_else(log::logger);
+        let metadata = log::Metadata::builder()
+            .level(to_log_level(record.level()))
+            .target(record.module())
+            .build();
+        if !logger.enabled(&metadata) {
+            return Ok(());
+        }
+
+        let mut serializer = KvSerializer::default();
+        let _ = record.kv().serialize(record, &mut serializer);
+        let len = serializer.kvs.len();
+        let _ = values.serialize(record, &mut serializer);
+        let mut kvs: Vec<(&str, log::kv::Value)> = vec![];
+        for (i, (key, value)) in serializer.kvs.iter().enumerate() {
+            if i >= len && kvs.iter().any(|(x, _)| x == key) {
+                continue;
+            }
+            kvs.push((key, log::kv::Value::from_serde(value)));
+        }
+        logger.log(
+            &Record::builder()
+                .metadata(metadata)
+                .module_path(Some(record.module()))
+                .file(Some(record.file()))
+                .line(Some(record.line()))
+                .args(*record.msg())
+                .key_values(&kvs)
+                .build(),
+        );
+        Ok(())
+    }
+}
+
+fn to_log_level(level: slog::Level) -> Level {
+    match level {
+        slog::Level::Critical | slog::Level::Error => Level::Error,
+        slog::Level::Warning => Level::Warn,
+        slog::Level::Info => Level::Info,
+        slog::Level::Debug => Level::Debug,
+        slog::Level::Trace => Level::Trace,
+    }
+}
+
+#[derive(Default)]
+struct KvSerializer {
+    kvs: Vec<(String, serde_json::Value)>,
+}
+
+impl KvSerializer {
+    fn push(&mut self, key: Key, value: serde_json::Value) -> slog::Result {
+        self.kvs.push((key.to_string(), value));
+        Ok(())
+    }
+}
+
+impl Serializer for KvSerializer {
+    fn emit_arguments(&mut self, key: Key, val: &Arguments) -> slog::Result {
+        self.push(key, val.to_string().into())
+    }
+
+    fn emit_unit(&mut self, key: Key) -> slog::Result {
+        self.push(key, serde_json::Value::Null)
+    }
+
+    fn emit_none(&mut self, key: Key) -> slog::Result {
+        self.push(key, serde_json::Value::Null)
+    }
+
+    fn emit_bool(&mut self, key: Key, val: bool) -> slog::Result {
+        self.push(key, val.into())
+    }
+
+    fn emit_str(&mut self, key: Key, val: &str) -> slog::Result {
+        self.push(key, val.into())
+    }
+
+    fn emit_i32(&mut self, key: Key, val: i32) -> slog::Result {
+        self.push(key, val.into())
+    }
+
+    fn emit_u32(&mut self, key: Key, val: u32) -> slog::Result {
+        self.push(key, val.into())
+    }
+
+    fn emit_i64(&mut self, key: Key, val: i64) -> slog::Result {
+        self.push(key, val.into())
+    }
+
+    fn emit_u64(&mut self, key: Key, val: u64) -> slog::Result {
+        self.push(key, val.into())
+    }
+
+    fn emit_isize(&mut self, key: Key, val: isize) -> slog::Result {
+        self.push(key, val.into())
+    }
+
+    fn emit_usize(&mut self, key: Key, val: usize) -> slog::Result {
+        self.push(key, val.into())
+    }
+
+    fn emit_f64(&mut self, key: Key, val: f64) -> slog::Result {
+        self.push(key, val.into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use log::{Level, Log, Metadata, Record};
+    use slog::Drain;
+
+    use crate::record::OwnedRecord;
+    use crate::sync::Mutex;
+
+    struct TestLog(Mutex<Vec<OwnedRecord>>);
+
+    impl Log for TestLog {
+        fn enabled(&self, _metadata: &Metadata) -> bool {
+            true
+        }
+
+        fn log(&self, record: &Record) {
+            self.0.lock().push(OwnedRecord::from(record));
+        }
+
+        fn flush(&self) {}
+    }
+
+    #[test]
+    fn test_forward_record() {
+        let log: &'static TestLog = Box::leak(Box::new(TestLog(Mutex::new(vec![]))));
+        let drain = super::SlogDrain { logger: Some(log) };
+        let logger = slog::Logger::root(drain.fuse(), slog::o!("service" => "test", "user" => "bar"));
+        slog::crit!(logger, "hello {}", "world"; "user" => "foo", "count" => 42);
+
+        let records = log.0.lock();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].level, Level::Error);
+        assert_eq!(&*records[0].target, module_path!());
+        assert_eq!(records[0].message, "hello world");
+        assert_eq!(
+            records[0].kvs,
+            vec![
+                ("count".to_string(), 42.into()),
+                ("user".to_string(), "foo".into()),
+                ("service".to_string(), "test".into()),
+            ]
+        );
+    }
+}
@@ -0,0 +1,108 @@
+//! Ad-hoc performance logging: a message with the `elapsed_ms` key-value pair
+//! is logged when the stopwatch is finished or dropped.
+//!
+//! ```
+//! use log::Level;
+//!
+//! let stopwatch = naive_logger::stopwatch!(Level::Info, "query users");
+//! // ...
+//! stopwatch.finish();
+//!
+//! let sum = naive_logger::time_block!(Level::Debug, "sum", {
+//!     (0..100).sum::<u32>()
+//! });
+//! ```
+
+use std::time::{Duration, Instant};
+
+use log::Level;
+
+use crate::scope::{elapsed_ms, log_at, Site};
+
+/// Starts a [`Stopwatch`] with the call site as the target.
+#[macro_export]
+macro_rules! stopwatch {
+    ($level:expr, $message:expr) => {
+        $crate::stopwatch::Stopwatch::start_at($crate::scope::__site!(), $level, $message)
+    };
+}
+
+/// Evaluates the block, and logs the message with the time it took; returns the block value.
+#[macro_export]
+macro_rules! time_block {
+    ($level:expr, $message:expr, $body:block) => {{
+        let stopwatch = $crate::stopwatch!($level, $message);
+        let result = $body;
+        stopwatch.finish();
+        result
+    }};
+}
+
+/// Logs the message with `elapsed_ms` when finished or dropped.
+#[must_use = "the stopwatch logs at once if not kept until the end of the measured code"]
+pub struct Stopwatch {
+    level: Level,
+    message: String,
+    site: Site,
+    start: Instant,
+}
+
+impl Stopwatch {
+    /// Starts the stopwatch; the records are logged with the target.
+    #[track_caller]
+    #[must_use = "the stopwatch logs at once if not kept until the end of the measured code"]
+    pub fn start<S: Into<String>>(level: Level, target: &'static str, message: S) -> Self {
+        let location = std::panic::Location::caller();
+        let site = Site {
+            target,
+            file: location.file(),
+            line: location.line(),
+        };
+        Self::start_at(site, level, message)
+    }
+
+    #[doc(hidden)]
+    #[must_use = "the stopwatch logs at once if not kept until the end of the measured code"]
+    pub fn start_at<S: Into<String>>(site: Site, level: Level, message: S) -> Self {
+        Self {
+            level,
+            message: message.into(),
+            site,
+            start: Instant::now(),
+        }
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        self.start.elapsed()
+    }
+
+    /// Logs the message now, instead of when dropped.
+    pub fn finish(self) {}
+}
+
+impl Drop for Stopwatch {
+    fn drop(&mut self) {
+        let kvs = [("elapsed_ms", elapsed_ms(self.start).into())];
+        log_at(&self.site, self.level, format_args!("{}", self.message), &kvs);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use log::Level;
+
+    #[test]
+    fn test_stopwatch() {
+        let stopwatch = super::Stopwatch::start(Level::Info, "test", "test_stopwatch");
+        assert_eq!(stopwatch.site.file, file!());
+        assert_eq!(stopwatch.site.line, line!() - 2);
+        stopwatch.finish();
+
+        let stopwatch = crate::stopwatch!(Level::Info, format!("{} {}", "test", "stopwatch"));
+        assert_eq!(stopwatch.site.target, module_path!());
+        assert_eq!(stopwatch.message, "test stopwatch");
+
+        let sum = crate::time_block!(Level::Debug, "sum", { (0..10).sum::<u32>() });
+        assert_eq!(sum, 45);
+    }
+}
@@ -0,0 +1,71 @@
+//! Locks used inside the logger.
+//!
+//! With the `parking_lot` feature enabled, the locks of `parking_lot` are used.
+//! Otherwise, the std locks are wrapped to ignore poisoning,
+//! so that a panic in one appender won't break the following log calls.
+
+#[cfg(feature = "parking_lot")]
+pub use parking_lot::{Mutex, RwLock};
+
+#[cfg(not(feature = "parking_lot"))]
+pub use self::std_impl::{Mutex, RwLock};
+
+#[cfg(not(feature = "parking_lot"))]
+mod std_impl {
+    use std::sync::{MutexGuard, PoisonError, RwLockReadGuard, RwLockWriteGuard};
+
+    #[derive(Default)]
+    pub struct Mutex<T: ?Sized>(std::sync::Mutex<T>);
+
+    impl<T> Mutex<T> {
+        pub fn new(value: T) -> Self {
+            Self(std::sync::Mutex::new(value))
+        }
+    }
+
+    impl<T: ?Sized> Mutex<T> {
+        pub fn lock(&self) -> MutexGuard<'_, T> {
+            self.0.lock().unwrap_or_else(PoisonError::into_inner)
+        }
+    }
+
+    #[derive(Default)]
+    pub struct RwLock<T: ?Sized>(std::sync::RwLock<T>);
+
+    impl<T> RwLock<T> {
+        pub fn new(value: T) -> Self {
+            Self(std::sync::RwLock::new(value))
+        }
+    }
+
+    impl<T: ?Sized> RwLock<T> {
+        pub fn read(&self) -> RwLockReadGuard<'_, T> {
+            self.0.read().unwrap_or_else(PoisonError::into_inner)
+        }
+
+        pub fn write(&self) -> RwLockWriteGuard<'_, T> {
+            self.0.write().unwrap_or_else(PoisonError::into_inner)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::Mutex;
+
+    #[test]
+    fn test_lock_after_panic() {
+        let mutex = Arc::new(Mutex::new(0));
+        let cloned = mutex.clone();
+        let result = std::thread::spawn(move || {
+            let mut guard = cloned.lock();
+            *guard += 1;
+            panic!("panic while holding the lock");
+        })
+        .join();
+        assert!(result.is_err());
+        assert_eq!(*mutex.lock(), 1);
+    }
+}
@@ -0,0 +1,149 @@
+//! Test support: capturing the log records to assert on them in the tests of the downstream crates.
+//!
+//! The [`CaptureAppender`] is registered as a forward target, and referenced by a `forward` appender:
+//!
+//! ```
+//! use log::Level;
+//! use naive_logger::test::CaptureAppender;
+//!
+//! let capture = CaptureAppender::register("capture");
+//! naive_logger::init_from_json(r#"{
+//!     "appenders": {"capture": {"kind": "forward", "name": "capture"}},
+//!     "root": {"level": "debug", "appenders": ["capture"]}
+//! }"#).unwrap();
+//!
+//! log::warn!(user_id = 42; "disk almost full");
+//! capture.assert_logged(Level::Warn, "almost full");
+//! capture.assert_not_logged(Level::Error, "");
+//! assert_eq!(capture.records()[0].kvs[0].1, 42);
+//! ```
+
+use std::sync::Arc;
+
+use log::{Level, Log, Metadata, Record};
+
+use crate::record::OwnedRecord;
+use crate::sync::Mutex;
+
+/// Stores the records forwarded to it, with the assertion helpers.
+/// The clones share the same records, so one can be registered while another is kept for the assertions.
+///
+/// The helpers flush the logger first, so that the records still queued in the appender workers are captured.
+#[derive(Clone, Default)]
+pub struct CaptureAppender {
+    records: Arc<Mutex<Vec<OwnedRecord>>>,
+}
+
+impl CaptureAppender {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates an appender and registers it as the forward target of the name;
+    /// it should be called before the initialization.
+    pub fn register(name: &str) -> Self {
+        let capture = Self::new();
+        crate::register_forward_target(name, Box::new(capture.clone()));
+        capture
+    }
+
+    /// The records captured so far, in the order they were logged.
+    pub fn records(&self) -> Vec<OwnedRecord> {
+        log::logger().flush();
+        self.records.lock().clone()
+    }
+
+    /// The records of the level whose messages contain the substring.
+    pub fn matching(&self, level: Level, substring: &str) -> Vec<OwnedRecord> {
+        self.records()
+            .into_iter()
+            .filter(|record| record.level == level && record.message.contains(substring))
+            .collect()
+    }
+
+    pub fn clear(&self) {
+        log::logger().flush();
+        self.records.lock().clear();
+    }
+
+    /// Panics unless a record of the level has been logged with the substring in its message.
+    #[track_caller]
+    pub fn assert_logged(&self, level: Level, substring: &str) {
+        if self.matching(level, substring).is_empty() {
+            panic!(
+                "no {} record containing '{}' was logged; captured:\n{}",
+                level,
+                substring,
+                self.summary()
+            );
+        }
+    }
+
+    /// Panics if any record of the level has been logged with the substring in its message.
+    #[track_caller]
+    pub fn assert_not_logged(&self, level: Level, substring: &str) {
+        if !self.matching(level, substring).is_empty() {
+            panic!(
+                "a {} record containing '{}' was logged; captured:\n{}",
+                level,
+                substring,
+                self.summary()
+            );
+        }
+    }
+
+    /// Lists the captured records, one line each, for the panic messages.
+    fn summary(&self) -> String {
+        let records = self.records.lock();
+        if records.is_empty() {
+            return "  (none)".to_string();
+        }
+        records
+            .iter()
+            .map(|record| format!("  {} {}: {}", record.level, record.target, record.message))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+impl Log for CaptureAppender {
+    fn enabled(&self, _metadata: &Metadata) -> bool {
+        true
+    }
+
+    fn log(&self, record: &Record) {
+        self.records.lock().push(OwnedRecord::from(record));
+    }
+
+    fn flush(&self) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use log::{Level, Log, Record};
+
+    #[test]
+    fn test_capture() {
+        let capture = super::CaptureAppender::new();
+        let kvs = [("user_id", log::kv::Value::from(42))];
+        capture.log(
+            &Record::builder()
+                .level(Level::Warn)
+                .target("app")
+                .args(format_args!("disk almost full"))
+                .key_values(&kvs)
+                .build(),
+        );
+        capture.assert_logged(Level::Warn, "almost");
+        capture.assert_not_logged(Level::Error, "");
+        assert_eq!(capture.matching(Level::Warn, "full").len(), 1);
+        assert_eq!(capture.records()[0].kvs, [("user_id".to_string(), 42.into())]);
+
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| capture.assert_logged(Level::Warn, "network")));
+        let message = result.unwrap_err().downcast::<String>().unwrap();
+        assert!(message.contains("WARN app: disk almost full"));
+
+        capture.clear();
+        assert!(capture.records().is_empty());
+    }
+}
@@ -0,0 +1,168 @@
+//! A bridge forwarding the `tracing` events into the `log` pipeline, enabled by the `tracing` feature.
+//!
+//! The events are converted into log records, with the `message` field as the message
+//! and the other fields as the key-value pairs; then they're processed by the installed logger
+//! like the records from the `log` macros:
+//!
+//! ```no_run
+//! use tracing_subscriber::layer::SubscriberExt;
+//!
+//! naive_logger::init("log.yaml").unwrap();
+//! let subscriber = tracing_subscriber::registry().with(naive_logger::tracing_bridge::TracingLayer::new());
+//! tracing::subscriber::set_global_default(subscriber).unwrap();
+//! ```
+
+use std::fmt::Debug;
+
+use log::{Level, Log, Record};
+use tracing_core::field::{Field, Visit};
+use tracing_core::{Event, Subscriber};
+use tracing_subscriber::layer::{Context, Layer};
+
+/// A `tracing_subscriber` layer forwarding the events to the installed logger.
+#[derive(Default)]
+pub struct TracingLayer {
+    /// The logger to forward to; the installed logger if none.
+    logger: Option<&'static dyn Log>,
+}
+
+impl TracingLayer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl<S: Subscriber> Layer<S> for TracingLayer {
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        let metadata = event.metadata();
+        let level = to_log_level(*metadata.level());
+        let logger = self.logger.unwrap_or_else(log::logger);
+        let log_metadata = log::Metadata::builder()
+            .level(level)
+            .target(metadata.target())
+            .build();
+        if !logger.enabled(&log_metadata) {
+            return;
+        }
+
+        let mut visitor = Visitor::default();
+        event.record(&mut visitor);
+        let kvs: Vec<(&str, log::kv::Value)> = visitor
+            .kvs
+            .iter()
+            .map(|(key, value)| (*key, log::kv::Value::from_serde(value)))
+            .collect();
+        logger.log(
+            &Record::builder()
+                .metadata(log_metadata)
+                .module_path(metadata.module_path())
+                .file(metadata.file())
+                .line(metadata.line())
+                .args(format_args!("{}", visitor.message))
+                .key_values(&kvs)
+                .build(),
+        );
+    }
+}
+
+fn to_log_level(level: tracing_core::Level) -> Level {
+    match level {
+        tracing_core::Level::ERROR => Level::Error,
+        tracing_core::Level::WARN => Level::Warn,
+        tracing_core::Level::INFO => Level::Info,
+        tracing_core::Level::DEBUG => Level::Debug,
+        tracing_core::Level::TRACE => Level::Trace,
+    }
+}
+
+#[derive(Default)]
+struct Visitor {
+    message: String,
+    kvs: Vec<(&'static str, serde_json::Value)>,
+}
+
+impl Visitor {
+    fn push(&mut self, field: &Field, value: serde_json::Value) {
+        self.kvs.push((field.name(), value));
+    }
+}
+
+impl Visit for Visitor {
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.push(field, value.into());
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.push(field, value.into());
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.push(field, value.into());
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.push(field, value.into());
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "message" {
+            self.message = value.to_string();
+        } else {
+            self.push(field, value.into());
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+        if field.name() == "message" {
+            self.message = format!("{:?}", value);
+        } else {
+            self.push(field, format!("{:?}", value).into());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use log::{Level, Log, Metadata, Record};
+    use tracing_subscriber::layer::SubscriberExt;
+
+    use crate::record::OwnedRecord;
+    use crate::sync::Mutex;
+
+    struct TestLog(Mutex<Vec<OwnedRecord>>);
+
+    impl Log for TestLog {
+        fn enabled(&self, _metadata: &Metadata) -> bool {
+            true
+        }
+
+        fn log(&self, record: &Record) {
+            self.0.lock().push(OwnedRecord::from(record));
+        }
+
+        fn flush(&self) {}
+    }
+
+    #[test]
+    fn test_forward_event() {
+        let log: &'static TestLog = Box::leak(Box::new(TestLog(Mutex::new(vec![]))));
+        let layer = super::TracingLayer { logger: Some(log) };
+        let subscriber = tracing_subscriber::registry().with(layer);
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::warn!(target: "test_tracing", user = "foo", count = 42, "hello {}", "world");
+        });
+
+        let records = log.0.lock();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].level, Level::Warn);
+        assert_eq!(&*records[0].target, "test_tracing");
+        assert_eq!(records[0].message, "hello world");
+        assert_eq!(
+            records[0].kvs,
+            vec![
+                ("user".to_string(), "foo".into()),
+                ("count".to_string(), 42.into()),
+            ]
+        );
+    }
+}